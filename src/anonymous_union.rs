@@ -6,37 +6,125 @@ use serde::{
 pub(crate) struct ChunkedEnum<InnerT> {
     num_bytes: usize,
     variants: &'static [&'static str],
-    discriminant: [u8; 8],
+    discriminant: Discriminant,
     inner: InnerT,
 }
 
+/// The discriminant of a [`ChunkedEnum`], as a little-endian bitset.
+///
+/// Union discriminants and the presence bits of structs with up to 64 skippable fields are
+/// stored inline, only the presence bits of wider structs need an allocation.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Discriminant {
+    low: [u8; 8],
+    high: Vec<u8>,
+}
+
+impl Discriminant {
+    /// A discriminant with at least `num_bits` bits, all unset.
+    #[inline]
+    pub(crate) fn with_bits(num_bits: usize) -> Self {
+        Self {
+            low: [0; 8],
+            high: vec![0; num_bits.div_ceil(8).saturating_sub(8)],
+        }
+    }
+
+    /// The first 64 bits of the discriminant, which is all of it for unions.
+    #[inline]
+    pub(crate) fn low_u64(&self) -> u64 {
+        u64::from_le_bytes(self.low)
+    }
+
+    #[inline]
+    pub(crate) fn bit(&self, i_bit: usize) -> bool {
+        self.byte(i_bit / 8) & (1 << (i_bit % 8)) != 0
+    }
+
+    #[inline]
+    pub(crate) fn set_bit(&mut self, i_bit: usize) {
+        let byte = self.byte(i_bit / 8) | (1 << (i_bit % 8));
+        self.set_byte(i_bit / 8, byte);
+    }
+
+    #[inline]
+    pub(crate) fn count_ones(&self) -> u32 {
+        self.low_u64().count_ones() + self.high.iter().map(|&byte| byte.count_ones()).sum::<u32>()
+    }
+
+    #[inline]
+    fn byte(&self, i_byte: usize) -> u8 {
+        match i_byte.checked_sub(8) {
+            None => self.low[i_byte],
+            Some(i_high) => self.high.get(i_high).copied().unwrap_or(0),
+        }
+    }
+
+    #[inline]
+    fn set_byte(&mut self, i_byte: usize, byte: u8) {
+        match i_byte.checked_sub(8) {
+            None => self.low[i_byte] = byte,
+            Some(i_high) => self.high[i_high] = byte,
+        }
+    }
+}
+
+impl From<u64> for Discriminant {
+    #[inline]
+    fn from(discriminant: u64) -> Self {
+        Self {
+            low: discriminant.to_le_bytes(),
+            high: Vec::new(),
+        }
+    }
+}
+
 impl<'value, ValueT> ChunkedEnum<&'value ValueT>
 where
     ValueT: Serialize,
 {
     #[inline]
-    pub(crate) fn serializable<ErrorT>(
+    pub(crate) fn serializable(
         num_bits: usize,
-        discriminant: u64,
+        discriminant: Discriminant,
         value: &'value ValueT,
-    ) -> Result<Self, ErrorT>
+    ) -> Self {
+        Self {
+            num_bytes: num_bits.div_ceil(8),
+            variants: &[], // unused for serialization
+            discriminant,
+            inner: value,
+        }
+    }
+}
+
+impl<ValueT> Serialize for ChunkedEnum<&'_ ValueT>
+where
+    ValueT: Serialize,
+{
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
-        ErrorT: serde::ser::Error,
+        S: serde::Serializer,
     {
-        if num_bits <= 64 {
-            Ok(Self {
-                num_bytes: num_bits.div_ceil(8),
-                variants: &[], // unused for serialization
-                discriminant: discriminant.to_le_bytes(),
-                inner: value,
-            })
-        } else {
-            Err(ErrorT::custom("too many bits in chunked enum"))
+        SerializeChunks {
+            num_bytes: self.num_bytes,
+            discriminant: &self.discriminant,
+            inner: self.inner,
         }
+        .serialize(serializer)
     }
 }
 
-impl<ValueT> Serialize for ChunkedEnum<&'_ ValueT>
+/// Serializes the bytes of a discriminant as nested variants, most significant first, see
+/// [`ChunkedEnum`].
+struct SerializeChunks<'a, ValueT> {
+    num_bytes: usize,
+    discriminant: &'a Discriminant,
+    inner: &'a ValueT,
+}
+
+impl<ValueT> Serialize for SerializeChunks<'_, ValueT>
 where
     ValueT: Serialize,
 {
@@ -49,7 +137,7 @@ where
         }
 
         let i_byte = self.num_bytes - 1;
-        let new_byte = self.discriminant[i_byte];
+        let new_byte = self.discriminant.byte(i_byte);
         if i_byte == 0 {
             serializer.serialize_newtype_variant(
                 UNION_ENUM_NAME,
@@ -62,7 +150,7 @@ where
                 UNION_ENUM_NAME,
                 u32::from(new_byte),
                 serialized_anonymous_variant(new_byte),
-                &ChunkedEnum {
+                &SerializeChunks {
                     num_bytes: i_byte,
                     ..*self
                 },
@@ -73,38 +161,29 @@ where
 
 impl<'de, SeederT, SeedT> ChunkedEnum<SeederT>
 where
-    SeederT: FnOnce(u64) -> SeedT,
+    SeederT: FnOnce(Discriminant) -> SeedT,
     SeedT: DeserializeSeed<'de>,
 {
     #[inline]
-    pub(crate) fn deserializable<ErrorT>(num_bits: usize, seeder: SeederT) -> Result<Self, ErrorT>
-    where
-        ErrorT: serde::de::Error,
-    {
-        if num_bits <= 64 {
-            let (num_bytes, variants) = match (num_bits / 8, num_bits % 8) {
-                (0, 0) => (0, &[][..]),
-                (quotient, 0) => (quotient, &UNION_ENUM_VARIANT_NAMES[..]),
-                (quotient, remainder) => {
-                    (quotient + 1, &UNION_ENUM_VARIANT_NAMES[..(1 << remainder)])
-                }
-            };
-
-            Ok(Self {
-                num_bytes,
-                variants,
-                discriminant: [0u8; 8],
-                inner: seeder,
-            })
-        } else {
-            Err(ErrorT::custom("too many bits in chunked enum"))
+    pub(crate) fn deserializable(num_bits: usize, seeder: SeederT) -> Self {
+        let (num_bytes, variants) = match (num_bits / 8, num_bits % 8) {
+            (0, 0) => (0, &[][..]),
+            (quotient, 0) => (quotient, &UNION_ENUM_VARIANT_NAMES[..]),
+            (quotient, remainder) => (quotient + 1, &UNION_ENUM_VARIANT_NAMES[..(1 << remainder)]),
+        };
+
+        Self {
+            num_bytes,
+            variants,
+            discriminant: Discriminant::with_bits(num_bits),
+            inner: seeder,
         }
     }
 }
 
 impl<'de, SeederT, SeedT> DeserializeSeed<'de> for ChunkedEnum<SeederT>
 where
-    SeederT: FnOnce(u64) -> SeedT,
+    SeederT: FnOnce(Discriminant) -> SeedT,
     SeedT: DeserializeSeed<'de>,
 {
     type Value = SeedT::Value;
@@ -117,14 +196,14 @@ where
         if self.num_bytes > 0 {
             deserializer.deserialize_enum(UNION_ENUM_NAME, self.variants, self)
         } else {
-            (self.inner)(u64::from_le_bytes(self.discriminant)).deserialize(deserializer)
+            (self.inner)(self.discriminant).deserialize(deserializer)
         }
     }
 }
 
 impl<'de, SeederT, SeedT> serde::de::Visitor<'de> for ChunkedEnum<SeederT>
 where
-    SeederT: FnOnce(u64) -> SeedT,
+    SeederT: FnOnce(Discriminant) -> SeedT,
     SeedT: DeserializeSeed<'de>,
 {
     type Value = SeedT::Value;
@@ -139,14 +218,14 @@ where
     {
         let i_byte = self.num_bytes - 1;
         let (new_byte, data) = data.variant_seed(AnonymousVariantSeed)?;
-        self.discriminant[i_byte] = new_byte;
+        self.discriminant.set_byte(i_byte, new_byte);
 
         if i_byte > 0 {
             self.num_bytes = i_byte;
             self.variants = UNION_ENUM_VARIANT_NAMES;
             data.newtype_variant_seed(self)
         } else {
-            data.newtype_variant_seed((self.inner)(u64::from_le_bytes(self.discriminant)))
+            data.newtype_variant_seed((self.inner)(self.discriminant))
         }
     }
 }
@@ -207,7 +286,7 @@ impl<'de> serde::de::Visitor<'de> for AnonymousVariantSeed {
 }
 
 const UNION_ENUM_NAME: &str = "Union";
-const UNION_ENUM_VARIANT_NAMES: &[&str; 256] = &{
+pub(crate) const UNION_ENUM_VARIANT_NAMES: &[&str; 256] = &{
     const HEX: [u8; 16] = *b"0123456789abcdef";
 
     // Creates the variant names `_00`, `_01`, ..., `_ff` as byte arrays.
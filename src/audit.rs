@@ -0,0 +1,378 @@
+use std::{cell::Cell, fmt::Write};
+
+use crate::{
+    DescribedBy, Schema, Trace,
+    dump::DumpError,
+    indices::FieldNameListIndex,
+    trace::{InvalidTraceError, ReadTraceExt, TraceNode},
+};
+
+/// The `serde` calls made while tracing a value, returned by [`DescribedBy::audit`].
+///
+/// Useful for finding out which part of a value (e.g. a field with a custom `serialize_with`)
+/// produced an unexpected shape: audit a few values, then compare the calls made at the paths
+/// whose types differ.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TraceAudit {
+    calls: Vec<SerdeCall>,
+}
+
+/// A single `serde` call recorded in a [`TraceAudit`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SerdeCall {
+    path: String,
+    method: &'static str,
+    type_name: Option<String>,
+    variant: Option<String>,
+    length: Option<usize>,
+    value: Option<String>,
+}
+
+impl TraceAudit {
+    /// All the calls, in the order they were made.
+    #[inline]
+    pub fn calls(&self) -> &[SerdeCall] {
+        &self.calls
+    }
+
+    /// The calls made for the value at the given path.
+    pub fn calls_at<'audit>(&'audit self, path: &str) -> impl Iterator<Item = &'audit SerdeCall> {
+        let path = path.to_owned();
+        self.calls.iter().filter(move |call| call.path == path)
+    }
+}
+
+impl SerdeCall {
+    /// A `jq`-like path to the value the call was made for, e.g. `.items[2].name` or `.` for
+    /// the root. Map keys are enclosed in braces, e.g. `.tags{"a"}`, to tell them apart from the
+    /// values, e.g. `.tags["a"]`.
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The name of the [`serde::Serializer`] method (or `skip_field`, for skipped struct
+    /// fields), e.g. `serialize_newtype_struct`.
+    #[inline]
+    pub fn method(&self) -> &'static str {
+        self.method
+    }
+
+    /// The `name` argument of the call, for structs and enums.
+    #[inline]
+    pub fn type_name(&self) -> Option<&str> {
+        self.type_name.as_deref()
+    }
+
+    /// The `variant` argument of the call, for enum variants.
+    #[inline]
+    pub fn variant(&self) -> Option<&str> {
+        self.variant.as_deref()
+    }
+
+    /// The `len` argument of the call, for sequences, maps, tuples and structs.
+    #[inline]
+    pub fn length(&self) -> Option<usize> {
+        self.length
+    }
+
+    /// The rendered value passed to the call, for primitives.
+    #[inline]
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+}
+
+impl std::fmt::Display for TraceAudit {
+    /// Writes one call per line.
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for call in &self.calls {
+            writeln!(formatter, "{call}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for SerdeCall {
+    /// Writes the call as `path: method(arguments)`.
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}: {}(", self.path, self.method)?;
+        let mut separator = "";
+        for name in [&self.type_name, &self.variant].into_iter().flatten() {
+            write!(formatter, "{separator}{name:?}")?;
+            separator = ", ";
+        }
+        if let Some(length) = self.length {
+            write!(formatter, "{separator}{length}")?;
+        }
+        if let Some(value) = &self.value {
+            write!(formatter, "{separator}{value}")?;
+        }
+        write!(formatter, ")")
+    }
+}
+
+impl DescribedBy<'_, &'_ Trace> {
+    /// Reconstructs the `serde` calls made while tracing this value, along with the paths they
+    /// were made at.
+    ///
+    /// Skipped struct fields are reported as `skip_field` calls after the struct's present
+    /// fields, since the trace doesn't record where they were skipped.
+    ///
+    /// Returns an error if the trace doesn't match the schema.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::{Serialize, Serializer};
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// fn as_string<S: Serializer>(port: &u16, serializer: S) -> Result<S::Ok, S::Error> {
+    ///     serializer.collect_str(port)
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Config {
+    ///     #[serde(serialize_with = "as_string")]
+    ///     port: u16,
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     host: Option<&'static str>,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&Config { port: 80, host: None })?;
+    /// let schema = builder.build()?;
+    ///
+    /// let audit = schema.describe_trace_ref(&trace).audit()?;
+    /// assert_eq!(
+    ///     audit.to_string(),
+    ///     ".: serialize_struct(\"Config\", 1)\n\
+    ///      .port: serialize_str(\"80\")\n\
+    ///      .host: skip_field()\n",
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn audit(&self) -> Result<TraceAudit, InvalidTraceError> {
+        let mut auditor = Auditor {
+            schema: self.1,
+            path: String::new(),
+            calls: Vec::new(),
+        };
+        auditor.audit(&Cell::new(&(self.0).0))?;
+        Ok(TraceAudit {
+            calls: auditor.calls,
+        })
+    }
+}
+
+struct Auditor<'schema> {
+    schema: &'schema Schema,
+    path: String,
+    calls: Vec<SerdeCall>,
+}
+
+impl Auditor<'_> {
+    fn audit(&mut self, tail: &Cell<&[u8]>) -> Result<(), DumpError> {
+        let start = tail.get();
+        let node = tail.pop_trace_node::<DumpError>()?;
+        let schema = self.schema;
+        let names = |name, variant: Option<_>| -> Result<_, DumpError> {
+            Ok((
+                Some(schema.type_name(name)?.to_owned()),
+                variant
+                    .map(|variant| schema.variant_name(variant).map(str::to_owned))
+                    .transpose()?,
+            ))
+        };
+        let tuple_length =
+            |length: u32| usize::try_from(length).expect("usize needs to be at least 32 bits");
+
+        let method = match node {
+            TraceNode::Bool => "serialize_bool",
+            TraceNode::I8 => "serialize_i8",
+            TraceNode::I16 => "serialize_i16",
+            TraceNode::I32 => "serialize_i32",
+            TraceNode::I64 => "serialize_i64",
+            TraceNode::I128 => "serialize_i128",
+            TraceNode::U8 => "serialize_u8",
+            TraceNode::U16 => "serialize_u16",
+            TraceNode::U32 => "serialize_u32",
+            TraceNode::U64 => "serialize_u64",
+            TraceNode::U128 => "serialize_u128",
+            TraceNode::F32 => "serialize_f32",
+            TraceNode::F64 => "serialize_f64",
+            TraceNode::Char => "serialize_char",
+            TraceNode::String => "serialize_str",
+            TraceNode::Bytes => "serialize_bytes",
+            TraceNode::None => "serialize_none",
+            TraceNode::Unit => "serialize_unit",
+
+            TraceNode::Some => {
+                self.push_call("serialize_some", (None, None), None, None);
+                return self.audit(tail);
+            }
+            TraceNode::UnitStruct(name) => {
+                self.push_call("serialize_unit_struct", names(name, None)?, None, None);
+                return Ok(());
+            }
+            TraceNode::UnitVariant(name, variant) => {
+                let names = names(name, Some(variant))?;
+                self.push_call("serialize_unit_variant", names, None, None);
+                return Ok(());
+            }
+            TraceNode::NewtypeStruct(name) => {
+                self.push_call("serialize_newtype_struct", names(name, None)?, None, None);
+                return self.audit(tail);
+            }
+            TraceNode::NewtypeVariant(name, variant) => {
+                let names = names(name, Some(variant))?;
+                self.push_call("serialize_newtype_variant", names, None, None);
+                return self.audit(tail);
+            }
+            TraceNode::Sequence => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                self.push_call("serialize_seq", (None, None), Some(length), None);
+                return self.audit_items(tail, length, "[", "]");
+            }
+            TraceNode::Tuple(length) => {
+                let length = tuple_length(length);
+                self.push_call("serialize_tuple", (None, None), Some(length), None);
+                return self.audit_items(tail, length, ".", "");
+            }
+            TraceNode::TupleStruct(length, name) => {
+                let length = tuple_length(length);
+                let names = names(name, None)?;
+                self.push_call("serialize_tuple_struct", names, Some(length), None);
+                return self.audit_items(tail, length, ".", "");
+            }
+            TraceNode::TupleVariant(length, name, variant) => {
+                let length = tuple_length(length);
+                let names = names(name, Some(variant))?;
+                self.push_call("serialize_tuple_variant", names, Some(length), None);
+                return self.audit_items(tail, length, ".", "");
+            }
+            TraceNode::Map => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                self.push_call("serialize_map", (None, None), Some(length), None);
+                return self.audit_entries(tail, length);
+            }
+            TraceNode::Struct(name, field_names) => {
+                return self.audit_struct(
+                    tail,
+                    "serialize_struct",
+                    names(name, None)?,
+                    field_names,
+                );
+            }
+            TraceNode::StructVariant(name, variant, field_names) => {
+                return self.audit_struct(
+                    tail,
+                    "serialize_struct_variant",
+                    names(name, Some(variant))?,
+                    field_names,
+                );
+            }
+        };
+
+        tail.set(start);
+        let value = match node {
+            TraceNode::None | TraceNode::Unit => {
+                let _ = tail.pop_trace_node::<DumpError>()?;
+                None
+            }
+            _ => Some(self.schema.trace_to_string(tail)?),
+        };
+        self.push_call(method, (None, None), None, value);
+        Ok(())
+    }
+
+    fn audit_items(
+        &mut self,
+        tail: &Cell<&[u8]>,
+        length: usize,
+        open: &str,
+        close: &str,
+    ) -> Result<(), DumpError> {
+        for index in 0..length {
+            let parent_length = self.path.len();
+            write!(self.path, "{open}{index}{close}")?;
+            self.audit(tail)?;
+            self.path.truncate(parent_length);
+        }
+        Ok(())
+    }
+
+    fn audit_entries(&mut self, tail: &Cell<&[u8]>, length: usize) -> Result<(), DumpError> {
+        for _ in 0..length {
+            let parent_length = self.path.len();
+            let key_start = tail.get();
+            let key = self.schema.trace_to_string(tail)?;
+            tail.set(key_start);
+
+            write!(self.path, "{{{key}}}")?;
+            self.audit(tail)?;
+            self.path.truncate(parent_length);
+
+            write!(self.path, "[{key}]")?;
+            self.audit(tail)?;
+            self.path.truncate(parent_length);
+        }
+        Ok(())
+    }
+
+    fn audit_struct(
+        &mut self,
+        tail: &Cell<&[u8]>,
+        method: &'static str,
+        names: (Option<String>, Option<String>),
+        field_names: FieldNameListIndex,
+    ) -> Result<(), DumpError> {
+        let all_fields = self
+            .schema
+            .field_name_list(field_names)?
+            .iter()
+            .map(|&name| self.schema.field_name(name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let present = self.schema.pop_trace_field_names(tail, field_names)?;
+        self.push_call(method, names, Some(present.len()), None);
+        for name in &present {
+            let parent_length = self.path.len();
+            write!(self.path, ".{name}")?;
+            self.audit(tail)?;
+            self.path.truncate(parent_length);
+        }
+        for name in all_fields {
+            if present.contains(&name) {
+                continue;
+            }
+            let parent_length = self.path.len();
+            write!(self.path, ".{name}")?;
+            self.push_call("skip_field", (None, None), None, None);
+            self.path.truncate(parent_length);
+        }
+        Ok(())
+    }
+
+    fn push_call(
+        &mut self,
+        method: &'static str,
+        (type_name, variant): (Option<String>, Option<String>),
+        length: Option<usize>,
+        value: Option<String>,
+    ) {
+        let path = if self.path.starts_with('.') {
+            self.path.clone()
+        } else {
+            format!(".{}", self.path)
+        };
+        self.calls.push(SerdeCall {
+            path,
+            method,
+            type_name,
+            variant,
+            length,
+            value,
+        });
+    }
+}
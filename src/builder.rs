@@ -1,11 +1,16 @@
 use crate::{
+    Dynamic, DynamicFields, FieldPath, FloatNormalization, LeafValue, LogicalTypes, Metrics,
+    PathSegment,
     indices::{
         FieldNameIndex, FieldNameListIndex, MemberIndex, MemberListIndex, SchemaNodeIndex,
         SchemaNodeListIndex, TraceIndex, TypeName, TypeNameIndex, VariantNameIndex,
     },
+    metrics::StaticMetrics,
     pool::{NonEmptyPool, Pool},
-    schema::{Schema, SchemaNode},
-    trace::{Trace, TraceNodeKind},
+    schema::{Schema, SchemaNames, SchemaNode},
+    stats::{SkipStats, UnionStats},
+    trace::{Trace, TraceBytes, TraceNodeKind, presence_len},
+    transform::{SealFn, TransformFn, Transformers},
 };
 use serde::{
     Deserialize, Serialize,
@@ -14,6 +19,10 @@ use serde::{
         SerializeTupleStruct, SerializeTupleVariant, Serializer,
     },
 };
+use std::{
+    borrow::Cow,
+    collections::{HashMap, hash_map::Entry},
+};
 use thiserror::Error;
 
 /// An in-progress schema built by successive calls to [`SchemaBuilder::trace`].
@@ -93,6 +102,23 @@ pub struct SchemaBuilder {
     field_names: NonEmptyPool<&'static str, FieldNameIndex>,
     variant_names: NonEmptyPool<&'static str, VariantNameIndex>,
     type_names: NonEmptyPool<&'static str, TypeNameIndex>,
+    float_normalization: FloatNormalization,
+    coerce_byte_sequences: bool,
+    forbid_unions: bool,
+    human_readable: bool,
+    version: u32,
+    min_reader_version: u32,
+    max_data_bytes: Option<usize>,
+    pub(crate) transformers: Transformers,
+    logical_types: LogicalTypes,
+    metrics: Option<StaticMetrics>,
+    /// The number of values traced so far, i.e. the occurrences of the root.
+    traced: u64,
+    /// The number of fields, including skipped ones, each struct was last traced with, see
+    /// [`StructSchemaBuilder::new`].
+    pub(crate) struct_fields: HashMap<TypeName, usize>,
+    /// Filled in by [`build`][`Self::build`].
+    union_stats: UnionStats,
 }
 
 impl SchemaBuilder {
@@ -101,17 +127,397 @@ impl SchemaBuilder {
         Self::default()
     }
 
+    /// Sets how floats are recorded by subsequent calls to [`trace`][`Self::trace`].
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::{FloatNormalization, SchemaBuilder};
+    ///
+    /// let mut builder = SchemaBuilder::new().float_normalization(FloatNormalization::Canonical);
+    /// let negative = builder.trace(&-0.0f64)?;
+    /// let positive = builder.trace(&0.0f64)?;
+    /// let schema = builder.build()?;
+    /// assert_eq!(
+    ///     postcard::to_stdvec(&schema.describe_trace(negative))?,
+    ///     postcard::to_stdvec(&schema.describe_trace(positive))?,
+    /// );
+    ///
+    /// let mut builder = SchemaBuilder::new().float_normalization(FloatNormalization::RejectNan);
+    /// assert!(builder.trace(&f32::NAN).is_err());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn float_normalization(mut self, normalization: FloatNormalization) -> Self {
+        self.float_normalization = normalization;
+        self
+    }
+
+    /// Limits the size of the data of each value traced by subsequent calls to
+    /// [`trace`][`Self::trace`], which fail with [`TraceError::DataBudget`] instead of building
+    /// a larger trace.
+    ///
+    /// The error identifies the innermost field that crossed the budget. Strings and byte arrays
+    /// are checked before they're copied, so a huge string fails without being copied.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::{SchemaBuilder, TraceError};
+    ///
+    /// #[derive(Serialize)]
+    /// struct Upload {
+    ///     name: String,
+    ///     chunks: Vec<String>,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new().max_data_bytes(1024);
+    /// let upload = Upload {
+    ///     name: "backup".to_owned(),
+    ///     chunks: vec!["a".repeat(512); 3],
+    /// };
+    /// let Err(TraceError::DataBudget(error)) = builder.trace(&upload) else {
+    ///     panic!("expected the budget to be exceeded");
+    /// };
+    /// assert_eq!(error.path().to_string(), ".chunks[1]");
+    /// assert_eq!(error.max_data_bytes(), 1024);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn max_data_bytes(mut self, max: usize) -> Self {
+        self.max_data_bytes = Some(max);
+        self
+    }
+
+    /// Reserves room for `capacity` distinct schema nodes, and as many lists of them, up front.
+    ///
+    /// Every shape traced so far, and every list of members of a union or of fields of a
+    /// struct, is interned once, so tracing types known to be large, e.g. enums with hundreds
+    /// of variants, otherwise grows the pools holding them over and over. This only affects
+    /// allocations, not the schema built.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// let mut reserved = SchemaBuilder::new().node_capacity(1024);
+    /// let _ = reserved.trace(&vec![Some(1u32), None])?;
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let _ = builder.trace(&vec![Some(1u32), None])?;
+    /// assert_eq!(reserved.build()?.fingerprint(), builder.build()?.fingerprint());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn node_capacity(mut self, capacity: usize) -> Self {
+        self.nodes.reserve(capacity);
+        self.node_lists.reserve(capacity);
+        self
+    }
+
+    /// Whether sequences of `u8`-s traced by subsequent calls to [`trace`][`Self::trace`] are
+    /// recorded as byte arrays, as if they were serialized with `serde_bytes`. Off by default.
+    ///
+    /// Byte arrays are stored as-is, rather than as one value per byte, so this makes `Vec<u8>`
+    /// and `[u8]` fields much smaller without annotating them. Empty sequences remain sequences,
+    /// since the type of their items isn't known. Byte arrays are only deserialized back into
+    /// sequences with [`DecodeOptions::byte_sequence_coercions`] enabled.
+    ///
+    /// [`DecodeOptions::byte_sequence_coercions`]: crate::DecodeOptions::byte_sequence_coercions
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::{Deserialize, Serialize, de::DeserializeSeed};
+    /// use serde_describe::{DecodeOptions, SchemaBuilder};
+    ///
+    /// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    /// struct Blob {
+    ///     data: Vec<u8>,
+    /// }
+    ///
+    /// let blob = Blob { data: vec![1, 2, 3] };
+    /// let mut builder = SchemaBuilder::new().coerce_byte_sequences(true);
+    /// let trace = builder.trace(&blob)?;
+    /// let schema = builder.build()?;
+    /// assert_eq!(schema.to_string(), "Blob_2 { data: bytes_1 }");
+    ///
+    /// let bytes = postcard::to_stdvec(&schema.describe_trace(trace))?;
+    /// let decoded = schema
+    ///     .describe_type::<Blob>()
+    ///     .with_options(DecodeOptions::new().byte_sequence_coercions(true))
+    ///     .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))?
+    ///     .0;
+    /// assert_eq!(decoded, blob);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn coerce_byte_sequences(mut self, coerce: bool) -> Self {
+        self.coerce_byte_sequences = coerce;
+        self
+    }
+
+    /// Whether subsequent calls to [`trace`][`Self::trace`] fail with [`TraceError::Union`]
+    /// rather than widen the items of a sequence, or the keys or values of a map, into a union
+    /// of different types. Off by default.
+    ///
+    /// Useful when every sequence is meant to be homogeneous, to catch the values which aren't
+    /// instead of silently making the schema, and every value encoded with it, larger. Unions of
+    /// `None` and `Some`, or of the variants of the same enum, are how those types are always
+    /// described, so they're still allowed; so are unions of the values traced by separate calls,
+    /// which can be avoided by tracing a single sequence of them instead.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::{SchemaBuilder, TraceError};
+    ///
+    /// #[derive(Serialize)]
+    /// #[serde(untagged)]
+    /// enum Reading {
+    ///     Celsius(f64),
+    ///     Label(&'static str),
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new().forbid_unions(true);
+    /// let _ = builder.trace(&vec![Some(21.5), None])?;
+    ///
+    /// let readings = vec![Reading::Celsius(21.5), Reading::Label("offline")];
+    /// let Err(TraceError::Union(error)) = builder.trace(&readings) else {
+    ///     panic!("expected the union to be rejected");
+    /// };
+    /// assert_eq!(error.path().to_string(), ".[1]");
+    /// assert_eq!((error.existing(), error.conflicting()), ("f64", "str"));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn forbid_unions(mut self, forbid: bool) -> Self {
+        self.forbid_unions = forbid;
+        self
+    }
+
+    /// Whether subsequent calls to [`trace`][`Self::trace`] present themselves to the traced
+    /// values as a human-readable format (see [`serde::Serializer::is_human_readable`]). Off by
+    /// default.
+    ///
+    /// Types like timestamps, UUIDs and IP addresses serialize differently depending on the mode,
+    /// e.g. as a string rather than as bytes, so data which will end up in a human-readable
+    /// format, such as JSON, may need to be traced in that mode. The mode is recorded in the
+    /// schema (see [`Schema::is_human_readable`]), and data is always deserialized in the mode
+    /// it was traced in, whatever the underlying format, such that these types read back what
+    /// they wrote.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::SchemaBuilder;
+    /// use std::net::Ipv4Addr;
+    ///
+    /// let address = Ipv4Addr::new(10, 0, 0, 1);
+    /// let mut builder = SchemaBuilder::new().human_readable(true);
+    /// let trace = builder.trace(&address)?;
+    /// let schema = builder.build()?;
+    /// assert!(schema.is_human_readable());
+    /// assert_eq!(schema.to_string(), "str_1");
+    ///
+    /// let bytes = postcard::to_stdvec(&schema.describe_trace(trace))?;
+    /// let decoded: Ipv4Addr =
+    ///     schema.deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))?;
+    /// assert_eq!(decoded, address);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn human_readable(mut self, human_readable: bool) -> Self {
+        self.human_readable = human_readable;
+        self
+    }
+
+    /// Sets the application-defined version recorded in built schemas, see [`Schema::version`].
+    /// Zero by default.
+    ///
+    /// The crate doesn't interpret versions itself; they let applications gate data by its
+    /// generation without wrapping schemas, e.g. by bumping the version whenever the traced types
+    /// change in a way older readers would misread. See [`min_reader_version`][`Self::min_reader_version`]
+    /// and [`Schema::check_reader_version`].
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets the oldest application-defined reader version which may read data described by built
+    /// schemas, checked by [`Schema::check_reader_version`]. Zero by default, i.e. any reader.
+    pub fn min_reader_version(mut self, min_reader_version: u32) -> Self {
+        self.min_reader_version = min_reader_version;
+        self
+    }
+
+    /// Sets the registry of logical types whose values are converted and recorded under their
+    /// logical type id by subsequent calls to [`trace`][`Self::trace`], see [`LogicalTypes`].
+    pub fn logical_types(mut self, types: LogicalTypes) -> Self {
+        self.logical_types = types;
+        self
+    }
+
+    /// Reports each value traced by subsequent calls to [`trace`][`Self::trace`] and
+    /// [`trace_schema_only`][`Self::trace_schema_only`], and whether it changed the schema, see
+    /// [`Metrics`].
+    pub fn metrics(mut self, metrics: &'static dyn Metrics) -> Self {
+        self.metrics = Some(StaticMetrics(metrics));
+        self
+    }
+
     /// Converts a type that supports [`serde::Serialize`] into a [`Trace`] and records its type
     /// into the schema.
     ///
+    /// The keys and values of a map may have different types from one entry to the next, e.g.
+    /// string-or-integer keys, whether the entries are serialized with `serialize_entry` or with
+    /// separate `serialize_key` and `serialize_value` calls: the key types and the value types
+    /// are each widened into a union, independently of each other. Serializing a key without its
+    /// value, or a value without its key, is an error.
+    ///
     /// See the top-level [`SchemaBuilder`] documentation for an example.
     pub fn trace<ValueT>(&mut self, value: &ValueT) -> Result<Trace, TraceError>
     where
         ValueT: Serialize,
     {
         let mut data = Vec::new();
+        self.trace_data(value, false, &mut data)?;
+        Ok(Trace(data))
+    }
+
+    /// Like [`trace`][`Self::trace`], but reuses the allocation of an existing trace, e.g. to
+    /// trace a stream of values into a single buffer without allocating one per value.
+    ///
+    /// The previous contents of `trace` are replaced. If tracing fails, `trace` is left with
+    /// unspecified contents, which must not be serialized.
+    ///
+    /// To write a traced value to a socket or a file, serialize it straight into the writer with
+    /// the format, e.g. `postcard::to_io(&schema.describe_trace_ref(&trace), writer)`, which
+    /// doesn't copy the trace either.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::{SchemaBuilder, Trace};
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let mut trace = Trace::default();
+    /// let mut lines = Vec::new();
+    /// for reading in [21.5f32, 21.75, 22.0] {
+    ///     builder.trace_in(&reading, &mut trace)?;
+    ///     lines.push(builder.clone().build()?.describe_trace_ref(&trace).to_string());
+    /// }
+    /// assert_eq!(lines, ["21.5", "21.75", "22.0"]);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn trace_in<ValueT>(&mut self, value: &ValueT, trace: &mut Trace) -> Result<(), TraceError>
+    where
+        ValueT: Serialize,
+    {
+        trace.0.clear();
+        self.trace_data(value, false, &mut trace.0).map(drop)
+    }
+
+    /// Records the type of a value into the schema, without keeping its trace.
+    ///
+    /// Cheaper than [`trace`][`Self::trace`] when only the schema is needed, e.g. to register
+    /// the schema of a large sample of values: the contents of strings and byte arrays aren't
+    /// copied.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// builder.trace_schema_only(&Some("a very long string"))?;
+    /// builder.trace_schema_only(&None::<&str>)?;
+    /// assert_eq!(builder.build()?.to_string(), "union_4(some_2(str_1), none_3)");
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn trace_schema_only<ValueT>(&mut self, value: &ValueT) -> Result<(), TraceError>
+    where
+        ValueT: Serialize,
+    {
+        self.trace_data(value, true, &mut Vec::new()).map(drop)
+    }
+
+    /// Builds the schema of a single value, without tracing its data, see
+    /// [`trace_schema_only`][`Self::trace_schema_only`].
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// let schema = SchemaBuilder::schema_only(&vec![(1u8, "one"), (2, "two")])?;
+    /// assert_eq!(schema.to_string(), "seq_4(tuple_3(u8_1, str_2))");
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn schema_only<ValueT>(value: &ValueT) -> Result<Schema, TraceError>
+    where
+        ValueT: Serialize,
+    {
+        let mut builder = Self::new();
+        builder.trace_schema_only(value)?;
+        builder.build()
+    }
+
+    /// Like [`trace_schema_only`][`Self::trace_schema_only`], returning whether the value
+    /// widened the schema.
+    pub(crate) fn widen_schema<ValueT>(&mut self, value: &ValueT) -> Result<bool, TraceError>
+    where
+        ValueT: Serialize,
+    {
+        self.trace_data(value, true, &mut Vec::new())
+    }
+
+    /// Records the type of a value into the schema, writing its trace into the empty `data`,
+    /// without the contents of strings and byte arrays if `schema_only` is set. Returns whether
+    /// the value widened the schema.
+    fn trace_data<ValueT>(
+        &mut self,
+        value: &ValueT,
+        schema_only: bool,
+        data: &mut Vec<u8>,
+    ) -> Result<bool, TraceError>
+    where
+        ValueT: Serialize,
+    {
+        let Some(StaticMetrics(metrics)) = self.metrics else {
+            return self.trace_unobserved(value, schema_only, data);
+        };
+        let result = self.trace_unobserved(value, schema_only, data);
+        match &result {
+            Ok(widened) => {
+                metrics.value_traced(if schema_only { 0 } else { data.len() });
+                if *widened {
+                    metrics.schema_widened();
+                }
+            }
+            Err(error) => metrics.trace_failed(error),
+        }
+        result
+    }
+
+    fn trace_unobserved<ValueT>(
+        &mut self,
+        value: &ValueT,
+        schema_only: bool,
+        data: &mut Vec<u8>,
+    ) -> Result<bool, TraceError>
+    where
+        ValueT: Serialize,
+    {
+        let max_data_bytes = self.max_data_bytes.unwrap_or(usize::MAX);
         let new_root = value.serialize(RootSerializer {
-            data: &mut data,
+            data,
             nodes: &mut self.nodes,
             node_lists: &mut self.node_lists,
             member_lists: &mut self.member_lists,
@@ -119,16 +525,143 @@ impl SchemaBuilder {
             field_names: &mut self.field_names,
             variant_names: &mut self.variant_names,
             type_names: &mut self.type_names,
+            float_normalization: self.float_normalization,
+            coerce_byte_sequences: self.coerce_byte_sequences,
+            forbid_unions: self.forbid_unions,
+            human_readable: self.human_readable,
+            max_data_bytes,
+            schema_only,
+            transformers: &self.transformers,
+            transform: None,
+            logical_types: &self.logical_types,
+            struct_fields: &mut self.struct_fields,
         })?;
-        self.root.union(new_root);
-        Ok(Trace(data))
+        if data.len() > max_data_bytes {
+            return Err(DataBudgetError::new(max_data_bytes).into());
+        }
+        let widened = self.root.union(self.traced, new_root, 1);
+        self.traced += 1;
+        Ok(widened)
+    }
+
+    /// Traces every value in a batch, then builds the schema which describes all of them.
+    ///
+    /// All the returned [`Trace`]-s are serialized against the same, widened schema, so values
+    /// of the same type are always encoded the same way, regardless of which other values were
+    /// in the batch.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::de::DeserializeSeed;
+    /// use serde_describe::{DescribedBy, SchemaBuilder};
+    ///
+    /// let values = [Some(1u32), None, Some(3)];
+    /// let (schema, traces) = SchemaBuilder::trace_all(&values)?;
+    ///
+    /// let serialized = traces
+    ///     .into_iter()
+    ///     .map(|trace| postcard::to_stdvec(&schema.describe_trace(trace)))
+    ///     .collect::<Result<Vec<_>, _>>()?;
+    /// for (bytes, value) in serialized.iter().zip(values) {
+    ///     let DescribedBy(roundtripped, _) = schema
+    ///         .describe_type::<Option<u32>>()
+    ///         .deserialize(&mut postcard::Deserializer::from_bytes(bytes))?;
+    ///     assert_eq!(roundtripped, value);
+    /// }
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn trace_all<ValuesT>(values: ValuesT) -> Result<(Schema, Vec<Trace>), TraceError>
+    where
+        ValuesT: IntoIterator,
+        ValuesT::Item: Serialize,
+    {
+        let mut builder = Self::new();
+        let traces = values
+            .into_iter()
+            .map(|value| builder.trace(&value))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((builder.build()?, traces))
+    }
+
+    /// Counts how many times each field of each struct traced so far was present, and how many
+    /// times it was skipped, e.g. by `#[serde(skip_serializing_if = "...")]`.
+    ///
+    /// Fields which are never skipped are counted too, so that optional fields which could be
+    /// required show up as never skipped.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Profile {
+    ///     name: &'static str,
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     email: Option<&'static str>,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// builder.trace_schema_only(&vec![
+    ///     Profile { name: "ana", email: Some("ana@example.com") },
+    ///     Profile { name: "bo", email: None },
+    /// ])?;
+    /// builder.trace_schema_only(&Profile { name: "cy", email: None })?;
+    ///
+    /// let stats = builder.skip_stats();
+    /// assert_eq!(stats.field("Profile", "email").map(|email| email.skipped()), Some(2));
+    /// assert_eq!(
+    ///     stats.to_string(),
+    ///     "Profile.email: 1 present, 2 skipped\nProfile.name: 3 present, 0 skipped",
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn skip_stats(&self) -> SkipStats {
+        let mut stats = SkipStats::default();
+        self.root.add_skip_stats(self.traced, self, &mut stats);
+        stats
     }
 
     /// Converts all the recorded value types into a schema that can be used to serialize the
     /// [`Trace`]-s returned by [`trace`][`Self::trace`].
     ///
     /// See the top-level [`SchemaBuilder`] documentation for an example.
-    pub fn build(mut self) -> Result<Schema, TraceError> {
+    pub fn build(self) -> Result<Schema, TraceError> {
+        self.build_with_union_stats().map(|(schema, _)| schema)
+    }
+
+    /// Like [`build`][`Self::build`], but also returns how many times each member of each union
+    /// in the schema was traced.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// enum Event {
+    ///     Click(u32, u32),
+    ///     Key(char),
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// builder.trace_schema_only(&vec![Event::Key('a'), Event::Click(1, 2), Event::Key('b')])?;
+    /// builder.trace_schema_only(&vec![Event::Key('c')])?;
+    /// let (schema, stats) = builder.build_with_union_stats()?;
+    /// assert_eq!(
+    ///     schema.to_string(),
+    ///     "seq_6(union_5(Event::Key_2(char_1), Event::Click_4(u32_3, u32_3)))",
+    /// );
+    /// assert_eq!(stats.to_string(), "union_5: [3, 1]");
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn build_with_union_stats(mut self) -> Result<(Schema, UnionStats), TraceError> {
         let schema = Schema {
             root_index: std::mem::take(&mut self.root).build(&mut self)?,
             nodes: self.nodes.into(),
@@ -138,8 +671,31 @@ impl SchemaBuilder {
             field_names: self.field_names.into(),
             variant_names: self.variant_names.into(),
             type_names: self.type_names.into(),
+            human_readable: self.human_readable,
+            version: self.version,
+            min_reader_version: self.min_reader_version,
+            hashed_names: false,
         };
-        Ok(schema)
+        Ok((schema, self.union_stats))
+    }
+}
+
+impl SchemaNames for SchemaBuilder {
+    fn type_name(&self, index: TypeNameIndex) -> &str {
+        self.type_names.get(index).map_or("", |name| *name)
+    }
+
+    fn variant_name(&self, index: VariantNameIndex) -> &str {
+        self.variant_names.get(index).map_or("", |name| *name)
+    }
+
+    fn field_names(&self, index: FieldNameListIndex) -> Vec<&str> {
+        self.field_name_lists
+            .get(index)
+            .map_or(&[][..], |list| &**list)
+            .iter()
+            .map(|&name| self.field_names.get(name).map_or("", |name| *name))
+            .collect()
     }
 }
 
@@ -154,6 +710,103 @@ pub enum TraceError {
     /// Custom serde serialization error.
     #[error("custom serialization error: {0}")]
     Custom(Box<str>),
+
+    /// A NaN float was traced by a builder configured with [`FloatNormalization::RejectNan`].
+    #[error("NaN floats are not permitted")]
+    Nan,
+
+    /// The value's data is larger than the [budget][`SchemaBuilder::max_data_bytes`].
+    #[error(transparent)]
+    DataBudget(#[from] DataBudgetError),
+
+    /// The items of a sequence, or the keys or values of a map, have different types, which
+    /// [`SchemaBuilder::forbid_unions`] forbids.
+    #[error(transparent)]
+    Union(#[from] UnionError),
+
+    /// A newtype registered as a [logical type][`crate::LogicalTypes`] wraps something other
+    /// than a single primitive value.
+    #[error("logical type `{0}` wraps something other than a single primitive value")]
+    LogicalType(&'static str),
+}
+
+impl TraceError {
+    /// Adds the segment of the value being unwound out of to the path of a budget or union
+    /// error.
+    #[inline]
+    fn within(mut self, segment: impl FnOnce() -> PathSegment) -> Self {
+        if let Self::DataBudget(DataBudgetError { path, .. })
+        | Self::Union(UnionError { path, .. }) = &mut self
+        {
+            path.push_front(segment());
+        }
+        self
+    }
+}
+
+/// Returned by tracing a value whose data is larger than the budget set with
+/// [`SchemaBuilder::max_data_bytes`].
+#[derive(Debug, Error)]
+#[error("data budget of {max_data_bytes} bytes exceeded at `{path}`")]
+pub struct DataBudgetError {
+    path: FieldPath,
+    max_data_bytes: usize,
+}
+
+impl DataBudgetError {
+    fn new(max_data_bytes: usize) -> Self {
+        Self {
+            path: FieldPath::root(),
+            max_data_bytes,
+        }
+    }
+
+    /// The path to the innermost value which crossed the budget.
+    ///
+    /// Map entries are named by their position in the map, like the items of a sequence.
+    #[inline]
+    pub fn path(&self) -> &FieldPath {
+        &self.path
+    }
+
+    /// The budget which was exceeded.
+    #[inline]
+    pub fn max_data_bytes(&self) -> usize {
+        self.max_data_bytes
+    }
+}
+
+/// Returned by tracing a sequence or map whose items, keys or values have different types, with
+/// [`SchemaBuilder::forbid_unions`] set.
+#[derive(Debug, Error)]
+#[error("unions are forbidden, but `{path}` is `{conflicting}`, unlike the earlier `{existing}`")]
+pub struct UnionError {
+    path: FieldPath,
+    existing: String,
+    conflicting: String,
+}
+
+impl UnionError {
+    /// The path to the item, key or value which didn't match the ones before it.
+    ///
+    /// Map entries are named by their position in the map, like the items of a sequence.
+    #[inline]
+    pub fn path(&self) -> &FieldPath {
+        &self.path
+    }
+
+    /// The type of the earlier items, keys or values, where it differs from the new one, e.g.
+    /// `u32` if a sequence of `Option<u32>`-s is followed by a `Some("string")`.
+    #[inline]
+    pub fn existing(&self) -> &str {
+        &self.existing
+    }
+
+    /// The type of the new item, key or value, where it differs from the earlier ones.
+    #[inline]
+    pub fn conflicting(&self) -> &str {
+        &self.conflicting
+    }
 }
 
 impl From<TraceLimitErrorKind> for TraceError {
@@ -167,8 +820,6 @@ impl From<TraceLimitErrorKind> for TraceError {
 #[error("tracing limits exceeded: {0}")]
 pub struct TraceLimitError(#[from] TraceLimitErrorKind);
 
-pub(crate) const MAX_SKIPPABLE_FIELDS: usize = 64;
-
 #[derive(Debug, Error)]
 pub(crate) enum TraceLimitErrorKind {
     #[error("too many schema nodes for u32")]
@@ -194,9 +845,6 @@ pub(crate) enum TraceLimitErrorKind {
 
     #[error("too many variants")]
     UnionVariants,
-
-    #[error("too many skippable fields")]
-    SkippableFields,
 }
 
 impl serde::ser::Error for TraceError {
@@ -209,6 +857,31 @@ impl serde::ser::Error for TraceError {
     }
 }
 
+/// Appends formatted strings to a trace for [`RootSerializer::collect_str`], within the data
+/// budget.
+struct TraceWriter<'a> {
+    data: &'a mut Vec<u8>,
+    used_bytes: usize,
+    length: usize,
+    max_data_bytes: usize,
+    over_budget: bool,
+    schema_only: bool,
+}
+
+impl std::fmt::Write for TraceWriter<'_> {
+    fn write_str(&mut self, string: &str) -> std::fmt::Result {
+        self.length += string.len();
+        if self.used_bytes.saturating_add(self.length) > self.max_data_bytes {
+            self.over_budget = true;
+            return Err(std::fmt::Error);
+        }
+        if !self.schema_only {
+            self.data.extend_from_slice(string.as_bytes());
+        }
+        Ok(())
+    }
+}
+
 pub(crate) struct RootSerializer<'a> {
     data: &'a mut Vec<u8>,
     nodes: &'a mut Pool<SchemaNode, SchemaNodeIndex>,
@@ -218,9 +891,24 @@ pub(crate) struct RootSerializer<'a> {
     field_names: &'a mut NonEmptyPool<&'static str, FieldNameIndex>,
     variant_names: &'a mut NonEmptyPool<&'static str, VariantNameIndex>,
     type_names: &'a mut NonEmptyPool<&'static str, TypeNameIndex>,
+    float_normalization: FloatNormalization,
+    /// See [`SchemaBuilder::coerce_byte_sequences`].
+    coerce_byte_sequences: bool,
+    /// See [`SchemaBuilder::forbid_unions`].
+    forbid_unions: bool,
+    human_readable: bool,
+    max_data_bytes: usize,
+    /// Skips copying the contents of strings and byte arrays, whose trace is discarded.
+    schema_only: bool,
+    transformers: &'a Transformers,
+    /// Rewrites the primitive values inside the value being traced, see
+    /// [`SchemaBuilder::transform_field`].
+    transform: Option<&'a TransformFn>,
+    logical_types: &'a LogicalTypes,
+    struct_fields: &'a mut HashMap<TypeName, usize>,
 }
 
-impl RootSerializer<'_> {
+impl<'a> RootSerializer<'a> {
     #[inline]
     fn reborrow<'b>(&'b mut self) -> RootSerializer<'b> {
         RootSerializer {
@@ -232,11 +920,162 @@ impl RootSerializer<'_> {
             field_names: self.field_names,
             variant_names: self.variant_names,
             type_names: self.type_names,
+            float_normalization: self.float_normalization,
+            coerce_byte_sequences: self.coerce_byte_sequences,
+            forbid_unions: self.forbid_unions,
+            human_readable: self.human_readable,
+            max_data_bytes: self.max_data_bytes,
+            schema_only: self.schema_only,
+            transformers: self.transformers,
+            transform: self.transform,
+            logical_types: self.logical_types,
+            struct_fields: self.struct_fields,
+        }
+    }
+
+    /// Fails if writing `additional` more bytes would exceed the data budget.
+    #[inline]
+    fn check_data_budget(&self, additional: usize) -> Result<(), TraceError> {
+        if self.data.len().saturating_add(additional) > self.max_data_bytes {
+            Err(DataBudgetError::new(self.max_data_bytes).into())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Fails if [unions are forbidden][`SchemaBuilder::forbid_unions`] and unioning `node` into
+    /// `existing` would make one of different types, or returns `node` back otherwise.
+    fn check_union(
+        &self,
+        existing: &SchemaBuilderNode,
+        node: SchemaBuilderNode,
+    ) -> Result<SchemaBuilderNode, TraceError> {
+        if !self.forbid_unions {
+            return Ok(node);
+        }
+        match existing.union_conflict(&node) {
+            Some((existing, conflicting)) => Err(UnionError {
+                path: FieldPath::root(),
+                existing: self.describe_node(existing),
+                conflicting: self.describe_node(conflicting),
+            }
+            .into()),
+            None => Ok(node),
+        }
+    }
+
+    /// Names the type of a node, without its contents, for [`UnionError`]-s.
+    fn describe_node(&self, node: &SchemaBuilderNode) -> String {
+        let type_name = |TypeName(name, variant): TypeName| {
+            let name = self.type_names.get(name).copied().unwrap_or_default();
+            match variant.and_then(|variant| self.variant_names.get(variant)) {
+                Some(variant) => format!("{name}::{variant}"),
+                None => name.to_owned(),
+            }
+        };
+        match node {
+            SchemaBuilderNode::Bool => "bool".to_owned(),
+            SchemaBuilderNode::I8 => "i8".to_owned(),
+            SchemaBuilderNode::I16 => "i16".to_owned(),
+            SchemaBuilderNode::I32 => "i32".to_owned(),
+            SchemaBuilderNode::I64 => "i64".to_owned(),
+            SchemaBuilderNode::I128 => "i128".to_owned(),
+            SchemaBuilderNode::U8 => "u8".to_owned(),
+            SchemaBuilderNode::U16 => "u16".to_owned(),
+            SchemaBuilderNode::U32 => "u32".to_owned(),
+            SchemaBuilderNode::U64 => "u64".to_owned(),
+            SchemaBuilderNode::U128 => "u128".to_owned(),
+            SchemaBuilderNode::F32 => "f32".to_owned(),
+            SchemaBuilderNode::F64 => "f64".to_owned(),
+            SchemaBuilderNode::Char => "char".to_owned(),
+            SchemaBuilderNode::String => "str".to_owned(),
+            SchemaBuilderNode::Bytes => "bytes".to_owned(),
+            SchemaBuilderNode::OptionNone => "none".to_owned(),
+            SchemaBuilderNode::OptionSome(_) => "some".to_owned(),
+            SchemaBuilderNode::Unit(None) => "unit".to_owned(),
+            SchemaBuilderNode::Unit(Some(name))
+            | SchemaBuilderNode::Newtype(name, _)
+            | SchemaBuilderNode::Record {
+                name: Some(name), ..
+            } => type_name(*name),
+            SchemaBuilderNode::Map(..) => "map".to_owned(),
+            SchemaBuilderNode::Sequence(..) => "seq".to_owned(),
+            SchemaBuilderNode::Union(_) => "union".to_owned(),
+            SchemaBuilderNode::Record {
+                name: None,
+                field_types,
+                ..
+            } => format!("tuple of {}", field_types.len()),
+        }
+    }
+
+    /// Adds a field, and the variant containing it if any, to the path of a budget error.
+    fn field_error(
+        &self,
+        name: Option<TypeName>,
+        field: PathSegment,
+        error: TraceError,
+    ) -> TraceError {
+        let error = error.within(|| field);
+        match name
+            .and_then(|TypeName(_, variant)| variant)
+            .and_then(|variant| self.variant_names.get(variant))
+        {
+            Some(variant) => error.within(|| PathSegment::Variant((*variant).into())),
+            None => error,
+        }
+    }
+
+    /// The transformer for the given field of a struct, or the current one if there isn't one.
+    #[inline]
+    fn field_transform(&self, TypeName(name, _): TypeName, field: &str) -> Option<&'a TransformFn> {
+        let transformers: &'a Transformers = self.transformers;
+        if transformers.is_empty() {
+            return self.transform;
+        }
+        self.type_names
+            .get(name)
+            .and_then(|name| transformers.for_field(name, field))
+            .or(self.transform)
+    }
+
+    /// The sealer of a field of a struct or variant, or of the unnamed contents of a newtype or
+    /// tuple, see [`SchemaBuilder::protection_policy`].
+    #[inline]
+    fn sealer(&self, TypeName(name, _): TypeName, field: Option<&str>) -> Option<&'a SealFn> {
+        let transformers: &'a Transformers = self.transformers;
+        if transformers.is_empty() {
+            return None;
+        }
+        self.type_names
+            .get(name)
+            .and_then(|name| transformers.sealer(name, field))
+    }
+
+    /// Records a whole value as the single byte string `seal` replaces it with, bypassing any
+    /// transformers.
+    fn serialize_sealed(
+        mut self,
+        seal: &SealFn,
+        value: &Dynamic,
+    ) -> Result<SchemaBuilderNode, TraceError> {
+        self.transform = None;
+        self.serialize_bytes(&seal(value))
+    }
+
+    #[inline]
+    fn enter_type(&mut self, name: &str) {
+        let transformers: &'a Transformers = self.transformers;
+        if !transformers.is_empty()
+            && let Some(transform) = transformers.for_type(name)
+        {
+            self.transform = Some(transform);
         }
     }
 
     #[inline]
     fn push_struct_name(&mut self, name: &'static str) -> Result<TypeName, TraceLimitErrorKind> {
+        self.enter_type(name);
         let name = self.type_names.intern(name)?;
         self.push_u32(name.into());
         Ok(TypeName(name, None))
@@ -248,6 +1087,7 @@ impl RootSerializer<'_> {
         name: &'static str,
         variant: &'static str,
     ) -> Result<TypeName, TraceLimitErrorKind> {
+        self.enter_type(name);
         let name = self.type_names.intern(name)?;
         let variant = self.variant_names.intern(variant)?;
         self.push_u32(name.into());
@@ -270,13 +1110,13 @@ impl RootSerializer<'_> {
         names: Vec<FieldNameIndex>,
     ) -> Result<FieldNameListIndex, TraceLimitErrorKind> {
         let names = self.field_name_lists.intern_from(names)?;
-        self.fill_reserved_bytes(index, &u32::from(names).to_le_bytes());
+        self.fill_reserved_bytes(index, &u32::from(names).to_trace_bytes());
         Ok(names)
     }
 
     #[inline]
     fn push_u32(&mut self, integer: u32) {
-        self.data.extend(integer.to_le_bytes());
+        self.data.extend(integer.to_trace_bytes());
     }
 
     #[inline]
@@ -284,7 +1124,7 @@ impl RootSerializer<'_> {
         self.data.extend(
             u32::try_from(length)
                 .map_err(|_| TraceLimitErrorKind::Values)?
-                .to_le_bytes(),
+                .to_trace_bytes(),
         );
         Ok(())
     }
@@ -301,7 +1141,7 @@ impl RootSerializer<'_> {
 
     #[inline]
     fn reserve_field_presence(&mut self, length: usize) -> Result<TraceIndex, TraceLimitErrorKind> {
-        self.reserve_bytes(std::mem::size_of::<u32>() * length)
+        self.reserve_bytes(presence_len(length))
     }
 
     #[inline]
@@ -314,7 +1154,9 @@ impl RootSerializer<'_> {
     #[inline]
     fn push_length_bytes(&mut self, bytes: &[u8]) -> Result<(), TraceLimitErrorKind> {
         self.push_u32_length(bytes.len())?;
-        self.data.extend(bytes);
+        if !self.schema_only {
+            self.data.extend(bytes);
+        }
         Ok(())
     }
 
@@ -323,14 +1165,44 @@ impl RootSerializer<'_> {
         self.data[index.into()..][..data.len()].copy_from_slice(data);
     }
 
+    /// Rewrites the trace of a sequence of `length` `u8`-s, whose length was reserved at `index`,
+    /// into the trace of a byte array: every item is a kind followed by the byte itself.
+    fn coerce_byte_sequence(&mut self, index: TraceIndex, length: usize) {
+        let index = usize::from(index);
+        self.data[index - 1] = TraceNodeKind::Bytes.into();
+        let items = index + std::mem::size_of::<u32>();
+        for item in 0..length {
+            self.data[items + item] = self.data[items + 2 * item + 1];
+        }
+        self.data.truncate(items + length);
+    }
+
+    /// Fills in the presence bitset of a struct, whose size depends on the number of skipped
+    /// fields, which is only known once the struct is finished. Resizes the bitset reserved for
+    /// `reserved_fields` fields if needed, moving the values of the fields after it, which only
+    /// happens the first time a struct is traced, see [`StructSchemaBuilder::new`].
     #[inline]
-    fn write_field_presence(
+    fn fill_reserved_field_presence(
         &mut self,
         index: TraceIndex,
-        field: MemberIndex,
-    ) -> Result<TraceIndex, TraceLimitErrorKind> {
-        self.fill_reserved_bytes(index, &u32::from(field).to_le_bytes());
-        TraceIndex::try_from(usize::from(index) + std::mem::size_of::<u32>())
+        reserved_fields: usize,
+        fields: usize,
+        skipped: &[MemberIndex],
+    ) {
+        let mut presence = vec![0u8; presence_len(fields)];
+        let mut skipped = skipped.iter().map(|&member| usize::from(member)).peekable();
+        for member in 0..fields {
+            if skipped.next_if_eq(&member).is_none() {
+                presence[member / 8] |= 1 << (member % 8);
+            }
+        }
+        let index = usize::from(index);
+        let reserved = presence_len(reserved_fields);
+        if presence.len() == reserved {
+            self.data[index..][..reserved].copy_from_slice(&presence);
+        } else {
+            self.data.splice(index..index + reserved, presence);
+        }
     }
 }
 
@@ -363,49 +1235,88 @@ pub(crate) enum SchemaBuilderNode {
     Unit(Option<TypeName>),
     Newtype(TypeName, Box<SchemaBuilderNode>),
 
-    Map(Box<SchemaBuilderNode>, Box<SchemaBuilderNode>),
-    Sequence(Box<SchemaBuilderNode>),
+    /// Keys, values and the number of entries.
+    Map(Box<SchemaBuilderNode>, Box<SchemaBuilderNode>, Occurrences),
+    /// Items and their number.
+    Sequence(Box<SchemaBuilderNode>, Occurrences),
 
-    Union(Vec<SchemaBuilderNode>),
+    /// Members and how many times each of them was traced.
+    ///
+    /// Members never share their outermost shape, since [`unify`][`Self::unify`] merges those,
+    /// which is what lets serialization pick a member from the outermost node of a trace alone.
+    /// So nested unions, e.g. those of `Option<Wrapper(Option<T>)>`, are deliberately not
+    /// flattened into a single union of `Some(Wrapper(None))`, `Some(Wrapper(Some(T)))`, etc.
+    Union(Vec<(SchemaBuilderNode, Occurrences)>),
 
     /// Tuple, tuple struct, tuple variant, struct or struct variant.
     Record {
         name: Option<TypeName>,
         field_names: Option<FieldNameListIndex>,
         field_types: Vec<SchemaBuilderNode>,
-        skippable: Vec<MemberIndex>,
+        /// The fields which were skipped, and how many times.
+        skippable: Vec<(MemberIndex, Occurrences)>,
     },
 }
 
 impl SchemaBuilderNode {
-    fn unify(&mut self, other: Self) -> Result<(), Self> {
+    /// Whether the node is a single primitive value, like the ones logical types wrap.
+    fn is_primitive(&self) -> bool {
+        matches!(
+            self,
+            SchemaBuilderNode::Bool
+                | SchemaBuilderNode::I8
+                | SchemaBuilderNode::I16
+                | SchemaBuilderNode::I32
+                | SchemaBuilderNode::I64
+                | SchemaBuilderNode::I128
+                | SchemaBuilderNode::U8
+                | SchemaBuilderNode::U16
+                | SchemaBuilderNode::U32
+                | SchemaBuilderNode::U64
+                | SchemaBuilderNode::U128
+                | SchemaBuilderNode::F32
+                | SchemaBuilderNode::F64
+                | SchemaBuilderNode::Char
+                | SchemaBuilderNode::String
+                | SchemaBuilderNode::Bytes
+        )
+    }
+
+    /// Merges `other`, traced `other_count` times, into this node, traced `count` times, or
+    /// returns it back if the two can only be merged into a union.
+    ///
+    /// Returns whether the node widened, i.e. whether it describes any shape it didn't before.
+    /// Occurrence counts don't count.
+    fn unify(&mut self, count: u64, other: Self, other_count: u64) -> Result<bool, Self> {
         match (&mut *self, other) {
             (SchemaBuilderNode::Union(lefts), right) => {
                 if lefts.is_empty() {
+                    let widened = right != SchemaBuilderNode::default();
                     *self = right;
+                    Ok(widened)
                 } else {
-                    right.add_to_nonempty_union(lefts);
+                    Ok(right.add_to_nonempty_union(other_count, lefts))
                 }
-                Ok(())
             }
+            (_, SchemaBuilderNode::Union(rights)) if rights.is_empty() => Ok(false),
             (left, mut right @ SchemaBuilderNode::Union(_)) => {
+                // The members of a non-empty union never unify with each other, so whatever
+                // `left` is, it widens into the union.
                 std::mem::swap(left, &mut right);
-                left.unify(right)
+                left.unify(other_count, right, count).map(|_| true)
             }
             (
                 SchemaBuilderNode::Newtype(left_name, left_inner),
                 SchemaBuilderNode::Newtype(right_name, right_inner),
             ) => {
                 if *left_name == right_name {
-                    left_inner.union(*right_inner);
-                    Ok(())
+                    Ok(left_inner.union(count, *right_inner, other_count))
                 } else {
                     Err(SchemaBuilderNode::Newtype(right_name, right_inner))
                 }
             }
             (SchemaBuilderNode::OptionSome(left), SchemaBuilderNode::OptionSome(right)) => {
-                left.union(*right);
-                Ok(())
+                Ok(left.union(count, *right, other_count))
             }
             (
                 SchemaBuilderNode::Record {
@@ -424,14 +1335,31 @@ impl SchemaBuilderNode {
                 if (*left_name, *left_field_names, left_field_types.len())
                     == (right_name, right_field_names, right_field_types.len())
                 {
-                    left_field_types
+                    let mut widened = false;
+                    for (index, (left, right)) in left_field_types
                         .iter_mut()
                         .zip(right_field_types)
-                        .for_each(|(left, right)| left.union(right));
+                        .enumerate()
+                    {
+                        widened |= left.union(
+                            count - skipped_count(left_skippable, index),
+                            right,
+                            other_count - skipped_count(&right_skippable, index),
+                        );
+                    }
+                    widened |= right_skippable
+                        .iter()
+                        .any(|&(index, _)| !left_skippable.iter().any(|&(kept, _)| kept == index));
                     left_skippable.extend(right_skippable);
-                    left_skippable.sort_unstable();
-                    left_skippable.dedup();
-                    Ok(())
+                    left_skippable.sort_unstable_by_key(|&(index, _)| index);
+                    left_skippable.dedup_by(|(index, skipped), (kept_index, kept)| {
+                        let duplicate = index == kept_index;
+                        if duplicate {
+                            kept.0 += skipped.0;
+                        }
+                        duplicate
+                    });
+                    Ok(widened)
                 } else {
                     Err(SchemaBuilderNode::Record {
                         name: right_name,
@@ -442,20 +1370,25 @@ impl SchemaBuilderNode {
                 }
             }
             (
-                SchemaBuilderNode::Map(left_keys, left_values),
-                SchemaBuilderNode::Map(right_keys, right_values),
+                SchemaBuilderNode::Map(left_keys, left_values, Occurrences(left_length)),
+                SchemaBuilderNode::Map(right_keys, right_values, Occurrences(right_length)),
             ) => {
-                left_keys.union(*right_keys);
-                left_values.union(*right_values);
-                Ok(())
+                let widened_keys = left_keys.union(*left_length, *right_keys, right_length);
+                let widened_values = left_values.union(*left_length, *right_values, right_length);
+                *left_length += right_length;
+                Ok(widened_keys || widened_values)
             }
-            (SchemaBuilderNode::Sequence(left), SchemaBuilderNode::Sequence(right)) => {
-                left.union(*right);
-                Ok(())
+            (
+                SchemaBuilderNode::Sequence(left, Occurrences(left_length)),
+                SchemaBuilderNode::Sequence(right, Occurrences(right_length)),
+            ) => {
+                let widened = left.union(*left_length, *right, right_length);
+                *left_length += right_length;
+                Ok(widened)
             }
             (left, right) => {
                 if *left == right {
-                    Ok(())
+                    Ok(false)
                 } else {
                     Err(right)
                 }
@@ -463,39 +1396,244 @@ impl SchemaBuilderNode {
         }
     }
 
+    /// Returns the first pair of nodes of different types which unioning `other` into this node
+    /// would put in a union, see [`SchemaBuilder::forbid_unions`].
+    ///
+    /// Unions of `None` and `Some`, or of the variants of the same enum, describe a single type,
+    /// so they don't count.
+    fn union_conflict<'a>(&'a self, other: &'a Self) -> Option<(&'a Self, &'a Self)> {
+        match (self, other) {
+            (SchemaBuilderNode::Union(lefts), _) if lefts.is_empty() => None,
+            (_, SchemaBuilderNode::Union(rights)) => rights
+                .iter()
+                .find_map(|(right, _)| self.union_conflict(right)),
+            (SchemaBuilderNode::Union(lefts), right) => {
+                match lefts.iter().find(|(left, _)| left.same_shape(right)) {
+                    Some((left, _)) => left.union_conflict(right),
+                    None => {
+                        let (first, _) = &lefts[0];
+                        (!first.same_type(right)).then_some((first, right))
+                    }
+                }
+            }
+            (SchemaBuilderNode::Newtype(_, left), SchemaBuilderNode::Newtype(_, right))
+            | (SchemaBuilderNode::OptionSome(left), SchemaBuilderNode::OptionSome(right))
+            | (SchemaBuilderNode::Sequence(left, _), SchemaBuilderNode::Sequence(right, _))
+                if self.same_shape(other) =>
+            {
+                left.union_conflict(right)
+            }
+            (
+                SchemaBuilderNode::Map(left_keys, left_values, _),
+                SchemaBuilderNode::Map(right_keys, right_values, _),
+            ) => left_keys
+                .union_conflict(right_keys)
+                .or_else(|| left_values.union_conflict(right_values)),
+            (
+                SchemaBuilderNode::Record {
+                    field_types: lefts, ..
+                },
+                SchemaBuilderNode::Record {
+                    field_types: rights,
+                    ..
+                },
+            ) if self.same_shape(other) => lefts
+                .iter()
+                .zip(rights)
+                .find_map(|(left, right)| left.union_conflict(right)),
+            (left, right) => {
+                (!left.same_shape(right) && !left.same_type(right)).then_some((left, right))
+            }
+        }
+    }
+
+    /// Whether [`unify`][`Self::unify`] would merge the two nodes, at least at the top level.
+    fn same_shape(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SchemaBuilderNode::Newtype(left, _), SchemaBuilderNode::Newtype(right, _)) => {
+                left == right
+            }
+            (
+                SchemaBuilderNode::Record {
+                    name: left_name,
+                    field_names: left_field_names,
+                    field_types: left_field_types,
+                    ..
+                },
+                SchemaBuilderNode::Record {
+                    name: right_name,
+                    field_names: right_field_names,
+                    field_types: right_field_types,
+                    ..
+                },
+            ) => {
+                (left_name, left_field_names, left_field_types.len())
+                    == (right_name, right_field_names, right_field_types.len())
+            }
+            (SchemaBuilderNode::OptionSome(_), SchemaBuilderNode::OptionSome(_))
+            | (SchemaBuilderNode::Map(..), SchemaBuilderNode::Map(..))
+            | (SchemaBuilderNode::Sequence(..), SchemaBuilderNode::Sequence(..)) => true,
+            (left, right) => left == right,
+        }
+    }
+
+    /// Whether the two nodes are different shapes of the same Rust type, i.e. `None` and
+    /// `Some`, or two variants of the same enum.
+    fn same_type(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (
+                SchemaBuilderNode::OptionNone | SchemaBuilderNode::OptionSome(_),
+                SchemaBuilderNode::OptionNone | SchemaBuilderNode::OptionSome(_),
+            )
+        ) || self
+            .enum_name()
+            .is_some_and(|name| other.enum_name() == Some(name))
+    }
+
+    /// The name of the enum this node is a variant of, if it is one.
+    fn enum_name(&self) -> Option<TypeNameIndex> {
+        match self {
+            SchemaBuilderNode::Unit(Some(TypeName(name, Some(_))))
+            | SchemaBuilderNode::Newtype(TypeName(name, Some(_)), _)
+            | SchemaBuilderNode::Record {
+                name: Some(TypeName(name, Some(_))),
+                ..
+            } => Some(*name),
+            _ => None,
+        }
+    }
+
+    /// Adds the fields of the structs in this node, traced `count` times, to `stats`.
+    fn add_skip_stats(&self, count: u64, builder: &SchemaBuilder, stats: &mut SkipStats) {
+        match self {
+            SchemaBuilderNode::OptionSome(inner) | SchemaBuilderNode::Newtype(_, inner) => {
+                inner.add_skip_stats(count, builder, stats);
+            }
+            SchemaBuilderNode::Sequence(items, Occurrences(length)) => {
+                items.add_skip_stats(*length, builder, stats);
+            }
+            SchemaBuilderNode::Map(keys, values, Occurrences(length)) => {
+                keys.add_skip_stats(*length, builder, stats);
+                values.add_skip_stats(*length, builder, stats);
+            }
+            SchemaBuilderNode::Union(members) => {
+                for (member, Occurrences(count)) in members {
+                    member.add_skip_stats(*count, builder, stats);
+                }
+            }
+            SchemaBuilderNode::Record {
+                name,
+                field_names,
+                field_types,
+                skippable,
+            } => {
+                let struct_name = name
+                    .zip(*field_names)
+                    .map(|(TypeName(name, variant), names)| {
+                        let name = builder.type_name(name);
+                        let name = match variant {
+                            Some(variant) => format!("{name}::{}", builder.variant_name(variant)),
+                            None => name.to_owned(),
+                        };
+                        (name, builder.field_names(names))
+                    });
+                for (index, field_type) in field_types.iter().enumerate() {
+                    let skipped = skipped_count(skippable, index);
+                    let present = count.saturating_sub(skipped);
+                    if let Some((name, field)) = struct_name
+                        .as_ref()
+                        .and_then(|(name, fields)| Some((name, *fields.get(index)?)))
+                    {
+                        stats.add(name.clone(), field, present, skipped);
+                    }
+                    field_type.add_skip_stats(present, builder, stats);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Like [`unify`][`Self::unify`], but makes a union of the two nodes if needed. Returns
+    /// whether the node widened.
     #[inline]
-    fn union(&mut self, other: Self) {
-        if let Err(other) = self.unify(other) {
-            let left = std::mem::take(self);
-            match self {
-                SchemaBuilderNode::Union(schemas) => *schemas = vec![left, other],
-                _ => unreachable!(),
+    fn union(&mut self, count: u64, other: Self, other_count: u64) -> bool {
+        match self.unify(count, other, other_count) {
+            Ok(widened) => widened,
+            Err(other) => {
+                let left = std::mem::take(self);
+                match self {
+                    SchemaBuilderNode::Union(schemas) => {
+                        *schemas = vec![
+                            (left, Occurrences(count)),
+                            (other, Occurrences(other_count)),
+                        ]
+                    }
+                    _ => unreachable!(),
+                }
+                true
             }
         }
     }
 
-    fn add_to_nonempty_union(self, lefts: &mut Vec<SchemaBuilderNode>) {
+    /// Unifies this node into the first member of `lefts` it unifies with, or appends it as a new
+    /// member. Returns whether the union widened.
+    fn add_to_nonempty_union(
+        self,
+        count: u64,
+        lefts: &mut Vec<(SchemaBuilderNode, Occurrences)>,
+    ) -> bool {
         assert!(!lefts.is_empty());
         match self {
             SchemaBuilderNode::Union(rights) => {
-                rights
-                    .into_iter()
-                    .for_each(|right| right.add_to_nonempty_union(lefts));
+                let mut widened = false;
+                for (right, Occurrences(count)) in rights {
+                    widened |= right.add_to_nonempty_union(count, lefts);
+                }
+                widened
             }
-            right => {
-                let right = lefts
-                    .iter_mut()
-                    .try_fold(right, |right, left| match left.unify(right) {
-                        Ok(()) => Err(()),
-                        Err(recovered) => Ok(recovered),
-                    })
-                    .ok();
-                lefts.extend(right);
+            mut right => {
+                for (left, Occurrences(left_count)) in lefts.iter_mut() {
+                    match left.unify(*left_count, right, count) {
+                        Ok(widened) => {
+                            *left_count += count;
+                            return widened;
+                        }
+                        Err(recovered) => right = recovered,
+                    }
+                }
+                lefts.push((right, Occurrences(count)));
+                true
             }
         }
     }
 }
 
+/// How many times a field of a record was skipped.
+#[inline]
+fn skipped_count(skippable: &[(MemberIndex, Occurrences)], index: usize) -> u64 {
+    skippable
+        .iter()
+        .find(|&&(skipped, _)| usize::from(skipped) == index)
+        .map_or(0, |&(_, Occurrences(count))| count)
+}
+
+/// How many times a node was traced, used for [`UnionStats`].
+///
+/// Counts don't affect whether nodes are equal, so traces which only repeat shapes already in the
+/// schema don't change it.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub(crate) struct Occurrences(u64);
+
+impl PartialEq for Occurrences {
+    #[inline]
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Occurrences {}
+
 impl Default for SchemaBuilderNode {
     #[inline]
     fn default() -> Self {
@@ -505,6 +1643,7 @@ impl Default for SchemaBuilderNode {
 
 impl SchemaBuilderNode {
     fn build(self, builder: &mut SchemaBuilder) -> Result<SchemaNodeIndex, TraceError> {
+        let mut member_counts = None;
         let built = match self {
             SchemaBuilderNode::Bool => SchemaNode::Bool,
             SchemaBuilderNode::I8 => SchemaNode::I8,
@@ -545,22 +1684,35 @@ impl SchemaBuilderNode {
                     }
                 }
             }
-            SchemaBuilderNode::Map(key, value) => {
+            SchemaBuilderNode::Map(key, value, _) => {
                 SchemaNode::Map(key.build(builder)?, value.build(builder)?)
             }
-            SchemaBuilderNode::Sequence(item) => SchemaNode::Sequence(item.build(builder)?),
+            SchemaBuilderNode::Sequence(item, _) => SchemaNode::Sequence(item.build(builder)?),
             SchemaBuilderNode::Union(variants) => {
-                let mut variants = variants
-                    .into_iter()
-                    .map(|variant| variant.build(builder))
-                    .collect::<Result<Vec<_>, _>>()?;
-                variants.sort_unstable();
-                variants.dedup();
+                // Members keep the order in which they were first traced, with new ones appended,
+                // since their positions are their discriminants: widening a union, or a type
+                // nested in one of its members, doesn't renumber the others.
+                let mut positions = HashMap::with_capacity(variants.len());
+                let mut counts = Vec::with_capacity(variants.len());
+                let mut built = Vec::with_capacity(variants.len());
+                for (variant, Occurrences(count)) in variants {
+                    let variant = variant.build(builder)?;
+                    match positions.entry(variant) {
+                        Entry::Occupied(position) => counts[*position.get()] += count,
+                        Entry::Vacant(position) => {
+                            position.insert(built.len());
+                            built.push(variant);
+                            counts.push(count);
+                        }
+                    }
+                }
+                let variants = built;
                 if variants.len()
                     > usize::try_from(u32::MAX).expect("usize must be at least 32 bits")
                 {
                     return Err(TraceError::from(TraceLimitErrorKind::UnionVariants));
                 }
+                member_counts = Some(counts);
                 SchemaNode::Union(builder.node_lists.intern_from(variants)?)
             }
             SchemaBuilderNode::Record {
@@ -580,15 +1732,16 @@ impl SchemaBuilderNode {
                 //    require discriminant bits.
                 // 3. Fields that are sometimes skipped (type != Union[], present in skip list).
                 //    Require discriminant bits.
-                skippable.retain(|&index| {
+                skippable.retain(|&(index, _)| {
                     !matches!(
                         &field_types[usize::from(index)],
                         SchemaBuilderNode::Union(variants) if variants.is_empty()
                     )
                 });
-                if skippable.len() > MAX_SKIPPABLE_FIELDS {
-                    return Err(TraceError::from(TraceLimitErrorKind::SkippableFields))?;
-                }
+                let skippable = skippable
+                    .into_iter()
+                    .map(|(index, _)| index)
+                    .collect::<Vec<_>>();
                 let field_types = field_types
                     .into_iter()
                     .map(|field_type| field_type.build(builder))
@@ -622,7 +1775,11 @@ impl SchemaBuilderNode {
                 }
             }
         };
-        builder.nodes.intern(built).map_err(Into::into)
+        let index = builder.nodes.intern(built)?;
+        if let Some(member_counts) = member_counts {
+            builder.union_stats.add(index, &member_counts);
+        }
+        Ok(index)
     }
 }
 
@@ -631,6 +1788,9 @@ macro_rules! fn_serialize_as_u8 {
         $(
             #[inline]
             fn $fn_name(mut self, value: $value_type) -> Result<Self::Ok, Self::Error> {
+                if let Some(transform) = self.transform.take() {
+                    return transform(LeafValue::$node(value)).serialize(self);
+                }
                 self.push_trace(TraceNodeKind::$node);
                 self.data.push(value as u8);
                 Ok(SchemaBuilderNode::$node)
@@ -639,14 +1799,16 @@ macro_rules! fn_serialize_as_u8 {
     };
 }
 
-macro_rules! fn_serialize_as_le_bytes {
+macro_rules! fn_serialize_as_trace_bytes {
     ($(($fn_name:ident, $value_type:ty, $node:ident ),)+) => {
         $(
             #[inline]
             fn $fn_name(mut self, value: $value_type) -> Result<Self::Ok, Self::Error> {
-
+                if let Some(transform) = self.transform.take() {
+                    return transform(LeafValue::$node(value)).serialize(self);
+                }
                 self.push_trace(TraceNodeKind::$node);
-                self.data.extend_from_slice(&value.to_le_bytes());
+                self.data.extend_from_slice(&value.to_trace_bytes());
                 Ok(SchemaBuilderNode::$node)
             }
         )+
@@ -671,7 +1833,7 @@ impl<'a> Serializer for RootSerializer<'a> {
         (serialize_u8, u8, U8),
     }
 
-    fn_serialize_as_le_bytes! {
+    fn_serialize_as_trace_bytes! {
         (serialize_i16, i16, I16),
         (serialize_i32, i32, I32),
         (serialize_i64, i64, I64),
@@ -680,12 +1842,41 @@ impl<'a> Serializer for RootSerializer<'a> {
         (serialize_u32, u32, U32),
         (serialize_u64, u64, U64),
         (serialize_u128, u128, U128),
-        (serialize_f32, f32, F32),
-        (serialize_f64, f64, F64),
+    }
+
+    #[inline]
+    fn serialize_f32(mut self, value: f32) -> Result<Self::Ok, Self::Error> {
+        if let Some(transform) = self.transform.take() {
+            return transform(LeafValue::F32(value)).serialize(self);
+        }
+        let value = self
+            .float_normalization
+            .normalize_f32(value)
+            .ok_or(TraceError::Nan)?;
+        self.push_trace(TraceNodeKind::F32);
+        self.data.extend_from_slice(&value.to_trace_bytes());
+        Ok(SchemaBuilderNode::F32)
+    }
+
+    #[inline]
+    fn serialize_f64(mut self, value: f64) -> Result<Self::Ok, Self::Error> {
+        if let Some(transform) = self.transform.take() {
+            return transform(LeafValue::F64(value)).serialize(self);
+        }
+        let value = self
+            .float_normalization
+            .normalize_f64(value)
+            .ok_or(TraceError::Nan)?;
+        self.push_trace(TraceNodeKind::F64);
+        self.data.extend_from_slice(&value.to_trace_bytes());
+        Ok(SchemaBuilderNode::F64)
     }
 
     #[inline]
     fn serialize_char(mut self, value: char) -> Result<Self::Ok, Self::Error> {
+        if let Some(transform) = self.transform.take() {
+            return transform(LeafValue::Char(value)).serialize(self);
+        }
         self.push_trace(TraceNodeKind::Char);
         self.push_u32(u32::from(value));
         Ok(SchemaBuilderNode::Char)
@@ -693,13 +1884,53 @@ impl<'a> Serializer for RootSerializer<'a> {
 
     #[inline]
     fn serialize_str(mut self, value: &str) -> Result<Self::Ok, Self::Error> {
+        if let Some(transform) = self.transform.take() {
+            return transform(LeafValue::Str(Cow::Borrowed(value))).serialize(self);
+        }
+        self.check_data_budget(value.len())?;
         self.push_trace(TraceNodeKind::String);
         self.push_length_bytes(value.as_bytes())?;
         Ok(SchemaBuilderNode::String)
     }
 
+    /// Formats the value straight into the trace, instead of into an intermediate `String`.
+    fn collect_str<T>(mut self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: std::fmt::Display + ?Sized,
+    {
+        if self.transform.is_some() {
+            return self.serialize_str(&value.to_string());
+        }
+        // Like `serialize_str`, only count the string itself against the budget.
+        let used_bytes = self.data.len();
+        self.push_trace(TraceNodeKind::String);
+        let length_index = self.reserve_u32()?;
+        let mut writer = TraceWriter {
+            used_bytes,
+            length: 0,
+            max_data_bytes: self.max_data_bytes,
+            over_budget: false,
+            schema_only: self.schema_only,
+            data: self.data,
+        };
+        if std::fmt::write(&mut writer, format_args!("{value}")).is_err() {
+            return Err(if writer.over_budget {
+                DataBudgetError::new(self.max_data_bytes).into()
+            } else {
+                TraceError::Custom("a Display implementation returned an error".into())
+            });
+        }
+        let length = u32::try_from(writer.length).map_err(|_| TraceLimitErrorKind::Values)?;
+        self.fill_reserved_bytes(length_index, &length.to_trace_bytes());
+        Ok(SchemaBuilderNode::String)
+    }
+
     #[inline]
     fn serialize_bytes(mut self, value: &[u8]) -> Result<Self::Ok, Self::Error> {
+        if let Some(transform) = self.transform.take() {
+            return transform(LeafValue::Bytes(Cow::Borrowed(value))).serialize(self);
+        }
+        self.check_data_budget(value.len())?;
         self.push_trace(TraceNodeKind::Bytes);
         self.push_length_bytes(value)?;
         Ok(SchemaBuilderNode::Bytes)
@@ -739,6 +1970,17 @@ impl<'a> Serializer for RootSerializer<'a> {
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
+        let transformers: &'a Transformers = self.transformers;
+        if !transformers.is_empty()
+            && let Some(seal) = transformers.sealer(name, None)
+        {
+            let value = Dynamic::Variant {
+                name: name.into(),
+                variant: variant.into(),
+                fields: DynamicFields::Unit,
+            };
+            return self.serialize_sealed(seal, &value);
+        }
         self.push_trace(TraceNodeKind::UnitVariant);
         Ok(SchemaBuilderNode::Unit(Some(
             self.push_variant_name(name, variant)?,
@@ -755,10 +1997,26 @@ impl<'a> Serializer for RootSerializer<'a> {
         T: ?Sized + Serialize,
     {
         self.push_trace(TraceNodeKind::NewtypeStruct);
-        Ok(SchemaBuilderNode::Newtype(
-            self.push_struct_name(name)?,
-            Box::new(T::serialize(value, self)?),
-        ))
+        let logical_types: &'a LogicalTypes = self.logical_types;
+        let logical = logical_types.for_type(name).map(|logical| {
+            self.transform = Some(&*logical.encode);
+            logical.id
+        });
+        let name = self.push_struct_name(logical.unwrap_or(name))?;
+        let inner = match self.sealer(name, None) {
+            Some(seal) => {
+                let value =
+                    Dynamic::from_value(value, self.float_normalization, self.human_readable)?;
+                self.serialize_sealed(seal, &value)?
+            }
+            None => T::serialize(value, self)?,
+        };
+        if let Some(id) = logical
+            && !inner.is_primitive()
+        {
+            return Err(TraceError::LogicalType(id));
+        }
+        Ok(SchemaBuilderNode::Newtype(name, Box::new(inner)))
     }
 
     #[inline]
@@ -773,9 +2031,15 @@ impl<'a> Serializer for RootSerializer<'a> {
         T: ?Sized + Serialize,
     {
         self.push_trace(TraceNodeKind::NewtypeVariant);
+        let name = self.push_variant_name(name, variant)?;
+        let inner = match self.sealer(name, None) {
+            Some(seal) => Dynamic::from_value(value, self.float_normalization, self.human_readable)
+                .and_then(|value| self.serialize_sealed(seal, &value)),
+            None => T::serialize(value, self),
+        };
         Ok(SchemaBuilderNode::Newtype(
-            self.push_variant_name(name, variant)?,
-            Box::new(T::serialize(value, self)?),
+            name,
+            Box::new(inner.map_err(|error| error.within(|| PathSegment::Variant(variant.into())))?),
         ))
     }
 
@@ -841,6 +2105,7 @@ impl<'a> Serializer for RootSerializer<'a> {
             key_schema: SchemaBuilderNode::default(),
             value_schema: SchemaBuilderNode::default(),
             length: 0,
+            awaiting_value: false,
             parent: self,
         })
     }
@@ -869,7 +2134,7 @@ impl<'a> Serializer for RootSerializer<'a> {
 
     #[inline]
     fn is_human_readable(&self) -> bool {
-        false
+        self.human_readable
     }
 }
 
@@ -889,9 +2154,13 @@ impl<'a> SerializeSeq for SequenceSchemaBuilder<'a> {
     where
         T: ?Sized + serde::Serialize,
     {
+        let index = self.length;
         self.length += 1;
-        self.item
-            .union(T::serialize(value, self.parent.reborrow())?);
+        let item = T::serialize(value, self.parent.reborrow())
+            .and_then(|item| self.parent.check_data_budget(0).map(|()| item))
+            .and_then(|item| self.parent.check_union(&self.item, item))
+            .map_err(|error| error.within(|| PathSegment::Index(index)))?;
+        self.item.union(index as u64, item, 1);
         Ok(())
     }
 
@@ -901,9 +2170,20 @@ impl<'a> SerializeSeq for SequenceSchemaBuilder<'a> {
             self.reserved_length,
             &u32::try_from(self.length)
                 .map_err(|_| TraceLimitErrorKind::Values)?
-                .to_le_bytes(),
+                .to_trace_bytes(),
         );
-        Ok(SchemaBuilderNode::Sequence(Box::new(self.item)))
+        if self.parent.coerce_byte_sequences
+            && self.length > 0
+            && matches!(self.item, SchemaBuilderNode::U8)
+        {
+            self.parent
+                .coerce_byte_sequence(self.reserved_length, self.length);
+            return Ok(SchemaBuilderNode::Bytes);
+        }
+        Ok(SchemaBuilderNode::Sequence(
+            Box::new(self.item),
+            Occurrences(self.length as u64),
+        ))
     }
 }
 
@@ -913,6 +2193,10 @@ pub(crate) struct MapSchemaBuilder<'a> {
     key_schema: SchemaBuilderNode,
     value_schema: SchemaBuilderNode,
     length: usize,
+    /// Whether a key was serialized without its value yet. Each value is unioned into the
+    /// value schema with the count of the values before it, which is only right if keys and
+    /// values alternate.
+    awaiting_value: bool,
 }
 
 impl SerializeMap for MapSchemaBuilder<'_> {
@@ -924,9 +2208,18 @@ impl SerializeMap for MapSchemaBuilder<'_> {
     where
         T: ?Sized + serde::Serialize,
     {
+        if std::mem::replace(&mut self.awaiting_value, true) {
+            return Err(TraceError::Custom(
+                "map key serialized without the value of the previous key".into(),
+            ));
+        }
+        let index = self.length;
         self.length += 1;
-        self.key_schema
-            .union(T::serialize(key, self.parent.reborrow())?);
+        let key = T::serialize(key, self.parent.reborrow())
+            .and_then(|key| self.parent.check_data_budget(0).map(|()| key))
+            .and_then(|key| self.parent.check_union(&self.key_schema, key))
+            .map_err(|error| error.within(|| PathSegment::Index(index)))?;
+        self.key_schema.union(index as u64, key, 1);
         Ok(())
     }
 
@@ -935,22 +2228,37 @@ impl SerializeMap for MapSchemaBuilder<'_> {
     where
         T: ?Sized + serde::Serialize,
     {
-        self.value_schema
-            .union(T::serialize(value, self.parent.reborrow())?);
+        if !std::mem::replace(&mut self.awaiting_value, false) {
+            return Err(TraceError::Custom(
+                "map value serialized without a key".into(),
+            ));
+        }
+        let index = self.length - 1;
+        let value = T::serialize(value, self.parent.reborrow())
+            .and_then(|value| self.parent.check_data_budget(0).map(|()| value))
+            .and_then(|value| self.parent.check_union(&self.value_schema, value))
+            .map_err(|error| error.within(|| PathSegment::Index(index)))?;
+        self.value_schema.union(index as u64, value, 1);
         Ok(())
     }
 
     #[inline]
     fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        if self.awaiting_value {
+            return Err(TraceError::Custom(
+                "map ended without the value of its last key".into(),
+            ));
+        }
         self.parent.fill_reserved_bytes(
             self.reserved_length,
             &u32::try_from(self.length)
                 .map_err(|_| TraceLimitErrorKind::Values)?
-                .to_le_bytes(),
+                .to_trace_bytes(),
         );
         Ok(SchemaBuilderNode::Map(
             Box::new(self.key_schema),
             Box::new(self.value_schema),
+            Occurrences(self.length as u64),
         ))
     }
 }
@@ -970,8 +2278,22 @@ impl SerializeTuple for TupleSchemaBuilder<'_> {
     where
         T: ?Sized + serde::Serialize,
     {
-        self.schemas
-            .push(T::serialize(value, self.parent.reborrow())?);
+        let index = self.schemas.len();
+        let parent = self.parent.reborrow();
+        let item = match self.name.and_then(|name| parent.sealer(name, None)) {
+            Some(seal) => {
+                Dynamic::from_value(value, parent.float_normalization, parent.human_readable)
+                    .and_then(|value| parent.serialize_sealed(seal, &value))
+            }
+            None => T::serialize(value, parent),
+        };
+        let item = item
+            .and_then(|item| self.parent.check_data_budget(0).map(|()| item))
+            .map_err(|error| {
+                let field = PathSegment::Field(index.to_string().into());
+                self.parent.field_error(self.name, field, error)
+            })?;
+        self.schemas.push(item);
         Ok(())
     }
 
@@ -1027,6 +2349,10 @@ pub(crate) struct StructSchemaBuilder<'a> {
     name: TypeName,
     reserved_field_name_list: TraceIndex,
     reserved_field_presence: TraceIndex,
+    /// The number of fields the presence bitset was reserved for.
+    reserved_fields: usize,
+    /// The number of fields the struct was last traced with, if it was traced before.
+    last_fields: Option<usize>,
     field_names: Vec<FieldNameIndex>,
     field_types: Vec<SchemaBuilderNode>,
     skipped: Vec<MemberIndex>,
@@ -1042,13 +2368,18 @@ impl<'a> StructSchemaBuilder<'a> {
         // Note that, maybe counter-intuitively, this `length` does NOT include skipped fields.
         // This explicitly documented by `serde`.
         //
-        // So we're reserving precisely as much data as we're going to serialize. This is important
-        // for the whole "skippable" field logic to work.
-        parent.push_u32_length(length)?;
+        // So the presence bitset is reserved for the number of fields the struct had when it was
+        // last traced, which is the same every time for Rust types. Only the first time can the
+        // bitset be too small, if enough fields are skipped, in which case it's grown in `end`,
+        // once the number of skipped fields is known.
+        let last_fields = parent.struct_fields.get(&name).copied();
+        let reserved_fields = last_fields.unwrap_or(length);
         Ok(Self {
             name,
             reserved_field_name_list,
-            reserved_field_presence: parent.reserve_field_presence(length)?,
+            reserved_field_presence: parent.reserve_field_presence(reserved_fields)?,
+            reserved_fields,
+            last_fields,
             field_names: Vec::with_capacity(length),
             field_types: Vec::with_capacity(length),
             skipped: Vec::new(),
@@ -1066,13 +2397,27 @@ impl SerializeStruct for StructSchemaBuilder<'_> {
     where
         T: ?Sized + serde::Serialize,
     {
-        self.reserved_field_presence = self.parent.write_field_presence(
-            self.reserved_field_presence,
-            MemberIndex::try_from(self.field_names.len())?,
-        )?;
+        // Fails for structs with more members than fit in a `MemberIndex`.
+        MemberIndex::try_from(self.field_names.len())?;
         self.field_names.push(self.parent.intern_field_name(key)?);
-        self.field_types
-            .push(T::serialize(value, self.parent.reborrow())?);
+        let mut parent = self.parent.reborrow();
+        let field = match parent.sealer(self.name, Some(key)) {
+            Some(seal) => {
+                Dynamic::from_value(value, parent.float_normalization, parent.human_readable)
+                    .and_then(|value| parent.serialize_sealed(seal, &value))
+            }
+            None => {
+                parent.transform = parent.field_transform(self.name, key);
+                T::serialize(value, parent)
+            }
+        };
+        let field = field
+            .and_then(|field| self.parent.check_data_budget(0).map(|()| field))
+            .map_err(|error| {
+                let field = PathSegment::Field(key.into());
+                self.parent.field_error(Some(self.name), field, error)
+            })?;
+        self.field_types.push(field);
         Ok(())
     }
 
@@ -1086,6 +2431,17 @@ impl SerializeStruct for StructSchemaBuilder<'_> {
 
     #[inline]
     fn end(mut self) -> Result<Self::Ok, Self::Error> {
+        self.parent.fill_reserved_field_presence(
+            self.reserved_field_presence,
+            self.reserved_fields,
+            self.field_names.len(),
+            &self.skipped,
+        );
+        if self.last_fields != Some(self.field_names.len()) {
+            self.parent
+                .struct_fields
+                .insert(self.name, self.field_names.len());
+        }
         let field_names = Some(
             self.parent
                 .fill_reserved_field_name_list(self.reserved_field_name_list, self.field_names)?,
@@ -1094,7 +2450,11 @@ impl SerializeStruct for StructSchemaBuilder<'_> {
             name: Some(self.name),
             field_names,
             field_types: self.field_types,
-            skippable: self.skipped,
+            skippable: self
+                .skipped
+                .into_iter()
+                .map(|index| (index, Occurrences(1)))
+                .collect(),
         })
     }
 }
@@ -0,0 +1,434 @@
+use std::{
+    cell::Cell,
+    hash::{Hash, Hasher},
+};
+use thiserror::Error;
+
+use crate::{
+    DescribedBy, FloatNormalization, Schema, SchemaBuilder, Trace,
+    dump::DumpError,
+    indices::FieldNameListIndex,
+    trace::{InvalidTraceError, ReadTraceExt, TraceNode, TraceNodeKind},
+};
+
+/// Returned by [`DescribedBy::encode_canonical`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum CanonicalEncodingError {
+    /// The trace doesn't match its schema.
+    #[error(transparent)]
+    InvalidTrace(#[from] InvalidTraceError),
+
+    /// A map has keys which can't be ordered canonically, e.g. floats, which have several
+    /// encodings of equal values, or structs. Only booleans, integers, chars, strings, byte
+    /// arrays and unit values (including unit variants) can be used as keys.
+    #[error(
+        "map keys of kind `{kind}` have no canonical order, only booleans, integers, chars, \
+         strings, byte arrays and unit values do"
+    )]
+    UnorderedMapKey {
+        /// The kind of the offending key, e.g. `F32` or `Struct`.
+        kind: Box<str>,
+    },
+}
+
+/// Prefix of the content of traces which don't match their schemas. Never a valid node kind.
+const INVALID_TRACE: u8 = u8::MAX;
+
+impl DescribedBy<'_, &'_ Trace> {
+    /// Whether this traced value has the same content as another, regardless of the schemas
+    /// they were traced with.
+    ///
+    /// Two values have the same content if they would produce the same sequence of `serialize_*`
+    /// calls, with the same names and values. In particular:
+    ///  * Floats are compared bitwise, so `NaN` is equal to itself and `-0.0` is not equal to
+    ///    `0.0`.
+    ///  * Struct fields are compared in the order in which they were serialized, skipped
+    ///    fields are simply absent.
+    ///
+    /// This is also the equality used by the [`PartialEq`] and [`Hash`] implementations of
+    /// [`DescribedBy<&Trace>`][`DescribedBy`] and [`DescribedBy<Trace>`][`DescribedBy`], which
+    /// allow using traced values as keys in maps, without deserializing them.
+    ///
+    /// A trace which doesn't match its schema is only equal to an identical trace described by
+    /// an identical [`Schema`].
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::SchemaBuilder;
+    /// use std::collections::HashSet;
+    ///
+    /// // Different schemas, since the second one contains a union.
+    /// let mut first_builder = SchemaBuilder::new();
+    /// let first = first_builder.trace(&vec![Some(1u32)])?;
+    /// let first_schema = first_builder.build()?;
+    ///
+    /// let mut second_builder = SchemaBuilder::new();
+    /// let second = second_builder.trace(&vec![Some(1u32)])?;
+    /// let _ = second_builder.trace(&vec![None::<u32>])?;
+    /// let second_schema = second_builder.build()?;
+    ///
+    /// let first = first_schema.describe_trace(first);
+    /// let second = second_schema.describe_trace(second);
+    /// assert!(first.content_eq(&second));
+    ///
+    /// let deduplicated = HashSet::from([first, second]);
+    /// assert_eq!(deduplicated.len(), 1);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn content_eq(&self, other: &DescribedBy<'_, &'_ Trace>) -> bool {
+        self.canonical_content() == other.canonical_content()
+    }
+
+    /// Encodes this traced value deterministically, e.g. for signing or for reproducible builds.
+    ///
+    /// The output depends only on the content of the value (see
+    /// [`content_eq`][`Self::content_eq`]), not on the schema it was traced with, nor on the
+    /// platform it was encoded on, with the following normalizations:
+    ///  * Map entries are sorted by their encoded keys, then values. Keys must be booleans,
+    ///    integers, chars, strings, byte arrays or unit values (e.g. unit variants), anything
+    ///    else is a [`CanonicalEncodingError::UnorderedMapKey`].
+    ///  * Every NaN is encoded as the same positive quiet NaN, and `-0.0` as `0.0`, like
+    ///    [`FloatNormalization::Canonical`].
+    ///
+    /// All numbers and lengths are fixed-width and little-endian, and every type, variant and
+    /// field name is spelled out, so the encoding doesn't depend on the order in which union
+    /// variants were discovered. It can't be decoded back, only compared.
+    ///
+    /// Also returns an error if the trace doesn't match its schema.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::SchemaBuilder;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let first = builder.trace(&HashMap::from([("a", 0.0), ("b", 1.0)]))?;
+    /// let second = builder.trace(&HashMap::from([("b", 1.0), ("a", -0.0)]))?;
+    /// let schema = builder.build()?;
+    ///
+    /// assert_eq!(
+    ///     schema.describe_trace_ref(&first).encode_canonical()?,
+    ///     schema.describe_trace_ref(&second).encode_canonical()?,
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn encode_canonical(&self) -> Result<Vec<u8>, CanonicalEncodingError> {
+        let mut output = Vec::with_capacity((self.0).0.len());
+        let tail = Cell::new(&(self.0).0[..]);
+        match self
+            .1
+            .canonicalize_trace(&tail, &mut output, CanonicalMode::Encoding)
+        {
+            Ok(()) if tail.get().is_empty() => Ok(output),
+            Ok(()) => {
+                Err(InvalidTraceError::from(DumpError::Trace("trailing bytes after value")).into())
+            }
+            Err(DumpError::UnorderedMapKey(kind)) => Err(CanonicalEncodingError::UnorderedMapKey {
+                kind: format!("{kind:?}").into(),
+            }),
+            Err(error) => Err(InvalidTraceError::from(error).into()),
+        }
+    }
+
+    /// Returns a representation of the trace with all names resolved, which is equal for all
+    /// traces with the same content.
+    pub(crate) fn canonical_content(&self) -> Vec<u8> {
+        let mut content = Vec::with_capacity((self.0).0.len());
+        let tail = Cell::new(&(self.0).0[..]);
+        match self
+            .1
+            .canonicalize_trace(&tail, &mut content, CanonicalMode::Content)
+        {
+            Ok(()) if tail.get().is_empty() => content,
+            _ => {
+                // The raw bytes only mean something together with the schema they index into, so
+                // include the schema's content too.
+                let schema = self.1.canonical_content();
+                content.clear();
+                content.push(INVALID_TRACE);
+                push_length(&mut content, schema.len());
+                content.extend_from_slice(&schema);
+                content.extend_from_slice(&(self.0).0);
+                content
+            }
+        }
+    }
+}
+
+impl DescribedBy<'_, Trace> {
+    /// See [`DescribedBy<&Trace>::content_eq`][`DescribedBy::content_eq`].
+    pub fn content_eq(&self, other: &DescribedBy<'_, Trace>) -> bool {
+        DescribedBy(&self.0, self.1).content_eq(&DescribedBy(&other.0, other.1))
+    }
+
+    /// See [`DescribedBy<&Trace>::encode_canonical`][`DescribedBy::encode_canonical`].
+    pub fn encode_canonical(&self) -> Result<Vec<u8>, CanonicalEncodingError> {
+        DescribedBy(&self.0, self.1).encode_canonical()
+    }
+}
+
+impl PartialEq for DescribedBy<'_, &'_ Trace> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.content_eq(other)
+    }
+}
+
+impl Eq for DescribedBy<'_, &'_ Trace> {}
+
+impl Hash for DescribedBy<'_, &'_ Trace> {
+    fn hash<HasherT: Hasher>(&self, state: &mut HasherT) {
+        self.canonical_content().hash(state);
+    }
+}
+
+impl PartialEq for DescribedBy<'_, Trace> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.content_eq(other)
+    }
+}
+
+impl Eq for DescribedBy<'_, Trace> {}
+
+impl Hash for DescribedBy<'_, Trace> {
+    #[inline]
+    fn hash<HasherT: Hasher>(&self, state: &mut HasherT) {
+        DescribedBy(&self.0, self.1).hash(state);
+    }
+}
+
+impl Schema {
+    /// Returns a representation of the schema itself, which is equal for all equal schemas.
+    ///
+    /// The trace of a schema alone isn't enough, since it refers to the names of its nodes by
+    /// the order in which they were first traced, e.g. the first kind of node in `nodes`.
+    pub(crate) fn canonical_content(&self) -> Vec<u8> {
+        let mut builder = SchemaBuilder::new();
+        let trace = builder.trace(self).expect("schemas are always traceable");
+        let schema = builder.build().expect("schemas are always traceable");
+        schema.describe_trace_ref(&trace).canonical_content()
+    }
+
+    fn canonicalize_trace(
+        &self,
+        tail: &Cell<&[u8]>,
+        output: &mut Vec<u8>,
+        mode: CanonicalMode,
+    ) -> Result<(), DumpError> {
+        let kind = *tail
+            .get()
+            .first()
+            .ok_or(DumpError::Trace("unexpected end of trace"))?;
+        output.push(kind);
+        match tail.pop_trace_node::<DumpError>()? {
+            TraceNode::Bool | TraceNode::I8 | TraceNode::U8 => copy_bytes(tail, output, 1)?,
+            TraceNode::I16 | TraceNode::U16 => copy_bytes(tail, output, 2)?,
+            TraceNode::I32 | TraceNode::U32 | TraceNode::Char => copy_bytes(tail, output, 4)?,
+            TraceNode::I64 | TraceNode::U64 => copy_bytes(tail, output, 8)?,
+            TraceNode::F32 => match mode {
+                CanonicalMode::Content => copy_bytes(tail, output, 4)?,
+                CanonicalMode::Encoding => {
+                    let value = FloatNormalization::Canonical
+                        .normalize_f32(tail.pop_f32::<DumpError>()?)
+                        .expect("canonical normalization accepts all floats");
+                    output.extend_from_slice(&value.to_le_bytes());
+                }
+            },
+            TraceNode::F64 => match mode {
+                CanonicalMode::Content => copy_bytes(tail, output, 8)?,
+                CanonicalMode::Encoding => {
+                    let value = FloatNormalization::Canonical
+                        .normalize_f64(tail.pop_f64::<DumpError>()?)
+                        .expect("canonical normalization accepts all floats");
+                    output.extend_from_slice(&value.to_le_bytes());
+                }
+            },
+            TraceNode::I128 | TraceNode::U128 => copy_bytes(tail, output, 16)?,
+            TraceNode::String | TraceNode::Bytes => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                push_length(output, length);
+                copy_bytes(tail, output, length)?;
+            }
+
+            TraceNode::None | TraceNode::Unit => {}
+            TraceNode::Some => self.canonicalize_trace(tail, output, mode)?,
+
+            TraceNode::UnitStruct(name) => push_str(output, self.type_name(name)?),
+            TraceNode::UnitVariant(name, variant) => {
+                push_str(output, self.type_name(name)?);
+                push_str(output, self.variant_name(variant)?);
+            }
+            TraceNode::NewtypeStruct(name) => {
+                push_str(output, self.type_name(name)?);
+                self.canonicalize_trace(tail, output, mode)?;
+            }
+            TraceNode::NewtypeVariant(name, variant) => {
+                push_str(output, self.type_name(name)?);
+                push_str(output, self.variant_name(variant)?);
+                self.canonicalize_trace(tail, output, mode)?;
+            }
+
+            TraceNode::Sequence => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                push_length(output, length);
+                self.canonicalize_trace_items(tail, output, length, mode)?;
+            }
+            TraceNode::Map => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                push_length(output, length);
+                match mode {
+                    CanonicalMode::Content => {
+                        self.canonicalize_trace_items(tail, output, length * 2, mode)?
+                    }
+                    CanonicalMode::Encoding => {
+                        self.canonicalize_trace_sorted_entries(tail, output, length)?
+                    }
+                }
+            }
+
+            TraceNode::Tuple(length) => {
+                output.extend_from_slice(&length.to_le_bytes());
+                self.canonicalize_trace_items(tail, output, length_from_u32(length), mode)?;
+            }
+            TraceNode::TupleStruct(length, name) => {
+                output.extend_from_slice(&length.to_le_bytes());
+                push_str(output, self.type_name(name)?);
+                self.canonicalize_trace_items(tail, output, length_from_u32(length), mode)?;
+            }
+            TraceNode::TupleVariant(length, name, variant) => {
+                output.extend_from_slice(&length.to_le_bytes());
+                push_str(output, self.type_name(name)?);
+                push_str(output, self.variant_name(variant)?);
+                self.canonicalize_trace_items(tail, output, length_from_u32(length), mode)?;
+            }
+
+            TraceNode::Struct(name, field_names) => {
+                push_str(output, self.type_name(name)?);
+                self.canonicalize_trace_fields(tail, output, field_names, mode)?;
+            }
+            TraceNode::StructVariant(name, variant, field_names) => {
+                push_str(output, self.type_name(name)?);
+                push_str(output, self.variant_name(variant)?);
+                self.canonicalize_trace_fields(tail, output, field_names, mode)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn canonicalize_trace_items(
+        &self,
+        tail: &Cell<&[u8]>,
+        output: &mut Vec<u8>,
+        length: usize,
+        mode: CanonicalMode,
+    ) -> Result<(), DumpError> {
+        for _ in 0..length {
+            self.canonicalize_trace(tail, output, mode)?;
+        }
+        Ok(())
+    }
+
+    fn canonicalize_trace_fields(
+        &self,
+        tail: &Cell<&[u8]>,
+        output: &mut Vec<u8>,
+        field_names: FieldNameListIndex,
+        mode: CanonicalMode,
+    ) -> Result<(), DumpError> {
+        let present = self.pop_trace_field_names(tail, field_names)?;
+        push_length(output, present.len());
+        for name in present {
+            push_str(output, name);
+            self.canonicalize_trace(tail, output, mode)?;
+        }
+        Ok(())
+    }
+
+    fn canonicalize_trace_sorted_entries(
+        &self,
+        tail: &Cell<&[u8]>,
+        output: &mut Vec<u8>,
+        length: usize,
+    ) -> Result<(), DumpError> {
+        let mut entries = Vec::with_capacity(length);
+        for _ in 0..length {
+            check_orderable_key(tail)?;
+            let mut key = Vec::new();
+            self.canonicalize_trace(tail, &mut key, CanonicalMode::Encoding)?;
+            let mut value = Vec::new();
+            self.canonicalize_trace(tail, &mut value, CanonicalMode::Encoding)?;
+            entries.push((key, value));
+        }
+        entries.sort_unstable();
+        for (key, value) in entries {
+            output.extend_from_slice(&key);
+            output.extend_from_slice(&value);
+        }
+        Ok(())
+    }
+}
+
+/// What the canonical representation of a trace is used for.
+#[derive(Copy, Clone)]
+enum CanonicalMode {
+    /// Comparing and hashing content: maps keep their order and floats are compared bitwise.
+    Content,
+    /// Deterministic encoding: map entries are sorted and floats are normalized.
+    Encoding,
+}
+
+/// Fails unless the next value is of a kind whose encoding orders it canonically.
+fn check_orderable_key(tail: &Cell<&[u8]>) -> Result<(), DumpError> {
+    let Some(&kind) = tail.get().first() else {
+        return Ok(());
+    };
+    match TraceNodeKind::try_from(kind) {
+        Ok(
+            TraceNodeKind::Bool
+            | TraceNodeKind::I8
+            | TraceNodeKind::I16
+            | TraceNodeKind::I32
+            | TraceNodeKind::I64
+            | TraceNodeKind::I128
+            | TraceNodeKind::U8
+            | TraceNodeKind::U16
+            | TraceNodeKind::U32
+            | TraceNodeKind::U64
+            | TraceNodeKind::U128
+            | TraceNodeKind::Char
+            | TraceNodeKind::String
+            | TraceNodeKind::Bytes
+            | TraceNodeKind::Unit
+            | TraceNodeKind::UnitStruct
+            | TraceNodeKind::UnitVariant,
+        )
+        // Invalid kinds are reported when the key is read.
+        | Err(_) => Ok(()),
+        Ok(kind) => Err(DumpError::UnorderedMapKey(kind)),
+    }
+}
+
+fn copy_bytes(tail: &Cell<&[u8]>, output: &mut Vec<u8>, length: usize) -> Result<(), DumpError> {
+    output.extend_from_slice(tail.pop_slice::<DumpError>(length)?);
+    Ok(())
+}
+
+fn push_length(output: &mut Vec<u8>, length: usize) {
+    let length = u32::try_from(length).expect("lengths in traces fit in u32");
+    output.extend_from_slice(&length.to_le_bytes());
+}
+
+fn push_str(output: &mut Vec<u8>, string: &str) {
+    push_length(output, string.len());
+    output.extend_from_slice(string.as_bytes());
+}
+
+fn length_from_u32(length: u32) -> usize {
+    usize::try_from(length).expect("usize needs to be at least 32 bits")
+}
@@ -1,22 +1,30 @@
 use serde::{
     Deserialize,
     de::{
-        DeserializeSeed, Deserializer, EnumAccess, Error as _, Expected, IgnoredAny, MapAccess,
-        SeqAccess, Unexpected, VariantAccess,
+        DeserializeSeed, Deserializer, EnumAccess, Error as _, Expected, IgnoredAny,
+        IntoDeserializer, MapAccess, SeqAccess, Unexpected, VariantAccess, value::SeqDeserializer,
     },
 };
-use std::marker::PhantomData;
+use std::{borrow::Cow, marker::PhantomData};
 
 use crate::{
-    Schema,
-    anonymous_union::ChunkedEnum,
+    Dynamic, DynamicFields, Schema,
+    anonymous_union::{ChunkedEnum, Discriminant},
     deferred::{self, CallResult, CanonicalVisit, DeferredDeserialize},
     described::{DescribedBy, SelfDescribed},
     indices::{
         FieldNameIndex, FieldNameListIndex, IsEmpty, MemberIndex, MemberListIndex, SchemaNodeIndex,
-        SchemaNodeListIndex, VariantNameIndex,
+        SchemaNodeListIndex, TypeNameIndex, VariantNameIndex,
     },
+    limits::{AllocationBudget, LengthLimited, charge_allocation, check_sequence_length},
+    logical::{LogicalType, StaticLogicalTypes},
+    metrics::StaticMetrics,
+    options::{DecodeOptions, EnumRepresentation, NumericCoercion, WithOptions},
+    plan::PlannedSchema,
+    raw_value::{self, CaptureEntries, CaptureItems, CaptureSeed, RAW_VALUE_TYPE_NAME},
     schema::SchemaNode,
+    tensor::Bytes,
+    transform::{LeafValue, LeafValueDeserializer},
 };
 
 impl<'de, T> Deserialize<'de> for SelfDescribed<T>
@@ -43,7 +51,23 @@ where
     where
         D: Deserializer<'de>,
     {
-        struct SchemaPairVisitor<SeedT>(SeedT);
+        self.with_options(DecodeOptions::default())
+            .deserialize(deserializer)
+    }
+}
+
+impl<'de, SeedT> DeserializeSeed<'de> for WithOptions<SelfDescribed<SeedT>>
+where
+    SeedT: DeserializeSeed<'de>,
+{
+    type Value = SelfDescribed<SeedT::Value>;
+
+    #[inline]
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SchemaPairVisitor<SeedT>(SeedT, DecodeOptions);
         impl<'de, SeedT> serde::de::Visitor<'de> for SchemaPairVisitor<SeedT>
         where
             SeedT: DeserializeSeed<'de>,
@@ -67,7 +91,7 @@ where
                     .ok_or_else(|| A::Error::invalid_length(0, &self))?;
 
                 Ok(SelfDescribed(
-                    seq.next_element_seed(DescribedBy(self.0, &schema))?
+                    seq.next_element_seed(DescribedBy(self.0, &schema).with_options(self.1))?
                         .ok_or_else(|| {
                             A::Error::custom(
                                 "missing described-elsewhere pair in described object pair",
@@ -78,7 +102,7 @@ where
             }
         }
 
-        deserializer.deserialize_tuple(2, SchemaPairVisitor(self.0))
+        deserializer.deserialize_tuple(2, SchemaPairVisitor((self.0).0, self.1))
     }
 }
 
@@ -93,34 +117,86 @@ where
     where
         D: Deserializer<'de>,
     {
-        SchemaDeserializer {
-            schema: self.1,
-            node: self.1.node(self.1.root_index).map_err(D::Error::custom)?,
-            inner: self.0,
+        self.with_options(DecodeOptions::default())
+            .deserialize(deserializer)
+    }
+}
+
+impl<'de, 'schema, SeedT> DeserializeSeed<'de> for WithOptions<DescribedBy<'schema, SeedT>>
+where
+    SeedT: DeserializeSeed<'de>,
+{
+    type Value = DescribedBy<'schema, SeedT::Value>;
+
+    #[inline]
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let WithOptions(DescribedBy(seed, schema), options) = self;
+        let _budget = AllocationBudget::enter(options.max_allocation);
+        let result = schema
+            .node(schema.root_index)
+            .map_err(D::Error::custom)
+            .and_then(|node| {
+                SchemaDeserializer::new(schema.into(), node, options, seed)
+                    .deserialize(deserializer)
+            });
+        if let Some(StaticMetrics(metrics)) = options.metrics {
+            match &result {
+                Ok(_) => metrics.value_decoded(),
+                Err(error) => metrics.decode_failed(error),
+            }
         }
-        .deserialize(deserializer)
-        .map(|value| DescribedBy(value, self.1))
+        result.map(|value| DescribedBy(value, schema))
     }
 }
 
 #[derive(Copy, Clone)]
 pub(crate) struct SchemaDeserializer<'schema, InnerT> {
-    schema: &'schema Schema,
+    schema: PlannedSchema<'schema>,
     node: SchemaNode,
+    options: DecodeOptions,
     inner: InnerT,
 }
 
 impl<'schema, InnerT> SchemaDeserializer<'schema, InnerT> {
+    #[inline]
+    pub(crate) fn new(
+        schema: PlannedSchema<'schema>,
+        node: SchemaNode,
+        options: DecodeOptions,
+        inner: InnerT,
+    ) -> Self {
+        Self {
+            schema,
+            node,
+            options,
+            inner,
+        }
+    }
+
     #[inline]
     fn variant_name_deserializer<ErrorT>(
         &self,
         variant: VariantNameIndex,
+        expected: Option<&'static [&'static str]>,
     ) -> Result<NameDeserializer<'schema, ErrorT>, ErrorT>
     where
         ErrorT: serde::de::Error,
     {
+        if let Some(name) =
+            expected.and_then(|expected| self.schema.resolved_variant(variant, expected))
+        {
+            return Ok(NameDeserializer {
+                name,
+                phantom: PhantomData,
+            });
+        }
+        let schema = self.schema.schema();
+        let name = schema.variant_name(variant).map_err(ErrorT::custom)?;
         Ok(NameDeserializer {
-            name: self.schema.variant_name(variant).map_err(ErrorT::custom)?,
+            name: schema.resolve_name(name, expected),
             phantom: PhantomData,
         })
     }
@@ -132,6 +208,7 @@ impl<'schema, InnerT> SchemaDeserializer<'schema, InnerT> {
     {
         Ok(Self {
             schema: self.schema,
+            options: self.options.nested()?,
             node: self.schema.node(node).map_err(ErrorT::custom)?,
             inner: self.inner,
         })
@@ -151,6 +228,7 @@ where
     {
         self.inner.deserialize(SchemaDeserializer {
             schema: self.schema,
+            options: self.options.nested()?,
             node: self.node,
             inner: deserializer,
         })
@@ -172,10 +250,12 @@ where
     {
         self.inner.deserialize_seq(SchemaSeqDeserializer {
             schema: self.schema,
+            options: self.options,
             item: self
                 .schema
                 .node(item)
                 .map_err(DeserializerT::Error::custom)?,
+            length: 0,
             inner: visitor,
         })
     }
@@ -197,6 +277,7 @@ where
             items.len(),
             SchemaTupleDeserializer {
                 schema: self.schema,
+                options: self.options,
                 items,
                 inner: visitor,
             },
@@ -215,6 +296,7 @@ where
     {
         self.inner.deserialize_map(SchemaMapDeserializer {
             schema: self.schema,
+            options: self.options,
             key: self
                 .schema
                 .node(key)
@@ -223,6 +305,8 @@ where
                 .schema
                 .node(value)
                 .map_err(DeserializerT::Error::custom)?,
+            length: 0,
+            value_pending: false,
             inner: visitor,
         })
     }
@@ -230,6 +314,7 @@ where
     #[inline]
     fn do_deserialize_struct<VisitorT>(
         self,
+        expected_fields: Option<&'static [&'static str]>,
         field_names: FieldNameListIndex,
         skip_list: MemberListIndex,
         field_types: SchemaNodeListIndex,
@@ -238,8 +323,91 @@ where
     where
         VisitorT: serde::de::Visitor<'de>,
     {
-        SchemaStructDeserializer::seed(self.schema, field_names, skip_list, field_types, visitor)?
-            .deserialize(self.inner)
+        SchemaStructDeserializer::seed(
+            self.schema,
+            self.options,
+            expected_fields,
+            field_names,
+            skip_list,
+            field_types,
+            visitor,
+        )?
+        .deserialize(self.inner)
+    }
+
+    /// Deserializes an enum variant as a map holding its name, see [`EnumRepresentation`].
+    fn deserialize_tagged_variant<VisitorT>(
+        self,
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, DeserializerT::Error>
+    where
+        VisitorT: serde::de::Visitor<'de>,
+    {
+        let schema = self.schema;
+        let representation = self.options.enum_representation;
+        let (variant, content) = match self.node {
+            SchemaNode::UnitVariant(_, variant) => {
+                self.inner.deserialize_unit(IgnoredAny)?;
+                (variant, None)
+            }
+            SchemaNode::NewtypeVariant(_, variant, inner) => (variant, Some(self.forward(inner)?)),
+            SchemaNode::TupleVariant(_, variant, items) => (
+                variant,
+                Some(Self {
+                    node: SchemaNode::Tuple(items),
+                    ..self
+                }),
+            ),
+            SchemaNode::StructVariant(name, variant, field_names, skip_list, field_types) => (
+                variant,
+                Some(Self {
+                    node: SchemaNode::Struct(name, field_names, skip_list, field_types),
+                    ..self
+                }),
+            ),
+            _ => return self.invalid_type_error(&visitor),
+        };
+        let variant = schema
+            .variant_name(variant)
+            .map_err(DeserializerT::Error::custom)?;
+        match (representation, content) {
+            (EnumRepresentation::Untagged, Some(content)) => content.deserialize_any(visitor),
+            (EnumRepresentation::Untagged, None) => visitor.visit_unit(),
+            (EnumRepresentation::External, Some(content)) => visitor.visit_map(VariantEntries {
+                tag: None,
+                content: Some((variant, content)),
+            }),
+            (EnumRepresentation::External, None) => visitor.visit_str(variant),
+            (EnumRepresentation::Internal { tag }, Some(mut content)) => {
+                // Like serde, tag the struct or map inside newtypes.
+                while let SchemaNode::NewtypeStruct(_, inner) = content.node {
+                    content = content.forward(inner)?;
+                }
+                let visitor = InternallyTagged {
+                    tag,
+                    variant,
+                    inner: visitor,
+                };
+                match content.node {
+                    SchemaNode::Struct(_, field_names, skip_list, field_types) => content
+                        .do_deserialize_struct(None, field_names, skip_list, field_types, visitor),
+                    SchemaNode::Map(key, value) => content.do_deserialize_map(key, value, visitor),
+                    _ => content.invalid_type_error(&visitor),
+                }
+            }
+            (EnumRepresentation::Internal { tag }, None) => {
+                visitor.visit_map(VariantEntries::<DeserializerT> {
+                    tag: Some((tag, variant)),
+                    content: None,
+                })
+            }
+            (EnumRepresentation::Adjacent { tag, content: key }, content) => {
+                visitor.visit_map(VariantEntries {
+                    tag: Some((tag, variant)),
+                    content: content.map(|content| (key, content)),
+                })
+            }
+        }
     }
 
     #[inline]
@@ -256,7 +424,13 @@ where
             SchemaNode::OptionSome(inner)
             | SchemaNode::NewtypeStruct(_, inner)
             | SchemaNode::NewtypeVariant(_, _, inner) => call.call(self.forward(inner)?),
-            actual if condition(actual) => call.call(self.inner),
+            actual if condition(actual) => match actual {
+                SchemaNode::String | SchemaNode::Bytes => call.call(LengthLimited {
+                    inner: self.inner,
+                    max_length: self.options.max_string_length,
+                }),
+                _ => call.call(self.inner),
+            },
             _ => self.invalid_type_error(&call),
         }
     }
@@ -264,6 +438,7 @@ where
     #[inline]
     fn deserialize_float<CallT>(
         self,
+        exact: SchemaNode,
         convert_f64: impl FnOnce(f64) -> CallT::CanonicalInput,
         call: CallT,
     ) -> CallResult<'de, CallT, DeserializerT>
@@ -276,14 +451,34 @@ where
             SchemaNode::OptionSome(inner)
             | SchemaNode::NewtypeStruct(_, inner)
             | SchemaNode::NewtypeVariant(_, _, inner) => call.call(self.forward(inner)?),
+            actual if !self.options.numeric_coercion.permits(actual, exact) => {
+                self.invalid_type_error(&call)
+            }
             SchemaNode::F32 => call.canonical_visit(f32::deserialize(self.inner)?.into()),
-            SchemaNode::F64 => call.canonical_visit(convert_f64(f64::deserialize(self.inner)?)),
+            SchemaNode::F64 => {
+                let value = f64::deserialize(self.inner)?;
+                if exact == SchemaNode::F32
+                    && self.options.numeric_coercion == NumericCoercion::Checked
+                    && value.is_finite()
+                    && (value as f32).is_infinite()
+                {
+                    return Err(DeserializerT::Error::invalid_value(
+                        Unexpected::Float(value),
+                        &call,
+                    ));
+                }
+                call.canonical_visit(convert_f64(value))
+            }
             _ => self.invalid_type_error(&call),
         }
     }
 
     #[inline]
-    fn deserialize_integer<CallT>(self, call: CallT) -> CallResult<'de, CallT, DeserializerT>
+    fn deserialize_integer<CallT>(
+        self,
+        exact: SchemaNode,
+        call: CallT,
+    ) -> CallResult<'de, CallT, DeserializerT>
     where
         CallT: CanonicalVisit<'de>,
         CallT::CanonicalInput: TryFrom<u8>
@@ -320,6 +515,9 @@ where
             SchemaNode::OptionSome(inner)
             | SchemaNode::NewtypeStruct(_, inner)
             | SchemaNode::NewtypeVariant(_, _, inner) => call.call(self.forward(inner)?),
+            actual if !self.options.numeric_coercion.permits(actual, exact) => {
+                self.invalid_type_error(&call)
+            }
 
             SchemaNode::I8 => integer_conversion!(i8, visit_i8, Signed),
             SchemaNode::I16 => integer_conversion!(i16, visit_i16, Signed),
@@ -341,6 +539,49 @@ where
         }
     }
 
+    #[inline]
+    fn deserialize_char_or_string<CallT>(self, call: CallT) -> CallResult<'de, CallT, DeserializerT>
+    where
+        CallT: CanonicalVisit<'de, CanonicalInput = char>,
+    {
+        match self.node {
+            SchemaNode::String if self.options.char_string_coercions => {
+                let string = String::deserialize(self.inner)?;
+                let mut chars = string.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(single), None) => call.canonical_visit(single),
+                    _ => Err(DeserializerT::Error::invalid_value(
+                        Unexpected::Str(&string),
+                        &call,
+                    )),
+                }
+            }
+            _ => self.deserialize_if(|node| matches!(node, SchemaNode::Char), call),
+        }
+    }
+
+    #[inline]
+    fn deserialize_string_coerced<CallT>(self, call: CallT) -> CallResult<'de, CallT, DeserializerT>
+    where
+        CallT: DeferredDeserialize<'de>,
+    {
+        match self.node {
+            SchemaNode::Char if self.options.char_string_coercions => {
+                let single = char::deserialize(self.inner)?;
+                serde::de::Visitor::visit_str(call.into_visitor(), single.encode_utf8(&mut [0; 4]))
+            }
+            SchemaNode::UnitVariant(_, variant) if self.options.enum_string_coercions => {
+                let name = self
+                    .schema
+                    .variant_name(variant)
+                    .map_err(DeserializerT::Error::custom)?;
+                self.inner.deserialize_unit(IgnoredAny)?;
+                serde::de::Visitor::visit_str(call.into_visitor(), name)
+            }
+            _ => self.deserialize_if(|node| matches!(node, SchemaNode::String), call),
+        }
+    }
+
     #[inline]
     fn deserialize_union<CallT>(
         self,
@@ -351,7 +592,8 @@ where
         CallT: DeferredDeserialize<'de>,
     {
         struct ResolvedUnion<'schema, CallT> {
-            schema: &'schema Schema,
+            schema: PlannedSchema<'schema>,
+            options: DecodeOptions,
             variants: &'schema [SchemaNodeIndex],
             discriminant: u64,
             deferred: CallT,
@@ -376,6 +618,7 @@ where
                     })?;
                 self.deferred.call(SchemaDeserializer {
                     schema: self.schema,
+                    options: self.options,
                     node: self.schema.node(node_index).map_err(D::Error::custom)?,
                     inner: deserializer,
                 })
@@ -395,16 +638,239 @@ where
         ChunkedEnum::deserializable(
             usize::try_from(usize::BITS - (variants.len() - 1).leading_zeros())
                 .expect("usize should be at least 32 bits"),
-            move |discriminant| ResolvedUnion {
+            move |discriminant: Discriminant| ResolvedUnion {
                 schema: self.schema,
-                discriminant,
+                options: self.options,
+                discriminant: discriminant.low_u64(),
                 variants,
                 deferred: call,
             },
-        )?
+        )
         .deserialize(self.inner)
     }
 
+    /// Reads the value as a [`Dynamic`], for a [`RawValue`][`crate::RawValue`].
+    fn capture(self) -> Result<Dynamic, DeserializerT::Error> {
+        let schema = self.schema;
+        let type_name = |name| -> Result<Box<str>, DeserializerT::Error> {
+            Ok(schema
+                .type_name(name)
+                .map_err(DeserializerT::Error::custom)?
+                .into())
+        };
+        let variant_name = |variant| -> Result<Box<str>, DeserializerT::Error> {
+            Ok(schema
+                .variant_name(variant)
+                .map_err(DeserializerT::Error::custom)?
+                .into())
+        };
+        let max_length = self.options.max_string_length;
+        Ok(match self.node {
+            SchemaNode::Bool => Dynamic::Bool(bool::deserialize(self.inner)?),
+            SchemaNode::I8 => Dynamic::I8(i8::deserialize(self.inner)?),
+            SchemaNode::I16 => Dynamic::I16(i16::deserialize(self.inner)?),
+            SchemaNode::I32 => Dynamic::I32(i32::deserialize(self.inner)?),
+            SchemaNode::I64 => Dynamic::I64(i64::deserialize(self.inner)?),
+            SchemaNode::I128 => Dynamic::I128(i128::deserialize(self.inner)?),
+            SchemaNode::U8 => Dynamic::U8(u8::deserialize(self.inner)?),
+            SchemaNode::U16 => Dynamic::U16(u16::deserialize(self.inner)?),
+            SchemaNode::U32 => Dynamic::U32(u32::deserialize(self.inner)?),
+            SchemaNode::U64 => Dynamic::U64(u64::deserialize(self.inner)?),
+            SchemaNode::U128 => Dynamic::U128(u128::deserialize(self.inner)?),
+            SchemaNode::F32 => Dynamic::F32(f32::deserialize(self.inner)?),
+            SchemaNode::F64 => Dynamic::F64(f64::deserialize(self.inner)?),
+            SchemaNode::Char => Dynamic::Char(char::deserialize(self.inner)?),
+            SchemaNode::String => Dynamic::Str(String::deserialize(LengthLimited {
+                inner: self.inner,
+                max_length,
+            })?),
+            SchemaNode::Bytes => Dynamic::Bytes(
+                Bytes::deserialize(LengthLimited {
+                    inner: self.inner,
+                    max_length,
+                })?
+                .0,
+            ),
+
+            SchemaNode::OptionNone => {
+                self.inner.deserialize_unit(IgnoredAny)?;
+                Dynamic::None
+            }
+            SchemaNode::OptionSome(inner) => {
+                Dynamic::Some(Box::new(self.forward(inner)?.capture()?))
+            }
+            SchemaNode::Unit => {
+                self.inner.deserialize_unit(IgnoredAny)?;
+                Dynamic::Unit
+            }
+            SchemaNode::UnitStruct(name) => {
+                self.inner.deserialize_unit(IgnoredAny)?;
+                Dynamic::Struct {
+                    name: type_name(name)?,
+                    fields: DynamicFields::Unit,
+                }
+            }
+            SchemaNode::UnitVariant(name, variant) => {
+                self.inner.deserialize_unit(IgnoredAny)?;
+                Dynamic::Variant {
+                    name: type_name(name)?,
+                    variant: variant_name(variant)?,
+                    fields: DynamicFields::Unit,
+                }
+            }
+            SchemaNode::NewtypeStruct(name, inner) => Dynamic::Struct {
+                name: type_name(name)?,
+                fields: DynamicFields::Newtype(Box::new(self.forward(inner)?.capture()?)),
+            },
+            SchemaNode::NewtypeVariant(name, variant, inner) => Dynamic::Variant {
+                name: type_name(name)?,
+                variant: variant_name(variant)?,
+                fields: DynamicFields::Newtype(Box::new(self.forward(inner)?.capture()?)),
+            },
+
+            SchemaNode::Sequence(item) => {
+                Dynamic::Seq(self.do_deserialize_seq(item, CaptureItems)?)
+            }
+            SchemaNode::Map(key, value) => Dynamic::Map(self.do_deserialize_map(
+                key,
+                value,
+                CaptureEntries::<CaptureSeed>(PhantomData),
+            )?),
+            SchemaNode::Tuple(items) => {
+                Dynamic::Tuple(self.do_deserialize_tuple(items, CaptureItems)?)
+            }
+            SchemaNode::TupleStruct(name, items) => Dynamic::Struct {
+                name: type_name(name)?,
+                fields: DynamicFields::Tuple(self.do_deserialize_tuple(items, CaptureItems)?),
+            },
+            SchemaNode::TupleVariant(name, variant, items) => Dynamic::Variant {
+                name: type_name(name)?,
+                variant: variant_name(variant)?,
+                fields: DynamicFields::Tuple(self.do_deserialize_tuple(items, CaptureItems)?),
+            },
+            SchemaNode::Struct(name, field_names, skip_list, field_types) => Dynamic::Struct {
+                name: type_name(name)?,
+                fields: self.capture_fields(field_names, skip_list, field_types)?,
+            },
+            SchemaNode::StructVariant(name, variant, field_names, skip_list, field_types) => {
+                Dynamic::Variant {
+                    name: type_name(name)?,
+                    variant: variant_name(variant)?,
+                    fields: self.capture_fields(field_names, skip_list, field_types)?,
+                }
+            }
+
+            SchemaNode::Union(_) => CaptureSeed.deserialize(self)?,
+        })
+    }
+
+    /// Reads the fields of a struct, see [`Self::capture`].
+    fn capture_fields(
+        self,
+        field_names: FieldNameListIndex,
+        skip_list: MemberListIndex,
+        field_types: SchemaNodeListIndex,
+    ) -> Result<DynamicFields, DeserializerT::Error> {
+        let schema = self.schema;
+        let mut present = self
+            .do_deserialize_struct(
+                None,
+                field_names,
+                skip_list,
+                field_types,
+                CaptureEntries::<PhantomData<String>>(PhantomData),
+            )?
+            .into_iter()
+            .peekable();
+        let fields = schema
+            .field_name_list(field_names)
+            .map_err(DeserializerT::Error::custom)?
+            .iter()
+            .map(|&name| {
+                let name = schema
+                    .field_name(name)
+                    .map_err(DeserializerT::Error::custom)?;
+                let value = present
+                    .next_if(|(present, _)| present == name)
+                    .map(|(_, value)| value);
+                Ok((name.into(), value))
+            })
+            .collect::<Result<_, DeserializerT::Error>>()?;
+        Ok(DynamicFields::Named(fields))
+    }
+
+    /// Skips the rest of a value which failed to deserialize, if recovering from such errors,
+    /// such that the format is left at the end of the value.
+    fn skip_after(self, error: DeserializerT::Error) -> DeserializerT::Error {
+        if self.options.skip_failed_values {
+            // If the value can't be skipped either, the original error is more useful.
+            let _ = self.deserialize_ignored_any(IgnoredAny);
+        }
+        error
+    }
+
+    /// Returns `unexpected` for a value which is reported without being read, skipping it if
+    /// recovering from errors.
+    fn skip_unexpected(
+        self,
+        unexpected: Unexpected<'static>,
+    ) -> Result<Unexpected<'de>, DeserializerT::Error> {
+        if self.options.skip_failed_values {
+            self.deserialize_ignored_any(IgnoredAny)?;
+        }
+        Ok(unexpected)
+    }
+
+    /// The registered logical type recorded under the type name `name`, if any.
+    fn logical_type(&self, name: TypeNameIndex) -> Option<&'static LogicalType> {
+        let StaticLogicalTypes(types) = self.options.logical_types?;
+        types.for_id(self.schema.type_name(name).ok()?)
+    }
+
+    /// Reads the value stored for a logical type, which is decoded and handed to `visitor` as the
+    /// content of a newtype.
+    fn visit_logical_type<V>(
+        self,
+        logical: &LogicalType,
+        inner: SchemaNodeIndex,
+        visitor: V,
+    ) -> Result<V::Value, DeserializerT::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let content = self.forward(inner)?;
+        let max_length = content.options.max_string_length;
+        let stored = match content.node {
+            SchemaNode::Bool => LeafValue::Bool(bool::deserialize(content.inner)?),
+            SchemaNode::I8 => LeafValue::I8(i8::deserialize(content.inner)?),
+            SchemaNode::I16 => LeafValue::I16(i16::deserialize(content.inner)?),
+            SchemaNode::I32 => LeafValue::I32(i32::deserialize(content.inner)?),
+            SchemaNode::I64 => LeafValue::I64(i64::deserialize(content.inner)?),
+            SchemaNode::I128 => LeafValue::I128(i128::deserialize(content.inner)?),
+            SchemaNode::U8 => LeafValue::U8(u8::deserialize(content.inner)?),
+            SchemaNode::U16 => LeafValue::U16(u16::deserialize(content.inner)?),
+            SchemaNode::U32 => LeafValue::U32(u32::deserialize(content.inner)?),
+            SchemaNode::U64 => LeafValue::U64(u64::deserialize(content.inner)?),
+            SchemaNode::U128 => LeafValue::U128(u128::deserialize(content.inner)?),
+            SchemaNode::F32 => LeafValue::F32(f32::deserialize(content.inner)?),
+            SchemaNode::F64 => LeafValue::F64(f64::deserialize(content.inner)?),
+            SchemaNode::Char => LeafValue::Char(char::deserialize(content.inner)?),
+            SchemaNode::String => LeafValue::Str(Cow::Owned(String::deserialize(LengthLimited {
+                inner: content.inner,
+                max_length,
+            })?)),
+            SchemaNode::Bytes => LeafValue::Bytes(Cow::Owned(
+                Bytes::deserialize(LengthLimited {
+                    inner: content.inner,
+                    max_length,
+                })?
+                .0,
+            )),
+            _ => return content.invalid_type_error(&"a primitive value wrapped by a logical type"),
+        };
+        visitor.visit_newtype_struct(LeafValueDeserializer::new((logical.decode)(stored)))
+    }
+
     fn unexpected(self) -> Result<Unexpected<'de>, DeserializerT::Error> {
         Ok(match self.node {
             SchemaNode::Bool => Unexpected::Bool(bool::deserialize(self.inner)?),
@@ -413,13 +879,17 @@ where
             SchemaNode::I16 => Unexpected::Signed(i64::from(i16::deserialize(self.inner)?)),
             SchemaNode::I32 => Unexpected::Signed(i64::from(i32::deserialize(self.inner)?)),
             SchemaNode::I64 => Unexpected::Signed(i64::deserialize(self.inner)?),
-            SchemaNode::I128 => Unexpected::Other("128-bit signed integer"),
+            SchemaNode::I128 => {
+                self.skip_unexpected(Unexpected::Other("128-bit signed integer"))?
+            }
 
             SchemaNode::U8 => Unexpected::Unsigned(u64::from(u8::deserialize(self.inner)?)),
             SchemaNode::U16 => Unexpected::Unsigned(u64::from(u16::deserialize(self.inner)?)),
             SchemaNode::U32 => Unexpected::Unsigned(u64::from(u32::deserialize(self.inner)?)),
             SchemaNode::U64 => Unexpected::Unsigned(u64::deserialize(self.inner)?),
-            SchemaNode::U128 => Unexpected::Other("128-bit unsigned integer"),
+            SchemaNode::U128 => {
+                self.skip_unexpected(Unexpected::Other("128-bit unsigned integer"))?
+            }
 
             SchemaNode::F32 => Unexpected::Float(f64::from(f32::deserialize(self.inner)?)),
             SchemaNode::F64 => Unexpected::Float(f64::deserialize(self.inner)?),
@@ -432,26 +902,37 @@ where
                 .map(Unexpected::Bytes)
                 .unwrap_or(Unexpected::Other("bytes")),
 
-            SchemaNode::OptionNone | SchemaNode::OptionSome(_) => Unexpected::Option,
+            // Unlike the above, these are reported without reading the value.
+            SchemaNode::OptionNone | SchemaNode::OptionSome(_) => {
+                self.skip_unexpected(Unexpected::Option)?
+            }
 
-            SchemaNode::Unit => Unexpected::Unit,
-            SchemaNode::UnitStruct(_) => Unexpected::Other("unit struct"),
-            SchemaNode::UnitVariant(_, _) => Unexpected::UnitVariant,
+            SchemaNode::Unit => self.skip_unexpected(Unexpected::Unit)?,
+            SchemaNode::UnitStruct(_) => self.skip_unexpected(Unexpected::Other("unit struct"))?,
+            SchemaNode::UnitVariant(_, _) => self.skip_unexpected(Unexpected::UnitVariant)?,
 
-            SchemaNode::NewtypeStruct(_, _) => Unexpected::NewtypeStruct,
-            SchemaNode::NewtypeVariant(_, _, _) => Unexpected::NewtypeVariant,
+            SchemaNode::NewtypeStruct(_, _) => self.skip_unexpected(Unexpected::NewtypeStruct)?,
+            SchemaNode::NewtypeVariant(_, _, _) => {
+                self.skip_unexpected(Unexpected::NewtypeVariant)?
+            }
 
-            SchemaNode::Sequence(_) => Unexpected::Seq,
-            SchemaNode::Map(_, _) => Unexpected::Map,
+            SchemaNode::Sequence(_) => self.skip_unexpected(Unexpected::Seq)?,
+            SchemaNode::Map(_, _) => self.skip_unexpected(Unexpected::Map)?,
 
-            SchemaNode::Tuple(_) => Unexpected::Other("tuple"),
-            SchemaNode::TupleStruct(_, _) => Unexpected::Other("tuple struct"),
-            SchemaNode::TupleVariant(_, _, _) => Unexpected::TupleVariant,
+            SchemaNode::Tuple(_) => self.skip_unexpected(Unexpected::Other("tuple"))?,
+            SchemaNode::TupleStruct(_, _) => {
+                self.skip_unexpected(Unexpected::Other("tuple struct"))?
+            }
+            SchemaNode::TupleVariant(_, _, _) => self.skip_unexpected(Unexpected::TupleVariant)?,
 
-            SchemaNode::Struct(_, _, _, _) => Unexpected::Other("struct"),
-            SchemaNode::StructVariant(_, _, _, _, _) => Unexpected::StructVariant,
+            SchemaNode::Struct(_, _, _, _) => self.skip_unexpected(Unexpected::Other("struct"))?,
+            SchemaNode::StructVariant(_, _, _, _, _) => {
+                self.skip_unexpected(Unexpected::StructVariant)?
+            }
 
-            SchemaNode::Union(_) => Unexpected::Other("enum, skippable field or untagged union"),
+            SchemaNode::Union(_) => {
+                self.skip_unexpected(Unexpected::Other("enum, skippable field or untagged union"))?
+            }
         })
     }
 
@@ -471,12 +952,20 @@ macro_rules! deserialize_simple {
         deserialize_simple!{@helper, $fn_name, self, visitor, (self.deserialize_if(|node| matches!(node, $node), deferred::$fn_name { visitor }))}
     };
 
-    ($fn_name:ident, @integer) => {
-        deserialize_simple!{@helper, $fn_name, self, visitor, (self.deserialize_integer(deferred::$fn_name { visitor }))}
+    ($fn_name:ident, @integer $exact:ident) => {
+        deserialize_simple!{@helper, $fn_name, self, visitor, (self.deserialize_integer(SchemaNode::$exact, deferred::$fn_name { visitor }))}
+    };
+
+    ($fn_name:ident, @float $exact:ident) => {
+        deserialize_simple!{@helper, $fn_name, self, visitor, (self.deserialize_float(SchemaNode::$exact, |value| value as _, deferred::$fn_name { visitor }))}
     };
 
-    ($fn_name:ident, @float) => {
-        deserialize_simple!{@helper, $fn_name, self, visitor, (self.deserialize_float(|value| value as _, deferred::$fn_name { visitor }))}
+    ($fn_name:ident, @char) => {
+        deserialize_simple!{@helper, $fn_name, self, visitor, (self.deserialize_char_or_string(deferred::$fn_name { visitor }))}
+    };
+
+    ($fn_name:ident, @string) => {
+        deserialize_simple!{@helper, $fn_name, self, visitor, (self.deserialize_string_coerced(deferred::$fn_name { visitor }))}
     };
 
     (@helper, $fn_name:ident, $self:ident, $visitor:ident, $with:tt) => {
@@ -500,6 +989,15 @@ where
         V: serde::de::Visitor<'de>,
     {
         match self.node {
+            SchemaNode::UnitVariant(..)
+            | SchemaNode::NewtypeVariant(..)
+            | SchemaNode::TupleVariant(..)
+            | SchemaNode::StructVariant(..)
+                if self.options.enum_representation != EnumRepresentation::Untagged =>
+            {
+                self.deserialize_tagged_variant(visitor)
+            }
+
             SchemaNode::Bool => self.inner.deserialize_bool(visitor),
 
             SchemaNode::I8 => self.inner.deserialize_i8(visitor),
@@ -518,8 +1016,14 @@ where
             SchemaNode::F64 => self.inner.deserialize_f64(visitor),
             SchemaNode::Char => self.inner.deserialize_char(visitor),
 
-            SchemaNode::String => self.inner.deserialize_str(visitor),
-            SchemaNode::Bytes => self.inner.deserialize_bytes(visitor),
+            SchemaNode::String => self.inner.deserialize_str(LengthLimited {
+                inner: visitor,
+                max_length: self.options.max_string_length,
+            }),
+            SchemaNode::Bytes => self.inner.deserialize_bytes(LengthLimited {
+                inner: visitor,
+                max_length: self.options.max_string_length,
+            }),
 
             SchemaNode::OptionNone => self
                 .inner
@@ -530,7 +1034,11 @@ where
                 self.deserialize_unit(visitor)
             }
 
-            SchemaNode::NewtypeStruct(_, inner) | SchemaNode::NewtypeVariant(_, _, inner) => {
+            SchemaNode::NewtypeStruct(name, inner) => match self.logical_type(name) {
+                Some(logical) => self.visit_logical_type(logical, inner, visitor),
+                None => visitor.visit_newtype_struct(self.forward(inner)?),
+            },
+            SchemaNode::NewtypeVariant(_, _, inner) => {
                 visitor.visit_newtype_struct(self.forward(inner)?)
             }
             SchemaNode::Sequence(item) => self.do_deserialize_seq(item, visitor),
@@ -542,7 +1050,7 @@ where
             }
             SchemaNode::Struct(_, field_names, skip_list, field_types)
             | SchemaNode::StructVariant(_, _, field_names, skip_list, field_types) => {
-                self.do_deserialize_struct(field_names, skip_list, field_types, visitor)
+                self.do_deserialize_struct(None, field_names, skip_list, field_types, visitor)
             }
             SchemaNode::Union(variants) => {
                 self.deserialize_union(variants, deferred::deserialize_any { visitor })
@@ -551,22 +1059,22 @@ where
     }
 
     deserialize_simple! { deserialize_bool, SchemaNode::Bool }
-    deserialize_simple! { deserialize_i8, @integer }
-    deserialize_simple! { deserialize_i16, @integer }
-    deserialize_simple! { deserialize_i32, @integer }
-    deserialize_simple! { deserialize_i64, @integer }
-    deserialize_simple! { deserialize_i128, @integer }
-    deserialize_simple! { deserialize_u8, @integer }
-    deserialize_simple! { deserialize_u16, @integer }
-    deserialize_simple! { deserialize_u32, @integer }
-    deserialize_simple! { deserialize_u64, @integer }
-    deserialize_simple! { deserialize_u128, @integer }
-    deserialize_simple! { deserialize_f32, @float }
-    deserialize_simple! { deserialize_f64, @float }
-    deserialize_simple! { deserialize_char, SchemaNode::Char }
-
-    deserialize_simple! { deserialize_str, SchemaNode::String }
-    deserialize_simple! { deserialize_string, SchemaNode::String }
+    deserialize_simple! { deserialize_i8, @integer I8 }
+    deserialize_simple! { deserialize_i16, @integer I16 }
+    deserialize_simple! { deserialize_i32, @integer I32 }
+    deserialize_simple! { deserialize_i64, @integer I64 }
+    deserialize_simple! { deserialize_i128, @integer I128 }
+    deserialize_simple! { deserialize_u8, @integer U8 }
+    deserialize_simple! { deserialize_u16, @integer U16 }
+    deserialize_simple! { deserialize_u32, @integer U32 }
+    deserialize_simple! { deserialize_u64, @integer U64 }
+    deserialize_simple! { deserialize_u128, @integer U128 }
+    deserialize_simple! { deserialize_f32, @float F32 }
+    deserialize_simple! { deserialize_f64, @float F64 }
+    deserialize_simple! { deserialize_char, @char }
+
+    deserialize_simple! { deserialize_str, @string }
+    deserialize_simple! { deserialize_string, @string }
     deserialize_simple! { deserialize_bytes, SchemaNode::Bytes }
     deserialize_simple! { deserialize_byte_buf, SchemaNode::Bytes }
     deserialize_simple! { deserialize_unit, SchemaNode::Unit | SchemaNode::UnitStruct(_) | SchemaNode::UnitVariant(_, _) }
@@ -612,10 +1120,15 @@ where
                 variants,
                 deferred::deserialize_newtype_struct { name, visitor },
             ),
+            _ if name == RAW_VALUE_TYPE_NAME => raw_value::visit_captured(self.capture()?, visitor),
             SchemaNode::OptionSome(inner) => self
                 .forward(inner)?
                 .deserialize_newtype_struct(name, visitor),
-            SchemaNode::NewtypeStruct(_, inner) | SchemaNode::NewtypeVariant(_, _, inner) => {
+            SchemaNode::NewtypeStruct(name, inner) => match self.logical_type(name) {
+                Some(logical) => self.visit_logical_type(logical, inner, visitor),
+                None => visitor.visit_newtype_struct(self.forward(inner)?),
+            },
+            SchemaNode::NewtypeVariant(_, _, inner) => {
                 visitor.visit_newtype_struct(self.forward(inner)?)
             }
             _ => visitor.visit_newtype_struct(self),
@@ -641,6 +1154,12 @@ where
             | SchemaNode::TupleVariant(_, _, field_types) => {
                 self.do_deserialize_tuple(field_types, visitor)
             }
+            SchemaNode::Bytes if self.options.byte_sequence_coercions => {
+                self.inner.deserialize_bytes(ByteSequenceVisitor {
+                    inner: visitor,
+                    max_length: self.options.max_sequence_length,
+                })
+            }
             _ => self.invalid_type_error(&visitor),
         }
     }
@@ -696,7 +1215,7 @@ where
             SchemaNode::Map(key, value) => self.do_deserialize_map(key, value, visitor),
             SchemaNode::Struct(_, field_names, skip_list, field_types)
             | SchemaNode::StructVariant(_, _, field_names, skip_list, field_types) => {
-                self.do_deserialize_struct(field_names, skip_list, field_types, visitor)
+                self.do_deserialize_struct(None, field_names, skip_list, field_types, visitor)
             }
 
             _ => self.invalid_type_error(&visitor),
@@ -729,9 +1248,8 @@ where
                 .deserialize_struct(name, fields, visitor),
 
             SchemaNode::Struct(_, field_names, skip_list, field_types)
-            | SchemaNode::StructVariant(_, _, field_names, skip_list, field_types) => {
-                self.do_deserialize_struct(field_names, skip_list, field_types, visitor)
-            }
+            | SchemaNode::StructVariant(_, _, field_names, skip_list, field_types) => self
+                .do_deserialize_struct(Some(fields), field_names, skip_list, field_types, visitor),
             SchemaNode::Map(key, value) => self.do_deserialize_map(key, value, visitor),
 
             _ => self.invalid_type_error(&visitor),
@@ -761,7 +1279,14 @@ where
                 .forward(inner)?
                 .deserialize_enum(name, variants, visitor),
 
-            _ => visitor.visit_enum(self),
+            SchemaNode::String if self.options.enum_string_coercions => {
+                visitor.visit_enum(String::deserialize(self.inner)?.into_deserializer())
+            }
+
+            _ => visitor.visit_enum(ExpectedVariants {
+                deserializer: self,
+                variants: Some(variants),
+            }),
         }
     }
 
@@ -795,7 +1320,7 @@ where
 
     #[inline]
     fn is_human_readable(&self) -> bool {
-        false
+        self.schema.human_readable
     }
 }
 
@@ -806,23 +1331,58 @@ where
     type Error = DeserializerT::Error;
     type Variant = Self;
 
+    #[inline]
     fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
     where
         V: DeserializeSeed<'de>,
     {
-        match self.node {
+        ExpectedVariants {
+            deserializer: self,
+            variants: None,
+        }
+        .variant_seed(seed)
+    }
+}
+
+/// Accesses an enum whose variant names are resolved against the expected ones, see
+/// [`Schema::resolve_name`].
+struct ExpectedVariants<'s, DeserializerT> {
+    deserializer: SchemaDeserializer<'s, DeserializerT>,
+    variants: Option<&'static [&'static str]>,
+}
+
+impl<'s, 'de, DeserializerT> EnumAccess<'de> for ExpectedVariants<'s, DeserializerT>
+where
+    DeserializerT: Deserializer<'de>,
+{
+    type Error = DeserializerT::Error;
+    type Variant = SchemaDeserializer<'s, DeserializerT>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let Self {
+            deserializer,
+            variants,
+        } = self;
+        match deserializer.node {
             SchemaNode::UnitVariant(_, variant)
             | SchemaNode::TupleVariant(_, variant, _)
-            | SchemaNode::StructVariant(_, variant, _, _, _) => seed
-                .deserialize(self.variant_name_deserializer(variant)?)
-                .map(move |value| (value, self)),
+            | SchemaNode::StructVariant(_, variant, _, _, _) => {
+                match seed.deserialize(deserializer.variant_name_deserializer(variant, variants)?) {
+                    Ok(value) => Ok((value, deserializer)),
+                    Err(error) => Err(deserializer.skip_after(error)),
+                }
+            }
             SchemaNode::NewtypeVariant(_, variant, inner) => {
-                self.forward(inner).and_then(|newtype| {
-                    seed.deserialize(newtype.variant_name_deserializer(variant)?)
-                        .map(|value| (value, newtype))
-                })
+                let newtype = deserializer.forward(inner)?;
+                match seed.deserialize(newtype.variant_name_deserializer(variant, variants)?) {
+                    Ok(value) => Ok((value, newtype)),
+                    Err(error) => Err(newtype.skip_after(error)),
+                }
             }
-            _ => self.invalid_type_error(&"enum variant"),
+            _ => deserializer.invalid_type_error(&"enum variant"),
         }
     }
 }
@@ -864,8 +1424,10 @@ where
 }
 
 pub struct SchemaSeqDeserializer<'schema, InnerT> {
-    schema: &'schema Schema,
+    schema: PlannedSchema<'schema>,
+    options: DecodeOptions,
     item: SchemaNode,
+    length: usize,
     inner: InnerT,
 }
 
@@ -883,11 +1445,18 @@ where
     where
         A: SeqAccess<'de>,
     {
-        self.inner.visit_seq(SchemaSeqDeserializer {
+        let mut seq = SchemaSeqDeserializer {
             schema: self.schema,
+            options: self.options,
             item: self.item,
+            length: 0,
             inner: seq,
-        })
+        };
+        let result = self.inner.visit_seq(&mut seq);
+        if result.is_err() && self.options.skip_failed_values {
+            while let Ok(Some(IgnoredAny)) = seq.next_element() {}
+        }
+        result
     }
 }
 
@@ -901,18 +1470,71 @@ where
     where
         T: DeserializeSeed<'de>,
     {
-        self.inner.next_element_seed(SchemaDeserializer {
+        let element = self.inner.next_element_seed(SchemaDeserializer {
             schema: self.schema,
+            options: self.options,
             node: self.item,
             inner: seed,
-        })
+        })?;
+        if element.is_some() {
+            self.length += 1;
+            check_sequence_length(self.length, self.options.max_sequence_length)?;
+        }
+        Ok(element)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        self.inner
+            .size_hint()
+            .map(|hint| hint.min(self.options.max_sequence_length))
+    }
+}
+
+/// Visits a byte array as a sequence of `u8`-s, see [`DecodeOptions::byte_sequence_coercions`].
+struct ByteSequenceVisitor<VisitorT> {
+    inner: VisitorT,
+    max_length: usize,
+}
+
+impl<'de, VisitorT> serde::de::Visitor<'de> for ByteSequenceVisitor<VisitorT>
+where
+    VisitorT: serde::de::Visitor<'de>,
+{
+    type Value = VisitorT::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        check_sequence_length(bytes.len(), self.max_length)?;
+        charge_allocation(bytes.len())?;
+        self.inner
+            .visit_seq(SeqDeserializer::new(bytes.iter().copied()))
+    }
+
+    fn visit_byte_buf<E>(self, bytes: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        check_sequence_length(bytes.len(), self.max_length)?;
+        charge_allocation(bytes.len())?;
+        self.inner
+            .visit_seq(SeqDeserializer::new(bytes.into_iter()))
     }
 }
 
 pub struct SchemaMapDeserializer<'schema, InnerT> {
-    schema: &'schema Schema,
+    schema: PlannedSchema<'schema>,
+    options: DecodeOptions,
     key: SchemaNode,
     value: SchemaNode,
+    length: usize,
+    value_pending: bool,
     inner: InnerT,
 }
 
@@ -930,12 +1552,23 @@ where
     where
         A: MapAccess<'de>,
     {
-        self.inner.visit_map(SchemaMapDeserializer {
+        let mut map = SchemaMapDeserializer {
             schema: self.schema,
+            options: self.options,
             key: self.key,
             value: self.value,
+            length: 0,
+            value_pending: false,
             inner: map,
-        })
+        };
+        let result = self.inner.visit_map(&mut map);
+        if result.is_err() && self.options.skip_failed_values {
+            if map.value_pending {
+                let _ = map.next_value::<IgnoredAny>();
+            }
+            while let Ok(Some((IgnoredAny, IgnoredAny))) = map.next_entry() {}
+        }
+        result
     }
 }
 
@@ -950,11 +1583,18 @@ where
     where
         K: DeserializeSeed<'de>,
     {
-        self.inner.next_key_seed(SchemaDeserializer {
+        let key = self.inner.next_key_seed(SchemaDeserializer {
             schema: self.schema,
+            options: self.options,
             node: self.key,
             inner: seed,
-        })
+        })?;
+        if key.is_some() {
+            self.value_pending = true;
+            self.length += 1;
+            check_sequence_length(self.length, self.options.max_sequence_length)?;
+        }
+        Ok(key)
     }
 
     #[inline]
@@ -962,8 +1602,10 @@ where
     where
         V: DeserializeSeed<'de>,
     {
+        self.value_pending = false;
         self.inner.next_value_seed(SchemaDeserializer {
             schema: self.schema,
+            options: self.options,
             node: self.value,
             inner: seed,
         })
@@ -979,23 +1621,38 @@ where
         K: DeserializeSeed<'de>,
         V: DeserializeSeed<'de>,
     {
-        self.inner.next_entry_seed(
+        let entry = self.inner.next_entry_seed(
             SchemaDeserializer {
                 schema: self.schema,
+                options: self.options,
                 node: self.key,
                 inner: kseed,
             },
             SchemaDeserializer {
                 schema: self.schema,
+                options: self.options,
                 node: self.value,
                 inner: vseed,
             },
-        )
+        )?;
+        if entry.is_some() {
+            self.length += 1;
+            check_sequence_length(self.length, self.options.max_sequence_length)?;
+        }
+        Ok(entry)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        self.inner
+            .size_hint()
+            .map(|hint| hint.min(self.options.max_sequence_length))
     }
 }
 
 pub struct SchemaTupleDeserializer<'schema, InnerT> {
-    schema: &'schema Schema,
+    schema: PlannedSchema<'schema>,
+    options: DecodeOptions,
     items: &'schema [SchemaNodeIndex],
     inner: InnerT,
 }
@@ -1014,11 +1671,17 @@ where
     where
         A: SeqAccess<'de>,
     {
-        self.inner.visit_seq(SchemaTupleDeserializer {
+        let mut seq = SchemaTupleDeserializer {
             schema: self.schema,
+            options: self.options,
             items: self.items,
             inner: seq,
-        })
+        };
+        let result = self.inner.visit_seq(&mut seq);
+        if result.is_err() && self.options.skip_failed_values {
+            while let Ok(Some(IgnoredAny)) = seq.next_element() {}
+        }
+        result
     }
 }
 
@@ -1035,6 +1698,7 @@ where
         if let Some(&node) = self.items.split_off_first() {
             self.inner.next_element_seed(SchemaDeserializer {
                 schema: self.schema,
+                options: self.options,
                 node: self.schema.node(node).map_err(Self::Error::custom)?,
                 inner: seed,
             })
@@ -1045,11 +1709,16 @@ where
 }
 
 pub struct SchemaStructDeserializer<'schema, InnerT> {
-    schema: &'schema Schema,
+    schema: PlannedSchema<'schema>,
+    options: DecodeOptions,
+    expected_fields: Option<&'static [&'static str]>,
     field_names: &'schema [FieldNameIndex],
+    // The positions of the fields among the expected ones, if resolved by a `DecodePlan`.
+    resolved_fields: &'schema [Option<u32>],
     field_types: &'schema [SchemaNodeIndex],
     skip_list: &'schema [MemberIndex],
-    discriminant: u64,
+    discriminant: Discriminant,
+    i_skip: usize,
     i_field: usize,
     next_value_schema: Option<SchemaNode>,
     inner: InnerT,
@@ -1060,7 +1729,9 @@ where
     InnerT: serde::de::Visitor<'de>,
 {
     pub fn seed<ErrorT>(
-        schema: &'schema Schema,
+        schema: PlannedSchema<'schema>,
+        options: DecodeOptions,
+        expected_fields: Option<&'static [&'static str]>,
         field_names: FieldNameListIndex,
         skip_list: MemberListIndex,
         field_types: SchemaNodeListIndex,
@@ -1069,11 +1740,21 @@ where
     where
         ErrorT: serde::de::Error,
     {
+        let resolved_fields = expected_fields
+            .and_then(|expected| schema.resolved_fields(field_names, expected))
+            .unwrap_or_default();
         let field_names = schema
+            .schema()
             .field_name_list(field_names)
             .map_err(ErrorT::custom)?;
-        let field_types = schema.node_list(field_types).map_err(ErrorT::custom)?;
-        let skip_list = schema.member_list(skip_list).map_err(ErrorT::custom)?;
+        let field_types = schema
+            .schema()
+            .node_list(field_types)
+            .map_err(ErrorT::custom)?;
+        let skip_list = schema
+            .schema()
+            .member_list(skip_list)
+            .map_err(ErrorT::custom)?;
 
         if field_names.len() != field_types.len() {
             return Err(ErrorT::custom(
@@ -1081,16 +1762,23 @@ where
             ));
         }
 
-        ChunkedEnum::deserializable(skip_list.len(), move |discriminant| Self {
-            schema,
-            field_names,
-            field_types,
-            skip_list,
-            discriminant,
-            i_field: 0,
-            next_value_schema: None,
-            inner,
-        })
+        Ok(ChunkedEnum::deserializable(
+            skip_list.len(),
+            move |discriminant| Self {
+                schema,
+                options,
+                expected_fields,
+                field_names,
+                resolved_fields,
+                field_types,
+                skip_list,
+                discriminant,
+                i_skip: 0,
+                i_field: 0,
+                next_value_schema: None,
+                inner,
+            },
+        ))
     }
 }
 
@@ -1099,39 +1787,136 @@ impl<'schema, InnerT> SchemaStructDeserializer<'schema, InnerT> {
     where
         ErrorT: serde::de::Error,
     {
-        loop {
-            let (name_index, node_index) = match (
-                self.field_names.split_off_first(),
-                self.field_types.split_off_first(),
-            ) {
-                (Some(&name_index), Some(&node_index)) => (name_index, node_index),
-                (None, None) => return Ok(None),
-                _ => unreachable!("types & names are verified to have the same length"),
+        while let Some((field_name, node, expected)) = self.next_field()? {
+            let Some(node) = node else {
+                self.check_missing(field_name, expected)?;
+                continue;
             };
+            self.check_known(field_name, expected)?;
+            return Ok(Some((field_name, node)));
+        }
+        Ok(None)
+    }
 
-            // Skip fields marked as such in the variant.
-            if let Some(&i_skip_field) = self.skip_list.first() {
-                let i_field = self.i_field;
-                self.i_field += 1;
-                if usize::from(i_skip_field) == i_field {
-                    let skipped = (self.discriminant & 1) == 0;
-                    self.discriminant >>= 1;
-                    self.skip_list.split_off_first();
-                    if skipped {
-                        continue;
-                    }
+    /// Returns the name of the next field, its type unless it's absent from the serialized
+    /// struct, and whether it's expected, if resolved by a [`DecodePlan`].
+    ///
+    /// [`DecodePlan`]: crate::DecodePlan
+    #[allow(clippy::type_complexity)]
+    fn next_field<ErrorT>(
+        &mut self,
+    ) -> Result<Option<(&'schema str, Option<SchemaNode>, Option<bool>)>, ErrorT>
+    where
+        ErrorT: serde::de::Error,
+    {
+        let (name_index, node_index) = match (
+            self.field_names.split_off_first(),
+            self.field_types.split_off_first(),
+        ) {
+            (Some(&name_index), Some(&node_index)) => (name_index, node_index),
+            (None, None) => return Ok(None),
+            _ => unreachable!("types & names are verified to have the same length"),
+        };
+
+        // Skip fields marked as such in the variant.
+        let mut skipped = false;
+        if let Some(&i_skip_field) = self.skip_list.first() {
+            let i_field = self.i_field;
+            self.i_field += 1;
+            if usize::from(i_skip_field) == i_field {
+                skipped = !self.discriminant.bit(self.i_skip);
+                self.i_skip += 1;
+                self.skip_list.split_off_first();
+            }
+        }
+
+        // Skip Union([]) fields.
+        skipped |= node_index.is_empty();
+
+        let resolved = self.resolved_fields.split_off_first().copied();
+        let field_name = match (resolved, self.expected_fields) {
+            (Some(Some(position)), Some(expected)) => {
+                expected[usize::try_from(position).expect("usize must be at least 32 bits")]
+            }
+            _ => {
+                let schema = self.schema.schema();
+                schema.resolve_name(
+                    schema.field_name(name_index).map_err(ErrorT::custom)?,
+                    self.expected_fields,
+                )
+            }
+        };
+        let node = if skipped {
+            None
+        } else {
+            Some(self.schema.node(node_index).map_err(ErrorT::custom)?)
+        };
+        Ok(Some((
+            field_name,
+            node,
+            resolved.map(|position| position.is_some()),
+        )))
+    }
+
+    /// Skips the values of the remaining fields, after the struct failed to deserialize.
+    fn skip_rest<'de>(&mut self)
+    where
+        InnerT: SeqAccess<'de>,
+    {
+        let mut next = self.next_value_schema.take();
+        loop {
+            if let Some(node) = next {
+                let skipped = self.inner.next_element_seed(SchemaDeserializer {
+                    schema: self.schema,
+                    options: self.options,
+                    node,
+                    inner: PhantomData::<IgnoredAny>,
+                });
+                if !matches!(skipped, Ok(Some(IgnoredAny))) {
+                    return;
                 }
             }
+            next = match self.next_field::<InnerT::Error>() {
+                Ok(Some((_, node, _))) => node,
+                _ => return,
+            };
+        }
+    }
 
-            // Skip Union([]) fields.
-            if node_index.is_empty() {
-                continue;
+    fn check_missing<ErrorT>(&self, field_name: &str, expected: Option<bool>) -> Result<(), ErrorT>
+    where
+        ErrorT: serde::de::Error,
+    {
+        if self.options.default_missing_fields || self.is_unexpected(field_name, expected) {
+            Ok(())
+        } else {
+            Err(ErrorT::custom(format_args!("missing field `{field_name}`")))
+        }
+    }
+
+    fn check_known<ErrorT>(&self, field_name: &str, expected: Option<bool>) -> Result<(), ErrorT>
+    where
+        ErrorT: serde::de::Error,
+    {
+        match self.expected_fields {
+            Some(expected_fields)
+                if self.options.deny_unknown_fields && self.is_unexpected(field_name, expected) =>
+            {
+                Err(ErrorT::unknown_field(field_name, expected_fields))
             }
+            _ => Ok(()),
+        }
+    }
 
-            return Ok(Some((
-                self.schema.field_name(name_index).map_err(ErrorT::custom)?,
-                self.schema.node(node_index).map_err(ErrorT::custom)?,
-            )));
+    /// Whether a field isn't among the expected fields, if they're known, using whether it's
+    /// `expected` if that's already been resolved.
+    #[inline]
+    fn is_unexpected(&self, field_name: &str, expected: Option<bool>) -> bool {
+        match expected {
+            Some(expected) => !expected,
+            None => self
+                .expected_fields
+                .is_some_and(|expected| !expected.contains(&field_name)),
         }
     }
 }
@@ -1178,16 +1963,25 @@ where
     where
         A: SeqAccess<'de>,
     {
-        self.inner.visit_map(SchemaStructDeserializer {
+        let mut map = SchemaStructDeserializer {
             schema: self.schema,
+            options: self.options,
+            expected_fields: self.expected_fields,
             field_names: self.field_names,
+            resolved_fields: self.resolved_fields,
             field_types: self.field_types,
             skip_list: self.skip_list,
             discriminant: self.discriminant,
+            i_skip: self.i_skip,
             i_field: self.i_field,
             next_value_schema: self.next_value_schema,
             inner: seq,
-        })
+        };
+        let result = self.inner.visit_map(&mut map);
+        if result.is_err() && self.options.skip_failed_values {
+            map.skip_rest();
+        }
+        result
     }
 }
 
@@ -1221,8 +2015,10 @@ where
         self.inner
             .next_element_seed(SchemaDeserializer {
                 schema: self.schema,
+                options: self.options,
                 node: self
                     .next_value_schema
+                    .take()
                     .expect("called next_value_seed with no next_key_seed"),
                 inner: seed,
             })?
@@ -1239,25 +2035,144 @@ where
         K: DeserializeSeed<'de>,
         V: DeserializeSeed<'de>,
     {
-        let Some((field_name, field_type)) = self.next()? else {
+        let Some(key) = self.next_key_seed(kseed)? else {
             return Ok(None);
         };
+        let value = self.next_value_seed(vseed)?;
+        Ok(Some((key, value)))
+    }
+}
 
-        let key = kseed.deserialize(NameDeserializer {
-            name: field_name,
+/// An enum variant presented as a map by [`EnumRepresentation::External`] or
+/// [`EnumRepresentation::Adjacent`]: the tag entry holding the variant's name, if any, followed by
+/// the content entry, if any.
+struct VariantEntries<'schema, DeserializerT> {
+    tag: Option<(&'schema str, &'schema str)>,
+    content: Option<(&'schema str, SchemaDeserializer<'schema, DeserializerT>)>,
+}
+
+impl<'schema, 'de, DeserializerT> MapAccess<'de> for VariantEntries<'schema, DeserializerT>
+where
+    DeserializerT: Deserializer<'de>,
+{
+    type Error = DeserializerT::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let name = match (self.tag, &self.content) {
+            (Some((tag, _)), _) => tag,
+            (None, Some((key, _))) => key,
+            (None, None) => return Ok(None),
+        };
+        seed.deserialize(NameDeserializer {
+            name,
             phantom: PhantomData,
-        })?;
+        })
+        .map(Some)
+    }
 
-        let value = self
-            .inner
-            .next_element_seed(SchemaDeserializer {
-                schema: self.schema,
-                node: field_type,
-                inner: vseed,
-            })?
-            .ok_or_else(|| Self::Error::custom("more struct keys than values"))?;
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        if let Some((_, variant)) = self.tag.take() {
+            return seed.deserialize(NameDeserializer {
+                name: variant,
+                phantom: PhantomData,
+            });
+        }
+        match self.content.take() {
+            Some((_, content)) => seed.deserialize(content),
+            None => Err(DeserializerT::Error::custom(
+                "variant value requested before key",
+            )),
+        }
+    }
 
-        Ok(Some((key, value)))
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(usize::from(self.tag.is_some()) + usize::from(self.content.is_some()))
+    }
+}
+
+/// Visits the content of an enum variant presented by [`EnumRepresentation::Internal`], adding
+/// the tag entry in front of its fields.
+struct InternallyTagged<'schema, VisitorT> {
+    tag: &'static str,
+    variant: &'schema str,
+    inner: VisitorT,
+}
+
+impl<'schema, 'de, VisitorT> serde::de::Visitor<'de> for InternallyTagged<'schema, VisitorT>
+where
+    VisitorT: serde::de::Visitor<'de>,
+{
+    type Value = VisitorT::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a variant containing a struct or map, to be internally tagged")
+    }
+
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.inner.visit_map(TagPrefixed {
+            tag: Some((self.tag, self.variant)),
+            pending_variant: None,
+            inner: map,
+        })
+    }
+}
+
+/// The fields of an internally tagged variant, preceded by its tag, see [`InternallyTagged`].
+struct TagPrefixed<'schema, MapAccessT> {
+    tag: Option<(&'static str, &'schema str)>,
+    pending_variant: Option<&'schema str>,
+    inner: MapAccessT,
+}
+
+impl<'schema, 'de, MapAccessT> MapAccess<'de> for TagPrefixed<'schema, MapAccessT>
+where
+    MapAccessT: MapAccess<'de>,
+{
+    type Error = MapAccessT::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let Some((tag, variant)) = self.tag.take() else {
+            return self.inner.next_key_seed(seed);
+        };
+        self.pending_variant = Some(variant);
+        seed.deserialize(NameDeserializer {
+            name: tag,
+            phantom: PhantomData,
+        })
+        .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.pending_variant.take() {
+            Some(variant) => seed.deserialize(NameDeserializer {
+                name: variant,
+                phantom: PhantomData,
+            }),
+            None => self.inner.next_value_seed(seed),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        self.inner
+            .size_hint()
+            .map(|hint| hint + usize::from(self.tag.is_some()))
     }
 }
 
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use thiserror::Error;
+
+use crate::{
+    Schema, SchemaFingerprint,
+    indices::{FieldNameIndex, MemberIndex, SchemaNodeIndex},
+    pool::PoolDelta,
+    schema::SchemaNode,
+};
+
+/// The difference between two versions of a [`Schema`], returned by [`Schema::delta_from`].
+///
+/// On a long-lived stream, the schema may occasionally widen as new shapes of values are seen.
+/// Rather than resending the whole schema every time, the sender can send the delta from the
+/// previous schema, which the receiver applies with [`Schema::apply_delta`].
+///
+/// Schemas are made of pools of nodes and names which only grow by appending, so the delta holds
+/// the entries after the longest prefix each pool shares with the previous schema. When the two
+/// schemas were traced from values seen in the same order, that's usually just the new entries;
+/// at worst, it's the whole schema.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::Serialize;
+/// use serde_describe::SchemaBuilder;
+///
+/// #[derive(Serialize)]
+/// enum Event {
+///     Click { x: u32, y: u32 },
+///     Scroll { delta: i32 },
+/// }
+///
+/// let mut builder = SchemaBuilder::new();
+/// let _ = builder.trace(&Event::Click { x: 1, y: 2 })?;
+/// let before = builder.clone().build()?;
+///
+/// // The schema widens when a new variant shows up.
+/// let _ = builder.trace(&Event::Scroll { delta: -3 })?;
+/// let after = builder.build()?;
+///
+/// // Send the delta instead of the full schema...
+/// let delta = after.delta_from(&before);
+/// let bytes = postcard::to_stdvec(&delta)?;
+/// assert!(bytes.len() < postcard::to_stdvec(&after)?.len());
+///
+/// // ...and apply it to the previous schema on the receiving end.
+/// let delta = postcard::from_bytes(&bytes)?;
+/// let received = before.apply_delta(delta)?;
+/// assert_eq!(received.fingerprint(), after.fingerprint());
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchemaDelta {
+    base: SchemaFingerprint,
+    root_index: SchemaNodeIndex,
+    nodes: PoolDelta<SchemaNode>,
+    node_lists: PoolDelta<Box<[SchemaNodeIndex]>>,
+    member_lists: PoolDelta<Box<[MemberIndex]>>,
+    field_name_lists: PoolDelta<Box<[FieldNameIndex]>>,
+    field_names: PoolDelta<Cow<'static, str>>,
+    variant_names: PoolDelta<Cow<'static, str>>,
+    type_names: PoolDelta<Cow<'static, str>>,
+}
+
+/// Returned by [`Schema::apply_delta`] when the delta can't be applied.
+#[derive(Clone, Debug, Error)]
+#[non_exhaustive]
+pub enum SchemaDeltaError {
+    /// The delta was computed from a different schema.
+    #[error("schema delta applies to schema {delta}, not {schema}")]
+    Mismatch {
+        /// The fingerprint of the schema the delta was computed from.
+        delta: SchemaFingerprint,
+
+        /// The fingerprint of the schema the delta was applied to.
+        schema: SchemaFingerprint,
+    },
+
+    /// The delta refers to entries which don't exist in the previous schema.
+    #[error("schema delta keeps more entries than the previous schema has")]
+    Invalid,
+}
+
+impl SchemaDelta {
+    /// The fingerprint of the schema this delta applies to.
+    #[inline]
+    pub fn base(&self) -> SchemaFingerprint {
+        self.base
+    }
+
+    /// Whether the delta adds no new entries to the schema.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.len()
+            + self.node_lists.len()
+            + self.member_lists.len()
+            + self.field_name_lists.len()
+            + self.field_names.len()
+            + self.variant_names.len()
+            + self.type_names.len()
+            == 0
+    }
+}
+
+impl Schema {
+    /// Computes the delta which turns `previous` into this schema, see [`SchemaDelta`].
+    pub fn delta_from(&self, previous: &Schema) -> SchemaDelta {
+        SchemaDelta {
+            base: previous.fingerprint(),
+            root_index: self.root_index,
+            nodes: self.nodes.delta_from(&previous.nodes),
+            node_lists: self.node_lists.delta_from(&previous.node_lists),
+            member_lists: self.member_lists.delta_from(&previous.member_lists),
+            field_name_lists: self.field_name_lists.delta_from(&previous.field_name_lists),
+            field_names: self.field_names.delta_from(&previous.field_names),
+            variant_names: self.variant_names.delta_from(&previous.variant_names),
+            type_names: self.type_names.delta_from(&previous.type_names),
+        }
+    }
+
+    /// Applies a delta computed with [`Self::delta_from`] to this schema, returning the newer
+    /// schema.
+    ///
+    /// Returns an error if the delta was computed from a different schema.
+    pub fn apply_delta(&self, delta: SchemaDelta) -> Result<Schema, SchemaDeltaError> {
+        let fingerprint = self.fingerprint();
+        if delta.base != fingerprint {
+            return Err(SchemaDeltaError::Mismatch {
+                delta: delta.base,
+                schema: fingerprint,
+            });
+        }
+        let invalid = || SchemaDeltaError::Invalid;
+        Ok(Schema {
+            root_index: delta.root_index,
+            nodes: self.nodes.apply_delta(delta.nodes).ok_or_else(invalid)?,
+            node_lists: self
+                .node_lists
+                .apply_delta(delta.node_lists)
+                .ok_or_else(invalid)?,
+            member_lists: self
+                .member_lists
+                .apply_delta(delta.member_lists)
+                .ok_or_else(invalid)?,
+            field_name_lists: self
+                .field_name_lists
+                .apply_delta(delta.field_name_lists)
+                .ok_or_else(invalid)?,
+            field_names: self
+                .field_names
+                .apply_delta(delta.field_names)
+                .ok_or_else(invalid)?,
+            variant_names: self
+                .variant_names
+                .apply_delta(delta.variant_names)
+                .ok_or_else(invalid)?,
+            type_names: self
+                .type_names
+                .apply_delta(delta.type_names)
+                .ok_or_else(invalid)?,
+            // Schemas of the same builder are always traced in the same mode, and versioned the
+            // same way.
+            human_readable: self.human_readable,
+            version: self.version,
+            min_reader_version: self.min_reader_version,
+            hashed_names: self.hashed_names,
+        })
+    }
+}
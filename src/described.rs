@@ -1,4 +1,4 @@
-use crate::Schema;
+use crate::{DecodeOptions, Schema, WithOptions};
 
 /// Wraps a serializable and/or deserializable type such that it gets serialized together with its
 /// schema, making non-self-describing formats effectively self-describing.
@@ -103,6 +103,24 @@ pub struct SelfDescribed<T>(pub T);
 #[derive(Copy, Clone)]
 pub struct DescribedBy<'schema, T>(pub T, pub &'schema Schema);
 
+impl<T> SelfDescribed<T> {
+    /// Wraps this deserialization seed such that it's deserialized with the given
+    /// [`DecodeOptions`] rather than the default ones.
+    #[inline]
+    pub fn with_options(self, options: DecodeOptions) -> WithOptions<Self> {
+        WithOptions(self, options)
+    }
+}
+
+impl<'schema, T> DescribedBy<'schema, T> {
+    /// Wraps this deserialization seed such that it's deserialized with the given
+    /// [`DecodeOptions`] rather than the default ones.
+    #[inline]
+    pub fn with_options(self, options: DecodeOptions) -> WithOptions<Self> {
+        WithOptions(self, options)
+    }
+}
+
 impl<T> From<T> for SelfDescribed<T> {
     #[inline]
     fn from(value: T) -> Self {
@@ -0,0 +1,336 @@
+use std::{cell::Cell, fmt::Write};
+
+use crate::{
+    DescribedBy, Schema, Trace,
+    dump::DumpError,
+    trace::{InvalidTraceError, ReadTraceExt, TraceNode},
+};
+
+/// The structural differences between two traced values, returned by [`DescribedBy::diff`].
+///
+/// The values are aligned by their shape rather than by their schemas, so values traced with
+/// different [`SchemaBuilder`][`crate::SchemaBuilder`]-s can be compared: struct fields are
+/// matched by name, map entries by key and sequence items by position.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TraceDiff {
+    changes: Vec<TraceChange>,
+}
+
+/// A single difference between two traced values, see [`TraceDiff`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TraceChange {
+    path: String,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+impl TraceDiff {
+    /// Whether the two values are structurally equal.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The differences between the two values, in traversal order.
+    #[inline]
+    pub fn changes(&self) -> &[TraceChange] {
+        &self.changes
+    }
+}
+
+impl TraceChange {
+    /// A `jq`-like path to the differing value, e.g. `.items[2].name` or `.` for the root.
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The rendered value in the first trace, or `None` if it was added in the second one.
+    #[inline]
+    pub fn before(&self) -> Option<&str> {
+        self.before.as_deref()
+    }
+
+    /// The rendered value in the second trace, or `None` if it was removed from the first one.
+    #[inline]
+    pub fn after(&self) -> Option<&str> {
+        self.after.as_deref()
+    }
+}
+
+impl std::fmt::Display for TraceDiff {
+    /// Writes one change per line, prefixed by `~`, `-` or `+` for changed, removed and added
+    /// values respectively.
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(formatter, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for TraceChange {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = &self.path;
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => write!(formatter, "~ {path}: {before} -> {after}"),
+            (Some(before), None) => write!(formatter, "- {path}: {before}"),
+            (None, Some(after)) => write!(formatter, "+ {path}: {after}"),
+            (None, None) => write!(formatter, "  {path}"),
+        }
+    }
+}
+
+impl DescribedBy<'_, &'_ Trace> {
+    /// Compares this traced value with another one, returning the paths at which they differ.
+    ///
+    /// Differing values are rendered as by the [`Display`][`std::fmt::Display`] implementation
+    /// of [`DescribedBy<&Trace>`][`DescribedBy`].
+    ///
+    /// Returns an error if either trace doesn't match its schema.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Config {
+    ///     name: &'static str,
+    ///     ports: Vec<u16>,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let before = builder.trace(&Config { name: "a", ports: vec![80] })?;
+    /// let before_schema = builder.build()?;
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let after = builder.trace(&Config { name: "b", ports: vec![80, 443] })?;
+    /// let after_schema = builder.build()?;
+    ///
+    /// let diff = before_schema
+    ///     .describe_trace_ref(&before)
+    ///     .diff(&after_schema.describe_trace_ref(&after))?;
+    /// assert_eq!(diff.to_string(), "~ .name: \"a\" -> \"b\"\n+ .ports[1]: 443\n");
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn diff(&self, other: &DescribedBy<'_, &'_ Trace>) -> Result<TraceDiff, InvalidTraceError> {
+        let mut differ = Differ {
+            before: self.1,
+            after: other.1,
+            path: String::new(),
+            changes: Vec::new(),
+        };
+        differ.diff(&Cell::new(&(self.0).0), &Cell::new(&(other.0).0))?;
+        Ok(TraceDiff {
+            changes: differ.changes,
+        })
+    }
+}
+
+struct Differ<'schema> {
+    before: &'schema Schema,
+    after: &'schema Schema,
+    path: String,
+    changes: Vec<TraceChange>,
+}
+
+/// The parts of a composite trace node which need to match for its children to be compared.
+#[derive(PartialEq)]
+enum Composite<'schema> {
+    Some,
+    Newtype(&'schema str, Option<&'schema str>),
+    Sequence,
+    Map,
+    Tuple(Option<(&'schema str, Option<&'schema str>)>, u32),
+    Struct(&'schema str, Option<&'schema str>),
+}
+
+impl<'schema> Composite<'schema> {
+    fn new(schema: &'schema Schema, node: TraceNode) -> Result<Option<Self>, DumpError> {
+        Ok(Some(match node {
+            TraceNode::Some => Self::Some,
+            TraceNode::NewtypeStruct(name) => Self::Newtype(schema.type_name(name)?, None),
+            TraceNode::NewtypeVariant(name, variant) => {
+                Self::Newtype(schema.type_name(name)?, Some(schema.variant_name(variant)?))
+            }
+            TraceNode::Sequence => Self::Sequence,
+            TraceNode::Map => Self::Map,
+            TraceNode::Tuple(length) => Self::Tuple(None, length),
+            TraceNode::TupleStruct(length, name) => {
+                Self::Tuple(Some((schema.type_name(name)?, None)), length)
+            }
+            TraceNode::TupleVariant(length, name, variant) => Self::Tuple(
+                Some((schema.type_name(name)?, Some(schema.variant_name(variant)?))),
+                length,
+            ),
+            TraceNode::Struct(name, _) => Self::Struct(schema.type_name(name)?, None),
+            TraceNode::StructVariant(name, variant, _) => {
+                Self::Struct(schema.type_name(name)?, Some(schema.variant_name(variant)?))
+            }
+            _ => return Ok(None),
+        }))
+    }
+}
+
+impl Differ<'_> {
+    fn diff(&mut self, before: &Cell<&[u8]>, after: &Cell<&[u8]>) -> Result<(), DumpError> {
+        let (before_start, after_start) = (before.get(), after.get());
+        let before_node = before.pop_trace_node::<DumpError>()?;
+        let after_node = after.pop_trace_node::<DumpError>()?;
+        let before_composite = Composite::new(self.before, before_node)?;
+        let after_composite = Composite::new(self.after, after_node)?;
+        match before_composite {
+            Some(composite) if before_composite == after_composite => match composite {
+                Composite::Some | Composite::Newtype(..) => self.diff(before, after),
+                Composite::Sequence => {
+                    let before_length = before.pop_length_u32::<DumpError>()?;
+                    let after_length = after.pop_length_u32::<DumpError>()?;
+                    self.diff_items(before, after, before_length, after_length, "[", "]")
+                }
+                Composite::Tuple(_, length) => {
+                    let length =
+                        usize::try_from(length).expect("usize needs to be at least 32 bits");
+                    self.diff_items(before, after, length, length, ".", "")
+                }
+                Composite::Map => {
+                    let before = pop_map_entries(self.before, before)?;
+                    let after = pop_map_entries(self.after, after)?;
+                    self.diff_entries(&before, &after, "[", "]")
+                }
+                Composite::Struct(..) => {
+                    let before = pop_struct_fields(self.before, before_node, before)?;
+                    let after = pop_struct_fields(self.after, after_node, after)?;
+                    self.diff_entries(&before, &after, ".", "")
+                }
+            },
+            _ => {
+                before.set(before_start);
+                after.set(after_start);
+                let before = self.before.trace_to_string(before)?;
+                let after = self.after.trace_to_string(after)?;
+                if before != after {
+                    self.push_change(Some(before), Some(after));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn diff_items(
+        &mut self,
+        before: &Cell<&[u8]>,
+        after: &Cell<&[u8]>,
+        before_length: usize,
+        after_length: usize,
+        open: &str,
+        close: &str,
+    ) -> Result<(), DumpError> {
+        for index in 0..before_length.max(after_length) {
+            let parent_length = self.path.len();
+            write!(self.path, "{open}{index}{close}")?;
+            match (index < before_length, index < after_length) {
+                (true, true) => self.diff(before, after)?,
+                (true, false) => {
+                    let before = self.before.trace_to_string(before)?;
+                    self.push_change(Some(before), None);
+                }
+                _ => {
+                    let after = self.after.trace_to_string(after)?;
+                    self.push_change(None, Some(after));
+                }
+            }
+            self.path.truncate(parent_length);
+        }
+        Ok(())
+    }
+
+    fn diff_entries(
+        &mut self,
+        before: &[(String, &[u8])],
+        after: &[(String, &[u8])],
+        open: &str,
+        close: &str,
+    ) -> Result<(), DumpError> {
+        for (key, before_value) in before {
+            let parent_length = self.path.len();
+            write!(self.path, "{open}{key}{close}")?;
+            let before_value = Cell::new(*before_value);
+            if let Some((_, after_value)) = after.iter().find(|(after_key, _)| after_key == key) {
+                self.diff(&before_value, &Cell::new(after_value))?;
+            } else {
+                let before = self.before.trace_to_string(&before_value)?;
+                self.push_change(Some(before), None);
+            }
+            self.path.truncate(parent_length);
+        }
+        for (key, after_value) in after {
+            if before.iter().any(|(before_key, _)| before_key == key) {
+                continue;
+            }
+            let parent_length = self.path.len();
+            write!(self.path, "{open}{key}{close}")?;
+            let after = self.after.trace_to_string(&Cell::new(after_value))?;
+            self.push_change(None, Some(after));
+            self.path.truncate(parent_length);
+        }
+        Ok(())
+    }
+
+    fn push_change(&mut self, before: Option<String>, after: Option<String>) {
+        let path = if self.path.starts_with('.') {
+            self.path.clone()
+        } else {
+            format!(".{}", self.path)
+        };
+        self.changes.push(TraceChange {
+            path,
+            before,
+            after,
+        });
+    }
+}
+
+/// Pops the next value in the trace, returning the slice of the trace it spanned.
+fn pop_value<'trace>(
+    schema: &Schema,
+    tail: &Cell<&'trace [u8]>,
+) -> Result<&'trace [u8], DumpError> {
+    let start = tail.get();
+    let _ = schema.trace_to_string(tail)?;
+    Ok(&start[..start.len() - tail.get().len()])
+}
+
+/// Pops all the entries of a map, returning their rendered keys and the traces of their
+/// values.
+fn pop_map_entries<'trace>(
+    schema: &Schema,
+    tail: &Cell<&'trace [u8]>,
+) -> Result<Vec<(String, &'trace [u8])>, DumpError> {
+    let length = tail.pop_length_u32::<DumpError>()?;
+    (0..length)
+        .map(|_| {
+            let key = schema.trace_to_string(tail)?;
+            Ok((key, pop_value(schema, tail)?))
+        })
+        .collect()
+}
+
+/// Pops all the fields of a struct, returning the names and the traces of the present ones.
+fn pop_struct_fields<'trace>(
+    schema: &Schema,
+    node: TraceNode,
+    tail: &Cell<&'trace [u8]>,
+) -> Result<Vec<(String, &'trace [u8])>, DumpError> {
+    let (TraceNode::Struct(_, field_names) | TraceNode::StructVariant(_, _, field_names)) = node
+    else {
+        unreachable!("only called for struct nodes");
+    };
+    schema
+        .pop_trace_field_names(tail, field_names)?
+        .into_iter()
+        .map(|name| Ok((name.to_owned(), pop_value(schema, tail)?)))
+        .collect()
+}
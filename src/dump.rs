@@ -1,12 +1,13 @@
 use serde::Serialize;
 use std::{
+    cell::Cell,
     collections::HashSet,
     fmt::{Display, Write},
 };
 use thiserror::Error;
 
 use crate::{
-    SchemaBuilder, TraceError,
+    DescribedBy, SchemaBuilder, Trace, TraceError,
     indices::{
         FieldNameListIndex, IndexIsEmpty, IsEmpty, MemberListIndex, SchemaNodeIndex,
         SchemaNodeListIndex, TypeNameIndex, VariantNameIndex,
@@ -15,6 +16,7 @@ use crate::{
         NoSuchFieldListError, NoSuchFieldNameError, NoSuchFieldNameListError, NoSuchNodeListError,
         NoSuchSchemaError, NoSuchTypeNameError, NoSuchVariantNameError, Schema, SchemaNode,
     },
+    trace::{InvalidTraceError, ReadTraceExt, TraceNode, TraceNodeKind, present_members},
 };
 
 impl std::fmt::Display for Schema {
@@ -39,6 +41,60 @@ impl std::fmt::Display for Schema {
     }
 }
 
+impl std::fmt::Display for DescribedBy<'_, &'_ Trace> {
+    /// Produces a human-readable, RON-like representation of the traced value, without needing
+    /// to deserialize it into a concrete type first.
+    ///
+    /// The output of this method is not stable.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Point {
+    ///     x: i32,
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     label: Option<String>,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&vec![
+    ///     Point { x: 1, label: None },
+    ///     Point { x: 2, label: Some("two".to_owned()) },
+    /// ])?;
+    /// let schema = builder.build()?;
+    ///
+    /// assert_eq!(
+    ///     schema.describe_trace_ref(&trace).to_string(),
+    ///     r#"[Point(x: 1), Point(x: 2, label: Some("two"))]"#,
+    /// );
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut context = if formatter.alternate() {
+            DumpContext::multiline()
+        } else {
+            DumpContext::oneline()
+        };
+        let tail = Cell::new(&*(self.0).0);
+        if let Err(error) = self.1.recursive_dump_trace(&mut context, &tail) {
+            write!(formatter, "<invalid trace: {error}>")
+        } else {
+            formatter.write_str(&context.output)
+        }
+    }
+}
+
+impl std::fmt::Display for DescribedBy<'_, Trace> {
+    /// See the implementation for [`DescribedBy<&Trace>`][`DescribedBy`].
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&DescribedBy(&self.0, self.1), formatter)
+    }
+}
+
 impl Schema {
     /// Produces a large, human-readable representation of the schema for a given value.
     ///
@@ -250,9 +306,214 @@ impl Schema {
             Ok(())
         })
     }
+    fn recursive_dump_trace(
+        &self,
+        context: &mut DumpContext,
+        tail: &Cell<&[u8]>,
+    ) -> Result<(), DumpError> {
+        match tail.pop_trace_node::<DumpError>()? {
+            TraceNode::Bool => write!(context, "{}", tail.pop_bool::<DumpError>()?)?,
+            TraceNode::I8 => write!(context, "{}", tail.pop_i8::<DumpError>()?)?,
+            TraceNode::I16 => write!(context, "{}", tail.pop_i16::<DumpError>()?)?,
+            TraceNode::I32 => write!(context, "{}", tail.pop_i32::<DumpError>()?)?,
+            TraceNode::I64 => write!(context, "{}", tail.pop_i64::<DumpError>()?)?,
+            TraceNode::I128 => write!(context, "{}", tail.pop_i128::<DumpError>()?)?,
+            TraceNode::U8 => write!(context, "{}", tail.pop_u8::<DumpError>()?)?,
+            TraceNode::U16 => write!(context, "{}", tail.pop_u16::<DumpError>()?)?,
+            TraceNode::U32 => write!(context, "{}", tail.pop_u32::<DumpError>()?)?,
+            TraceNode::U64 => write!(context, "{}", tail.pop_u64::<DumpError>()?)?,
+            TraceNode::U128 => write!(context, "{}", tail.pop_u128::<DumpError>()?)?,
+            TraceNode::F32 => write!(context, "{:?}", tail.pop_f32::<DumpError>()?)?,
+            TraceNode::F64 => write!(context, "{:?}", tail.pop_f64::<DumpError>()?)?,
+            TraceNode::Char => write!(context, "{:?}", tail.pop_char::<DumpError>()?)?,
+            TraceNode::String => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                write!(context, "{:?}", tail.pop_str::<DumpError>(length)?)?
+            }
+            TraceNode::Bytes => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                write!(
+                    context,
+                    "b\"{}\"",
+                    tail.pop_slice::<DumpError>(length)?.escape_ascii()
+                )?
+            }
+
+            TraceNode::None => write!(context, "None")?,
+            TraceNode::Some => self.dump_trace_items(context, tail, "Some", "(", ")", 1)?,
+
+            TraceNode::Unit => write!(context, "()")?,
+            TraceNode::UnitStruct(name) => context.write_str(self.type_name(name)?)?,
+            TraceNode::UnitVariant(_, variant) => context.write_str(self.variant_name(variant)?)?,
+
+            TraceNode::NewtypeStruct(name) => {
+                self.dump_trace_items(context, tail, self.type_name(name)?, "(", ")", 1)?
+            }
+            TraceNode::NewtypeVariant(_, variant) => {
+                self.dump_trace_items(context, tail, self.variant_name(variant)?, "(", ")", 1)?
+            }
+
+            TraceNode::Sequence => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                self.dump_trace_items(context, tail, "", "[", "]", length)?
+            }
+            TraceNode::Map => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                self.dump_trace_entries(context, tail, "", "{", "}", length, |context, tail| {
+                    self.recursive_dump_trace(context, tail)
+                })?
+            }
+
+            TraceNode::Tuple(length) => {
+                self.dump_trace_items(context, tail, "", "(", ")", length_from_u32(length))?
+            }
+            TraceNode::TupleStruct(length, name) => self.dump_trace_items(
+                context,
+                tail,
+                self.type_name(name)?,
+                "(",
+                ")",
+                length_from_u32(length),
+            )?,
+            TraceNode::TupleVariant(length, _, variant) => self.dump_trace_items(
+                context,
+                tail,
+                self.variant_name(variant)?,
+                "(",
+                ")",
+                length_from_u32(length),
+            )?,
+
+            TraceNode::Struct(name, field_names) => {
+                self.dump_trace_struct(context, tail, self.type_name(name)?, field_names)?
+            }
+            TraceNode::StructVariant(_, variant, field_names) => {
+                self.dump_trace_struct(context, tail, self.variant_name(variant)?, field_names)?
+            }
+        }
+        Ok(())
+    }
+
+    fn dump_trace_struct(
+        &self,
+        context: &mut DumpContext,
+        tail: &Cell<&[u8]>,
+        name: &str,
+        field_names: FieldNameListIndex,
+    ) -> Result<(), DumpError> {
+        let present = self.pop_trace_field_names(tail, field_names)?;
+        let mut present = present.iter();
+        self.dump_trace_entries(
+            context,
+            tail,
+            name,
+            "(",
+            ")",
+            present.len(),
+            |context, _| {
+                context.write_str(present.next().expect("impossible"))?;
+                Ok(())
+            },
+        )
+    }
+
+    /// Pops the presence list of a traced struct, returning the names of the present fields.
+    pub(crate) fn pop_trace_field_names(
+        &self,
+        tail: &Cell<&[u8]>,
+        field_names: FieldNameListIndex,
+    ) -> Result<Vec<&str>, DumpError> {
+        let field_names = self.field_name_list(field_names)?;
+        present_members(tail.pop_presence::<DumpError>(field_names.len())?)
+            .map(|member| {
+                let name = field_names
+                    .get(usize::from(member))
+                    .ok_or(DumpError::Trace("struct member out of bounds"))?;
+                Ok(self.field_name(*name)?)
+            })
+            .collect()
+    }
+
+    /// Renders the next value in the trace on a single line.
+    pub(crate) fn trace_to_string(&self, tail: &Cell<&[u8]>) -> Result<String, DumpError> {
+        let mut context = DumpContext::oneline();
+        self.recursive_dump_trace(&mut context, tail)?;
+        Ok(context.output)
+    }
+
+    fn dump_trace_items(
+        &self,
+        context: &mut DumpContext,
+        tail: &Cell<&[u8]>,
+        name: &str,
+        open: &str,
+        close: &str,
+        length: usize,
+    ) -> Result<(), DumpError> {
+        self.dump_trace_list(context, tail, name, open, close, length, |context| {
+            self.recursive_dump_trace(context, tail)
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn dump_trace_entries(
+        &self,
+        context: &mut DumpContext,
+        tail: &Cell<&[u8]>,
+        name: &str,
+        open: &str,
+        close: &str,
+        length: usize,
+        mut dump_key: impl FnMut(&mut DumpContext, &Cell<&[u8]>) -> Result<(), DumpError>,
+    ) -> Result<(), DumpError> {
+        self.dump_trace_list(context, tail, name, open, close, length, |context| {
+            dump_key(context, tail)?;
+            context.write_str(": ")?;
+            self.recursive_dump_trace(context, tail)
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn dump_trace_list(
+        &self,
+        context: &mut DumpContext,
+        tail: &Cell<&[u8]>,
+        name: &str,
+        open: &str,
+        close: &str,
+        length: usize,
+        mut dump_item: impl FnMut(&mut DumpContext) -> Result<(), DumpError>,
+    ) -> Result<(), DumpError> {
+        context.write_str(name)?;
+        context.write_str(open)?;
+        if length == 0 {
+            context.write_str(close)?;
+            return Ok(());
+        }
+        // `check_wrapping` may run the closure twice, so the trace needs to be rewound.
+        let start = tail.get();
+        context.check_wrapping(|context| {
+            tail.set(start);
+            context.write_newline()?;
+            context.nest();
+            for i_item in 0..length {
+                context.write_indent()?;
+                dump_item(context)?;
+                context.write_comma(i_item == length - 1)?;
+            }
+            context.unnest();
+            context.write_indent()?;
+            context.write_str(close)?;
+            Ok(())
+        })
+    }
+}
+
+pub(crate) fn length_from_u32(length: u32) -> usize {
+    usize::try_from(length).expect("usize needs to be at least 32 bits")
 }
 
-#[derive(Clone, Copy, Debug, Error)]
+#[derive(Clone, Debug, Error)]
 pub(crate) enum DumpError {
     #[error("dump error: {0}")]
     FieldName(#[from] NoSuchFieldNameError),
@@ -277,6 +538,30 @@ pub(crate) enum DumpError {
 
     #[error("dump formatting error: {0}")]
     Fmt(#[from] std::fmt::Error),
+
+    #[error("dump error: {0}")]
+    Trace(&'static str),
+
+    #[error("dump error: map keys of kind `{0:?}` have no canonical order")]
+    UnorderedMapKey(TraceNodeKind),
+
+    #[error("dump error: {0}")]
+    Custom(Box<str>),
+}
+
+impl From<DumpError> for InvalidTraceError {
+    fn from(error: DumpError) -> Self {
+        Self(error.to_string().into())
+    }
+}
+
+impl serde::ser::Error for DumpError {
+    fn custom<T>(msg: T) -> Self
+    where
+        T: Display,
+    {
+        Self::Custom(msg.to_string().into())
+    }
 }
 
 struct DumpContext {
@@ -0,0 +1,496 @@
+use serde::{
+    Serialize, Serializer,
+    ser::{
+        SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+};
+use std::{
+    cell::Cell,
+    collections::HashSet,
+    sync::{LazyLock, Mutex},
+};
+
+use crate::{
+    DescribedBy, FloatNormalization, InvalidTraceError, Schema, SchemaBuilder, Trace, TraceError,
+    dump::{DumpError, length_from_u32},
+    indices::FieldNameListIndex,
+    trace::{ReadTraceExt, TraceNode},
+};
+
+/// An owned, mutable tree holding any traced value, for transforming values whose types aren't
+/// known statically, returned by [`DescribedBy::to_dynamic`].
+///
+/// Values keep the exact kinds of the `serde` data model (e.g. a `u16` stays a `u16`), and their
+/// type, variant and field names, so serializing an unchanged value produces the same trace and
+/// schema. Implements [`Serialize`], so it can be traced again with
+/// [`SchemaBuilder::trace`][`crate::SchemaBuilder::trace`], or written to any other format.
+///
+/// `serde` requires names to live for the whole program, so the names of serialized values are
+/// interned and never freed. This is fine for names coming from schemas, but serializing values
+/// with an unbounded number of distinct generated names leaks memory.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::Serialize;
+/// use serde_describe::{Dynamic, SchemaBuilder};
+///
+/// #[derive(Serialize)]
+/// struct User {
+///     name: &'static str,
+///     visits: u32,
+/// }
+///
+/// let mut builder = SchemaBuilder::new();
+/// let trace = builder.trace(&User { name: "ana", visits: 3 })?;
+/// let schema = builder.build()?;
+///
+/// let mut user = schema.describe_trace_ref(&trace).to_dynamic()?;
+/// if let Some(Dynamic::U32(visits)) = user.field_mut("visits") {
+///     *visits += 1;
+/// }
+/// assert_eq!(user.field("name").and_then(Dynamic::as_str), Some("ana"));
+///
+/// let mut builder = SchemaBuilder::new();
+/// let trace = builder.trace(&user)?;
+/// let schema = builder.build()?;
+/// assert_eq!(schema.describe_trace(trace).to_string(), r#"User(name: "ana", visits: 4)"#);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum Dynamic {
+    /// A `()`.
+    Unit,
+    /// A `bool`.
+    Bool(bool),
+    /// An `i8`.
+    I8(i8),
+    /// An `i16`.
+    I16(i16),
+    /// An `i32`.
+    I32(i32),
+    /// An `i64`.
+    I64(i64),
+    /// An `i128`.
+    I128(i128),
+    /// A `u8`.
+    U8(u8),
+    /// A `u16`.
+    U16(u16),
+    /// A `u32`.
+    U32(u32),
+    /// A `u64`.
+    U64(u64),
+    /// A `u128`.
+    U128(u128),
+    /// An `f32`.
+    F32(f32),
+    /// An `f64`.
+    F64(f64),
+    /// A `char`.
+    Char(char),
+    /// A string.
+    Str(String),
+    /// A byte string.
+    Bytes(Vec<u8>),
+    /// An absent optional value.
+    None,
+    /// A present optional value.
+    Some(Box<Dynamic>),
+    /// A sequence.
+    Seq(Vec<Dynamic>),
+    /// A tuple.
+    Tuple(Vec<Dynamic>),
+    /// A map, as a list of entries.
+    Map(Vec<(Dynamic, Dynamic)>),
+    /// A unit, newtype, tuple or regular struct.
+    Struct {
+        /// The name of the struct.
+        name: Box<str>,
+        /// The contents of the struct.
+        fields: DynamicFields,
+    },
+    /// A variant of an enum.
+    Variant {
+        /// The name of the enum.
+        name: Box<str>,
+        /// The name of the variant.
+        variant: Box<str>,
+        /// The contents of the variant.
+        fields: DynamicFields,
+    },
+}
+
+/// The contents of a [`Dynamic::Struct`] or [`Dynamic::Variant`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum DynamicFields {
+    /// No contents, e.g. `struct Marker;`.
+    Unit,
+    /// A single wrapped value, e.g. `struct Meters(f64);`.
+    Newtype(Box<Dynamic>),
+    /// Unnamed fields, e.g. `struct Point(i32, i32);`.
+    Tuple(Vec<Dynamic>),
+    /// Named fields, in order. Skipped fields (e.g. by `skip_serializing_if`) have no value.
+    Named(Vec<(Box<str>, Option<Dynamic>)>),
+}
+
+impl Dynamic {
+    /// The value of an integer of any width, or `None` for other values, or a `u128` which
+    /// doesn't fit an `i128`.
+    pub fn as_i128(&self) -> Option<i128> {
+        Some(match *self {
+            Self::I8(value) => value.into(),
+            Self::I16(value) => value.into(),
+            Self::I32(value) => value.into(),
+            Self::I64(value) => value.into(),
+            Self::I128(value) => value,
+            Self::U8(value) => value.into(),
+            Self::U16(value) => value.into(),
+            Self::U32(value) => value.into(),
+            Self::U64(value) => value.into(),
+            Self::U128(value) => value.try_into().ok()?,
+            _ => return None,
+        })
+    }
+
+    /// The value of a float of either width, or `None` for other values.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Self::F32(value) => Some(value.into()),
+            Self::F64(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of a string, or `None` for other values.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The value of a named field of a struct or variant, or `None` if there's no such field, or
+    /// it was skipped.
+    pub fn field(&self, name: &str) -> Option<&Dynamic> {
+        match self {
+            Self::Struct {
+                fields: DynamicFields::Named(fields),
+                ..
+            }
+            | Self::Variant {
+                fields: DynamicFields::Named(fields),
+                ..
+            } => fields
+                .iter()
+                .find(|(field, _)| **field == *name)
+                .and_then(|(_, value)| value.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// Like [`field`][`Self::field`], but returns a mutable reference.
+    pub fn field_mut(&mut self, name: &str) -> Option<&mut Dynamic> {
+        match self {
+            Self::Struct {
+                fields: DynamicFields::Named(fields),
+                ..
+            }
+            | Self::Variant {
+                fields: DynamicFields::Named(fields),
+                ..
+            } => fields
+                .iter_mut()
+                .find(|(field, _)| **field == *name)
+                .and_then(|(_, value)| value.as_mut()),
+            _ => None,
+        }
+    }
+
+    /// Captures any serializable value, e.g. to seal it whole for a
+    /// [`ProtectionPolicy`][`crate::ProtectionPolicy`].
+    pub(crate) fn from_value<ValueT>(
+        value: &ValueT,
+        float_normalization: FloatNormalization,
+        human_readable: bool,
+    ) -> Result<Self, TraceError>
+    where
+        ValueT: ?Sized + Serialize,
+    {
+        let mut builder = SchemaBuilder::new()
+            .float_normalization(float_normalization)
+            .human_readable(human_readable);
+        let trace = builder.trace(&value)?;
+        let schema = builder.build()?;
+        schema
+            .describe_trace_ref(&trace)
+            .to_dynamic()
+            .map_err(|error| TraceError::Custom(error.to_string().into()))
+    }
+}
+
+impl DescribedBy<'_, &'_ Trace> {
+    /// Converts the traced value into a [`Dynamic`] tree, without needing a Rust type for it.
+    pub fn to_dynamic(&self) -> Result<Dynamic, InvalidTraceError> {
+        Ok(self.1.pop_dynamic(&Cell::new(&(self.0).0))?)
+    }
+}
+
+impl Schema {
+    fn pop_dynamic(&self, tail: &Cell<&[u8]>) -> Result<Dynamic, DumpError> {
+        Ok(match tail.pop_trace_node::<DumpError>()? {
+            TraceNode::Bool => Dynamic::Bool(tail.pop_bool::<DumpError>()?),
+            TraceNode::I8 => Dynamic::I8(tail.pop_i8::<DumpError>()?),
+            TraceNode::I16 => Dynamic::I16(tail.pop_i16::<DumpError>()?),
+            TraceNode::I32 => Dynamic::I32(tail.pop_i32::<DumpError>()?),
+            TraceNode::I64 => Dynamic::I64(tail.pop_i64::<DumpError>()?),
+            TraceNode::I128 => Dynamic::I128(tail.pop_i128::<DumpError>()?),
+            TraceNode::U8 => Dynamic::U8(tail.pop_u8::<DumpError>()?),
+            TraceNode::U16 => Dynamic::U16(tail.pop_u16::<DumpError>()?),
+            TraceNode::U32 => Dynamic::U32(tail.pop_u32::<DumpError>()?),
+            TraceNode::U64 => Dynamic::U64(tail.pop_u64::<DumpError>()?),
+            TraceNode::U128 => Dynamic::U128(tail.pop_u128::<DumpError>()?),
+            TraceNode::F32 => Dynamic::F32(tail.pop_f32::<DumpError>()?),
+            TraceNode::F64 => Dynamic::F64(tail.pop_f64::<DumpError>()?),
+            TraceNode::Char => Dynamic::Char(tail.pop_char::<DumpError>()?),
+            TraceNode::String => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                Dynamic::Str(tail.pop_str::<DumpError>(length)?.to_owned())
+            }
+            TraceNode::Bytes => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                Dynamic::Bytes(tail.pop_slice::<DumpError>(length)?.to_vec())
+            }
+
+            TraceNode::None => Dynamic::None,
+            TraceNode::Some => Dynamic::Some(Box::new(self.pop_dynamic(tail)?)),
+            TraceNode::Unit => Dynamic::Unit,
+
+            TraceNode::Sequence => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                Dynamic::Seq(self.pop_dynamic_items(tail, length)?)
+            }
+            TraceNode::Map => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                Dynamic::Map(
+                    (0..length)
+                        .map(|_| Ok((self.pop_dynamic(tail)?, self.pop_dynamic(tail)?)))
+                        .collect::<Result<_, DumpError>>()?,
+                )
+            }
+            TraceNode::Tuple(length) => {
+                Dynamic::Tuple(self.pop_dynamic_items(tail, length_from_u32(length))?)
+            }
+
+            TraceNode::UnitStruct(name) => Dynamic::Struct {
+                name: self.type_name(name)?.into(),
+                fields: DynamicFields::Unit,
+            },
+            TraceNode::NewtypeStruct(name) => Dynamic::Struct {
+                name: self.type_name(name)?.into(),
+                fields: DynamicFields::Newtype(Box::new(self.pop_dynamic(tail)?)),
+            },
+            TraceNode::TupleStruct(length, name) => Dynamic::Struct {
+                name: self.type_name(name)?.into(),
+                fields: DynamicFields::Tuple(
+                    self.pop_dynamic_items(tail, length_from_u32(length))?,
+                ),
+            },
+            TraceNode::Struct(name, field_names) => Dynamic::Struct {
+                name: self.type_name(name)?.into(),
+                fields: self.pop_dynamic_fields(tail, field_names)?,
+            },
+
+            TraceNode::UnitVariant(name, variant) => Dynamic::Variant {
+                name: self.type_name(name)?.into(),
+                variant: self.variant_name(variant)?.into(),
+                fields: DynamicFields::Unit,
+            },
+            TraceNode::NewtypeVariant(name, variant) => Dynamic::Variant {
+                name: self.type_name(name)?.into(),
+                variant: self.variant_name(variant)?.into(),
+                fields: DynamicFields::Newtype(Box::new(self.pop_dynamic(tail)?)),
+            },
+            TraceNode::TupleVariant(length, name, variant) => Dynamic::Variant {
+                name: self.type_name(name)?.into(),
+                variant: self.variant_name(variant)?.into(),
+                fields: DynamicFields::Tuple(
+                    self.pop_dynamic_items(tail, length_from_u32(length))?,
+                ),
+            },
+            TraceNode::StructVariant(name, variant, field_names) => Dynamic::Variant {
+                name: self.type_name(name)?.into(),
+                variant: self.variant_name(variant)?.into(),
+                fields: self.pop_dynamic_fields(tail, field_names)?,
+            },
+        })
+    }
+
+    fn pop_dynamic_items(
+        &self,
+        tail: &Cell<&[u8]>,
+        length: usize,
+    ) -> Result<Vec<Dynamic>, DumpError> {
+        (0..length).map(|_| self.pop_dynamic(tail)).collect()
+    }
+
+    fn pop_dynamic_fields(
+        &self,
+        tail: &Cell<&[u8]>,
+        field_names: FieldNameListIndex,
+    ) -> Result<DynamicFields, DumpError> {
+        let mut present = self
+            .pop_trace_field_names(tail, field_names)?
+            .into_iter()
+            .peekable();
+        let fields = self
+            .field_name_list(field_names)?
+            .iter()
+            .map(|&name| {
+                let name = self.field_name(name)?;
+                let value = match present.next_if_eq(&name) {
+                    Some(_) => Some(self.pop_dynamic(tail)?),
+                    None => None,
+                };
+                Ok((name.into(), value))
+            })
+            .collect::<Result<_, DumpError>>()?;
+        Ok(DynamicFields::Named(fields))
+    }
+}
+
+impl Serialize for Dynamic {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Unit => serializer.serialize_unit(),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::I8(value) => serializer.serialize_i8(*value),
+            Self::I16(value) => serializer.serialize_i16(*value),
+            Self::I32(value) => serializer.serialize_i32(*value),
+            Self::I64(value) => serializer.serialize_i64(*value),
+            Self::I128(value) => serializer.serialize_i128(*value),
+            Self::U8(value) => serializer.serialize_u8(*value),
+            Self::U16(value) => serializer.serialize_u16(*value),
+            Self::U32(value) => serializer.serialize_u32(*value),
+            Self::U64(value) => serializer.serialize_u64(*value),
+            Self::U128(value) => serializer.serialize_u128(*value),
+            Self::F32(value) => serializer.serialize_f32(*value),
+            Self::F64(value) => serializer.serialize_f64(*value),
+            Self::Char(value) => serializer.serialize_char(*value),
+            Self::Str(value) => serializer.serialize_str(value),
+            Self::Bytes(value) => serializer.serialize_bytes(value),
+            Self::None => serializer.serialize_none(),
+            Self::Some(value) => serializer.serialize_some(value),
+            Self::Seq(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Self::Tuple(items) => {
+                let mut tuple = serializer.serialize_tuple(items.len())?;
+                for item in items {
+                    tuple.serialize_element(item)?;
+                }
+                tuple.end()
+            }
+            Self::Map(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Self::Struct { name, fields } => {
+                let name = intern(name);
+                match fields {
+                    DynamicFields::Unit => serializer.serialize_unit_struct(name),
+                    DynamicFields::Newtype(value) => {
+                        serializer.serialize_newtype_struct(name, value)
+                    }
+                    DynamicFields::Tuple(items) => {
+                        let mut tuple = serializer.serialize_tuple_struct(name, items.len())?;
+                        for item in items {
+                            tuple.serialize_field(item)?;
+                        }
+                        tuple.end()
+                    }
+                    DynamicFields::Named(fields) => {
+                        let mut strukt = serializer.serialize_struct(name, present_len(fields))?;
+                        for (field, value) in fields {
+                            match value {
+                                Some(value) => strukt.serialize_field(intern(field), value)?,
+                                None => strukt.skip_field(intern(field))?,
+                            }
+                        }
+                        strukt.end()
+                    }
+                }
+            }
+            // The position of variants isn't recorded, so they're serialized as the first one.
+            Self::Variant {
+                name,
+                variant,
+                fields,
+            } => {
+                let (name, variant) = (intern(name), intern(variant));
+                match fields {
+                    DynamicFields::Unit => serializer.serialize_unit_variant(name, 0, variant),
+                    DynamicFields::Newtype(value) => {
+                        serializer.serialize_newtype_variant(name, 0, variant, value)
+                    }
+                    DynamicFields::Tuple(items) => {
+                        let mut tuple =
+                            serializer.serialize_tuple_variant(name, 0, variant, items.len())?;
+                        for item in items {
+                            tuple.serialize_field(item)?;
+                        }
+                        tuple.end()
+                    }
+                    DynamicFields::Named(fields) => {
+                        let mut strukt = serializer.serialize_struct_variant(
+                            name,
+                            0,
+                            variant,
+                            present_len(fields),
+                        )?;
+                        for (field, value) in fields {
+                            match value {
+                                Some(value) => strukt.serialize_field(intern(field), value)?,
+                                None => strukt.skip_field(intern(field))?,
+                            }
+                        }
+                        strukt.end()
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn present_len(fields: &[(Box<str>, Option<Dynamic>)]) -> usize {
+    fields.iter().filter(|(_, value)| value.is_some()).count()
+}
+
+/// Returns a `'static` copy of a name, leaking each distinct name once.
+pub(crate) fn intern(name: &str) -> &'static str {
+    static NAMES: LazyLock<Mutex<HashSet<&'static str>>> = LazyLock::new(Default::default);
+
+    let mut names = NAMES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    match names.get(name) {
+        Some(name) => name,
+        None => {
+            let name = Box::leak(Box::<str>::from(name));
+            names.insert(name);
+            name
+        }
+    }
+}
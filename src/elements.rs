@@ -0,0 +1,641 @@
+use serde::{
+    Deserialize,
+    de::{
+        DeserializeSeed, Deserializer, EnumAccess, Error as _, IntoDeserializer, MapAccess,
+        SeqAccess, Unexpected, VariantAccess, Visitor, value::BorrowedStrDeserializer,
+    },
+    forward_to_deserialize_any,
+};
+use std::{fmt::Display, marker::PhantomData};
+
+use crate::{DescribedBy, Trace, TraceEvent, TraceReader};
+
+/// Iterates over the elements of a traced sequence, returned by
+/// [`DescribedBy::iter_sequence`].
+///
+/// Each element is deserialized into a `T` directly from the trace. Elements which don't match
+/// `T` are returned as an [`ElementValue`] instead, holding their [`TraceEvent`]-s for custom
+/// handling. Useful when a sequence mixes versions of a record.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_describe::{SchemaBuilder, TraceEvent};
+///
+/// #[derive(Serialize)]
+/// #[serde(untagged)]
+/// enum Written {
+///     V1 { name: &'static str },
+///     V2 { name: &'static str, age: u32 },
+/// }
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct V2 {
+///     name: String,
+///     age: u32,
+/// }
+///
+/// let mut builder = SchemaBuilder::new();
+/// let trace = builder.trace(&[
+///     Written::V2 { name: "ana", age: 30 },
+///     Written::V1 { name: "bob" },
+/// ])?;
+/// let schema = builder.build()?;
+///
+/// let mut elements = schema
+///     .describe_trace_ref(&trace)
+///     .iter_sequence::<V2>()
+///     .expect("the value is a sequence");
+/// assert_eq!(elements.next().unwrap().unwrap(), V2 { name: "ana".to_owned(), age: 30 });
+/// let old = elements.next().unwrap().unwrap_err();
+/// assert_eq!(old.events[1], TraceEvent::Str("bob"));
+/// assert!(elements.next().is_none());
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SequenceElements<'data, T> {
+    reader: TraceReader<'data>,
+    remaining: usize,
+    phantom: PhantomData<fn() -> T>,
+}
+
+/// An element of a sequence which couldn't be deserialized, returned by [`SequenceElements`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ElementValue<'data> {
+    /// The events of the element, see [`TraceReader`]. Incomplete if the trace doesn't match its
+    /// schema.
+    pub events: Vec<TraceEvent<'data>>,
+
+    /// Why the element couldn't be deserialized.
+    pub error: String,
+}
+
+impl<'data> DescribedBy<'data, &'data Trace> {
+    /// Returns an iterator deserializing the elements of this traced sequence (or tuple) one by
+    /// one, see [`SequenceElements`].
+    ///
+    /// Returns `None` if the traced value is not a sequence or tuple.
+    pub fn iter_sequence<T>(&self) -> Option<SequenceElements<'data, T>>
+    where
+        T: Deserialize<'data>,
+    {
+        let mut reader = self.reader();
+        let remaining = match reader.next_event() {
+            Ok(Some(
+                TraceEvent::Seq { len }
+                | TraceEvent::Tuple { len }
+                | TraceEvent::TupleStruct { len, .. },
+            )) => len,
+            _ => return None,
+        };
+        Some(SequenceElements {
+            reader,
+            remaining,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl<'data> TraceEvent<'data> {
+    /// The number of values which directly follow this event as its children, e.g. the number
+    /// of items of a sequence, or twice the number of entries of a map.
+    pub fn children(&self) -> usize {
+        match self {
+            Self::Some | Self::NewtypeStruct { .. } | Self::NewtypeVariant { .. } => 1,
+            Self::Seq { len }
+            | Self::Tuple { len }
+            | Self::TupleStruct { len, .. }
+            | Self::TupleVariant { len, .. } => *len,
+            Self::Map { len } => len * 2,
+            Self::Struct { fields, .. } | Self::StructVariant { fields, .. } => fields.len(),
+            _ => 0,
+        }
+    }
+}
+
+impl<'data, T> SequenceElements<'data, T>
+where
+    T: Deserialize<'data>,
+{
+    /// Consumes the iterator, picking `n` of the remaining elements uniformly at random using
+    /// reservoir sampling, returned in sequence order.
+    ///
+    /// Only the picked elements are kept in memory, and elements are only deserialized when
+    /// they're (provisionally) picked, so sampling a long sequence is cheap. If the trace doesn't
+    /// match its schema, sampling stops at the invalid element, which is returned last.
+    ///
+    /// `random` is called for random numbers, e.g. `|| rng.next_u64()` using the `rand` crate;
+    /// the same sequence of numbers always picks the same elements. Returns every remaining
+    /// element if there are no more than `n`.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&(0..1000u32).collect::<Vec<_>>())?;
+    /// let schema = builder.build()?;
+    ///
+    /// // A simple xorshift generator; use a proper random number generator in practice.
+    /// let mut state = 0x2545_f491_4f6c_dd1du64;
+    /// let sample = schema
+    ///     .describe_trace_ref(&trace)
+    ///     .iter_sequence::<u32>()
+    ///     .expect("the value is a sequence")
+    ///     .sample(3, || {
+    ///         state ^= state << 13;
+    ///         state ^= state >> 7;
+    ///         state ^= state << 17;
+    ///         state
+    ///     });
+    /// assert_eq!(sample.len(), 3);
+    /// assert!(sample.iter().all(Result::is_ok));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn sample<RandomT>(
+        mut self,
+        n: usize,
+        mut random: RandomT,
+    ) -> Vec<Result<T, ElementValue<'data>>>
+    where
+        RandomT: FnMut() -> u64,
+    {
+        let mut reservoir = Vec::with_capacity(n.min(self.remaining));
+        let mut invalid = None;
+        for index in 0..std::mem::take(&mut self.remaining) {
+            let slot = if reservoir.len() < n {
+                Some(reservoir.len())
+            } else {
+                Some(random_below(&mut random, index + 1)).filter(|&slot| slot < n)
+            };
+            // Elements which aren't picked are skipped over without being deserialized.
+            let read = match slot {
+                Some(slot) => read_value(&mut self.reader).map(|element| {
+                    if slot == reservoir.len() {
+                        reservoir.push((index, element));
+                    } else {
+                        reservoir[slot] = (index, element);
+                    }
+                }),
+                None => read_events(&mut self.reader).map(drop),
+            };
+            if let Err(error) = read {
+                invalid = Some(error);
+                break;
+            }
+        }
+
+        reservoir.sort_unstable_by_key(|(index, _)| *index);
+        reservoir
+            .into_iter()
+            .map(|(_, element)| element)
+            .chain(invalid.map(Err))
+            .collect()
+    }
+}
+
+impl<'data, T> Iterator for SequenceElements<'data, T>
+where
+    T: Deserialize<'data>,
+{
+    type Item = Result<T, ElementValue<'data>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        Some(read_value(&mut self.reader).unwrap_or_else(|invalid| {
+            // Nothing after an invalid element can be trusted, so stop iterating.
+            self.remaining = 0;
+            Err(invalid)
+        }))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+/// Reads the next value from a trace and deserializes it into a `T`, returning its events if
+/// it doesn't match `T`.
+///
+/// The outer error is returned if the value couldn't be read, in which case the reader can't be
+/// trusted any further.
+pub(crate) fn read_value<'data, T>(
+    reader: &mut TraceReader<'data>,
+) -> Result<Result<T, ElementValue<'data>>, ElementValue<'data>>
+where
+    T: Deserialize<'data>,
+{
+    let events = read_events(reader)?;
+    let mut deserializer = EventDeserializer {
+        events: events.iter(),
+    };
+    Ok(match T::deserialize(&mut deserializer) {
+        Ok(value) => Ok(value),
+        Err(EventError(error)) => Err(ElementValue { events, error }),
+    })
+}
+
+/// Reads the events of the next value from a trace, see [`read_value`].
+pub(crate) fn read_events<'data>(
+    reader: &mut TraceReader<'data>,
+) -> Result<Vec<TraceEvent<'data>>, ElementValue<'data>> {
+    let mut events = Vec::new();
+    let mut pending = 1usize;
+    while pending > 0 {
+        let event = match reader.next_event() {
+            Ok(Some(event)) => event,
+            Err(error) => {
+                let error = error.to_string();
+                return Err(ElementValue { events, error });
+            }
+            Ok(None) => {
+                let error = "unexpected end of trace".to_owned();
+                return Err(ElementValue { events, error });
+            }
+        };
+        pending = pending - 1 + event.children();
+        events.push(event);
+    }
+    Ok(events)
+}
+
+/// Returns a random number in `0..bound`, using the high bits of a product to avoid the
+/// (slightly more biased) modulo.
+fn random_below<RandomT>(random: &mut RandomT, bound: usize) -> usize
+where
+    RandomT: FnMut() -> u64,
+{
+    ((u128::from(random()) * bound as u128) >> 64) as usize
+}
+
+#[derive(Debug)]
+struct EventError(String);
+
+impl Display for EventError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for EventError {}
+
+impl serde::de::Error for EventError {
+    fn custom<T: Display>(message: T) -> Self {
+        Self(message.to_string())
+    }
+}
+
+/// Deserializes a value from its [`TraceEvent`]-s.
+struct EventDeserializer<'events, 'data> {
+    events: std::slice::Iter<'events, TraceEvent<'data>>,
+}
+
+impl<'events, 'data> EventDeserializer<'events, 'data> {
+    fn next_event(&mut self) -> Result<&'events TraceEvent<'data>, EventError> {
+        self.events
+            .next()
+            .ok_or_else(|| EventError::custom("unexpected end of element"))
+    }
+
+    fn peek_event(&self) -> Option<&'events TraceEvent<'data>> {
+        self.events.as_slice().first()
+    }
+
+    fn skip(&mut self) -> Result<(), EventError> {
+        let mut pending = 1usize;
+        while pending > 0 {
+            pending = pending - 1 + self.next_event()?.children();
+        }
+        Ok(())
+    }
+
+    fn visit_items<VisitorT>(
+        &mut self,
+        len: usize,
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        let mut items = Items {
+            deserializer: self,
+            remaining: len,
+            fields: &[],
+        };
+        let value = visitor.visit_seq(&mut items)?;
+        items.skip_rest()?;
+        Ok(value)
+    }
+
+    fn visit_entries<VisitorT>(
+        &mut self,
+        len: usize,
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        let mut entries = Items {
+            deserializer: self,
+            remaining: len * 2,
+            fields: &[],
+        };
+        let value = visitor.visit_map(&mut entries)?;
+        entries.skip_rest()?;
+        Ok(value)
+    }
+
+    fn visit_fields<VisitorT>(
+        &mut self,
+        fields: &'events [&'data str],
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        let mut fields = Items {
+            deserializer: self,
+            remaining: fields.len(),
+            fields,
+        };
+        let value = visitor.visit_map(&mut fields)?;
+        fields.skip_rest()?;
+        Ok(value)
+    }
+}
+
+impl<'data> Deserializer<'data> for &mut EventDeserializer<'_, 'data> {
+    type Error = EventError;
+
+    fn deserialize_any<VisitorT>(self, visitor: VisitorT) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        match self.next_event()? {
+            TraceEvent::Bool(value) => visitor.visit_bool(*value),
+            TraceEvent::I8(value) => visitor.visit_i8(*value),
+            TraceEvent::I16(value) => visitor.visit_i16(*value),
+            TraceEvent::I32(value) => visitor.visit_i32(*value),
+            TraceEvent::I64(value) => visitor.visit_i64(*value),
+            TraceEvent::I128(value) => visitor.visit_i128(*value),
+            TraceEvent::U8(value) => visitor.visit_u8(*value),
+            TraceEvent::U16(value) => visitor.visit_u16(*value),
+            TraceEvent::U32(value) => visitor.visit_u32(*value),
+            TraceEvent::U64(value) => visitor.visit_u64(*value),
+            TraceEvent::U128(value) => visitor.visit_u128(*value),
+            TraceEvent::F32(value) => visitor.visit_f32(*value),
+            TraceEvent::F64(value) => visitor.visit_f64(*value),
+            TraceEvent::Char(value) => visitor.visit_char(*value),
+            TraceEvent::Str(value) => visitor.visit_borrowed_str(value),
+            TraceEvent::Bytes(value) => visitor.visit_borrowed_bytes(value),
+
+            TraceEvent::None => visitor.visit_none(),
+            TraceEvent::Some => visitor.visit_some(self),
+            TraceEvent::Unit | TraceEvent::UnitStruct { .. } => visitor.visit_unit(),
+            TraceEvent::NewtypeStruct { .. } => visitor.visit_newtype_struct(self),
+
+            TraceEvent::Seq { len }
+            | TraceEvent::Tuple { len }
+            | TraceEvent::TupleStruct { len, .. } => self.visit_items(*len, visitor),
+            TraceEvent::Map { len } => self.visit_entries(*len, visitor),
+            TraceEvent::Struct { fields, .. } => self.visit_fields(fields, visitor),
+
+            event @ (TraceEvent::UnitVariant { .. }
+            | TraceEvent::NewtypeVariant { .. }
+            | TraceEvent::TupleVariant { .. }
+            | TraceEvent::StructVariant { .. }) => visitor.visit_enum(Variant {
+                deserializer: self,
+                event,
+            }),
+        }
+    }
+
+    fn deserialize_option<VisitorT>(self, visitor: VisitorT) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        match self.peek_event() {
+            Some(TraceEvent::None) => {
+                self.next_event()?;
+                visitor.visit_none()
+            }
+            Some(TraceEvent::Some) => {
+                self.next_event()?;
+                visitor.visit_some(self)
+            }
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<VisitorT>(
+        self,
+        _name: &'static str,
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        if let Some(TraceEvent::NewtypeStruct { .. }) = self.peek_event() {
+            self.next_event()?;
+        }
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<VisitorT>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        match self.peek_event() {
+            Some(TraceEvent::Str(variant)) => {
+                self.next_event()?;
+                visitor.visit_enum(BorrowedStrDeserializer::new(variant))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<VisitorT>(
+        self,
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        self.skip()?;
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        <VisitorT: Visitor<'data>>
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+/// The items of a sequence, the keys and values of a map, or the fields of a struct.
+struct Items<'deserializer, 'events, 'data> {
+    deserializer: &'deserializer mut EventDeserializer<'events, 'data>,
+    remaining: usize,
+    fields: &'events [&'data str],
+}
+
+impl Items<'_, '_, '_> {
+    fn skip_rest(&mut self) -> Result<(), EventError> {
+        for _ in 0..self.remaining {
+            self.deserializer.skip()?;
+        }
+        Ok(())
+    }
+}
+
+impl<'data> SeqAccess<'data> for Items<'_, '_, 'data> {
+    type Error = EventError;
+
+    fn next_element_seed<SeedT>(&mut self, seed: SeedT) -> Result<Option<SeedT::Value>, EventError>
+    where
+        SeedT: DeserializeSeed<'data>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'data> MapAccess<'data> for Items<'_, '_, 'data> {
+    type Error = EventError;
+
+    fn next_key_seed<SeedT>(&mut self, seed: SeedT) -> Result<Option<SeedT::Value>, EventError>
+    where
+        SeedT: DeserializeSeed<'data>,
+    {
+        if let Some((&field, fields)) = self.fields.split_first() {
+            // Struct fields are named by the struct, only their values are in the trace.
+            self.fields = fields;
+            return seed
+                .deserialize(BorrowedStrDeserializer::new(field))
+                .map(Some);
+        }
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+
+    fn next_value_seed<SeedT>(&mut self, seed: SeedT) -> Result<SeedT::Value, EventError>
+    where
+        SeedT: DeserializeSeed<'data>,
+    {
+        self.remaining = self.remaining.saturating_sub(1);
+        seed.deserialize(&mut *self.deserializer)
+    }
+}
+
+/// An enum variant, after its event was read.
+struct Variant<'deserializer, 'events, 'data> {
+    deserializer: &'deserializer mut EventDeserializer<'events, 'data>,
+    event: &'events TraceEvent<'data>,
+}
+
+impl<'deserializer, 'events, 'data> EnumAccess<'data> for Variant<'deserializer, 'events, 'data> {
+    type Error = EventError;
+    type Variant = Self;
+
+    fn variant_seed<SeedT>(self, seed: SeedT) -> Result<(SeedT::Value, Self), EventError>
+    where
+        SeedT: DeserializeSeed<'data>,
+    {
+        let (TraceEvent::UnitVariant { variant, .. }
+        | TraceEvent::NewtypeVariant { variant, .. }
+        | TraceEvent::TupleVariant { variant, .. }
+        | TraceEvent::StructVariant { variant, .. }) = self.event
+        else {
+            unreachable!("only variant events are read as enums");
+        };
+        let variant = seed.deserialize(variant.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'data> VariantAccess<'data> for Variant<'_, '_, 'data> {
+    type Error = EventError;
+
+    fn unit_variant(self) -> Result<(), EventError> {
+        match self.event {
+            TraceEvent::UnitVariant { .. } => Ok(()),
+            _ => Err(self.unexpected(&"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<SeedT>(self, seed: SeedT) -> Result<SeedT::Value, EventError>
+    where
+        SeedT: DeserializeSeed<'data>,
+    {
+        match self.event {
+            TraceEvent::NewtypeVariant { .. } => seed.deserialize(self.deserializer),
+            _ => Err(self.unexpected(&"newtype variant")),
+        }
+    }
+
+    fn tuple_variant<VisitorT>(
+        self,
+        _len: usize,
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        match self.event {
+            TraceEvent::TupleVariant { len, .. } => self.deserializer.visit_items(*len, visitor),
+            _ => Err(self.unexpected(&"tuple variant")),
+        }
+    }
+
+    fn struct_variant<VisitorT>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: VisitorT,
+    ) -> Result<VisitorT::Value, EventError>
+    where
+        VisitorT: Visitor<'data>,
+    {
+        match self.event {
+            TraceEvent::StructVariant { fields, .. } => {
+                self.deserializer.visit_fields(fields, visitor)
+            }
+            _ => Err(self.unexpected(&"struct variant")),
+        }
+    }
+}
+
+impl Variant<'_, '_, '_> {
+    fn unexpected(self, expected: &'static &'static str) -> EventError {
+        let unexpected = match self.event {
+            TraceEvent::UnitVariant { .. } => Unexpected::UnitVariant,
+            TraceEvent::NewtypeVariant { .. } => Unexpected::NewtypeVariant,
+            TraceEvent::TupleVariant { .. } => Unexpected::TupleVariant,
+            _ => Unexpected::StructVariant,
+        };
+        EventError::invalid_type(unexpected, expected)
+    }
+}
@@ -0,0 +1,263 @@
+use serde::{
+    Serialize, Serializer,
+    ser::{
+        SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+};
+use std::cell::Cell;
+
+use crate::{
+    DescribedBy, Schema, Trace,
+    dump::length_from_u32,
+    dynamic::intern,
+    indices::{FieldNameListIndex, TypeNameIndex, VariantNameIndex},
+    trace::{ReadTraceExt, TraceNode},
+};
+
+/// Wraps a described trace to embed it in a larger value as the value it describes, e.g. for a
+/// gateway wrapping the payloads it received in an envelope of its own.
+///
+/// A [`DescribedBy`] trace serializes in the compact encoding read back with its schema, which
+/// is meant to be stored next to that schema. An embedded trace instead serializes exactly like
+/// the value it was traced from, so tracing the envelope adds the payload's types to the
+/// envelope's schema, and the receiver can deserialize the envelope with the payload's Rust type
+/// in it. The trace is replayed as is, so neither the payload's Rust types nor a
+/// [`Dynamic`][`crate::Dynamic`] copy are needed.
+///
+/// Names are interned like for [`Dynamic`][`crate::Dynamic`]-s, and variants are serialized as
+/// the first of their enum, since their positions aren't traced.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_describe::{Embedded, SchemaBuilder, SelfDescribed};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Order {
+///     item: String,
+///     quantity: u32,
+/// }
+///
+/// // The payload, as received by the gateway.
+/// let mut builder = SchemaBuilder::new();
+/// let order = Order { item: "tea".to_owned(), quantity: 2 };
+/// let trace = builder.trace(&order)?;
+/// let schema = builder.build()?;
+///
+/// #[derive(Serialize)]
+/// struct Envelope<'a> {
+///     route: &'static str,
+///     payload: Embedded<'a, 'a>,
+/// }
+///
+/// let envelope = Envelope {
+///     route: "orders",
+///     payload: Embedded(schema.describe_trace_ref(&trace)),
+/// };
+/// let bytes = postcard::to_stdvec(&SelfDescribed(&envelope))?;
+///
+/// #[derive(Debug, PartialEq, Deserialize)]
+/// struct ReceivedEnvelope {
+///     route: String,
+///     payload: Order,
+/// }
+///
+/// let SelfDescribed(received) = postcard::from_bytes::<SelfDescribed<ReceivedEnvelope>>(&bytes)?;
+/// assert_eq!(received, ReceivedEnvelope { route: "orders".to_owned(), payload: order });
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Copy, Clone)]
+pub struct Embedded<'schema, 'trace>(pub DescribedBy<'schema, &'trace Trace>);
+
+impl Serialize for Embedded<'_, '_> {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let tail = Cell::new(&*(self.0).0.0);
+        EmbeddedValue {
+            schema: self.0.1,
+            tail: &tail,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Serializes the next value of a trace each time it's serialized, see [`Embedded`].
+#[derive(Copy, Clone)]
+struct EmbeddedValue<'a> {
+    schema: &'a Schema,
+    tail: &'a Cell<&'a [u8]>,
+}
+
+impl EmbeddedValue<'_> {
+    fn type_name<ErrorT>(&self, index: TypeNameIndex) -> Result<&str, ErrorT>
+    where
+        ErrorT: serde::ser::Error,
+    {
+        self.schema.type_name(index).map_err(ErrorT::custom)
+    }
+
+    fn variant_name<ErrorT>(&self, index: VariantNameIndex) -> Result<&str, ErrorT>
+    where
+        ErrorT: serde::ser::Error,
+    {
+        self.schema.variant_name(index).map_err(ErrorT::custom)
+    }
+
+    /// Returns the names of the fields of a struct, each with whether it's present in the trace.
+    fn fields<ErrorT>(
+        &self,
+        field_names: FieldNameListIndex,
+    ) -> Result<Vec<(&'static str, bool)>, ErrorT>
+    where
+        ErrorT: serde::ser::Error,
+    {
+        let mut present = self
+            .schema
+            .pop_trace_field_names(self.tail, field_names)
+            .map_err(ErrorT::custom)?
+            .into_iter()
+            .peekable();
+        self.schema
+            .field_name_list(field_names)
+            .map_err(ErrorT::custom)?
+            .iter()
+            .map(|&name| {
+                let name = self.schema.field_name(name).map_err(ErrorT::custom)?;
+                Ok((intern(name), present.next_if_eq(&name).is_some()))
+            })
+            .collect()
+    }
+}
+
+impl Serialize for EmbeddedValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let tail = self.tail;
+        match tail.pop_trace_node()? {
+            TraceNode::Bool => serializer.serialize_bool(tail.pop_bool()?),
+            TraceNode::I8 => serializer.serialize_i8(tail.pop_i8()?),
+            TraceNode::I16 => serializer.serialize_i16(tail.pop_i16()?),
+            TraceNode::I32 => serializer.serialize_i32(tail.pop_i32()?),
+            TraceNode::I64 => serializer.serialize_i64(tail.pop_i64()?),
+            TraceNode::I128 => serializer.serialize_i128(tail.pop_i128()?),
+            TraceNode::U8 => serializer.serialize_u8(tail.pop_u8()?),
+            TraceNode::U16 => serializer.serialize_u16(tail.pop_u16()?),
+            TraceNode::U32 => serializer.serialize_u32(tail.pop_u32()?),
+            TraceNode::U64 => serializer.serialize_u64(tail.pop_u64()?),
+            TraceNode::U128 => serializer.serialize_u128(tail.pop_u128()?),
+            TraceNode::F32 => serializer.serialize_f32(tail.pop_f32()?),
+            TraceNode::F64 => serializer.serialize_f64(tail.pop_f64()?),
+            TraceNode::Char => serializer.serialize_char(tail.pop_char()?),
+            TraceNode::String => serializer.serialize_str(tail.pop_str(tail.pop_length_u32()?)?),
+            TraceNode::Bytes => serializer.serialize_bytes(tail.pop_slice(tail.pop_length_u32()?)?),
+            TraceNode::None => serializer.serialize_none(),
+            TraceNode::Some => serializer.serialize_some(self),
+            TraceNode::Unit => serializer.serialize_unit(),
+
+            TraceNode::Sequence => {
+                let length = tail.pop_length_u32()?;
+                let mut seq = serializer.serialize_seq(Some(length))?;
+                for _ in 0..length {
+                    seq.serialize_element(self)?;
+                }
+                seq.end()
+            }
+            TraceNode::Map => {
+                let length = tail.pop_length_u32()?;
+                let mut map = serializer.serialize_map(Some(length))?;
+                for _ in 0..length {
+                    map.serialize_entry(self, self)?;
+                }
+                map.end()
+            }
+            TraceNode::Tuple(length) => {
+                let length = length_from_u32(length);
+                let mut tuple = serializer.serialize_tuple(length)?;
+                for _ in 0..length {
+                    tuple.serialize_element(self)?;
+                }
+                tuple.end()
+            }
+
+            TraceNode::UnitStruct(name) => {
+                serializer.serialize_unit_struct(intern(self.type_name(name)?))
+            }
+            TraceNode::NewtypeStruct(name) => {
+                serializer.serialize_newtype_struct(intern(self.type_name(name)?), self)
+            }
+            TraceNode::TupleStruct(length, name) => {
+                let length = length_from_u32(length);
+                let mut tuple =
+                    serializer.serialize_tuple_struct(intern(self.type_name(name)?), length)?;
+                for _ in 0..length {
+                    tuple.serialize_field(self)?;
+                }
+                tuple.end()
+            }
+            TraceNode::Struct(name, field_names) => {
+                let name = intern(self.type_name(name)?);
+                let fields = self.fields(field_names)?;
+                let present = fields.iter().filter(|&&(_, present)| present).count();
+                let mut strukt = serializer.serialize_struct(name, present)?;
+                for (field, present) in fields {
+                    if present {
+                        strukt.serialize_field(field, self)?;
+                    } else {
+                        strukt.skip_field(field)?;
+                    }
+                }
+                strukt.end()
+            }
+
+            TraceNode::UnitVariant(name, variant) => serializer.serialize_unit_variant(
+                intern(self.type_name(name)?),
+                0,
+                intern(self.variant_name(variant)?),
+            ),
+            TraceNode::NewtypeVariant(name, variant) => serializer.serialize_newtype_variant(
+                intern(self.type_name(name)?),
+                0,
+                intern(self.variant_name(variant)?),
+                self,
+            ),
+            TraceNode::TupleVariant(length, name, variant) => {
+                let length = length_from_u32(length);
+                let mut tuple = serializer.serialize_tuple_variant(
+                    intern(self.type_name(name)?),
+                    0,
+                    intern(self.variant_name(variant)?),
+                    length,
+                )?;
+                for _ in 0..length {
+                    tuple.serialize_field(self)?;
+                }
+                tuple.end()
+            }
+            TraceNode::StructVariant(name, variant, field_names) => {
+                let (name, variant) = (
+                    intern(self.type_name(name)?),
+                    intern(self.variant_name(variant)?),
+                );
+                let fields = self.fields(field_names)?;
+                let present = fields.iter().filter(|&&(_, present)| present).count();
+                let mut strukt = serializer.serialize_struct_variant(name, 0, variant, present)?;
+                for (field, present) in fields {
+                    if present {
+                        strukt.serialize_field(field, self)?;
+                    } else {
+                        strukt.skip_field(field)?;
+                    }
+                }
+                strukt.end()
+            }
+        }
+    }
+}
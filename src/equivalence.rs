@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use crate::{
+    Schema,
+    indices::{
+        FieldNameListIndex, MemberListIndex, SchemaNodeIndex, SchemaNodeListIndex, TypeNameIndex,
+        VariantNameIndex,
+    },
+    schema::SchemaNode,
+};
+
+impl Schema {
+    /// Whether this schema describes the same data model as another.
+    ///
+    /// Unlike comparing [fingerprints][`Schema::fingerprint`], this ignores how the schemas
+    /// happen to be laid out: the indices assigned to their types and names, the order of the
+    /// members of unions and the order of struct fields, all of which depend on the order in
+    /// which values were traced. So two schemas traced independently from the same types compare
+    /// equivalent, e.g. in a schema registry or a test. Whether the schemas were traced in
+    /// human-readable mode still matters, while their [versions][`Schema::version`] don't.
+    ///
+    /// Traces aren't interchangeable between equivalent schemas, since they refer to the members
+    /// of unions by position. Malformed schemas are never equivalent to anything.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// let mut first = SchemaBuilder::new();
+    /// first.trace(&vec![1u32])?;
+    /// first.trace(&vec!["one"])?;
+    /// let first = first.build()?;
+    ///
+    /// let mut second = SchemaBuilder::new();
+    /// second.trace(&vec!["two"])?;
+    /// second.trace(&vec![2u32])?;
+    /// let second = second.build()?;
+    ///
+    /// assert_ne!(first.fingerprint(), second.fingerprint());
+    /// assert!(first.equivalent(&second));
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn equivalent(&self, other: &Schema) -> bool {
+        self.human_readable == other.human_readable
+            && Equivalence {
+                left: self,
+                right: other,
+                known: HashMap::new(),
+            }
+            .node(self.root_index, other.root_index)
+            .unwrap_or(false)
+    }
+}
+
+/// Compares the nodes of two schemas structurally, see [`Schema::equivalent`].
+///
+/// Methods return `None` if either schema is malformed.
+struct Equivalence<'schema> {
+    left: &'schema Schema,
+    right: &'schema Schema,
+    // Pairs of nodes already compared, or `None` while being compared, which only happens again
+    // if a malformed schema has cycles.
+    known: HashMap<(SchemaNodeIndex, SchemaNodeIndex), Option<bool>>,
+}
+
+impl Equivalence<'_> {
+    fn node(&mut self, left: SchemaNodeIndex, right: SchemaNodeIndex) -> Option<bool> {
+        if let Some(&known) = self.known.get(&(left, right)) {
+            return known;
+        }
+        self.known.insert((left, right), None);
+        let equivalent =
+            self.node_contents(self.left.node(left).ok()?, self.right.node(right).ok()?)?;
+        self.known.insert((left, right), Some(equivalent));
+        Some(equivalent)
+    }
+
+    fn node_contents(&mut self, left: SchemaNode, right: SchemaNode) -> Option<bool> {
+        Some(match (left, right) {
+            (SchemaNode::OptionSome(left), SchemaNode::OptionSome(right))
+            | (SchemaNode::Sequence(left), SchemaNode::Sequence(right)) => {
+                self.node(left, right)?
+            }
+            (SchemaNode::UnitStruct(left), SchemaNode::UnitStruct(right)) => {
+                self.type_names(left, right)?
+            }
+            (
+                SchemaNode::UnitVariant(left_name, left_variant),
+                SchemaNode::UnitVariant(right_name, right_variant),
+            ) => {
+                self.type_names(left_name, right_name)?
+                    && self.variant_names(left_variant, right_variant)?
+            }
+            (
+                SchemaNode::NewtypeStruct(left_name, left),
+                SchemaNode::NewtypeStruct(right_name, right),
+            ) => self.type_names(left_name, right_name)? && self.node(left, right)?,
+            (
+                SchemaNode::NewtypeVariant(left_name, left_variant, left),
+                SchemaNode::NewtypeVariant(right_name, right_variant, right),
+            ) => {
+                self.type_names(left_name, right_name)?
+                    && self.variant_names(left_variant, right_variant)?
+                    && self.node(left, right)?
+            }
+            (SchemaNode::Map(left_key, left_value), SchemaNode::Map(right_key, right_value)) => {
+                self.node(left_key, right_key)? && self.node(left_value, right_value)?
+            }
+            (SchemaNode::Tuple(left), SchemaNode::Tuple(right)) => self.node_lists(left, right)?,
+            (
+                SchemaNode::TupleStruct(left_name, left),
+                SchemaNode::TupleStruct(right_name, right),
+            ) => self.type_names(left_name, right_name)? && self.node_lists(left, right)?,
+            (
+                SchemaNode::TupleVariant(left_name, left_variant, left),
+                SchemaNode::TupleVariant(right_name, right_variant, right),
+            ) => {
+                self.type_names(left_name, right_name)?
+                    && self.variant_names(left_variant, right_variant)?
+                    && self.node_lists(left, right)?
+            }
+            (
+                SchemaNode::Struct(left_name, left_fields, left_skips, left_types),
+                SchemaNode::Struct(right_name, right_fields, right_skips, right_types),
+            ) => {
+                self.type_names(left_name, right_name)?
+                    && self.fields(
+                        (left_fields, left_skips, left_types),
+                        (right_fields, right_skips, right_types),
+                    )?
+            }
+            (
+                SchemaNode::StructVariant(
+                    left_name,
+                    left_variant,
+                    left_fields,
+                    left_skips,
+                    left_types,
+                ),
+                SchemaNode::StructVariant(
+                    right_name,
+                    right_variant,
+                    right_fields,
+                    right_skips,
+                    right_types,
+                ),
+            ) => {
+                self.type_names(left_name, right_name)?
+                    && self.variant_names(left_variant, right_variant)?
+                    && self.fields(
+                        (left_fields, left_skips, left_types),
+                        (right_fields, right_skips, right_types),
+                    )?
+            }
+            (SchemaNode::Union(left), SchemaNode::Union(right)) => {
+                self.union_members(left, right)?
+            }
+            // Every other pair of the same kind was matched above, so this only compares kinds
+            // without contents.
+            (left, right) => left == right,
+        })
+    }
+
+    fn node_lists(
+        &mut self,
+        left: SchemaNodeListIndex,
+        right: SchemaNodeListIndex,
+    ) -> Option<bool> {
+        let (left, right) = (
+            self.left.node_list(left).ok()?,
+            self.right.node_list(right).ok()?,
+        );
+        if left.len() != right.len() {
+            return Some(false);
+        }
+        for (&left, &right) in left.iter().zip(right) {
+            if !self.node(left, right)? {
+                return Some(false);
+            }
+        }
+        Some(true)
+    }
+
+    fn union_members(
+        &mut self,
+        left: SchemaNodeListIndex,
+        right: SchemaNodeListIndex,
+    ) -> Option<bool> {
+        let (left, right) = (
+            self.left.node_list(left).ok()?,
+            self.right.node_list(right).ok()?,
+        );
+        if left.len() != right.len() {
+            return Some(false);
+        }
+        let mut matched = vec![false; right.len()];
+        'members: for &left in left {
+            for (i_right, &right) in right.iter().enumerate() {
+                if !matched[i_right] && self.node(left, right)? {
+                    matched[i_right] = true;
+                    continue 'members;
+                }
+            }
+            return Some(false);
+        }
+        Some(true)
+    }
+
+    fn fields(
+        &mut self,
+        left: (FieldNameListIndex, MemberListIndex, SchemaNodeListIndex),
+        right: (FieldNameListIndex, MemberListIndex, SchemaNodeListIndex),
+    ) -> Option<bool> {
+        let (left, right) = (
+            sorted_fields(self.left, left)?,
+            sorted_fields(self.right, right)?,
+        );
+        if left.len() != right.len() {
+            return Some(false);
+        }
+        for ((left_name, left_optional, left), (right_name, right_optional, right)) in
+            left.into_iter().zip(right)
+        {
+            if left_name != right_name
+                || left_optional != right_optional
+                || !self.node(left, right)?
+            {
+                return Some(false);
+            }
+        }
+        Some(true)
+    }
+
+    fn type_names(&self, left: TypeNameIndex, right: TypeNameIndex) -> Option<bool> {
+        Some(self.left.type_name(left).ok()? == self.right.type_name(right).ok()?)
+    }
+
+    fn variant_names(&self, left: VariantNameIndex, right: VariantNameIndex) -> Option<bool> {
+        Some(self.left.variant_name(left).ok()? == self.right.variant_name(right).ok()?)
+    }
+}
+
+/// Returns the name, whether it's skipped sometimes, and the type of each of the fields of a
+/// struct, sorted by name.
+fn sorted_fields(
+    schema: &Schema,
+    (names, skips, types): (FieldNameListIndex, MemberListIndex, SchemaNodeListIndex),
+) -> Option<Vec<(&str, bool, SchemaNodeIndex)>> {
+    let names = schema.field_name_list(names).ok()?;
+    let skips = schema.member_list(skips).ok()?;
+    let types = schema.node_list(types).ok()?;
+    if names.len() != types.len() {
+        return None;
+    }
+    let mut fields = names
+        .iter()
+        .zip(types)
+        .enumerate()
+        .map(|(i_field, (&name, &node))| {
+            let optional = skips.iter().any(|&skip| usize::from(skip) == i_field);
+            Some((schema.field_name(name).ok()?, optional, node))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    fields.sort_by_key(|&(name, _, _)| name);
+    Some(fields)
+}
@@ -0,0 +1,523 @@
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{
+    Schema,
+    indices::{
+        FieldNameListIndex, MemberListIndex, SchemaNodeIndex, SchemaNodeListIndex, VariantNameIndex,
+    },
+    prune::InvalidSchemaError,
+    schema::SchemaNode,
+};
+
+/// Why two schemas differ, returned by [`Schema::explain_difference`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SchemaExplanation {
+    differences: Vec<SchemaDifference>,
+}
+
+/// A single reason why two schemas differ, see [`SchemaExplanation`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SchemaDifference {
+    path: String,
+    reason: String,
+}
+
+impl SchemaExplanation {
+    /// Whether the schemas describe the same data model, like [`Schema::equivalent`].
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// The differences between the two schemas, in traversal order.
+    #[inline]
+    pub fn differences(&self) -> &[SchemaDifference] {
+        &self.differences
+    }
+}
+
+impl SchemaDifference {
+    /// A `jq`-like path to the differing type, e.g. `.items[].name` or `.` for the root.
+    ///
+    /// `[]` stands for the items of a sequence or the values of a map, `{}` for the keys of a
+    /// map, and `::Variant` for the contents of an enum variant.
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The human-readable reason, e.g. ``field `x` is sometimes skipped in the first schema,
+    /// but never in the second``.
+    #[inline]
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl std::fmt::Display for SchemaExplanation {
+    /// Writes one difference per line.
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for difference in &self.differences {
+            writeln!(formatter, "{difference}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for SchemaDifference {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{}: {}", self.path, self.reason)
+    }
+}
+
+impl Schema {
+    /// Explains why this schema and another one differ, e.g. to find out why two batches of
+    /// values which should have shared a schema didn't.
+    ///
+    /// The schemas are compared like by [`Schema::equivalent`], so differences which only come
+    /// from the order values were traced in aren't reported. Instead, each reason names a path
+    /// and what differs there: a field which is sometimes skipped in only one of the schemas, or
+    /// only exists in one, a variant or a member of a union which only appears in one, a
+    /// different integer width, and so on. This schema is called the first one, and `other` the
+    /// second one.
+    ///
+    /// Returns an error if either schema is malformed.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// enum Status {
+    ///     Active,
+    ///     Suspended { days: u32 },
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     nickname: Option<&'static str>,
+    ///     status: Status,
+    /// }
+    ///
+    /// let first = SchemaBuilder::trace_all([
+    ///     User { nickname: Some("ana"), status: Status::Active },
+    ///     User { nickname: None, status: Status::Active },
+    /// ])?
+    /// .0;
+    /// let second = SchemaBuilder::trace_all([User {
+    ///     nickname: Some("bo"),
+    ///     status: Status::Suspended { days: 3 },
+    /// }])?
+    /// .0;
+    ///
+    /// assert_eq!(
+    ///     first.explain_difference(&second)?.to_string(),
+    ///     "\
+    ///         .: field `nickname` is sometimes skipped in the first schema, but never in the second\n\
+    ///         .status: variant `Status::Active` appears only in the first schema\n\
+    ///         .status: variant `Status::Suspended` appears only in the second schema\n",
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn explain_difference(
+        &self,
+        other: &Schema,
+    ) -> Result<SchemaExplanation, InvalidSchemaError> {
+        let mut explainer = Explainer {
+            first: self,
+            second: other,
+            path: String::new(),
+            visited: HashSet::new(),
+            differences: Vec::new(),
+        };
+        if self.human_readable != other.human_readable {
+            let which = if self.human_readable {
+                "first"
+            } else {
+                "second"
+            };
+            explainer.push(format!(
+                "only the {which} schema was traced in human-readable mode"
+            ));
+        }
+        explainer.node(self.root_index, other.root_index)?;
+        Ok(SchemaExplanation {
+            differences: explainer.differences,
+        })
+    }
+}
+
+/// Walks two schemas side by side, see [`Schema::explain_difference`].
+struct Explainer<'schema> {
+    first: &'schema Schema,
+    second: &'schema Schema,
+    path: String,
+    // Pairs of nodes already compared, so shared and recursive types are only explained once.
+    visited: HashSet<(SchemaNodeIndex, SchemaNodeIndex)>,
+    differences: Vec<SchemaDifference>,
+}
+
+/// What's compared to match up the members of two unions: the kind of a node, and the names of
+/// its type and variant, if any.
+#[derive(Clone, Copy, PartialEq)]
+struct Key<'schema> {
+    kind: &'static str,
+    name: Option<&'schema str>,
+    variant: Option<&'schema str>,
+}
+
+impl<'schema> Key<'schema> {
+    fn new(schema: &'schema Schema, node: SchemaNode) -> Result<Self, InvalidSchemaError> {
+        let (kind, name, variant) = match node {
+            SchemaNode::Bool => ("bool", None, None),
+            SchemaNode::I8 => ("i8", None, None),
+            SchemaNode::I16 => ("i16", None, None),
+            SchemaNode::I32 => ("i32", None, None),
+            SchemaNode::I64 => ("i64", None, None),
+            SchemaNode::I128 => ("i128", None, None),
+            SchemaNode::U8 => ("u8", None, None),
+            SchemaNode::U16 => ("u16", None, None),
+            SchemaNode::U32 => ("u32", None, None),
+            SchemaNode::U64 => ("u64", None, None),
+            SchemaNode::U128 => ("u128", None, None),
+            SchemaNode::F32 => ("f32", None, None),
+            SchemaNode::F64 => ("f64", None, None),
+            SchemaNode::Char => ("char", None, None),
+            SchemaNode::String => ("str", None, None),
+            SchemaNode::Bytes => ("bytes", None, None),
+            SchemaNode::OptionNone => ("none", None, None),
+            SchemaNode::OptionSome(_) => ("some", None, None),
+            SchemaNode::Unit => ("unit", None, None),
+            SchemaNode::Sequence(_) => ("seq", None, None),
+            SchemaNode::Map(..) => ("map", None, None),
+            SchemaNode::Tuple(_) => ("tuple", None, None),
+            SchemaNode::Union(_) => ("union", None, None),
+            SchemaNode::UnitStruct(name) => ("unit struct", Some(name), None),
+            SchemaNode::NewtypeStruct(name, _) => ("newtype struct", Some(name), None),
+            SchemaNode::TupleStruct(name, _) => ("tuple struct", Some(name), None),
+            SchemaNode::Struct(name, ..) => ("struct", Some(name), None),
+            SchemaNode::UnitVariant(name, variant) => ("unit variant", Some(name), Some(variant)),
+            SchemaNode::NewtypeVariant(name, variant, _) => {
+                ("newtype variant", Some(name), Some(variant))
+            }
+            SchemaNode::TupleVariant(name, variant, _) => {
+                ("tuple variant", Some(name), Some(variant))
+            }
+            SchemaNode::StructVariant(name, variant, ..) => {
+                ("struct variant", Some(name), Some(variant))
+            }
+        };
+        Ok(Self {
+            kind,
+            name: name
+                .map(|name| schema.type_name(name))
+                .transpose()
+                .map_err(InvalidSchemaError::new)?,
+            variant: variant
+                .map(|variant| schema.variant_name(variant))
+                .transpose()
+                .map_err(InvalidSchemaError::new)?,
+        })
+    }
+}
+
+impl std::fmt::Display for Key<'_> {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.name, self.variant) {
+            (Some(name), Some(variant)) => write!(formatter, "{name}::{variant}"),
+            (Some(name), None) => formatter.write_str(name),
+            _ => formatter.write_str(self.kind),
+        }
+    }
+}
+
+/// Describes a node which is a different type in each schema.
+fn changed(first: Key, second: Key) -> String {
+    const INTEGERS: [&str; 10] = [
+        "i8", "i16", "i32", "i64", "i128", "u8", "u16", "u32", "u64", "u128",
+    ];
+    let is_integer = |key: Key| INTEGERS.contains(&key.kind);
+    let is_float = |key: Key| matches!(key.kind, "f32" | "f64");
+    let what = if is_integer(first)
+        && is_integer(second)
+        && first.kind.as_bytes()[0] == second.kind.as_bytes()[0]
+    {
+        "integer width differs"
+    } else if is_float(first) && is_float(second) {
+        "float width differs"
+    } else {
+        "type differs"
+    };
+    format!("{what}: `{first}` in the first schema, `{second}` in the second")
+}
+
+/// Describes a node, or a member of a union, which only appears in one of the schemas.
+fn only_in(key: Key, which: &str) -> String {
+    if key.variant.is_some() {
+        format!("variant `{key}` appears only in the {which} schema")
+    } else {
+        format!("`{key}` appears only in the {which} schema")
+    }
+}
+
+impl Explainer<'_> {
+    fn node(
+        &mut self,
+        first: SchemaNodeIndex,
+        second: SchemaNodeIndex,
+    ) -> Result<(), InvalidSchemaError> {
+        if !self.visited.insert((first, second)) {
+            return Ok(());
+        }
+        let firsts = members(self.first, first)?;
+        let mut seconds = members(self.second, second)?
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        let mut matched = Vec::new();
+        let mut first_only = Vec::new();
+        for (first_node, first_key) in firsts {
+            let position = seconds
+                .iter()
+                .position(|second| second.is_some_and(|(_, second_key)| second_key == first_key));
+            match position.and_then(|position| seconds[position].take()) {
+                Some((second_node, _)) => matched.push((first_node, second_node)),
+                None => first_only.push(first_key),
+            }
+        }
+        let second_only = seconds
+            .into_iter()
+            .flatten()
+            .map(|(_, key)| key)
+            .collect::<Vec<_>>();
+        match (&*first_only, &*second_only) {
+            (&[first], &[second]) if first.variant.is_none() && second.variant.is_none() => {
+                self.push(changed(first, second));
+            }
+            _ => {
+                for &key in &first_only {
+                    self.push(only_in(key, "first"));
+                }
+                for &key in &second_only {
+                    self.push(only_in(key, "second"));
+                }
+            }
+        }
+        for (first, second) in matched {
+            self.contents(first, second)?;
+        }
+        Ok(())
+    }
+
+    /// Compares the children of two nodes with the same [`Key`].
+    fn contents(
+        &mut self,
+        first: SchemaNode,
+        second: SchemaNode,
+    ) -> Result<(), InvalidSchemaError> {
+        match (first, second) {
+            (SchemaNode::OptionSome(first), SchemaNode::OptionSome(second))
+            | (SchemaNode::NewtypeStruct(_, first), SchemaNode::NewtypeStruct(_, second)) => {
+                self.node(first, second)
+            }
+            (SchemaNode::Sequence(first), SchemaNode::Sequence(second)) => {
+                self.within("[]", |explainer| explainer.node(first, second))
+            }
+            (
+                SchemaNode::Map(first_key, first_value),
+                SchemaNode::Map(second_key, second_value),
+            ) => {
+                self.within("{}", |explainer| explainer.node(first_key, second_key))?;
+                self.within("[]", |explainer| explainer.node(first_value, second_value))
+            }
+            (SchemaNode::Tuple(first), SchemaNode::Tuple(second))
+            | (SchemaNode::TupleStruct(_, first), SchemaNode::TupleStruct(_, second)) => {
+                self.items(first, second)
+            }
+            (
+                SchemaNode::Struct(_, first_names, first_skips, first_types),
+                SchemaNode::Struct(_, second_names, second_skips, second_types),
+            ) => self.fields(
+                (first_names, first_skips, first_types),
+                (second_names, second_skips, second_types),
+            ),
+            (
+                SchemaNode::NewtypeVariant(_, variant, first),
+                SchemaNode::NewtypeVariant(_, _, second),
+            ) => self.within_variant(variant, |explainer| explainer.node(first, second)),
+            (
+                SchemaNode::TupleVariant(_, variant, first),
+                SchemaNode::TupleVariant(_, _, second),
+            ) => self.within_variant(variant, |explainer| explainer.items(first, second)),
+            (
+                SchemaNode::StructVariant(_, variant, first_names, first_skips, first_types),
+                SchemaNode::StructVariant(_, _, second_names, second_skips, second_types),
+            ) => self.within_variant(variant, |explainer| {
+                explainer.fields(
+                    (first_names, first_skips, first_types),
+                    (second_names, second_skips, second_types),
+                )
+            }),
+            // Nodes with the same key and no children are the same.
+            _ => Ok(()),
+        }
+    }
+
+    fn items(
+        &mut self,
+        first: SchemaNodeListIndex,
+        second: SchemaNodeListIndex,
+    ) -> Result<(), InvalidSchemaError> {
+        let first = self
+            .first
+            .node_list(first)
+            .map_err(InvalidSchemaError::new)?;
+        let second = self
+            .second
+            .node_list(second)
+            .map_err(InvalidSchemaError::new)?;
+        if first.len() != second.len() {
+            self.push(format!(
+                "has {} items in the first schema and {} in the second",
+                first.len(),
+                second.len()
+            ));
+            return Ok(());
+        }
+        for (index, (&first, &second)) in first.iter().zip(second).enumerate() {
+            self.within(format_args!(".{index}"), |explainer| {
+                explainer.node(first, second)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn fields(
+        &mut self,
+        first: (FieldNameListIndex, MemberListIndex, SchemaNodeListIndex),
+        second: (FieldNameListIndex, MemberListIndex, SchemaNodeListIndex),
+    ) -> Result<(), InvalidSchemaError> {
+        let first = fields(self.first, first)?;
+        let second = fields(self.second, second)?;
+        for &(name, first_skipped, first) in &first {
+            let Some(&(_, second_skipped, second)) = second
+                .iter()
+                .find(|&&(second_name, _, _)| second_name == name)
+            else {
+                self.push(format!("field `{name}` appears only in the first schema"));
+                continue;
+            };
+            match (first_skipped, second_skipped) {
+                (true, false) => self.push(format!(
+                    "field `{name}` is sometimes skipped in the first schema, but never in the \
+                     second"
+                )),
+                (false, true) => self.push(format!(
+                    "field `{name}` is sometimes skipped in the second schema, but never in the \
+                     first"
+                )),
+                _ => {}
+            }
+            self.within(format_args!(".{name}"), |explainer| {
+                explainer.node(first, second)
+            })?;
+        }
+        for &(name, _, _) in &second {
+            if !first.iter().any(|&(first_name, _, _)| first_name == name) {
+                self.push(format!("field `{name}` appears only in the second schema"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs `explain` with `segment` appended to the current path.
+    fn within(
+        &mut self,
+        segment: impl std::fmt::Display,
+        explain: impl FnOnce(&mut Self) -> Result<(), InvalidSchemaError>,
+    ) -> Result<(), InvalidSchemaError> {
+        let parent_length = self.path.len();
+        write!(self.path, "{segment}").expect("writing to a string can't fail");
+        let result = explain(self);
+        self.path.truncate(parent_length);
+        result
+    }
+
+    fn within_variant(
+        &mut self,
+        variant: VariantNameIndex,
+        explain: impl FnOnce(&mut Self) -> Result<(), InvalidSchemaError>,
+    ) -> Result<(), InvalidSchemaError> {
+        let variant = self
+            .first
+            .variant_name(variant)
+            .map_err(InvalidSchemaError::new)?;
+        self.within(format_args!("::{variant}"), explain)
+    }
+
+    fn push(&mut self, reason: String) {
+        let path = if self.path.starts_with('.') {
+            self.path.clone()
+        } else {
+            format!(".{}", self.path)
+        };
+        self.differences.push(SchemaDifference { path, reason });
+    }
+}
+
+/// Returns the members of a union, or the node itself if it isn't one, with their keys.
+fn members(
+    schema: &Schema,
+    index: SchemaNodeIndex,
+) -> Result<Vec<(SchemaNode, Key<'_>)>, InvalidSchemaError> {
+    let mut members = Vec::new();
+    let mut pending = vec![index];
+    let mut expanded = HashSet::new();
+    while let Some(index) = pending.pop() {
+        let node = schema.node(index).map_err(InvalidSchemaError::new)?;
+        if let SchemaNode::Union(list) = node {
+            if expanded.insert(index) {
+                let list = schema.node_list(list).map_err(InvalidSchemaError::new)?;
+                pending.extend(list.iter().rev());
+            }
+        } else {
+            members.push((node, Key::new(schema, node)?));
+        }
+    }
+    Ok(members)
+}
+
+/// Returns the name, whether it's skipped sometimes, and the type of each of the fields of a
+/// struct.
+fn fields(
+    schema: &Schema,
+    (names, skips, types): (FieldNameListIndex, MemberListIndex, SchemaNodeListIndex),
+) -> Result<Vec<(&str, bool, SchemaNodeIndex)>, InvalidSchemaError> {
+    let names = schema
+        .field_name_list(names)
+        .map_err(InvalidSchemaError::new)?;
+    let skips = schema.member_list(skips).map_err(InvalidSchemaError::new)?;
+    let types = schema.node_list(types).map_err(InvalidSchemaError::new)?;
+    if names.len() != types.len() {
+        return Err(InvalidSchemaError::new(
+            "struct has a different number of field names and types",
+        ));
+    }
+    names
+        .iter()
+        .zip(types)
+        .enumerate()
+        .map(|(i_field, (&name, &node))| {
+            let skipped = skips.iter().any(|&skip| usize::from(skip) == i_field);
+            Ok((
+                schema.field_name(name).map_err(InvalidSchemaError::new)?,
+                skipped,
+                node,
+            ))
+        })
+        .collect()
+}
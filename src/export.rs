@@ -0,0 +1,358 @@
+use serde::{Serialize, Serializer, ser::SerializeMap};
+use serde_json::Number;
+use std::io::Write;
+use thiserror::Error;
+
+use crate::{DescribedBy, Dynamic, DynamicFields, EnumRepresentation, InvalidTraceError, Trace};
+
+/// Options for converting traced values to JSON, see [`DescribedBy::to_json`] and
+/// [`JsonExportOptions::write_json_lines`].
+///
+/// Requires the `export` feature.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct JsonExportOptions {
+    enum_representation: EnumRepresentation,
+    byte_encoding: ByteEncoding,
+}
+
+/// How byte strings are written to JSON, see [`JsonExportOptions::byte_encoding`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ByteEncoding {
+    /// An array of numbers, like `serde_json` itself. This is the default.
+    #[default]
+    Array,
+
+    /// A string holding the standard, padded base64 encoding of the bytes.
+    Base64,
+
+    /// A string holding the lowercase hexadecimal encoding of the bytes.
+    Hex,
+}
+
+/// Returned by [`DescribedBy::to_json`], [`DescribedBy::to_json_writer`] and
+/// [`JsonExportOptions::write_json_lines`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum JsonExportError {
+    /// The trace doesn't match its schema.
+    #[error(transparent)]
+    Trace(#[from] InvalidTraceError),
+
+    /// A variant can't be internally tagged, since its content isn't a struct or map.
+    #[error("variant `{0}` can't be internally tagged, since its content isn't a struct or map")]
+    Untaggable(Box<str>),
+
+    /// Writing the output failed.
+    #[error("failed to write JSON: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl JsonExportOptions {
+    /// The default options: externally tagged enums and bytes as arrays of numbers, matching
+    /// the output of `serde_json` for the traced types.
+    pub const fn new() -> Self {
+        Self {
+            enum_representation: EnumRepresentation::External,
+            byte_encoding: ByteEncoding::Array,
+        }
+    }
+
+    /// How enum variants are written, see [`EnumRepresentation`]. Defaults to
+    /// [`EnumRepresentation::External`].
+    pub const fn enum_representation(mut self, representation: EnumRepresentation) -> Self {
+        self.enum_representation = representation;
+        self
+    }
+
+    /// How byte strings are written, see [`ByteEncoding`].
+    pub const fn byte_encoding(mut self, encoding: ByteEncoding) -> Self {
+        self.byte_encoding = encoding;
+        self
+    }
+
+    /// Writes traced values as [JSON Lines](https://jsonlines.org/), one value per line, such
+    /// that they can be consumed by JSON tooling (e.g. `jq`). See [`DescribedBy::to_json`].
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::{JsonExportOptions, SchemaBuilder};
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let traces = [
+    ///     builder.trace(&("start", None::<u32>))?,
+    ///     builder.trace(&("step", Some(2u32)))?,
+    /// ];
+    /// let schema = builder.build()?;
+    ///
+    /// let mut output = Vec::new();
+    /// JsonExportOptions::new().write_json_lines(
+    ///     &mut output,
+    ///     traces.iter().map(|trace| schema.describe_trace_ref(trace)),
+    /// )?;
+    /// assert_eq!(String::from_utf8(output)?, "[\"start\",null]\n[\"step\",2]\n");
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn write_json_lines<'schema, 'trace, WriteT, ValuesT>(
+        &self,
+        mut output: WriteT,
+        values: ValuesT,
+    ) -> Result<(), JsonExportError>
+    where
+        WriteT: Write,
+        ValuesT: IntoIterator<Item = DescribedBy<'schema, &'trace Trace>>,
+    {
+        for value in values {
+            value.to_json_writer(&mut output, *self)?;
+            output.write_all(b"\n")?;
+        }
+        output.flush()?;
+        Ok(())
+    }
+}
+
+impl Default for JsonExportOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DescribedBy<'_, &'_ Trace> {
+    /// Converts the traced value to compact JSON, without needing a Rust type for it.
+    ///
+    /// Values are written like `serde_json` writes them, with enums and byte strings written
+    /// according to `options`. Map keys which aren't strings are written as the JSON of the key,
+    /// e.g. `"1"` for an integer key. 128-bit integers which don't fit 64 bits are written as
+    /// strings, and non-finite floats as `null`.
+    ///
+    /// Requires the `export` feature.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::{ByteEncoding, EnumRepresentation, JsonExportOptions, SchemaBuilder};
+    ///
+    /// #[derive(Serialize)]
+    /// enum Event {
+    ///     Upload {
+    ///         #[serde(with = "serde_bytes")]
+    ///         digest: Vec<u8>,
+    ///     },
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&Event::Upload { digest: vec![0xca, 0xfe] })?;
+    /// let schema = builder.build()?;
+    ///
+    /// let json = schema.describe_trace_ref(&trace).to_json(
+    ///     JsonExportOptions::new()
+    ///         .enum_representation(EnumRepresentation::Internal { tag: "type" })
+    ///         .byte_encoding(ByteEncoding::Hex),
+    /// )?;
+    /// assert_eq!(json, r#"{"type":"Upload","digest":"cafe"}"#);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_json(&self, options: JsonExportOptions) -> Result<String, JsonExportError> {
+        let mut output = Vec::new();
+        self.to_json_writer(&mut output, options)?;
+        Ok(String::from_utf8(output).expect("JSON is always UTF-8"))
+    }
+
+    /// Like [`to_json`][`Self::to_json`], but writes the JSON to `output` instead of returning
+    /// it, e.g. straight into a file or a socket.
+    ///
+    /// Requires the `export` feature.
+    pub fn to_json_writer<WriteT>(
+        &self,
+        output: WriteT,
+        options: JsonExportOptions,
+    ) -> Result<(), JsonExportError>
+    where
+        WriteT: Write,
+    {
+        let json = options.convert(self.to_dynamic()?)?;
+        serde_json::to_writer(output, &json).map_err(std::io::Error::from)?;
+        Ok(())
+    }
+}
+
+/// A JSON value which, unlike `serde_json::Value`, keeps the fields of objects in order.
+enum Json {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Serialize for Json {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Null => serializer.serialize_unit(),
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::Number(value) => value.serialize(serializer),
+            Self::String(value) => serializer.serialize_str(value),
+            Self::Array(items) => serializer.collect_seq(items),
+            Self::Object(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (key, value) in fields {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl JsonExportOptions {
+    fn convert(&self, dynamic: Dynamic) -> Result<Json, JsonExportError> {
+        Ok(match dynamic {
+            Dynamic::Unit | Dynamic::None => Json::Null,
+            Dynamic::Bool(value) => Json::Bool(value),
+            Dynamic::I8(value) => Json::Number(value.into()),
+            Dynamic::I16(value) => Json::Number(value.into()),
+            Dynamic::I32(value) => Json::Number(value.into()),
+            Dynamic::I64(value) => Json::Number(value.into()),
+            Dynamic::I128(value) => match i64::try_from(value) {
+                Ok(value) => Json::Number(value.into()),
+                Err(_) => Json::String(value.to_string()),
+            },
+            Dynamic::U8(value) => Json::Number(value.into()),
+            Dynamic::U16(value) => Json::Number(value.into()),
+            Dynamic::U32(value) => Json::Number(value.into()),
+            Dynamic::U64(value) => Json::Number(value.into()),
+            Dynamic::U128(value) => match u64::try_from(value) {
+                Ok(value) => Json::Number(value.into()),
+                Err(_) => Json::String(value.to_string()),
+            },
+            // Widened through its shortest representation, such that e.g. `0.1` stays `0.1`.
+            Dynamic::F32(value) => float(value.to_string().parse().unwrap_or(f64::NAN)),
+            Dynamic::F64(value) => float(value),
+            Dynamic::Char(value) => Json::String(value.to_string()),
+            Dynamic::Str(value) => Json::String(value),
+            Dynamic::Bytes(bytes) => match self.byte_encoding {
+                ByteEncoding::Array => Json::Array(
+                    bytes
+                        .into_iter()
+                        .map(|byte| Json::Number(byte.into()))
+                        .collect(),
+                ),
+                ByteEncoding::Base64 => Json::String(base64(&bytes)),
+                ByteEncoding::Hex => Json::String(hex(&bytes)),
+            },
+            Dynamic::Some(value) => self.convert(*value)?,
+            Dynamic::Seq(items) | Dynamic::Tuple(items) => self.convert_items(items)?,
+            Dynamic::Map(entries) => Json::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| Ok((self.convert_key(key)?, self.convert(value)?)))
+                    .collect::<Result<_, JsonExportError>>()?,
+            ),
+            Dynamic::Struct { fields, .. } => self.convert_fields(fields)?.unwrap_or(Json::Null),
+            Dynamic::Variant {
+                variant, fields, ..
+            } => self.convert_variant(variant, fields)?,
+        })
+    }
+
+    fn convert_items(&self, items: Vec<Dynamic>) -> Result<Json, JsonExportError> {
+        Ok(Json::Array(
+            items
+                .into_iter()
+                .map(|item| self.convert(item))
+                .collect::<Result<_, _>>()?,
+        ))
+    }
+
+    /// Converts the contents of a struct or variant, or returns `None` for unit contents.
+    fn convert_fields(&self, fields: DynamicFields) -> Result<Option<Json>, JsonExportError> {
+        Ok(Some(match fields {
+            DynamicFields::Unit => return Ok(None),
+            DynamicFields::Newtype(value) => self.convert(*value)?,
+            DynamicFields::Tuple(items) => self.convert_items(items)?,
+            DynamicFields::Named(fields) => Json::Object(
+                fields
+                    .into_iter()
+                    .filter_map(|(name, value)| Some((name, value?)))
+                    .map(|(name, value)| Ok((name.into(), self.convert(value)?)))
+                    .collect::<Result<_, JsonExportError>>()?,
+            ),
+        }))
+    }
+
+    fn convert_variant(
+        &self,
+        variant: Box<str>,
+        fields: DynamicFields,
+    ) -> Result<Json, JsonExportError> {
+        let content = self.convert_fields(fields)?;
+        Ok(match (self.enum_representation, content) {
+            (EnumRepresentation::Untagged, content) => content.unwrap_or(Json::Null),
+            (EnumRepresentation::External, None) => Json::String(variant.into()),
+            (EnumRepresentation::External, Some(content)) => {
+                Json::Object(vec![(variant.into(), content)])
+            }
+            (EnumRepresentation::Internal { tag }, content) => {
+                let mut object = vec![(tag.to_owned(), Json::String(variant.to_string()))];
+                match content {
+                    None => {}
+                    Some(Json::Object(fields)) => object.extend(fields),
+                    Some(_) => return Err(JsonExportError::Untaggable(variant)),
+                }
+                Json::Object(object)
+            }
+            (EnumRepresentation::Adjacent { tag, content: key }, content) => {
+                let mut object = vec![(tag.to_owned(), Json::String(variant.into()))];
+                object.extend(content.map(|content| (key.to_owned(), content)));
+                Json::Object(object)
+            }
+        })
+    }
+
+    /// Converts a map key to a string, writing keys which aren't strings as their JSON.
+    fn convert_key(&self, key: Dynamic) -> Result<String, JsonExportError> {
+        Ok(match self.convert(key)? {
+            Json::String(key) => key,
+            key => serde_json::to_string(&key).expect("JSON values always serialize"),
+        })
+    }
+}
+
+fn float(value: f64) -> Json {
+    Number::from_f64(value).map_or(Json::Null, Json::Number)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (i_byte, &byte)| {
+                group | u32::from(byte) << (16 - 8 * i_byte)
+            });
+        for i_char in 0..4 {
+            if i_char <= chunk.len() {
+                let index = (group >> (18 - 6 * i_char)) & 0x3f;
+                encoded.push(char::from(ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{DescribedBy, Schema, Trace};
+
+/// A stable, 64-bit hash of a [`Schema`], returned by [`Schema::fingerprint`].
+///
+/// Equal schemas always have equal fingerprints, across processes and platforms, so fingerprints
+/// may be persisted and used to look up schemas (e.g. in a schema registry).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub struct SchemaFingerprint(pub u64);
+
+impl std::fmt::Display for SchemaFingerprint {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "{:016x}", self.0)
+    }
+}
+
+/// A [`Trace`] separated from its [`Schema`], which remembers the
+/// [fingerprint][`SchemaFingerprint`] of the schema it was traced with.
+///
+/// Unlike a bare [`Trace`], it implements [`Serialize`] and [`Deserialize`], so the data and the
+/// schema can be stored separately, then safely put back together with [`Schema::bind`], which
+/// checks that the schema is the right one.
+///
+/// The serialized trace is in this crate's internal format, which is only guaranteed to be
+/// readable by the same major version of the crate.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{DetachedTrace, Schema, SchemaBuilder};
+///
+/// let mut builder = SchemaBuilder::new();
+/// let trace = builder.trace(&vec![1u32, 2, 3])?;
+/// let schema = builder.build()?;
+///
+/// // Store the schema and data separately.
+/// let detached = schema.describe_trace(trace).detach();
+/// let schema_bytes = postcard::to_stdvec(&schema)?;
+/// let data_bytes = postcard::to_stdvec(&detached)?;
+///
+/// // Then bind them back together before use.
+/// let schema = postcard::from_bytes::<Schema>(&schema_bytes)?;
+/// let detached = postcard::from_bytes::<DetachedTrace>(&data_bytes)?;
+/// let described = schema.bind(detached)?;
+/// assert_eq!(described.to_string(), "[1, 2, 3]");
+///
+/// // Binding to a different schema is an error.
+/// let mut builder = SchemaBuilder::new();
+/// let _ = builder.trace(&"unrelated")?;
+/// let other_schema = builder.build()?;
+/// let detached = postcard::from_bytes::<DetachedTrace>(&data_bytes)?;
+/// assert!(other_schema.bind(detached).is_err());
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DetachedTrace {
+    pub(crate) fingerprint: SchemaFingerprint,
+    pub(crate) trace: Vec<u8>,
+}
+
+impl DetachedTrace {
+    /// The fingerprint of the schema this trace was produced with.
+    #[inline]
+    pub fn fingerprint(&self) -> SchemaFingerprint {
+        self.fingerprint
+    }
+}
+
+/// Returned by [`Schema::bind`] when a [`DetachedTrace`] was produced with a different schema.
+#[derive(Clone, Debug, Error)]
+#[error("trace was produced with schema {trace}, not {schema}")]
+pub struct SchemaMismatchError {
+    /// The fingerprint recorded in the trace.
+    pub trace: SchemaFingerprint,
+
+    /// The fingerprint of the schema the trace was bound to.
+    pub schema: SchemaFingerprint,
+}
+
+impl Schema {
+    /// Computes the [fingerprint][`SchemaFingerprint`] of this schema.
+    ///
+    /// This hashes the whole schema, so it should be computed once and saved if needed often.
+    pub fn fingerprint(&self) -> SchemaFingerprint {
+        // Hash the content of the schema, rather than its in-memory representation, which makes
+        // the fingerprint as stable as the schema's serialized format.
+        SchemaFingerprint(fnv1a(&self.canonical_content()))
+    }
+
+    /// Puts back together a trace separated from this schema with [`DescribedBy::detach`].
+    ///
+    /// Returns an error if the trace was produced with a different schema.
+    pub fn bind(
+        &self,
+        detached: DetachedTrace,
+    ) -> Result<DescribedBy<'_, Trace>, SchemaMismatchError> {
+        let fingerprint = self.fingerprint();
+        if detached.fingerprint == fingerprint {
+            Ok(DescribedBy(Trace(detached.trace), self))
+        } else {
+            Err(SchemaMismatchError {
+                trace: detached.fingerprint,
+                schema: fingerprint,
+            })
+        }
+    }
+}
+
+impl DescribedBy<'_, Trace> {
+    /// Separates the trace from its schema, recording the schema's fingerprint such that the two
+    /// can be safely put back together with [`Schema::bind`].
+    pub fn detach(self) -> DetachedTrace {
+        DetachedTrace {
+            fingerprint: self.1.fingerprint(),
+            trace: (self.0).0,
+        }
+    }
+}
+
+/// 64-bit FNV-1a: simple, dependency-free and fixed forever, unlike `std`'s `DefaultHasher`.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_extend(FNV1A_OFFSET_BASIS, bytes)
+}
+
+/// The FNV-1a hash of nothing, which [`fnv1a_extend`] starts from.
+pub(crate) const FNV1A_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// Continues an FNV-1a hash with more bytes.
+pub(crate) fn fnv1a_extend(hash: u64, bytes: &[u8]) -> u64 {
+    const PRIME: u64 = 0x0100_0000_01b3;
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
@@ -0,0 +1,108 @@
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{Error, Visitor},
+};
+use std::marker::PhantomData;
+
+/// The name of the newtype [`F16`] is traced as.
+const F16_TYPE_NAME: &str = "serde_describe::f16";
+
+/// The name of the newtype [`Bf16`] is traced as.
+const BF16_TYPE_NAME: &str = "serde_describe::bf16";
+
+/// Wraps a [`half::f16`] such that it's serialized in two bytes, as a newtype around its bits,
+/// rather than being promoted to an `f32`.
+///
+/// It's recorded in the schema as the `serde_describe::f16` newtype, so readers can tell it apart
+/// from plain integers.
+///
+/// Requires the `half` feature.
+///
+/// Example
+/// -------
+/// ```rust
+/// use half::f16;
+/// use serde_describe::{F16, SelfDescribed};
+///
+/// let weights = vec![F16(f16::from_f32(0.5)), F16(f16::from_f32(-1.25))];
+/// let bytes = postcard::to_stdvec(&SelfDescribed(&weights))?;
+/// let SelfDescribed(roundtripped) = postcard::from_bytes::<SelfDescribed<Vec<F16>>>(&bytes)?;
+/// assert_eq!(roundtripped, weights);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct F16(pub half::f16);
+
+/// Like [`F16`], but for a [`half::bf16`], recorded as the `serde_describe::bf16` newtype.
+///
+/// Requires the `half` feature.
+#[derive(Copy, Clone, Debug, Default, PartialEq, PartialOrd)]
+pub struct Bf16(pub half::bf16);
+
+macro_rules! impl_half_float {
+    ($wrapper:ident, $inner:ty, $name:expr) => {
+        impl From<$inner> for $wrapper {
+            #[inline]
+            fn from(value: $inner) -> Self {
+                Self(value)
+            }
+        }
+
+        impl From<$wrapper> for $inner {
+            #[inline]
+            fn from(value: $wrapper) -> Self {
+                value.0
+            }
+        }
+
+        impl Serialize for $wrapper {
+            #[inline]
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_newtype_struct($name, &self.0.to_bits())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $wrapper {
+            #[inline]
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer
+                    .deserialize_newtype_struct($name, HalfFloatVisitor::<Self>(PhantomData))
+            }
+        }
+
+        impl<'de> Visitor<'de> for HalfFloatVisitor<$wrapper> {
+            type Value = $wrapper;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(formatter, "a {}", $name)
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_u16(self)
+            }
+
+            fn visit_u16<E>(self, bits: u16) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                Ok($wrapper(<$inner>::from_bits(bits)))
+            }
+        }
+    };
+}
+
+/// Deserializes the wrapper `T`.
+struct HalfFloatVisitor<T>(PhantomData<T>);
+
+impl_half_float!(F16, half::f16, F16_TYPE_NAME);
+impl_half_float!(Bf16, half::bf16, BF16_TYPE_NAME);
@@ -0,0 +1,432 @@
+use thiserror::Error;
+
+use crate::{
+    Schema,
+    builder::{TraceLimitError, TraceLimitErrorKind},
+    indices::{
+        FieldNameIndex, FieldNameListIndex, IsEmpty, MemberIndex, MemberListIndex, SchemaNodeIndex,
+        SchemaNodeListIndex, TypeNameIndex, VariantNameIndex,
+    },
+    pool::{NonEmptyPool, Pool},
+    schema::SchemaNode,
+};
+
+/// Assembles a [`Schema`] by hand, without tracing any values.
+///
+/// Useful for code generators and for bridging other schema languages, where the Rust types
+/// described by the schema may not exist. Each `add_*` method returns a [`SchemaNodeId`] handle
+/// which can be used to build larger types out of smaller ones.
+///
+/// The resulting schema can deserialize data serialized by a [`SchemaBuilder`] with a matching
+/// schema, as well as data produced by other implementations of the same format. Note that the
+/// members of unions are ordered by when they were first added to the builder, which determines
+/// their discriminants, so the order in which types are added matters for schemas with unions.
+///
+/// [`SchemaBuilder`]: crate::SchemaBuilder
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize, de::DeserializeSeed};
+/// use serde_describe::{DescribedBy, PrimitiveType, SchemaBuilder, SchemaGraphBuilder};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Point {
+///     x: i32,
+///     #[serde(default, skip_serializing_if = "Vec::is_empty")]
+///     tags: Vec<String>,
+/// }
+///
+/// let mut graph = SchemaGraphBuilder::new();
+/// let int = graph.add_primitive(PrimitiveType::I32)?;
+/// let string = graph.add_primitive(PrimitiveType::String)?;
+/// let tags = graph.add_sequence(string)?;
+/// let point = graph.add_struct("Point", &[("x", int), ("tags", tags)], &["tags"])?;
+/// let points = graph.add_sequence(point)?;
+/// let schema = graph.build(points)?;
+/// assert_eq!(
+///     schema.to_string(),
+///     "seq_5(Point_4 { x: i32_1, tags?: seq_3(str_2) })",
+/// );
+///
+/// // The hand-built schema matches the traced one, so it can read the same data.
+/// let original = vec![
+///     Point { x: 1, tags: vec![] },
+///     Point { x: 2, tags: vec!["two".to_owned()] },
+/// ];
+/// let mut builder = SchemaBuilder::new();
+/// let trace = builder.trace(&original)?;
+/// let bytes = postcard::to_stdvec(&builder.build()?.describe_trace(trace))?;
+/// let DescribedBy(roundtripped, _) = schema
+///     .describe_type::<Vec<Point>>()
+///     .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))?;
+/// assert_eq!(roundtripped, original);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Default, Clone)]
+pub struct SchemaGraphBuilder {
+    nodes: Pool<SchemaNode, SchemaNodeIndex>,
+    node_lists: Pool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
+    member_lists: Pool<Box<[MemberIndex]>, MemberListIndex>,
+    field_name_lists: NonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
+    field_names: NonEmptyPool<String, FieldNameIndex>,
+    variant_names: NonEmptyPool<String, VariantNameIndex>,
+    type_names: NonEmptyPool<String, TypeNameIndex>,
+}
+
+/// A handle to a type added to a [`SchemaGraphBuilder`].
+///
+/// Handles are only meaningful for the builder that returned them.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SchemaNodeId(SchemaNodeIndex);
+
+/// Types without any names or children, see [`SchemaGraphBuilder::add_primitive`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum PrimitiveType {
+    /// `bool`.
+    Bool,
+    /// `i8`.
+    I8,
+    /// `i16`.
+    I16,
+    /// `i32`.
+    I32,
+    /// `i64`.
+    I64,
+    /// `i128`.
+    I128,
+    /// `u8`.
+    U8,
+    /// `u16`.
+    U16,
+    /// `u32`.
+    U32,
+    /// `u64`.
+    U64,
+    /// `u128`.
+    U128,
+    /// `f32`.
+    F32,
+    /// `f64`.
+    F64,
+    /// `char`.
+    Char,
+    /// Strings.
+    String,
+    /// Byte arrays, as serialized by e.g. `serde_bytes`.
+    Bytes,
+    /// The unit type, `()`.
+    Unit,
+}
+
+/// Errors returned by [`SchemaGraphBuilder`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SchemaGraphError {
+    /// The schema is in some way too large, and built-in limits were exceeded.
+    #[error(transparent)]
+    Limit(#[from] TraceLimitError),
+
+    /// A [`SchemaNodeId`] was not returned by this builder.
+    #[error("schema node id {0:?} was not returned by this builder")]
+    UnknownNode(SchemaNodeId),
+
+    /// A field marked as skippable is not one of the fields of the struct.
+    #[error("skippable field {0:?} is not a field of the struct")]
+    UnknownSkippableField(Box<str>),
+}
+
+impl From<TraceLimitErrorKind> for SchemaGraphError {
+    fn from(kind: TraceLimitErrorKind) -> Self {
+        Self::Limit(kind.into())
+    }
+}
+
+impl SchemaGraphBuilder {
+    /// Creates a new, empty [`SchemaGraphBuilder`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Converts the builder into a schema whose root is the given type.
+    pub fn build(self, root: SchemaNodeId) -> Result<Schema, SchemaGraphError> {
+        let root_index = self.check(root)?;
+        Ok(Schema {
+            root_index,
+            nodes: self.nodes.into(),
+            node_lists: self.node_lists.into(),
+            member_lists: self.member_lists.into(),
+            field_name_lists: self.field_name_lists.into(),
+            field_names: self.field_names.into(),
+            variant_names: self.variant_names.into(),
+            type_names: self.type_names.into(),
+            human_readable: false,
+            version: 0,
+            min_reader_version: 0,
+            hashed_names: false,
+        })
+    }
+
+    /// Adds a primitive type.
+    pub fn add_primitive(
+        &mut self,
+        primitive: PrimitiveType,
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        self.add(match primitive {
+            PrimitiveType::Bool => SchemaNode::Bool,
+            PrimitiveType::I8 => SchemaNode::I8,
+            PrimitiveType::I16 => SchemaNode::I16,
+            PrimitiveType::I32 => SchemaNode::I32,
+            PrimitiveType::I64 => SchemaNode::I64,
+            PrimitiveType::I128 => SchemaNode::I128,
+            PrimitiveType::U8 => SchemaNode::U8,
+            PrimitiveType::U16 => SchemaNode::U16,
+            PrimitiveType::U32 => SchemaNode::U32,
+            PrimitiveType::U64 => SchemaNode::U64,
+            PrimitiveType::U128 => SchemaNode::U128,
+            PrimitiveType::F32 => SchemaNode::F32,
+            PrimitiveType::F64 => SchemaNode::F64,
+            PrimitiveType::Char => SchemaNode::Char,
+            PrimitiveType::String => SchemaNode::String,
+            PrimitiveType::Bytes => SchemaNode::Bytes,
+            PrimitiveType::Unit => SchemaNode::Unit,
+        })
+    }
+
+    /// Adds an `Option` of the given type, which may be either `None` or `Some`.
+    pub fn add_option(&mut self, inner: SchemaNodeId) -> Result<SchemaNodeId, SchemaGraphError> {
+        let some = self.add_some(inner)?;
+        let none = self.add_none()?;
+        self.add_union(&[none, some])
+    }
+
+    /// Adds an `Option` which is always `None`.
+    pub fn add_none(&mut self) -> Result<SchemaNodeId, SchemaGraphError> {
+        self.add(SchemaNode::OptionNone)
+    }
+
+    /// Adds an `Option` of the given type which is always `Some`.
+    pub fn add_some(&mut self, inner: SchemaNodeId) -> Result<SchemaNodeId, SchemaGraphError> {
+        let inner = self.check(inner)?;
+        self.add(SchemaNode::OptionSome(inner))
+    }
+
+    /// Adds a sequence (e.g. a `Vec`) of the given type.
+    pub fn add_sequence(&mut self, item: SchemaNodeId) -> Result<SchemaNodeId, SchemaGraphError> {
+        let item = self.check(item)?;
+        self.add(SchemaNode::Sequence(item))
+    }
+
+    /// Adds a map with the given key and value types.
+    pub fn add_map(
+        &mut self,
+        key: SchemaNodeId,
+        value: SchemaNodeId,
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let key = self.check(key)?;
+        let value = self.check(value)?;
+        self.add(SchemaNode::Map(key, value))
+    }
+
+    /// Adds an anonymous tuple of the given types.
+    pub fn add_tuple(&mut self, items: &[SchemaNodeId]) -> Result<SchemaNodeId, SchemaGraphError> {
+        let items = self.add_list(items)?;
+        self.add(SchemaNode::Tuple(items))
+    }
+
+    /// Adds a unit struct, e.g. `struct Name;`.
+    pub fn add_unit_struct(&mut self, name: &str) -> Result<SchemaNodeId, SchemaGraphError> {
+        let name = self.type_names.intern_from(name)?;
+        self.add(SchemaNode::UnitStruct(name))
+    }
+
+    /// Adds a newtype struct, e.g. `struct Name(Inner);`.
+    pub fn add_newtype_struct(
+        &mut self,
+        name: &str,
+        inner: SchemaNodeId,
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let inner = self.check(inner)?;
+        let name = self.type_names.intern_from(name)?;
+        self.add(SchemaNode::NewtypeStruct(name, inner))
+    }
+
+    /// Adds a tuple struct, e.g. `struct Name(A, B);`.
+    pub fn add_tuple_struct(
+        &mut self,
+        name: &str,
+        items: &[SchemaNodeId],
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let items = self.add_list(items)?;
+        let name = self.type_names.intern_from(name)?;
+        self.add(SchemaNode::TupleStruct(name, items))
+    }
+
+    /// Adds a struct with named fields, e.g. `struct Name { a: A, b: B }`.
+    ///
+    /// Fields listed in `skippable` may be absent from serialized values (e.g. because of
+    /// `#[serde(skip_serializing_if = "...")]`).
+    pub fn add_struct(
+        &mut self,
+        name: &str,
+        fields: &[(&str, SchemaNodeId)],
+        skippable: &[&str],
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let name = self.type_names.intern_from(name)?;
+        let (field_names, skip_list, field_types) = self.add_fields(fields, skippable)?;
+        self.add(SchemaNode::Struct(
+            name,
+            field_names,
+            skip_list,
+            field_types,
+        ))
+    }
+
+    /// Adds a unit variant of an enum, e.g. `Name::Variant`.
+    ///
+    /// Use [`add_union`][`Self::add_union`] to combine the variants of an enum into a single
+    /// type.
+    pub fn add_unit_variant(
+        &mut self,
+        name: &str,
+        variant: &str,
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let name = self.type_names.intern_from(name)?;
+        let variant = self.variant_names.intern_from(variant)?;
+        self.add(SchemaNode::UnitVariant(name, variant))
+    }
+
+    /// Adds a newtype variant of an enum, e.g. `Name::Variant(Inner)`.
+    pub fn add_newtype_variant(
+        &mut self,
+        name: &str,
+        variant: &str,
+        inner: SchemaNodeId,
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let inner = self.check(inner)?;
+        let name = self.type_names.intern_from(name)?;
+        let variant = self.variant_names.intern_from(variant)?;
+        self.add(SchemaNode::NewtypeVariant(name, variant, inner))
+    }
+
+    /// Adds a tuple variant of an enum, e.g. `Name::Variant(A, B)`.
+    pub fn add_tuple_variant(
+        &mut self,
+        name: &str,
+        variant: &str,
+        items: &[SchemaNodeId],
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let items = self.add_list(items)?;
+        let name = self.type_names.intern_from(name)?;
+        let variant = self.variant_names.intern_from(variant)?;
+        self.add(SchemaNode::TupleVariant(name, variant, items))
+    }
+
+    /// Adds a struct variant of an enum, e.g. `Name::Variant { a: A, b: B }`.
+    ///
+    /// See [`add_struct`][`Self::add_struct`] for the meaning of `skippable`.
+    pub fn add_struct_variant(
+        &mut self,
+        name: &str,
+        variant: &str,
+        fields: &[(&str, SchemaNodeId)],
+        skippable: &[&str],
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let name = self.type_names.intern_from(name)?;
+        let variant = self.variant_names.intern_from(variant)?;
+        let (field_names, skip_list, field_types) = self.add_fields(fields, skippable)?;
+        self.add(SchemaNode::StructVariant(
+            name,
+            variant,
+            field_names,
+            skip_list,
+            field_types,
+        ))
+    }
+
+    /// Adds a type whose values may be of any of the given types, e.g. the variants of an enum
+    /// or the members of an untagged enum.
+    ///
+    /// Nested unions are flattened and duplicates are removed. An empty union is a type with no
+    /// values, used for e.g. the items of sequences which are always empty.
+    pub fn add_union(
+        &mut self,
+        members: &[SchemaNodeId],
+    ) -> Result<SchemaNodeId, SchemaGraphError> {
+        let mut flattened = Vec::with_capacity(members.len());
+        for &member in members {
+            let index = self.check(member)?;
+            match self.nodes.get(index) {
+                Some(&SchemaNode::Union(nested)) => flattened.extend_from_slice(
+                    self.node_lists
+                        .get(nested)
+                        .expect("union member lists are always interned"),
+                ),
+                _ => flattened.push(index),
+            }
+        }
+        flattened.sort_unstable();
+        flattened.dedup();
+        if flattened.len() == 1 {
+            return Ok(SchemaNodeId(flattened[0]));
+        }
+        let members = self.node_lists.intern_from(flattened)?;
+        self.add(SchemaNode::Union(members))
+    }
+
+    fn add(&mut self, node: SchemaNode) -> Result<SchemaNodeId, SchemaGraphError> {
+        Ok(SchemaNodeId(self.nodes.intern(node)?))
+    }
+
+    fn add_list(
+        &mut self,
+        items: &[SchemaNodeId],
+    ) -> Result<SchemaNodeListIndex, SchemaGraphError> {
+        let items = items
+            .iter()
+            .map(|&item| self.check(item))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.node_lists.intern_from(items)?)
+    }
+
+    fn add_fields(
+        &mut self,
+        fields: &[(&str, SchemaNodeId)],
+        skippable: &[&str],
+    ) -> Result<(FieldNameListIndex, MemberListIndex, SchemaNodeListIndex), SchemaGraphError> {
+        let mut skip_list = skippable
+            .iter()
+            .map(|&skippable| {
+                let position = fields
+                    .iter()
+                    .position(|&(name, _)| name == skippable)
+                    .ok_or_else(|| SchemaGraphError::UnknownSkippableField(skippable.into()))?;
+                Ok(MemberIndex::try_from(position)?)
+            })
+            .collect::<Result<Vec<_>, SchemaGraphError>>()?;
+        // As in `SchemaBuilder`, fields which are always skipped are not considered skippable.
+        skip_list.retain(|&member| !fields[usize::from(member)].1.0.is_empty());
+        skip_list.sort_unstable();
+        skip_list.dedup();
+
+        let field_names = fields
+            .iter()
+            .map(|&(name, _)| self.field_names.intern_from(name))
+            .collect::<Result<Box<[_]>, _>>()?;
+        let field_types = fields.iter().map(|&(_, node)| node).collect::<Vec<_>>();
+        Ok((
+            self.field_name_lists.intern(field_names)?,
+            self.member_lists.intern_from(skip_list)?,
+            self.add_list(&field_types)?,
+        ))
+    }
+
+    fn check(&self, id: SchemaNodeId) -> Result<SchemaNodeIndex, SchemaGraphError> {
+        self.nodes
+            .get(id.0)
+            .map(|_| id.0)
+            .ok_or(SchemaGraphError::UnknownNode(id))
+    }
+}
@@ -0,0 +1,480 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::{PrimitiveType, Schema, SchemaGraphBuilder, SchemaGraphError, SchemaNodeId};
+
+/// Errors returned by [`Schema::from_avro`] and [`Schema::from_json_schema`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum ImportError {
+    /// The document is not valid JSON.
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The document is not a valid schema.
+    #[error("invalid schema: {0}")]
+    Invalid(Box<str>),
+
+    /// The document uses a feature which has no equivalent in this crate's schemas.
+    #[error("unsupported schema: {0}")]
+    Unsupported(Box<str>),
+
+    /// A type refers to another type which isn't defined.
+    #[error("unknown type {0:?}")]
+    UnknownType(Box<str>),
+
+    /// A type refers to itself, which can't be represented, since schemas cannot be recursive.
+    #[error("recursive type {0:?} cannot be represented")]
+    Recursive(Box<str>),
+
+    /// The schema is too large.
+    #[error(transparent)]
+    Graph(#[from] SchemaGraphError),
+}
+
+impl Schema {
+    /// Converts an [Avro](https://avro.apache.org/docs/) schema document into a [`Schema`].
+    ///
+    /// Types are mapped to their `serde` equivalents: records to structs, enums to unions of unit
+    /// variants, arrays to sequences, maps to maps with string keys, fixed to bytes and unions to
+    /// unions. Unions with `"null"` become options. Logical types are ignored, in favour of their
+    /// underlying types.
+    ///
+    /// As with [`SchemaGraphBuilder`], union members are ordered by when their types were first
+    /// added, so the same union may be ordered differently in a traced schema.
+    ///
+    /// Requires the `import` feature.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::Schema;
+    ///
+    /// let schema = Schema::from_avro(r#"{
+    ///     "type": "record",
+    ///     "name": "User",
+    ///     "fields": [
+    ///         {"name": "id", "type": "long"},
+    ///         {"name": "email", "type": ["null", "string"]}
+    ///     ]
+    /// }"#)?;
+    /// assert_eq!(
+    ///     schema.to_string(),
+    ///     "User_6 { id: i64_1, email: union_5(some_3(str_2), none_4) }",
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_avro(document: &str) -> Result<Self, ImportError> {
+        let document = serde_json::from_str::<Value>(document)?;
+        let mut importer = AvroImporter::default();
+        let root = importer.import(&document, None)?;
+        Ok(importer.graph.build(root)?)
+    }
+
+    /// Converts a [JSON Schema](https://json-schema.org/) document into a [`Schema`].
+    ///
+    /// Supports the `type`, `properties`, `required`, `additionalProperties`, `items`,
+    /// `prefixItems`, `enum` (of strings), `anyOf`, `oneOf` and `$ref` (within the document)
+    /// keywords. Integers become `i64`, numbers `f64`, objects with `properties` become structs
+    /// named after their `title` (or definition), with non-`required` properties skippable, and
+    /// objects with only `additionalProperties` become maps. Nullable types become options.
+    ///
+    /// Properties are ordered alphabetically, which must match the order in which fields are
+    /// serialized.
+    ///
+    /// Requires the `import` feature.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::Schema;
+    ///
+    /// let schema = Schema::from_json_schema(r#"{
+    ///     "title": "Point",
+    ///     "type": "object",
+    ///     "properties": {
+    ///         "x": {"type": "number"},
+    ///         "y": {"type": "number"},
+    ///         "tags": {"type": "array", "items": {"type": "string"}}
+    ///     },
+    ///     "required": ["x", "y"]
+    /// }"#)?;
+    /// assert_eq!(
+    ///     schema.to_string(),
+    ///     "Point_4 { tags?: seq_2(str_1), x: f64_3, y: f64_3 }",
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn from_json_schema(document: &str) -> Result<Self, ImportError> {
+        let document = serde_json::from_str::<Value>(document)?;
+        let mut importer = JsonSchemaImporter {
+            document: &document,
+            graph: SchemaGraphBuilder::new(),
+            references: HashMap::new(),
+        };
+        let root = importer.import(&document, None)?;
+        Ok(importer.graph.build(root)?)
+    }
+}
+
+#[derive(Default)]
+struct AvroImporter {
+    graph: SchemaGraphBuilder,
+
+    /// Named types by full name; `None` while the type is being defined.
+    named: HashMap<String, Option<SchemaNodeId>>,
+}
+
+impl AvroImporter {
+    fn import(
+        &mut self,
+        schema: &Value,
+        namespace: Option<&str>,
+    ) -> Result<SchemaNodeId, ImportError> {
+        match schema {
+            Value::String(name) => self.import_name(name, namespace),
+            Value::Array(members) => {
+                let members = members
+                    .iter()
+                    .map(|member| match member {
+                        Value::String(null) if null == "null" => Ok(None),
+                        member => self.import(member, namespace).map(Some),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                nullable_union(&mut self.graph, &members)
+            }
+            Value::Object(object) => match object.get("type") {
+                Some(Value::String(kind)) => match &**kind {
+                    "record" | "error" => self.import_record(object, namespace),
+                    "enum" => self.import_enum(object, namespace),
+                    "fixed" => {
+                        let (_, full_name) = self.declare(object, namespace)?;
+                        let node = self.graph.add_primitive(PrimitiveType::Bytes)?;
+                        self.named.insert(full_name, Some(node));
+                        Ok(node)
+                    }
+                    "array" => {
+                        let items = self.import(required(object, "items")?, namespace)?;
+                        Ok(self.graph.add_sequence(items)?)
+                    }
+                    "map" => {
+                        let key = self.graph.add_primitive(PrimitiveType::String)?;
+                        let value = self.import(required(object, "values")?, namespace)?;
+                        Ok(self.graph.add_map(key, value)?)
+                    }
+                    name => self.import_name(name, namespace),
+                },
+                Some(inner) => self.import(inner, namespace),
+                None => Err(invalid("type object without `type`")),
+            },
+            _ => Err(invalid("schemas must be strings, arrays or objects")),
+        }
+    }
+
+    fn import_name(
+        &mut self,
+        name: &str,
+        namespace: Option<&str>,
+    ) -> Result<SchemaNodeId, ImportError> {
+        let primitive = match name {
+            "null" => PrimitiveType::Unit,
+            "boolean" => PrimitiveType::Bool,
+            "int" => PrimitiveType::I32,
+            "long" => PrimitiveType::I64,
+            "float" => PrimitiveType::F32,
+            "double" => PrimitiveType::F64,
+            "bytes" => PrimitiveType::Bytes,
+            "string" => PrimitiveType::String,
+            _ => {
+                let qualified = namespace.map(|namespace| format!("{namespace}.{name}"));
+                return match qualified
+                    .and_then(|qualified| self.named.get(&qualified))
+                    .or_else(|| self.named.get(name))
+                {
+                    Some(Some(node)) => Ok(*node),
+                    Some(None) => Err(ImportError::Recursive(name.into())),
+                    None => Err(ImportError::UnknownType(name.into())),
+                };
+            }
+        };
+        Ok(self.graph.add_primitive(primitive)?)
+    }
+
+    fn import_record(
+        &mut self,
+        object: &Map<String, Value>,
+        namespace: Option<&str>,
+    ) -> Result<SchemaNodeId, ImportError> {
+        let (name, full_name) = self.declare(object, namespace)?;
+        let namespace = full_name.rsplit_once('.').map(|(namespace, _)| namespace);
+        let Value::Array(fields) = required(object, "fields")? else {
+            return Err(invalid("record `fields` must be an array"));
+        };
+        let fields = fields
+            .iter()
+            .map(|field| {
+                let Value::String(name) = required_in(field, "name")? else {
+                    return Err(invalid("field `name` must be a string"));
+                };
+                Ok((
+                    &**name,
+                    self.import(required_in(field, "type")?, namespace)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let node = self.graph.add_struct(name, &fields, &[])?;
+        self.named.insert(full_name, Some(node));
+        Ok(node)
+    }
+
+    fn import_enum(
+        &mut self,
+        object: &Map<String, Value>,
+        namespace: Option<&str>,
+    ) -> Result<SchemaNodeId, ImportError> {
+        let Value::Array(symbols) = required(object, "symbols")? else {
+            return Err(invalid("enum `symbols` must be an array"));
+        };
+        let (name, full_name) = self.declare(object, namespace)?;
+        let variants = symbols
+            .iter()
+            .map(|symbol| match symbol {
+                Value::String(symbol) => Ok(self.graph.add_unit_variant(name, symbol)?),
+                _ => Err(invalid("enum symbols must be strings")),
+            })
+            .collect::<Result<Vec<_>, ImportError>>()?;
+        let node = self.graph.add_union(&variants)?;
+        self.named.insert(full_name, Some(node));
+        Ok(node)
+    }
+
+    /// Records that a named type is being defined, returning its short and full names.
+    fn declare<'object>(
+        &mut self,
+        object: &'object Map<String, Value>,
+        namespace: Option<&str>,
+    ) -> Result<(&'object str, String), ImportError> {
+        let Value::String(name) = required(object, "name")? else {
+            return Err(invalid("type `name` must be a string"));
+        };
+        let namespace = match object.get("namespace") {
+            Some(Value::String(namespace)) => Some(&**namespace),
+            _ => namespace,
+        };
+        let (short_name, full_name) = match (name.rsplit_once('.'), namespace) {
+            (Some((_, short_name)), _) => (short_name, name.clone()),
+            (None, Some(namespace)) if !namespace.is_empty() => {
+                (&**name, format!("{namespace}.{name}"))
+            }
+            (None, _) => (&**name, name.clone()),
+        };
+        if self.named.insert(full_name.clone(), None).is_some() {
+            return Err(invalid(format!("type {full_name:?} is defined twice")));
+        }
+        Ok((short_name, full_name))
+    }
+}
+
+struct JsonSchemaImporter<'document> {
+    document: &'document Value,
+    graph: SchemaGraphBuilder,
+
+    /// Imported `$ref`-s; `None` while the referenced type is being imported.
+    references: HashMap<&'document str, Option<SchemaNodeId>>,
+}
+
+impl<'document> JsonSchemaImporter<'document> {
+    fn import(
+        &mut self,
+        schema: &'document Value,
+        name: Option<&'document str>,
+    ) -> Result<SchemaNodeId, ImportError> {
+        let object = match schema {
+            Value::Object(object) => object,
+            Value::Bool(false) => return Ok(self.graph.add_union(&[])?),
+            _ => return Err(unsupported("schemas which accept any value")),
+        };
+        let name = match object.get("title") {
+            Some(Value::String(title)) => Some(&**title),
+            _ => name,
+        };
+
+        if let Some(reference) = object.get("$ref") {
+            return self.import_reference(reference);
+        }
+        if let Some(Value::Array(symbols)) = object.get("enum") {
+            let name = name.unwrap_or("Enum");
+            let variants = symbols
+                .iter()
+                .map(|symbol| match symbol {
+                    Value::String(symbol) => Ok(self.graph.add_unit_variant(name, symbol)?),
+                    _ => Err(unsupported("non-string `enum` values")),
+                })
+                .collect::<Result<Vec<_>, ImportError>>()?;
+            return Ok(self.graph.add_union(&variants)?);
+        }
+        if let Some(Value::Array(members)) = object.get("anyOf").or_else(|| object.get("oneOf")) {
+            let members = members
+                .iter()
+                .map(|member| match member.get("type") {
+                    Some(Value::String(null)) if null == "null" => Ok(None),
+                    _ => self.import(member, None).map(Some),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            return nullable_union(&mut self.graph, &members);
+        }
+
+        match object.get("type") {
+            Some(Value::String(kind)) => self.import_type(object, kind, name),
+            Some(Value::Array(kinds)) => {
+                let members = kinds
+                    .iter()
+                    .map(|kind| match kind {
+                        Value::String(null) if null == "null" => Ok(None),
+                        Value::String(kind) => self.import_type(object, kind, name).map(Some),
+                        _ => Err(invalid("`type` must be a string or an array of strings")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                nullable_union(&mut self.graph, &members)
+            }
+            _ => Err(unsupported("schemas without a `type`")),
+        }
+    }
+
+    fn import_type(
+        &mut self,
+        object: &'document Map<String, Value>,
+        kind: &str,
+        name: Option<&'document str>,
+    ) -> Result<SchemaNodeId, ImportError> {
+        let primitive = match kind {
+            "null" => PrimitiveType::Unit,
+            "boolean" => PrimitiveType::Bool,
+            "integer" => PrimitiveType::I64,
+            "number" => PrimitiveType::F64,
+            "string" => PrimitiveType::String,
+            "array" => {
+                return match (object.get("prefixItems"), object.get("items")) {
+                    (Some(Value::Array(items)), _) | (None, Some(Value::Array(items))) => {
+                        let items = items
+                            .iter()
+                            .map(|item| self.import(item, None))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        Ok(self.graph.add_tuple(&items)?)
+                    }
+                    (None, Some(item)) => {
+                        let item = self.import(item, None)?;
+                        Ok(self.graph.add_sequence(item)?)
+                    }
+                    _ => Err(unsupported("arrays without `items`")),
+                };
+            }
+            "object" => {
+                return match (object.get("properties"), object.get("additionalProperties")) {
+                    (Some(Value::Object(properties)), _) => {
+                        self.import_struct(object, properties, name.unwrap_or("Object"))
+                    }
+                    (None, Some(values @ Value::Object(_))) => {
+                        let key = self.graph.add_primitive(PrimitiveType::String)?;
+                        let value = self.import(values, None)?;
+                        Ok(self.graph.add_map(key, value)?)
+                    }
+                    _ => Err(unsupported(
+                        "objects without `properties` or `additionalProperties`",
+                    )),
+                };
+            }
+            kind => return Err(ImportError::UnknownType(kind.into())),
+        };
+        Ok(self.graph.add_primitive(primitive)?)
+    }
+
+    fn import_struct(
+        &mut self,
+        object: &'document Map<String, Value>,
+        properties: &'document Map<String, Value>,
+        name: &str,
+    ) -> Result<SchemaNodeId, ImportError> {
+        let required = match object.get("required") {
+            Some(Value::Array(required)) => required.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        let fields = properties
+            .iter()
+            .map(|(name, schema)| Ok((&**name, self.import(schema, None)?)))
+            .collect::<Result<Vec<_>, ImportError>>()?;
+        let skippable = properties
+            .keys()
+            .map(|name| &**name)
+            .filter(|name| !required.contains(name))
+            .collect::<Vec<_>>();
+        Ok(self.graph.add_struct(name, &fields, &skippable)?)
+    }
+
+    fn import_reference(
+        &mut self,
+        reference: &'document Value,
+    ) -> Result<SchemaNodeId, ImportError> {
+        let Value::String(reference) = reference else {
+            return Err(invalid("`$ref` must be a string"));
+        };
+        match self.references.get(&**reference) {
+            Some(Some(node)) => return Ok(*node),
+            Some(None) => return Err(ImportError::Recursive(reference.as_str().into())),
+            None => {}
+        }
+        let schema = reference
+            .strip_prefix('#')
+            .and_then(|pointer| self.document.pointer(pointer))
+            .ok_or_else(|| ImportError::UnknownType(reference.as_str().into()))?;
+        let name = reference.rsplit('/').next();
+        self.references.insert(reference, None);
+        let node = self.import(schema, name)?;
+        self.references.insert(reference, Some(node));
+        Ok(node)
+    }
+}
+
+/// Builds a union of the given types, where `None` stands for `null` and makes the union an
+/// option.
+fn nullable_union(
+    graph: &mut SchemaGraphBuilder,
+    members: &[Option<SchemaNodeId>],
+) -> Result<SchemaNodeId, ImportError> {
+    let non_null = members.iter().flatten().copied().collect::<Vec<_>>();
+    let union = graph.add_union(&non_null)?;
+    Ok(
+        match (non_null.len() == members.len(), non_null.is_empty()) {
+            (true, _) => union,
+            (false, true) => graph.add_none()?,
+            (false, false) => graph.add_option(union)?,
+        },
+    )
+}
+
+fn required<'object>(
+    object: &'object Map<String, Value>,
+    key: &str,
+) -> Result<&'object Value, ImportError> {
+    object
+        .get(key)
+        .ok_or_else(|| invalid(format!("missing required key `{key}`")))
+}
+
+fn required_in<'value>(value: &'value Value, key: &str) -> Result<&'value Value, ImportError> {
+    match value {
+        Value::Object(object) => required(object, key),
+        _ => Err(invalid(format!("expected an object with key `{key}`"))),
+    }
+}
+
+fn invalid(message: impl Into<Box<str>>) -> ImportError {
+    ImportError::Invalid(message.into())
+}
+
+fn unsupported(what: &str) -> ImportError {
+    ImportError::Unsupported(what.into())
+}
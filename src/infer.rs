@@ -0,0 +1,105 @@
+use serde::Serialize;
+
+use crate::{Schema, SchemaBuilder, TraceError};
+
+/// The schema inferred from a sample of values, returned by [`SchemaBuilder::infer_schema`].
+#[derive(Clone, Debug)]
+pub struct SchemaInference {
+    schema: Schema,
+    widened_by: Vec<usize>,
+    sampled: usize,
+}
+
+impl SchemaInference {
+    /// The schema of the sampled values.
+    #[inline]
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Returns the schema of the sampled values.
+    #[inline]
+    pub fn into_schema(self) -> Schema {
+        self.schema
+    }
+
+    /// The positions in the sample of the values which widened the schema, i.e. which had a
+    /// shape not seen in any of the values before them. The first value always widens it.
+    #[inline]
+    pub fn widened_by(&self) -> &[usize] {
+        &self.widened_by
+    }
+
+    /// How many values were taken from the sample before the schema stopped changing, or the
+    /// sample ran out.
+    #[inline]
+    pub fn sampled(&self) -> usize {
+        self.sampled
+    }
+}
+
+impl SchemaBuilder {
+    /// Infers the schema of a sample of values, e.g. the records of a batch being ingested.
+    ///
+    /// Only the schema is recorded, see [`trace_schema_only`][`Self::trace_schema_only`]. Stops
+    /// taking values from the sample once `stable_after` values in a row haven't widened the
+    /// schema, so large (or endless) samples can be inferred cheaply; pass `usize::MAX` to
+    /// record every value.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// struct Record {
+    ///     id: u32,
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     note: Option<String>,
+    /// }
+    ///
+    /// let records = (0..).map(|id| Record {
+    ///     id,
+    ///     note: (id == 3).then(|| "third".to_owned()),
+    /// });
+    /// let inference = SchemaBuilder::infer_schema(records, 100)?;
+    /// assert_eq!(inference.widened_by(), [0, 3]);
+    /// assert_eq!(inference.sampled(), 104);
+    /// assert_eq!(
+    ///     inference.schema().to_string(),
+    ///     "Record_4 { id: u32_1, note?: some_3(str_2) }",
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn infer_schema<ValuesT>(
+        values: ValuesT,
+        stable_after: usize,
+    ) -> Result<SchemaInference, TraceError>
+    where
+        ValuesT: IntoIterator,
+        ValuesT::Item: Serialize,
+    {
+        let mut builder = Self::new();
+        let mut widened_by = Vec::new();
+        let mut sampled = 0;
+        for value in values {
+            if builder.widen_schema(&value)? {
+                widened_by.push(sampled);
+            }
+            sampled += 1;
+            if widened_by
+                .last()
+                .is_some_and(|&last| sampled - last > stable_after)
+            {
+                break;
+            }
+        }
+        Ok(SchemaInference {
+            schema: builder.build()?,
+            widened_by,
+            sampled,
+        })
+    }
+}
@@ -0,0 +1,158 @@
+use serde::{Deserialize, Deserializer, Serialize, de::Error as _};
+use std::collections::HashMap;
+
+use crate::{DescribedBy, Schema, SchemaBuilder, SchemaFingerprint, Trace, TraceError};
+
+/// Encodes values for a key-value store (e.g. `sled` or RocksDB), storing each schema once.
+///
+/// Every value is stored as the [fingerprint][`SchemaFingerprint`] of the schema it was written
+/// with, followed by its data-only payload. The schemas themselves are stored once each, under
+/// reserved keys returned by [`schema_key`][`Self::schema_key`].
+///
+/// Values are traced into a schema which widens as new shapes of values are written. When a
+/// write widens the schema, [`encode`][`Self::encode`] returns the new schema, which must be
+/// stored before the value. Values written earlier keep their fingerprint and stay readable with
+/// the schema they were written with, so nothing needs to be rewritten.
+///
+/// The codec neither reads nor writes the store, and leaves the serialization format to the
+/// caller.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::KvCodec;
+/// use std::collections::BTreeMap;
+///
+/// // Stands in for the key-value store.
+/// let mut store = BTreeMap::<Vec<u8>, Vec<u8>>::new();
+///
+/// let mut codec = KvCodec::new();
+/// for (key, value) in [("a", Some(1u32)), ("b", None)] {
+///     let encoded = codec.encode(&value)?;
+///     if let Some(schema) = encoded.new_schema {
+///         store.insert(KvCodec::schema_key(encoded.fingerprint), postcard::to_stdvec(schema)?);
+///     }
+///     let mut bytes = KvCodec::value_prefix(encoded.fingerprint).to_vec();
+///     bytes.extend(postcard::to_stdvec(&encoded.data)?);
+///     store.insert(key.as_bytes().to_vec(), bytes);
+/// }
+///
+/// // On startup, a new codec loads the stored schemas before reading values.
+/// let mut codec = KvCodec::new();
+/// for (key, bytes) in store.range(KvCodec::SCHEMA_KEY_PREFIX.to_vec()..) {
+///     if key.starts_with(KvCodec::SCHEMA_KEY_PREFIX) {
+///         codec.insert_schema(postcard::from_bytes(bytes)?);
+///     }
+/// }
+/// let (fingerprint, payload) = KvCodec::split_value(&store[&b"a"[..]]).unwrap();
+/// let value: Option<u32> =
+///     codec.decode(fingerprint, &mut postcard::Deserializer::from_bytes(payload))?;
+/// assert_eq!(value, Some(1));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct KvCodec {
+    builder: SchemaBuilder,
+    current: Option<SchemaFingerprint>,
+    schemas: HashMap<SchemaFingerprint, Schema>,
+}
+
+/// A value encoded by [`KvCodec::encode`].
+pub struct KvEncoded<'codec> {
+    /// The schema the value was written with, to be stored under
+    /// [`KvCodec::schema_key`] before the value, if this write widened it.
+    pub new_schema: Option<&'codec Schema>,
+
+    /// The fingerprint of the schema the value was written with, to be stored ahead of the
+    /// payload with [`KvCodec::value_prefix`].
+    pub fingerprint: SchemaFingerprint,
+
+    /// The payload, to be serialized with the schema.
+    pub data: DescribedBy<'codec, Trace>,
+}
+
+impl KvCodec {
+    /// The prefix of the keys schemas are stored under. Starts with a zero byte to sort ahead
+    /// of, and be unlikely to collide with, textual keys.
+    pub const SCHEMA_KEY_PREFIX: &'static [u8] = b"\0serde_describe/schema/";
+
+    /// Creates a new codec, which doesn't know any schemas yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The key the schema with the given fingerprint is stored under.
+    pub fn schema_key(fingerprint: SchemaFingerprint) -> Vec<u8> {
+        let mut key = Self::SCHEMA_KEY_PREFIX.to_vec();
+        key.extend_from_slice(&fingerprint.0.to_be_bytes());
+        key
+    }
+
+    /// The bytes stored ahead of the payload of a value written with the given schema.
+    #[inline]
+    pub fn value_prefix(fingerprint: SchemaFingerprint) -> [u8; 8] {
+        fingerprint.0.to_le_bytes()
+    }
+
+    /// Splits stored value bytes into the fingerprint of their schema and their payload, or
+    /// returns `None` if they're too short.
+    pub fn split_value(bytes: &[u8]) -> Option<(SchemaFingerprint, &[u8])> {
+        let (prefix, payload) = bytes.split_first_chunk::<8>()?;
+        Some((SchemaFingerprint(u64::from_le_bytes(*prefix)), payload))
+    }
+
+    /// Registers a schema read back from the store, returning its fingerprint.
+    pub fn insert_schema(&mut self, schema: Schema) -> SchemaFingerprint {
+        let fingerprint = schema.fingerprint();
+        self.schemas.insert(fingerprint, schema);
+        fingerprint
+    }
+
+    /// The schema with the given fingerprint, if it's known.
+    #[inline]
+    pub fn schema(&self, fingerprint: SchemaFingerprint) -> Option<&Schema> {
+        self.schemas.get(&fingerprint)
+    }
+
+    /// Traces a value for writing, returning the new schema to store first if the value
+    /// widened it.
+    ///
+    /// This rebuilds the schema for every value, so batch writes are better served by
+    /// [`SchemaBuilder::trace_all`].
+    pub fn encode<ValueT>(&mut self, value: &ValueT) -> Result<KvEncoded<'_>, TraceError>
+    where
+        ValueT: Serialize,
+    {
+        let trace = self.builder.trace(value)?;
+        let schema = self.builder.clone().build()?;
+        let fingerprint = schema.fingerprint();
+        let widened = self.current != Some(fingerprint);
+        self.current = Some(fingerprint);
+        let schema = self.schemas.entry(fingerprint).or_insert(schema);
+        Ok(KvEncoded {
+            new_schema: widened.then_some(&*schema),
+            fingerprint,
+            data: schema.describe_trace(trace),
+        })
+    }
+
+    /// Deserializes the payload of a value written with the schema with the given fingerprint.
+    ///
+    /// Returns an error if the schema isn't known.
+    pub fn decode<'de, DeserializeT, DeserializerT>(
+        &self,
+        fingerprint: SchemaFingerprint,
+        deserializer: DeserializerT,
+    ) -> Result<DeserializeT, DeserializerT::Error>
+    where
+        DeserializeT: Deserialize<'de>,
+        DeserializerT: Deserializer<'de>,
+    {
+        self.schema(fingerprint)
+            .ok_or_else(|| {
+                DeserializerT::Error::custom(format_args!("unknown schema {fingerprint}"))
+            })?
+            .deserialize_described(deserializer)
+    }
+}
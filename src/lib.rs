@@ -53,21 +53,116 @@
 #![deny(missing_docs)]
 
 pub(crate) mod anonymous_union;
+pub(crate) mod audit;
 pub(crate) mod builder;
+pub(crate) mod canonical;
 pub(crate) mod de;
 pub(crate) mod deferred;
+pub(crate) mod delta;
 pub(crate) mod described;
+pub(crate) mod diff;
 pub(crate) mod dump;
+pub(crate) mod dynamic;
+pub(crate) mod elements;
+pub(crate) mod embedded;
+pub(crate) mod equivalence;
+pub(crate) mod explain;
+#[cfg(feature = "export")]
+pub(crate) mod export;
+pub(crate) mod fingerprint;
+#[cfg(feature = "half")]
+pub(crate) mod float16;
+pub(crate) mod graph;
+#[cfg(feature = "import")]
+pub(crate) mod import;
 pub(crate) mod indices;
+pub(crate) mod infer;
+pub(crate) mod kv;
+pub(crate) mod limits;
+pub(crate) mod literal;
+pub(crate) mod logical;
+pub(crate) mod messaging;
+pub(crate) mod metrics;
+pub(crate) mod name_hash;
+pub(crate) mod options;
+pub(crate) mod path;
+pub(crate) mod plan;
 pub(crate) mod pool;
+#[cfg(feature = "crypto")]
+pub(crate) mod protect;
+pub(crate) mod prune;
+pub(crate) mod qualified;
+pub(crate) mod raw_value;
+pub(crate) mod reader;
+pub(crate) mod recover;
+pub(crate) mod roots;
 pub(crate) mod schema;
+#[cfg(feature = "crypto")]
+pub(crate) mod sealed;
 pub(crate) mod ser;
+pub(crate) mod session;
+#[cfg(feature = "tracing")]
+pub(crate) mod sink;
+pub(crate) mod stats;
+pub(crate) mod tensor;
+#[cfg(feature = "test-vectors")]
+pub(crate) mod test_vectors;
 pub(crate) mod trace;
+pub(crate) mod transform;
+pub(crate) mod typed;
 
-pub use builder::{SchemaBuilder, TraceError};
+pub use audit::{SerdeCall, TraceAudit};
+pub use builder::{DataBudgetError, SchemaBuilder, TraceError, UnionError};
+pub use canonical::CanonicalEncodingError;
+pub use delta::{SchemaDelta, SchemaDeltaError};
 pub use described::{DescribedBy, SelfDescribed};
-pub use schema::Schema;
-pub use trace::Trace;
+pub use diff::{TraceChange, TraceDiff};
+pub use dynamic::{Dynamic, DynamicFields};
+pub use elements::{ElementValue, SequenceElements};
+pub use embedded::Embedded;
+pub use explain::{SchemaDifference, SchemaExplanation};
+#[cfg(feature = "export")]
+pub use export::{ByteEncoding, JsonExportError, JsonExportOptions};
+pub use fingerprint::{DetachedTrace, SchemaFingerprint, SchemaMismatchError};
+#[cfg(feature = "half")]
+pub use float16::{Bf16, F16};
+pub use graph::{PrimitiveType, SchemaGraphBuilder, SchemaGraphError, SchemaNodeId};
+#[cfg(feature = "import")]
+pub use import::ImportError;
+pub use infer::SchemaInference;
+pub use kv::{KvCodec, KvEncoded};
+pub use limits::{Limits, limits};
+pub use logical::LogicalTypes;
+pub use messaging::{MessageEncoder, MessageHeader, MessageHeaderError, SchemaStore};
+pub use metrics::Metrics;
+pub use options::{
+    DecodeOptions, EnumRepresentation, FloatNormalization, NumericCoercion, WithOptions,
+};
+pub use path::{FieldPath, FieldPathError, PathSegment};
+pub use plan::DecodePlan;
+#[cfg(feature = "crypto")]
+pub use protect::{Protection, ProtectionPolicy};
+pub use prune::InvalidSchemaError;
+pub use qualified::Qualified;
+pub use raw_value::RawValue;
+pub use reader::{TraceEvent, TraceNodes, TraceReader, TrailingDataError};
+pub use recover::{ElementError, Recovered};
+pub use roots::RootSeed;
+pub use schema::{ReaderVersionError, Schema};
+#[cfg(feature = "crypto")]
+pub use sealed::{OpenSealedError, SealedTrace, SealingKey};
+pub use session::{
+    ChunkedWriter, DecoderSession, EncodedMessage, EncoderSession, SchemaUpdate, SessionError,
+};
+#[cfg(feature = "tracing")]
+pub use sink::{LogEvent, LogValue, TracingLayer};
+pub use stats::{FieldPresence, SkipStats, UnionStats};
+pub use tensor::{Tensor, TensorElement, TensorShapeError};
+#[cfg(feature = "test-vectors")]
+pub use test_vectors::{TestVector, TestVectorError};
+pub use trace::{InvalidTraceError, Trace};
+pub use transform::LeafValue;
+pub use typed::{Typed, TypedConversionError};
 
 #[cfg(test)]
 mod tests;
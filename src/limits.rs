@@ -0,0 +1,403 @@
+use serde::de::{Deserializer, EnumAccess, Error, MapAccess, SeqAccess, Visitor};
+use std::{cell::Cell, marker::PhantomData};
+
+use crate::anonymous_union::UNION_ENUM_VARIANT_NAMES;
+
+/// The largest count the format can store, since counts and indices are 32-bit.
+const MAX_U32_COUNT: usize = {
+    assert!(usize::BITS >= 32, "usize must be at least 32 bits");
+    u32::MAX as usize
+};
+
+/// Returns the limits of the format, see [`Limits`].
+///
+/// Example
+/// -------
+/// ```rust
+/// let limits = serde_describe::limits();
+///
+/// // Lengths are stored as 32-bit integers.
+/// assert_eq!(limits.max_length(), u32::MAX as usize);
+///
+/// // A union of 300 types nests its discriminant two enums deep.
+/// assert!(300 > limits.discriminant_variants());
+/// ```
+#[inline]
+pub const fn limits() -> Limits {
+    Limits {
+        max_union_members: MAX_U32_COUNT,
+        discriminant_variants: UNION_ENUM_VARIANT_NAMES.len(),
+        max_fields: MAX_U32_COUNT,
+        max_length: MAX_U32_COUNT,
+        max_schema_nodes: MAX_U32_COUNT,
+        max_names: MAX_U32_COUNT,
+    }
+}
+
+/// The limits of the format, e.g. to check a data model up front, rather than when a large
+/// value is first traced. Tracing fails with a [`TraceError::Limit`] beyond the maximums.
+///
+/// These are built into the format, unlike the limits of [`DecodeOptions`], which only protect
+/// deserialization from hostile inputs and are unlimited unless set.
+///
+/// [`TraceError::Limit`]: crate::TraceError::Limit
+/// [`DecodeOptions`]: crate::DecodeOptions
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Limits {
+    max_union_members: usize,
+    discriminant_variants: usize,
+    max_fields: usize,
+    max_length: usize,
+    max_schema_nodes: usize,
+    max_names: usize,
+}
+
+impl Limits {
+    /// The maximum number of different types in a union, e.g. of the variants of an enum found
+    /// in a single sequence.
+    #[inline]
+    pub const fn max_union_members(&self) -> usize {
+        self.max_union_members
+    }
+
+    /// The number of variants of each enum a discriminant is serialized as, where the
+    /// discriminant picks the member of a union, or records which of a struct's skippable fields
+    /// are present.
+    ///
+    /// A discriminant with more values is serialized as one enum nested in another per byte, i.e.
+    /// unions of more than 256 members and structs with more than 8 skippable fields nest
+    /// deeper, which counts towards the recursion limit of formats which have one.
+    #[inline]
+    pub const fn discriminant_variants(&self) -> usize {
+        self.discriminant_variants
+    }
+
+    /// The maximum number of fields of a struct, tuple or variant.
+    #[inline]
+    pub const fn max_fields(&self) -> usize {
+        self.max_fields
+    }
+
+    /// The maximum number of items of a sequence or entries of a map, and the maximum length in
+    /// bytes of a string or byte string.
+    #[inline]
+    pub const fn max_length(&self) -> usize {
+        self.max_length
+    }
+
+    /// The maximum number of distinct nodes in a schema.
+    #[inline]
+    pub const fn max_schema_nodes(&self) -> usize {
+        self.max_schema_nodes
+    }
+
+    /// The maximum number of distinct type, variant and field names in a schema.
+    #[inline]
+    pub const fn max_names(&self) -> usize {
+        self.max_names
+    }
+}
+
+thread_local! {
+    /// The bytes left to allocate for the described value being deserialized on this thread, if
+    /// it has a [`DecodeOptions::max_allocation`], since `serde` gives deserializers no way to
+    /// share state across the parts of a value.
+    ///
+    /// [`DecodeOptions::max_allocation`]: crate::DecodeOptions::max_allocation
+    static ALLOCATION_BUDGET: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Limits the bytes allocated while deserializing a described value to `max`, until dropped.
+///
+/// Values deserialized inside others, e.g. [`Embedded`][`crate::Embedded`] ones, are charged to
+/// the enclosing value's budget too. Budgets must be dropped in the reverse order they were
+/// entered in, on the thread they were entered on, which holds as long as they're kept in local
+/// variables.
+pub(crate) struct AllocationBudget {
+    previous: Option<usize>,
+    initial: usize,
+    /// The budget belongs to the thread it was entered on.
+    phantom: PhantomData<*const ()>,
+}
+
+impl AllocationBudget {
+    /// Returns `None` if the allocation is unlimited, in which case the budget of any enclosing
+    /// value still applies.
+    #[inline]
+    pub(crate) fn enter(max: usize) -> Option<Self> {
+        if max == usize::MAX {
+            return None;
+        }
+        let previous = ALLOCATION_BUDGET.get();
+        let initial = previous.map_or(max, |previous| previous.min(max));
+        ALLOCATION_BUDGET.set(Some(initial));
+        Some(Self {
+            previous,
+            initial,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl Drop for AllocationBudget {
+    fn drop(&mut self) {
+        // Restores the enclosing budget, less what was spent within this one. Saturates rather
+        // than trusting that nothing in between replaced the budget with a larger one.
+        let left = ALLOCATION_BUDGET.get().unwrap_or(0);
+        let spent = self.initial.saturating_sub(left);
+        ALLOCATION_BUDGET.set(self.previous.map(|previous| previous.saturating_sub(spent)));
+    }
+}
+
+/// Spends `length` bytes of the current [`AllocationBudget`], if any, or fails if that's more
+/// than is left.
+pub(crate) fn charge_allocation<ErrorT>(length: usize) -> Result<(), ErrorT>
+where
+    ErrorT: Error,
+{
+    match ALLOCATION_BUDGET.get() {
+        Some(left) if length > left => Err(ErrorT::custom(format_args!(
+            "allocating {length} more bytes exceeds the maximum allocation"
+        ))),
+        Some(left) => {
+            ALLOCATION_BUDGET.set(Some(left - length));
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Wraps a deserializer or a visitor, failing if a string or byte string longer than
+/// `max_length` bytes is deserialized, see [`DecodeOptions::max_string_length`], or if it
+/// exceeds the [`AllocationBudget`].
+///
+/// [`DecodeOptions::max_string_length`]: crate::DecodeOptions::max_string_length
+pub(crate) struct LengthLimited<InnerT> {
+    pub(crate) inner: InnerT,
+    pub(crate) max_length: usize,
+}
+
+impl<InnerT> LengthLimited<InnerT> {
+    #[inline]
+    fn check<ErrorT>(&self, length: usize) -> Result<(), ErrorT>
+    where
+        ErrorT: Error,
+    {
+        if length > self.max_length {
+            Err(ErrorT::custom(format_args!(
+                "string of {length} bytes exceeds the maximum length of {}",
+                self.max_length
+            )))
+        } else {
+            charge_allocation(length)
+        }
+    }
+
+    #[inline]
+    fn wrap<OtherT>(&self, inner: OtherT) -> LengthLimited<OtherT> {
+        LengthLimited {
+            inner,
+            max_length: self.max_length,
+        }
+    }
+}
+
+/// Fails if a sequence or map has more than `max_length` elements, see
+/// [`DecodeOptions::max_sequence_length`].
+///
+/// [`DecodeOptions::max_sequence_length`]: crate::DecodeOptions::max_sequence_length
+pub(crate) fn check_sequence_length<ErrorT>(length: usize, max_length: usize) -> Result<(), ErrorT>
+where
+    ErrorT: Error,
+{
+    if length > max_length {
+        Err(ErrorT::custom(format_args!(
+            "sequence of more than {max_length} elements exceeds the maximum length"
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($($method:ident($($arg:ident: $type:ty),*),)+) => {
+        $(
+            #[inline]
+            fn $method<V>(self, $($arg: $type,)* visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                let visitor = self.wrap(visitor);
+                self.inner.$method($($arg,)* visitor)
+            }
+        )+
+    };
+}
+
+impl<'de, DeserializerT> Deserializer<'de> for LengthLimited<DeserializerT>
+where
+    DeserializerT: Deserializer<'de>,
+{
+    type Error = DeserializerT::Error;
+
+    forward_deserialize! {
+        deserialize_any(),
+        deserialize_bool(),
+        deserialize_i8(),
+        deserialize_i16(),
+        deserialize_i32(),
+        deserialize_i64(),
+        deserialize_i128(),
+        deserialize_u8(),
+        deserialize_u16(),
+        deserialize_u32(),
+        deserialize_u64(),
+        deserialize_u128(),
+        deserialize_f32(),
+        deserialize_f64(),
+        deserialize_char(),
+        deserialize_str(),
+        deserialize_string(),
+        deserialize_bytes(),
+        deserialize_byte_buf(),
+        deserialize_option(),
+        deserialize_unit(),
+        deserialize_unit_struct(name: &'static str),
+        deserialize_newtype_struct(name: &'static str),
+        deserialize_seq(),
+        deserialize_tuple(len: usize),
+        deserialize_tuple_struct(name: &'static str, len: usize),
+        deserialize_map(),
+        deserialize_struct(name: &'static str, fields: &'static [&'static str]),
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]),
+        deserialize_identifier(),
+        deserialize_ignored_any(),
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+macro_rules! forward_visit {
+    ($($method:ident($type:ty),)+) => {
+        $(
+            #[inline]
+            fn $method<E>(self, value: $type) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                self.inner.$method(value)
+            }
+        )+
+    };
+}
+
+macro_rules! check_visit {
+    ($($method:ident($type:ty),)+) => {
+        $(
+            #[inline]
+            fn $method<E>(self, value: $type) -> Result<Self::Value, E>
+            where
+                E: Error,
+            {
+                self.check(value.len())?;
+                self.inner.$method(value)
+            }
+        )+
+    };
+}
+
+impl<'de, VisitorT> Visitor<'de> for LengthLimited<VisitorT>
+where
+    VisitorT: Visitor<'de>,
+{
+    type Value = VisitorT::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.inner.expecting(formatter)
+    }
+
+    forward_visit! {
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+    }
+
+    check_visit! {
+        visit_str(&str),
+        visit_borrowed_str(&'de str),
+        visit_string(String),
+        visit_bytes(&[u8]),
+        visit_borrowed_bytes(&'de [u8]),
+        visit_byte_buf(Vec<u8>),
+    }
+
+    #[inline]
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        self.inner.visit_none()
+    }
+
+    #[inline]
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        self.inner.visit_unit()
+    }
+
+    #[inline]
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.inner.visit_some(deserializer)
+    }
+
+    #[inline]
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        self.inner.visit_newtype_struct(deserializer)
+    }
+
+    #[inline]
+    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.inner.visit_seq(seq)
+    }
+
+    #[inline]
+    fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        self.inner.visit_map(map)
+    }
+
+    #[inline]
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        self.inner.visit_enum(data)
+    }
+}
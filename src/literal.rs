@@ -0,0 +1,272 @@
+use std::{cell::Cell, fmt::Write};
+
+use crate::{
+    DescribedBy, InvalidTraceError, Schema, Trace,
+    dump::{DumpError, length_from_u32},
+    indices::FieldNameListIndex,
+    trace::{ReadTraceExt, TraceNode},
+};
+
+impl DescribedBy<'_, &'_ Trace> {
+    /// Prints a Rust expression constructing the traced value, e.g. to turn a captured payload
+    /// into a test fixture.
+    ///
+    /// The expression uses the serialized names of types, variants and fields, so it only
+    /// compiles as is for types which aren't renamed by `serde` attributes. Values whose type
+    /// isn't recorded in the trace are written such that they convert into the most likely
+    /// types: strings as `"..".into()`, byte strings as `b"..".to_vec().into()`, sequences as
+    /// `vec![..]` and maps as `[(key, value), ..].into_iter().collect()`. Structs with skipped
+    /// fields are completed with `..Default::default()`.
+    ///
+    /// The output is on a single line, to be formatted with `rustfmt` if needed.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// enum Shape {
+    ///     Circle { radius: f64 },
+    ///     Point,
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Drawing {
+    ///     title: String,
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     author: Option<String>,
+    ///     shapes: Vec<Shape>,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&Drawing {
+    ///     title: "Sun".to_owned(),
+    ///     author: None,
+    ///     shapes: vec![Shape::Circle { radius: 1.0 }, Shape::Point],
+    /// })?;
+    /// let schema = builder.build()?;
+    /// assert_eq!(
+    ///     schema.describe_trace_ref(&trace).to_rust_literal()?,
+    ///     "Drawing { title: \"Sun\".into(), shapes: vec![Shape::Circle { radius: 1.0 }, \
+    ///      Shape::Point], ..Default::default() }",
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn to_rust_literal(&self) -> Result<String, InvalidTraceError> {
+        let mut output = String::new();
+        self.1
+            .write_rust_literal(&mut output, &Cell::new(&(self.0).0))?;
+        Ok(output)
+    }
+}
+
+impl Schema {
+    fn write_rust_literal(&self, output: &mut String, tail: &Cell<&[u8]>) -> Result<(), DumpError> {
+        match tail.pop_trace_node::<DumpError>()? {
+            TraceNode::Bool => write!(output, "{}", tail.pop_bool::<DumpError>()?)?,
+            TraceNode::I8 => write!(output, "{}", tail.pop_i8::<DumpError>()?)?,
+            TraceNode::I16 => write!(output, "{}", tail.pop_i16::<DumpError>()?)?,
+            TraceNode::I32 => write!(output, "{}", tail.pop_i32::<DumpError>()?)?,
+            TraceNode::I64 => write!(output, "{}", tail.pop_i64::<DumpError>()?)?,
+            TraceNode::I128 => write!(output, "{}", tail.pop_i128::<DumpError>()?)?,
+            TraceNode::U8 => write!(output, "{}", tail.pop_u8::<DumpError>()?)?,
+            TraceNode::U16 => write!(output, "{}", tail.pop_u16::<DumpError>()?)?,
+            TraceNode::U32 => write!(output, "{}", tail.pop_u32::<DumpError>()?)?,
+            TraceNode::U64 => write!(output, "{}", tail.pop_u64::<DumpError>()?)?,
+            TraceNode::U128 => write!(output, "{}", tail.pop_u128::<DumpError>()?)?,
+            TraceNode::F32 => write_float(output, "f32", tail.pop_f32::<DumpError>()?)?,
+            TraceNode::F64 => write_float(output, "f64", tail.pop_f64::<DumpError>()?)?,
+            TraceNode::Char => write!(output, "{:?}", tail.pop_char::<DumpError>()?)?,
+            TraceNode::String => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                write!(output, "{:?}.into()", tail.pop_str::<DumpError>(length)?)?
+            }
+            TraceNode::Bytes => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                let bytes = tail.pop_slice::<DumpError>(length)?;
+                write!(output, "b\"{}\".to_vec().into()", bytes.escape_ascii())?
+            }
+
+            TraceNode::None => output.push_str("None"),
+            TraceNode::Some => self.write_rust_items(output, tail, "Some(", ")", 1)?,
+
+            TraceNode::Unit => output.push_str("()"),
+            TraceNode::UnitStruct(name) => output.push_str(self.type_name(name)?),
+            TraceNode::UnitVariant(name, variant) => write!(
+                output,
+                "{}::{}",
+                self.type_name(name)?,
+                self.variant_name(variant)?,
+            )?,
+
+            TraceNode::NewtypeStruct(name) => {
+                let open = format!("{}(", self.type_name(name)?);
+                self.write_rust_items(output, tail, &open, ")", 1)?
+            }
+            TraceNode::NewtypeVariant(name, variant) => {
+                let name = self.type_name(name)?;
+                let open = format!("{name}::{}(", self.variant_name(variant)?);
+                self.write_rust_items(output, tail, &open, ")", 1)?
+            }
+
+            TraceNode::Sequence => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                self.write_rust_items(output, tail, "vec![", "]", length)?
+            }
+            TraceNode::Map => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                if length == 0 {
+                    output.push_str("Default::default()");
+                    return Ok(());
+                }
+                output.push('[');
+                for i_entry in 0..length {
+                    if i_entry > 0 {
+                        output.push_str(", ");
+                    }
+                    self.write_rust_items(output, tail, "(", ")", 2)?;
+                }
+                output.push_str("].into_iter().collect()");
+            }
+
+            TraceNode::Tuple(1) => self.write_rust_items(output, tail, "(", ",)", 1)?,
+            TraceNode::Tuple(length) => {
+                self.write_rust_items(output, tail, "(", ")", length_from_u32(length))?
+            }
+            TraceNode::TupleStruct(length, name) => {
+                let open = format!("{}(", self.type_name(name)?);
+                self.write_rust_items(output, tail, &open, ")", length_from_u32(length))?
+            }
+            TraceNode::TupleVariant(length, name, variant) => {
+                let name = self.type_name(name)?;
+                let open = format!("{name}::{}(", self.variant_name(variant)?);
+                self.write_rust_items(output, tail, &open, ")", length_from_u32(length))?
+            }
+
+            TraceNode::Struct(name, field_names) => {
+                let name = self.type_name(name)?;
+                self.write_rust_struct(output, tail, name, field_names, true)?
+            }
+            TraceNode::StructVariant(name, variant, field_names) => {
+                let name = format!("{}::{}", self.type_name(name)?, self.variant_name(variant)?);
+                // Variants can't be completed with `..Default::default()`, so skipped fields are
+                // left out, to be filled in by hand.
+                self.write_rust_struct(output, tail, &name, field_names, false)?
+            }
+        }
+        Ok(())
+    }
+
+    fn write_rust_items(
+        &self,
+        output: &mut String,
+        tail: &Cell<&[u8]>,
+        open: &str,
+        close: &str,
+        length: usize,
+    ) -> Result<(), DumpError> {
+        output.push_str(open);
+        for i_item in 0..length {
+            if i_item > 0 {
+                output.push_str(", ");
+            }
+            self.write_rust_literal(output, tail)?;
+        }
+        output.push_str(close);
+        Ok(())
+    }
+
+    fn write_rust_struct(
+        &self,
+        output: &mut String,
+        tail: &Cell<&[u8]>,
+        name: &str,
+        field_names: FieldNameListIndex,
+        fill_defaults: bool,
+    ) -> Result<(), DumpError> {
+        let present = self.pop_trace_field_names(tail, field_names)?;
+        let skipped = present.len() < self.field_name_list(field_names)?.len();
+        output.push_str(name);
+        output.push_str(" {");
+        for (i_field, field) in present.iter().enumerate() {
+            output.push_str(if i_field > 0 { ", " } else { " " });
+            if is_keyword(field) {
+                output.push_str("r#");
+            }
+            output.push_str(field);
+            output.push_str(": ");
+            self.write_rust_literal(output, tail)?;
+        }
+        if skipped && fill_defaults {
+            output.push_str(if present.is_empty() { " " } else { ", " });
+            output.push_str("..Default::default()");
+        }
+        output.push_str(if present.is_empty() && !(skipped && fill_defaults) {
+            "}"
+        } else {
+            " }"
+        });
+        Ok(())
+    }
+}
+
+/// Writes a float such that it's parsed back as a float, spelling out non-finite values.
+fn write_float<FloatT>(output: &mut String, type_name: &str, value: FloatT) -> std::fmt::Result
+where
+    FloatT: Into<f64> + std::fmt::Debug + Copy,
+{
+    let wide = value.into();
+    if wide.is_nan() {
+        write!(output, "{type_name}::NAN")
+    } else if wide == f64::INFINITY {
+        write!(output, "{type_name}::INFINITY")
+    } else if wide == f64::NEG_INFINITY {
+        write!(output, "{type_name}::NEG_INFINITY")
+    } else {
+        write!(output, "{value:?}")
+    }
+}
+
+/// Whether a field name needs to be written as a raw identifier.
+fn is_keyword(name: &str) -> bool {
+    matches!(
+        name,
+        "as" | "async"
+            | "await"
+            | "break"
+            | "const"
+            | "continue"
+            | "dyn"
+            | "else"
+            | "enum"
+            | "extern"
+            | "false"
+            | "fn"
+            | "for"
+            | "gen"
+            | "if"
+            | "impl"
+            | "in"
+            | "let"
+            | "loop"
+            | "match"
+            | "mod"
+            | "move"
+            | "mut"
+            | "pub"
+            | "ref"
+            | "return"
+            | "static"
+            | "struct"
+            | "trait"
+            | "true"
+            | "type"
+            | "unsafe"
+            | "use"
+            | "where"
+            | "while"
+    )
+}
@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use crate::{LeafValue, transform::TransformFn};
+
+/// A registry of custom logical types: newtypes wrapping a primitive value, which are recorded in
+/// the schema under a logical type id, and whose values are converted when they're traced and
+/// converted back when they're deserialized.
+///
+/// A logical type id identifies the meaning of the stored value independently of the Rust type
+/// that wrote it, e.g. `"duration-ms"` for a duration stored as a number of milliseconds.
+/// Independent readers which register the same id interpret the stored value the same way,
+/// whatever their own types are called. Readers without the registry just see the stored value.
+///
+/// Pass the registry to [`SchemaBuilder::logical_types`][`crate::SchemaBuilder::logical_types`]
+/// when tracing and to [`DecodeOptions::logical_types`][`crate::DecodeOptions::logical_types`]
+/// when deserializing. Conversions only apply to newtypes which are deserialized as newtypes
+/// (e.g. a `#[derive(Deserialize)]` newtype struct), and only to a single primitive value:
+/// tracing a registered newtype which wraps anything else fails with
+/// [`TraceError::LogicalType`][`crate::TraceError::LogicalType`].
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize, de::DeserializeSeed};
+/// use serde_describe::{DecodeOptions, LeafValue, LogicalTypes, SchemaBuilder};
+/// use std::sync::LazyLock;
+///
+/// /// A duration in seconds, stored as milliseconds.
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Seconds(f64);
+///
+/// static LOGICAL_TYPES: LazyLock<LogicalTypes> = LazyLock::new(|| {
+///     LogicalTypes::new().register(
+///         "Seconds",
+///         "duration-ms",
+///         |value| match value {
+///             LeafValue::F64(seconds) => LeafValue::U64((seconds * 1000.0) as u64),
+///             other => other,
+///         },
+///         |value| match value {
+///             LeafValue::U64(millis) => LeafValue::F64(millis as f64 / 1000.0),
+///             other => other,
+///         },
+///     )
+/// });
+///
+/// let mut builder = SchemaBuilder::new().logical_types(LOGICAL_TYPES.clone());
+/// let trace = builder.trace(&Seconds(1.5))?;
+/// let schema = builder.build()?;
+/// assert_eq!(schema.to_string(), "duration-ms_2(u64_1)");
+///
+/// let bytes = postcard::to_stdvec(&schema.describe_trace(trace))?;
+/// let seconds = schema
+///     .describe_type::<Seconds>()
+///     .with_options(DecodeOptions::new().logical_types(&LOGICAL_TYPES))
+///     .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))?
+///     .0;
+/// assert_eq!(seconds, Seconds(1.5));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct LogicalTypes {
+    types: Vec<LogicalType>,
+}
+
+#[derive(Clone)]
+pub(crate) struct LogicalType {
+    type_name: &'static str,
+    pub(crate) id: &'static str,
+    pub(crate) encode: Arc<TransformFn>,
+    pub(crate) decode: Arc<TransformFn>,
+}
+
+impl LogicalTypes {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the newtype named `type_name` as the logical type `id`.
+    ///
+    /// When traced, the value wrapped by the newtype is passed through `encode` and the newtype
+    /// is recorded under the name `id`. When deserialized, the stored value is passed through
+    /// `decode` before it's handed to the newtype. Registering the same type name or id again
+    /// replaces the previous registration.
+    pub fn register<EncodeT, DecodeT>(
+        mut self,
+        type_name: &'static str,
+        id: &'static str,
+        encode: EncodeT,
+        decode: DecodeT,
+    ) -> Self
+    where
+        EncodeT: for<'value> Fn(LeafValue<'value>) -> LeafValue<'value> + Send + Sync + 'static,
+        DecodeT: for<'value> Fn(LeafValue<'value>) -> LeafValue<'value> + Send + Sync + 'static,
+    {
+        self.types
+            .retain(|logical| logical.type_name != type_name && logical.id != id);
+        self.types.push(LogicalType {
+            type_name,
+            id,
+            encode: Arc::new(encode),
+            decode: Arc::new(decode),
+        });
+        self
+    }
+
+    /// Whether no logical types are registered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    pub(crate) fn for_type(&self, type_name: &str) -> Option<&LogicalType> {
+        self.types
+            .iter()
+            .find(|logical| logical.type_name == type_name)
+    }
+
+    pub(crate) fn for_id(&self, id: &str) -> Option<&LogicalType> {
+        self.types.iter().find(|logical| logical.id == id)
+    }
+}
+
+impl std::fmt::Debug for LogicalTypes {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_map()
+            .entries(
+                self.types
+                    .iter()
+                    .map(|logical| (logical.type_name, logical.id)),
+            )
+            .finish()
+    }
+}
+
+/// A registry referenced by [`DecodeOptions`][`crate::DecodeOptions`], compared by address.
+#[derive(Copy, Clone)]
+pub(crate) struct StaticLogicalTypes(pub(crate) &'static LogicalTypes);
+
+impl PartialEq for StaticLogicalTypes {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for StaticLogicalTypes {}
+
+impl std::hash::Hash for StaticLogicalTypes {
+    #[inline]
+    fn hash<HasherT: std::hash::Hasher>(&self, state: &mut HasherT) {
+        std::ptr::hash(self.0, state);
+    }
+}
+
+impl std::fmt::Debug for StaticLogicalTypes {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(formatter)
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Deserializer, Serialize, de::Error as _};
+use std::collections::HashMap;
+use thiserror::Error;
+
+use crate::{DescribedBy, Schema, SchemaBuilder, SchemaFingerprint, Trace, TraceError};
+
+/// The header of a message on a message bus (e.g. Kafka), identifying the schema its payload
+/// was written with.
+///
+/// Follows the convention of Confluent's schema registry: a zero magic byte followed by the
+/// schema identifier in big-endian, here the 8-byte [`SchemaFingerprint`]. The schemas
+/// themselves are shared through a [`SchemaStore`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct MessageHeader {
+    /// The fingerprint of the schema the payload was written with.
+    pub fingerprint: SchemaFingerprint,
+}
+
+/// Returned by [`MessageHeader::split`] for messages without a valid header.
+#[derive(Clone, Debug, Error)]
+#[non_exhaustive]
+pub enum MessageHeaderError {
+    /// The message is shorter than a header.
+    #[error("message of {0} bytes is too short for a header")]
+    TooShort(usize),
+
+    /// The message doesn't start with the magic byte.
+    #[error("message starts with {0:#04x} instead of the magic byte")]
+    BadMagic(u8),
+}
+
+impl MessageHeader {
+    /// The first byte of every message.
+    pub const MAGIC: u8 = 0;
+
+    /// The length of a header in bytes.
+    pub const LEN: usize = 9;
+
+    /// Encodes the header, to be written ahead of the payload.
+    pub fn to_bytes(self) -> [u8; Self::LEN] {
+        let mut bytes = [Self::MAGIC; Self::LEN];
+        bytes[1..].copy_from_slice(&self.fingerprint.0.to_be_bytes());
+        bytes
+    }
+
+    /// Splits a message into its header and payload.
+    pub fn split(message: &[u8]) -> Result<(Self, &[u8]), MessageHeaderError> {
+        let (header, payload) = message
+            .split_first_chunk::<{ Self::LEN }>()
+            .ok_or(MessageHeaderError::TooShort(message.len()))?;
+        let (&magic, fingerprint) = header.split_first().expect("headers are not empty");
+        if magic != Self::MAGIC {
+            return Err(MessageHeaderError::BadMagic(magic));
+        }
+        let fingerprint = u64::from_be_bytes(fingerprint.try_into().expect("impossible"));
+        Ok((
+            Self {
+                fingerprint: SchemaFingerprint(fingerprint),
+            },
+            payload,
+        ))
+    }
+}
+
+/// Where the schemas of messages are kept, looked up by their fingerprints.
+///
+/// Implemented for `HashMap<SchemaFingerprint, Schema>`. Implement it to share schemas through
+/// a schema registry, caching fetched schemas locally.
+pub trait SchemaStore {
+    /// Returns the schema with the given fingerprint, if it's known.
+    fn schema(&mut self, fingerprint: SchemaFingerprint) -> Option<&Schema>;
+
+    /// Records a schema which messages are about to be written with.
+    fn register(&mut self, fingerprint: SchemaFingerprint, schema: &Schema);
+
+    /// Deserializes the payload of a message with the schema named by its header.
+    ///
+    /// Returns an error if the schema isn't known.
+    fn decode<'de, DeserializeT, DeserializerT>(
+        &mut self,
+        header: MessageHeader,
+        deserializer: DeserializerT,
+    ) -> Result<DeserializeT, DeserializerT::Error>
+    where
+        Self: Sized,
+        DeserializeT: Deserialize<'de>,
+        DeserializerT: Deserializer<'de>,
+    {
+        let fingerprint = header.fingerprint;
+        self.schema(fingerprint)
+            .ok_or_else(|| {
+                DeserializerT::Error::custom(format_args!("unknown schema {fingerprint}"))
+            })?
+            .deserialize_described(deserializer)
+    }
+}
+
+impl SchemaStore for HashMap<SchemaFingerprint, Schema> {
+    #[inline]
+    fn schema(&mut self, fingerprint: SchemaFingerprint) -> Option<&Schema> {
+        self.get(&fingerprint)
+    }
+
+    #[inline]
+    fn register(&mut self, fingerprint: SchemaFingerprint, schema: &Schema) {
+        self.entry(fingerprint).or_insert_with(|| schema.clone());
+    }
+}
+
+/// Encodes messages, registering their schemas with a [`SchemaStore`].
+///
+/// Values are traced into a schema which widens as new shapes of values are seen; each new
+/// version of the schema is registered before the first message written with it.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{MessageEncoder, MessageHeader, SchemaStore};
+/// use std::collections::HashMap;
+///
+/// let mut encoder = MessageEncoder::new(HashMap::new());
+/// let (header, data) = encoder.encode(&("temperature", 21.5f32))?;
+/// let mut message = header.to_bytes().to_vec();
+/// message.extend(postcard::to_stdvec(&data)?);
+///
+/// // Consumers look up the schema named by the header.
+/// let mut store = encoder.into_store();
+/// let (header, payload) = MessageHeader::split(&message)?;
+/// let value: (String, f32) =
+///     store.decode(header, &mut postcard::Deserializer::from_bytes(payload))?;
+/// assert_eq!(value, ("temperature".to_owned(), 21.5));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct MessageEncoder<StoreT> {
+    store: StoreT,
+    builder: SchemaBuilder,
+    current: Option<(SchemaFingerprint, Schema)>,
+}
+
+impl<StoreT> MessageEncoder<StoreT>
+where
+    StoreT: SchemaStore,
+{
+    /// Creates an encoder which registers schemas with the given store.
+    pub fn new(store: StoreT) -> Self {
+        Self {
+            store,
+            builder: SchemaBuilder::new(),
+            current: None,
+        }
+    }
+
+    /// Traces a value, returning the header and payload of its message.
+    ///
+    /// This rebuilds the schema for every value, so it's best suited to streams of small to
+    /// medium-sized schemas.
+    pub fn encode<ValueT>(
+        &mut self,
+        value: &ValueT,
+    ) -> Result<(MessageHeader, DescribedBy<'_, Trace>), TraceError>
+    where
+        ValueT: Serialize,
+    {
+        let trace = self.builder.trace(value)?;
+        let schema = self.builder.clone().build()?;
+        let fingerprint = schema.fingerprint();
+        if self.current.as_ref().map(|(current, _)| *current) != Some(fingerprint) {
+            self.store.register(fingerprint, &schema);
+            self.current = Some((fingerprint, schema));
+        }
+        let (_, schema) = self.current.as_ref().expect("a schema was registered");
+        Ok((MessageHeader { fingerprint }, schema.describe_trace(trace)))
+    }
+
+    /// The store schemas are registered with.
+    #[inline]
+    pub fn store(&self) -> &StoreT {
+        &self.store
+    }
+
+    /// Returns the store schemas are registered with.
+    #[inline]
+    pub fn into_store(self) -> StoreT {
+        self.store
+    }
+}
@@ -0,0 +1,121 @@
+use std::fmt::Display;
+
+use crate::TraceError;
+
+/// Receives counters from tracing and decoding, e.g. to export them to Prometheus.
+///
+/// Pass a `'static` implementation to [`SchemaBuilder::metrics`][`crate::SchemaBuilder::metrics`]
+/// to observe tracing, and to [`DecodeOptions::metrics`][`crate::DecodeOptions::metrics`] to
+/// observe deserializing described values. Every method does nothing by default, so only the
+/// interesting ones need to be implemented. Methods are called synchronously from the traced or
+/// decoding thread, so they should be cheap, like incrementing atomic counters.
+///
+/// No durations are reported, since reading the clock panics on `wasm32-unknown-unknown`; time
+/// the calls to [`trace`][`crate::SchemaBuilder::trace`] or to `deserialize` instead.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::de::DeserializeSeed;
+/// use serde_describe::{DecodeOptions, Metrics, SchemaBuilder, SelfDescribed};
+/// use std::{
+///     marker::PhantomData,
+///     sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+/// };
+///
+/// #[derive(Default)]
+/// struct Counters {
+///     traced: AtomicU64,
+///     trace_bytes: AtomicUsize,
+///     widened: AtomicU64,
+///     decode_errors: AtomicU64,
+/// }
+///
+/// impl Metrics for Counters {
+///     fn value_traced(&self, trace_bytes: usize) {
+///         self.traced.fetch_add(1, Ordering::Relaxed);
+///         self.trace_bytes.fetch_add(trace_bytes, Ordering::Relaxed);
+///     }
+///
+///     fn schema_widened(&self) {
+///         self.widened.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn decode_failed(&self, _error: &dyn std::fmt::Display) {
+///         self.decode_errors.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// static COUNTERS: Counters = Counters {
+///     traced: AtomicU64::new(0),
+///     trace_bytes: AtomicUsize::new(0),
+///     widened: AtomicU64::new(0),
+///     decode_errors: AtomicU64::new(0),
+/// };
+///
+/// let mut builder = SchemaBuilder::new().metrics(&COUNTERS);
+/// builder.trace(&Some(1u32))?;
+/// builder.trace(&Some(2u32))?;
+/// builder.trace(&None::<u32>)?;
+/// assert_eq!(COUNTERS.traced.load(Ordering::Relaxed), 3);
+/// assert_eq!(COUNTERS.widened.load(Ordering::Relaxed), 2);
+///
+/// let bytes = postcard::to_stdvec(&SelfDescribed("text"))?;
+/// let result = SelfDescribed(PhantomData::<u32>)
+///     .with_options(DecodeOptions::default().metrics(&COUNTERS))
+///     .deserialize(&mut postcard::Deserializer::from_bytes(&bytes));
+/// assert!(result.is_err());
+/// assert_eq!(COUNTERS.decode_errors.load(Ordering::Relaxed), 1);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub trait Metrics: Sync {
+    /// A value was traced, into a trace of `trace_bytes` bytes, which is zero for
+    /// [`trace_schema_only`][`crate::SchemaBuilder::trace_schema_only`].
+    fn value_traced(&self, trace_bytes: usize) {
+        let _ = trace_bytes;
+    }
+
+    /// A traced value changed the schema, e.g. by adding a type or a member to a union. The
+    /// first value traced by a builder always does.
+    fn schema_widened(&self) {}
+
+    /// Tracing a value failed.
+    fn trace_failed(&self, error: &TraceError) {
+        let _ = error;
+    }
+
+    /// A described value was deserialized.
+    fn value_decoded(&self) {}
+
+    /// Deserializing a described value failed, with the format's error.
+    fn decode_failed(&self, error: &dyn Display) {
+        let _ = error;
+    }
+}
+
+/// A [`Metrics`] implementation referenced by options, compared by address.
+#[derive(Copy, Clone)]
+pub(crate) struct StaticMetrics(pub(crate) &'static dyn Metrics);
+
+impl PartialEq for StaticMetrics {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::addr_eq(self.0, other.0)
+    }
+}
+
+impl Eq for StaticMetrics {}
+
+impl std::hash::Hash for StaticMetrics {
+    #[inline]
+    fn hash<HasherT: std::hash::Hasher>(&self, state: &mut HasherT) {
+        std::ptr::hash(self.0 as *const dyn Metrics as *const (), state);
+    }
+}
+
+impl std::fmt::Debug for StaticMetrics {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "Metrics({:p})", self.0 as *const dyn Metrics)
+    }
+}
@@ -0,0 +1,112 @@
+use std::borrow::Cow;
+
+use crate::{Schema, fingerprint::fnv1a};
+
+impl Schema {
+    /// Returns a copy of this schema with every type, variant and field name replaced by its
+    /// 64-bit hash, e.g. to keep schemas small when they name hundreds of types and both ends
+    /// share the Rust types anyway.
+    ///
+    /// Serialized schemas store each hashed name in 8 bytes. Hashes show up as `#` followed by
+    /// sixteen hex digits wherever names would, e.g. when the schema or its traces are displayed
+    /// or converted to [`Dynamic`][`crate::Dynamic`]-s. When deserializing into a Rust type,
+    /// field and variant names are matched by hash against the ones the type expects, so
+    /// decoding works as with the full names. Type names are never needed to decode, though
+    /// [logical types][`crate::LogicalTypes`] and anything else looking types up by name won't
+    /// find them. Hashing an already hashed schema returns it unchanged.
+    ///
+    /// The traces of this schema are also traces of the hashed one, so the original schema
+    /// can be kept as a side table to tell what the hashes stand for, e.g. when debugging.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    /// enum Notification {
+    ///     AccountSuspendedForSuspiciousActivity { account_identifier: u64 },
+    /// }
+    ///
+    /// let value = Notification::AccountSuspendedForSuspiciousActivity { account_identifier: 7 };
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&value)?;
+    /// let schema = builder.build()?;
+    ///
+    /// let hashed = schema.hash_names();
+    /// assert!(postcard::to_stdvec(&hashed)?.len() < postcard::to_stdvec(&schema)?.len());
+    /// assert!(hashed.describe_trace_ref(&trace).to_string().starts_with("#"));
+    ///
+    /// let bytes = postcard::to_stdvec(&hashed.describe_trace(trace))?;
+    /// let decoded: Notification =
+    ///     hashed.deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))?;
+    /// assert_eq!(decoded, value);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn hash_names(&self) -> Schema {
+        if self.hashed_names {
+            return self.clone();
+        }
+        let hash = |name: &Cow<'static, str>| hex_name(name_hash(name));
+        Schema {
+            field_names: self.field_names.map(hash),
+            variant_names: self.variant_names.map(hash),
+            type_names: self.type_names.map(hash),
+            hashed_names: true,
+            ..self.clone()
+        }
+    }
+
+    /// Whether names are replaced by their hashes, see [`Schema::hash_names`].
+    #[inline]
+    pub fn has_hashed_names(&self) -> bool {
+        self.hashed_names
+    }
+
+    /// Returns the name among `expected` whose hash `name` is, if names are hashed, or `name`
+    /// itself otherwise.
+    pub(crate) fn resolve_name<'name>(
+        &self,
+        name: &'name str,
+        expected: Option<&'static [&'static str]>,
+    ) -> &'name str {
+        if !self.hashed_names {
+            return name;
+        }
+        let hash = unhex_name(name);
+        expected
+            .and_then(|expected| {
+                expected
+                    .iter()
+                    .find(|&&expected| name_hash(expected) == hash)
+            })
+            .map_or(name, |&expected| expected)
+    }
+}
+
+/// Hashes a name. The empty name, used by anonymous types, hashes to zero so it stays empty.
+fn name_hash(name: &str) -> u64 {
+    if name.is_empty() {
+        0
+    } else {
+        fnv1a(name.as_bytes())
+    }
+}
+
+/// Formats a hashed name, see [`Schema::hash_names`].
+pub(crate) fn hex_name(hash: u64) -> Cow<'static, str> {
+    if hash == 0 {
+        Cow::Borrowed("")
+    } else {
+        Cow::Owned(format!("#{hash:016x}"))
+    }
+}
+
+/// Parses a name formatted by [`hex_name`], or returns zero if it isn't one.
+pub(crate) fn unhex_name(name: &str) -> u64 {
+    name.strip_prefix('#')
+        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+        .unwrap_or(0)
+}
@@ -0,0 +1,544 @@
+use crate::{
+    LogicalTypes, Metrics, logical::StaticLogicalTypes, metrics::StaticMetrics, schema::SchemaNode,
+};
+
+/// Controls how strictly serialized data is matched against the type it's deserialized into.
+///
+/// The same data may be read by different consumers with different requirements: an archival
+/// tool may want to accept anything that can be made to fit, while a service validating its
+/// inputs may want to reject anything that doesn't match its types exactly.
+///
+/// The default, [`DecodeOptions::new`], decodes data the same way this crate always has: numbers
+/// may be read into other numeric types of the same kind and skipped or unknown fields are
+/// permitted, but no other coercions are. [`DecodeOptions::lenient`] permits all supported
+/// coercions, while [`DecodeOptions::strict`] disables all of them, such that you can selectively
+/// re-enable the ones you need.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize, de::DeserializeSeed};
+/// use serde_describe::{DecodeOptions, SelfDescribed};
+/// use std::marker::PhantomData;
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Narrow {
+///     x: u8,
+/// }
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Wide {
+///     x: u64,
+/// }
+///
+/// let bytes = postcard::to_stdvec(&SelfDescribed(Narrow { x: 10 }))?;
+///
+/// // By default, a `u8` can be read into a `u64`.
+/// let SelfDescribed(wide) = postcard::from_bytes::<SelfDescribed<Wide>>(&bytes)?;
+/// assert_eq!(wide, Wide { x: 10 });
+///
+/// // But not in strict mode.
+/// let strict = SelfDescribed(PhantomData::<Wide>)
+///     .with_options(DecodeOptions::strict())
+///     .deserialize(&mut postcard::Deserializer::from_bytes(&bytes));
+/// assert!(strict.is_err());
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct DecodeOptions {
+    pub(crate) deny_unknown_fields: bool,
+    pub(crate) numeric_coercion: NumericCoercion,
+    pub(crate) default_missing_fields: bool,
+    pub(crate) char_string_coercions: bool,
+    pub(crate) enum_string_coercions: bool,
+    pub(crate) byte_sequence_coercions: bool,
+    pub(crate) enum_representation: EnumRepresentation,
+    pub(crate) logical_types: Option<StaticLogicalTypes>,
+    pub(crate) metrics: Option<StaticMetrics>,
+    pub(crate) max_sequence_length: usize,
+    pub(crate) max_string_length: usize,
+    pub(crate) max_allocation: usize,
+    /// Counts down as values are nested, see [`Self::nested`].
+    pub(crate) max_depth: usize,
+    // Only set by `Schema::deserialize_recovering`, which needs values that fail to deserialize
+    // to be skipped, such that it can carry on with the next element.
+    pub(crate) skip_failed_values: bool,
+}
+
+impl DecodeOptions {
+    /// The default options, which decode data the same way as
+    /// [`SelfDescribed`][`crate::SelfDescribed`] and [`DescribedBy`][`crate::DescribedBy`] did
+    /// before options existed.
+    ///
+    /// Numbers are coerced as per [`NumericCoercion::Lossy`], skipped fields are left for the
+    /// deserialized type to default and unknown fields are passed on to it, but all other
+    /// coercions are disabled.
+    pub const fn new() -> Self {
+        Self {
+            deny_unknown_fields: false,
+            numeric_coercion: NumericCoercion::Lossy,
+            default_missing_fields: true,
+            char_string_coercions: false,
+            enum_string_coercions: false,
+            byte_sequence_coercions: false,
+            enum_representation: EnumRepresentation::Untagged,
+            logical_types: None,
+            metrics: None,
+            max_sequence_length: usize::MAX,
+            max_string_length: usize::MAX,
+            max_allocation: usize::MAX,
+            max_depth: usize::MAX,
+            skip_failed_values: false,
+        }
+    }
+
+    /// Options permitting all supported coercions, except for
+    /// [`enum_string_coercions`][`Self::enum_string_coercions`], which must be enabled
+    /// explicitly.
+    pub const fn lenient() -> Self {
+        Self {
+            deny_unknown_fields: false,
+            numeric_coercion: NumericCoercion::Checked,
+            default_missing_fields: true,
+            char_string_coercions: true,
+            enum_string_coercions: false,
+            byte_sequence_coercions: true,
+            enum_representation: EnumRepresentation::Untagged,
+            logical_types: None,
+            metrics: None,
+            max_sequence_length: usize::MAX,
+            max_string_length: usize::MAX,
+            max_allocation: usize::MAX,
+            max_depth: usize::MAX,
+            skip_failed_values: false,
+        }
+    }
+
+    /// Options which require the serialized data to match the deserialized type exactly.
+    ///
+    /// That includes [`default_missing_fields(false)`][`Self::default_missing_fields`], so a
+    /// skipped field is an error even if the deserialized type marks it `#[serde(default)]`.
+    /// Re-enable it for types whose producers skip such fields.
+    pub const fn strict() -> Self {
+        Self {
+            deny_unknown_fields: true,
+            numeric_coercion: NumericCoercion::Exact,
+            default_missing_fields: false,
+            char_string_coercions: false,
+            enum_string_coercions: false,
+            byte_sequence_coercions: false,
+            enum_representation: EnumRepresentation::Untagged,
+            logical_types: None,
+            metrics: None,
+            max_sequence_length: usize::MAX,
+            max_string_length: usize::MAX,
+            max_allocation: usize::MAX,
+            max_depth: usize::MAX,
+            skip_failed_values: false,
+        }
+    }
+
+    /// Whether struct fields present in the data but not declared by the deserialized type are
+    /// an error, rather than being passed on to the type's `Deserialize` implementation.
+    ///
+    /// Only applies when the deserialized type declares its fields, which is the case for structs
+    /// and struct variants, but not for maps or `#[serde(flatten)]`-ed structs.
+    pub const fn deny_unknown_fields(mut self, deny: bool) -> Self {
+        self.deny_unknown_fields = deny;
+        self
+    }
+
+    /// Which conversions are permitted when numbers are deserialized into a different numeric type
+    /// than the one they were serialized as (e.g. `u8` into `u64` or `f64` into `f32`).
+    pub const fn numeric_coercion(mut self, coercion: NumericCoercion) -> Self {
+        self.numeric_coercion = coercion;
+        self
+    }
+
+    /// Whether fields skipped at serialization time (e.g. via `skip_serializing_if`) are left for
+    /// the deserialized type to default. If `false`, such fields are a `missing field` error.
+    ///
+    /// Disabling this catches producers which stopped sending a field, which otherwise goes
+    /// unnoticed for `Option` fields, since `serde` deserializes missing `Option`-s as `None`.
+    /// Deserializers aren't told which fields are `#[serde(default)]`, so those are an error
+    /// too when skipped; only fields the deserialized type doesn't declare are exempt.
+    pub const fn default_missing_fields(mut self, allow: bool) -> Self {
+        self.default_missing_fields = allow;
+        self
+    }
+
+    /// Whether a `char` may be deserialized as a string and a single-character string may be
+    /// deserialized as a `char`.
+    pub const fn char_string_coercions(mut self, allow: bool) -> Self {
+        self.char_string_coercions = allow;
+        self
+    }
+
+    /// Whether a string may be deserialized as a unit variant of an enum with the same name and,
+    /// conversely, whether a unit variant may be deserialized as a string containing its name.
+    ///
+    /// Useful for tightening the type of a field that used to be a free-form string tag. Off by
+    /// default, and not enabled by [`lenient`][`Self::lenient`] either, since it changes which
+    /// variant an untagged enum or a dynamic value decodes to.
+    pub const fn enum_string_coercions(mut self, allow: bool) -> Self {
+        self.enum_string_coercions = allow;
+        self
+    }
+
+    /// Whether a byte array may be deserialized as a sequence of `u8`-s, e.g. into a `Vec<u8>`
+    /// which isn't annotated with `serde_bytes`.
+    ///
+    /// Needed to read back sequences recorded as byte arrays by
+    /// [`SchemaBuilder::coerce_byte_sequences`][`crate::SchemaBuilder::coerce_byte_sequences`].
+    pub const fn byte_sequence_coercions(mut self, allow: bool) -> Self {
+        self.byte_sequence_coercions = allow;
+        self
+    }
+
+    /// How enum variants are presented to types which deserialize whatever they're given (via
+    /// `deserialize_any`), e.g. dynamic values such as `serde_json::Value`, or serde's internally
+    /// tagged and untagged enums. Untagged by default.
+    ///
+    /// Used to re-serialize data into human-readable formats, or to deserialize it into types
+    /// with a different enum representation than the traced ones, matching existing API
+    /// contracts.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::{Deserialize, Serialize, de::DeserializeSeed};
+    /// use serde_describe::{DecodeOptions, EnumRepresentation, SchemaBuilder};
+    ///
+    /// #[derive(Serialize)]
+    /// enum Shape {
+    ///     Circle { radius: u32 },
+    ///     Empty,
+    /// }
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// #[serde(tag = "type")]
+    /// enum ApiShape {
+    ///     Circle { radius: u32 },
+    ///     Empty,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&[Shape::Circle { radius: 2 }, Shape::Empty])?;
+    /// let schema = builder.build()?;
+    /// let bytes = postcard::to_stdvec(&schema.describe_trace(trace))?;
+    ///
+    /// let options = DecodeOptions::new()
+    ///     .enum_representation(EnumRepresentation::Internal { tag: "type" });
+    /// let shapes = schema
+    ///     .describe_type::<[ApiShape; 2]>()
+    ///     .with_options(options)
+    ///     .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))?
+    ///     .0;
+    /// assert_eq!(shapes, [ApiShape::Circle { radius: 2 }, ApiShape::Empty]);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn enum_representation(mut self, representation: EnumRepresentation) -> Self {
+        self.enum_representation = representation;
+        self
+    }
+
+    /// The registry of logical types whose stored values are converted back when they're
+    /// deserialized, see [`LogicalTypes`]. None by default, in which case the stored values are
+    /// deserialized as-is.
+    ///
+    /// Takes a `'static` registry, e.g. a `static` initialized with
+    /// [`LazyLock`][`std::sync::LazyLock`], such that options remain cheap to copy.
+    pub const fn logical_types(mut self, types: &'static LogicalTypes) -> Self {
+        self.logical_types = Some(StaticLogicalTypes(types));
+        self
+    }
+
+    /// Where to report each described value which is deserialized, or fails to, see
+    /// [`Metrics`]. None by default.
+    ///
+    /// Takes a `'static` implementation, like [`logical_types`][`Self::logical_types`].
+    pub const fn metrics(mut self, metrics: &'static dyn Metrics) -> Self {
+        self.metrics = Some(StaticMetrics(metrics));
+        self
+    }
+
+    /// The maximum number of elements in a sequence, or entries in a map, handed to the
+    /// deserialized type. Longer sequences are an error. Unlimited by default.
+    ///
+    /// Also caps the size hints passed on to the deserialized type, which commonly uses them to
+    /// preallocate, so a corrupted length prefix doesn't cause a huge allocation up front.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::de::DeserializeSeed;
+    /// use serde_describe::{DecodeOptions, SelfDescribed};
+    /// use std::marker::PhantomData;
+    ///
+    /// let bytes = postcard::to_stdvec(&SelfDescribed(vec![1u32, 2, 3]))?;
+    /// let limited = SelfDescribed(PhantomData::<Vec<u32>>)
+    ///     .with_options(DecodeOptions::new().max_sequence_length(2))
+    ///     .deserialize(&mut postcard::Deserializer::from_bytes(&bytes));
+    /// assert!(limited.is_err());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn max_sequence_length(mut self, max: usize) -> Self {
+        self.max_sequence_length = max;
+        self
+    }
+
+    /// The maximum length in bytes of a string or byte string handed to the deserialized type.
+    /// Longer strings are an error. Unlimited by default.
+    ///
+    /// Strings are requested from the underlying format as borrowed, so formats reading from a
+    /// slice, like `postcard::from_bytes`, don't allocate them at all. Formats reading from a
+    /// stream may still buffer a string before its length is checked.
+    pub const fn max_string_length(mut self, max: usize) -> Self {
+        self.max_string_length = max;
+        self
+    }
+
+    /// The maximum total length in bytes of all the strings and byte strings handed to the
+    /// deserialized type while deserializing a described value. Exceeding it is an error.
+    /// Unlimited by default.
+    ///
+    /// Unlike [`max_string_length`][`Self::max_string_length`], this bounds how much a value
+    /// made of many short strings can allocate. Together with
+    /// [`max_sequence_length`][`Self::max_sequence_length`] and [`max_depth`][`Self::max_depth`],
+    /// it bounds the memory a hostile value can make the deserialized type allocate.
+    ///
+    /// The budget is kept per thread while a value is deserialized, since `serde` gives
+    /// deserializers no way to share state across the parts of a value. Values deserialized
+    /// inside the value on the same thread, e.g. by a `Deserialize` implementation which decodes
+    /// an [`Embedded`][`crate::Embedded`] or another described value, are charged to it too.
+    /// Anything deserialized on another thread, e.g. work handed to a thread pool by a
+    /// `Deserialize` implementation, isn't charged.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::de::DeserializeSeed;
+    /// use serde_describe::{DecodeOptions, SelfDescribed};
+    /// use std::marker::PhantomData;
+    ///
+    /// let bytes = postcard::to_stdvec(&SelfDescribed(vec!["abc"; 4]))?;
+    /// let decode = |options| {
+    ///     SelfDescribed(PhantomData::<Vec<String>>)
+    ///         .with_options(options)
+    ///         .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+    /// };
+    /// assert!(decode(DecodeOptions::new().max_allocation(12)).is_ok());
+    /// assert!(decode(DecodeOptions::new().max_allocation(11)).is_err());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn max_allocation(mut self, max: usize) -> Self {
+        self.max_allocation = max;
+        self
+    }
+
+    /// The maximum nesting depth of deserialized values, counting every container, option,
+    /// newtype and union level, including the root. Deeper values are an error. Unlimited by
+    /// default.
+    ///
+    /// Deserializing recurses once per level, so a hostile schema nesting a few hundred thousand
+    /// levels could otherwise overflow the stack. A limit of a few hundred is plenty for most
+    /// types.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::de::DeserializeSeed;
+    /// use serde_describe::{DecodeOptions, SelfDescribed};
+    /// use std::marker::PhantomData;
+    ///
+    /// let bytes = postcard::to_stdvec(&SelfDescribed(vec![vec![Some(1u8)]]))?;
+    /// let decode = |options| {
+    ///     SelfDescribed(PhantomData::<Vec<Vec<Option<u8>>>>)
+    ///         .with_options(options)
+    ///         .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+    /// };
+    /// assert!(decode(DecodeOptions::new().max_depth(4)).is_ok());
+    /// assert!(decode(DecodeOptions::new().max_depth(3)).is_err());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub const fn max_depth(mut self, max: usize) -> Self {
+        self.max_depth = max;
+        self
+    }
+
+    /// The options for a value nested one level deeper, or an error if that's too deep.
+    pub(crate) fn nested<ErrorT>(mut self) -> Result<Self, ErrorT>
+    where
+        ErrorT: serde::de::Error,
+    {
+        self.max_depth = self
+            .max_depth
+            .checked_sub(1)
+            .ok_or_else(|| ErrorT::custom("value nested deeper than the maximum depth"))?;
+        Ok(self)
+    }
+}
+
+impl Default for DecodeOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Conversions permitted between numeric types at deserialization time, see
+/// [`DecodeOptions::numeric_coercion`].
+///
+/// Integers and floats are never converted into one another.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum NumericCoercion {
+    /// Numbers must be deserialized as precisely the type they were serialized as.
+    Exact,
+
+    /// Numbers may be deserialized into any type that can represent every value of the
+    /// serialized type: e.g. `u8` into `u16` or `i32`, and `f32` into `f64`, but not `i8` into
+    /// `u64`.
+    Widening,
+
+    /// Numbers may be deserialized into any type of the same kind (integer or float), as long as
+    /// the particular value is in range for the type. Out of range values are an error.
+    ///
+    /// Floats are only range checked: a finite `f64` which overflows `f32` is an error, but
+    /// precision may be lost.
+    Checked,
+
+    /// Like [`Checked`][`Self::Checked`], except that a finite `f64` which overflows `f32` is
+    /// rounded to infinity, rather than being an error. This is the default, see
+    /// [`DecodeOptions::new`].
+    Lossy,
+}
+
+impl NumericCoercion {
+    pub(crate) fn permits(self, from: SchemaNode, into: SchemaNode) -> bool {
+        match self {
+            _ if from == into => true,
+            Self::Exact => false,
+            Self::Checked | Self::Lossy => true,
+            Self::Widening => match (numeric_range(from), numeric_range(into)) {
+                (Some((from_signed, from_bits)), Some((into_signed, into_bits))) => {
+                    match (from_signed, into_signed) {
+                        (false, false) | (true, true) => from_bits <= into_bits,
+                        (false, true) => from_bits < into_bits,
+                        (true, false) => false,
+                    }
+                }
+                _ => matches!((from, into), (SchemaNode::F32, SchemaNode::F64)),
+            },
+        }
+    }
+}
+
+/// How enum variants are presented to `deserialize_any`, see
+/// [`DecodeOptions::enum_representation`].
+///
+/// Mirrors the enum representations supported by serde's derive macros.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum EnumRepresentation {
+    /// Only the content of a variant is presented, its name is dropped, like
+    /// `#[serde(untagged)]`. This is the default.
+    #[default]
+    Untagged,
+
+    /// `{"Variant": content}`, with unit variants presented as the string `"Variant"`. This is
+    /// serde's default representation.
+    External,
+
+    /// `{"type": "Variant", ..}`, with the fields of the variant following the tag, like
+    /// `#[serde(tag = "type")]`.
+    ///
+    /// Like in serde, only unit variants, struct variants and newtype variants of structs or
+    /// maps can be internally tagged. Other variants are an error.
+    Internal {
+        /// The key of the variant's name.
+        tag: &'static str,
+    },
+
+    /// `{"t": "Variant", "c": content}`, with the content omitted for unit variants, like
+    /// `#[serde(tag = "t", content = "c")]`.
+    Adjacent {
+        /// The key of the variant's name.
+        tag: &'static str,
+        /// The key of the variant's content.
+        content: &'static str,
+    },
+}
+
+/// Returns `(signed, bits)` for integer nodes.
+fn numeric_range(node: SchemaNode) -> Option<(bool, u32)> {
+    Some(match node {
+        SchemaNode::I8 => (true, 8),
+        SchemaNode::I16 => (true, 16),
+        SchemaNode::I32 => (true, 32),
+        SchemaNode::I64 => (true, 64),
+        SchemaNode::I128 => (true, 128),
+        SchemaNode::U8 => (false, 8),
+        SchemaNode::U16 => (false, 16),
+        SchemaNode::U32 => (false, 32),
+        SchemaNode::U64 => (false, 64),
+        SchemaNode::U128 => (false, 128),
+        _ => return None,
+    })
+}
+
+/// How floats are recorded when tracing values, see [`SchemaBuilder::float_normalization`].
+///
+/// NaNs may carry arbitrary sign and payload bits depending on how they were computed, and `-0.0`
+/// compares equal to `0.0`, so values which compare equal may be serialized differently. This
+/// matters when serialized data is hashed or compared byte by byte.
+///
+/// [`SchemaBuilder::float_normalization`]: crate::SchemaBuilder::float_normalization
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum FloatNormalization {
+    /// Floats are recorded bit for bit. This is the default.
+    #[default]
+    Preserve,
+
+    /// Every NaN is recorded as the same positive quiet NaN and `-0.0` is recorded as `0.0`.
+    Canonical,
+
+    /// NaNs are an error and `-0.0` is recorded as `0.0`.
+    RejectNan,
+}
+
+impl FloatNormalization {
+    /// Returns the value to record, or `None` if it's rejected.
+    pub(crate) fn normalize_f32(self, value: f32) -> Option<f32> {
+        match self {
+            Self::Preserve => Some(value),
+            Self::Canonical if value.is_nan() => Some(f32::NAN),
+            Self::RejectNan if value.is_nan() => None,
+            Self::Canonical | Self::RejectNan => Some(if value == 0.0 { 0.0 } else { value }),
+        }
+    }
+
+    /// Returns the value to record, or `None` if it's rejected.
+    pub(crate) fn normalize_f64(self, value: f64) -> Option<f64> {
+        match self {
+            Self::Preserve => Some(value),
+            Self::Canonical if value.is_nan() => Some(f64::NAN),
+            Self::RejectNan if value.is_nan() => None,
+            Self::Canonical | Self::RejectNan => Some(if value == 0.0 { 0.0 } else { value }),
+        }
+    }
+}
+
+/// Wraps a [`SelfDescribed`][`crate::SelfDescribed`] or [`DescribedBy`][`crate::DescribedBy`]
+/// seed to be deserialized with custom [`DecodeOptions`].
+///
+/// Most conveniently constructed via [`SelfDescribed::with_options`][`crate::SelfDescribed::with_options`]
+/// or [`DescribedBy::with_options`][`crate::DescribedBy::with_options`].
+#[derive(Copy, Clone, Debug)]
+pub struct WithOptions<T>(pub T, pub DecodeOptions);
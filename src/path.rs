@@ -0,0 +1,254 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error as _};
+use std::{fmt::Write as _, str::FromStr};
+use thiserror::Error;
+
+/// A path to a value nested inside another, e.g. `.items[2].name`.
+///
+/// Paths use the same `jq`-like syntax as [`TraceChange::path`][`crate::TraceChange::path`] and
+/// [`SerdeCall::path`][`crate::SerdeCall::path`], so their paths can be parsed into a
+/// [`FieldPath`] to be inspected or compared:
+///
+/// | Segment                  | Syntax            | Example          |
+/// |--------------------------|-------------------|------------------|
+/// | [`PathSegment::Field`]   | `.name`           | `.items`, `.0`   |
+/// | [`PathSegment::Index`]   | `[index]`         | `[2]`            |
+/// | [`PathSegment::Key`]     | `[key]`           | `["id"]`         |
+/// | [`PathSegment::Variant`] | `::Variant`       | `::Circle`       |
+///
+/// The items of tuples are fields named by their position. Map keys are written the way the key
+/// is [displayed][`crate::DescribedBy`], so string keys are quoted; integer keys are parsed as
+/// indices. The empty path, which refers to the root value, is written as `.`.
+///
+/// Paths serialize as strings.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{FieldPath, PathSegment};
+///
+/// let path: FieldPath = r#".shapes[1]::Circle.0["key"]"#.parse()?;
+/// assert_eq!(
+///     path.segments(),
+///     [
+///         PathSegment::Field("shapes".into()),
+///         PathSegment::Index(1),
+///         PathSegment::Variant("Circle".into()),
+///         PathSegment::Field("0".into()),
+///         PathSegment::Key(r#""key""#.into()),
+///     ],
+/// );
+/// assert_eq!(path.to_string(), r#".shapes[1]::Circle.0["key"]"#);
+///
+/// let parent = path.parent().unwrap();
+/// assert!(path.starts_with(&parent));
+/// assert_eq!(parent.to_string(), ".shapes[1]::Circle.0");
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct FieldPath {
+    segments: Vec<PathSegment>,
+}
+
+/// A segment of a [`FieldPath`].
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum PathSegment {
+    /// A field of a struct, or an item of a tuple, named by its position.
+    Field(Box<str>),
+
+    /// An item of a sequence.
+    Index(usize),
+
+    /// The value of a map entry, named by its displayed key.
+    Key(Box<str>),
+
+    /// The content of an enum variant.
+    Variant(Box<str>),
+}
+
+/// Returned when parsing an invalid [`FieldPath`].
+#[derive(Clone, Debug, Error)]
+#[error("invalid path {path:?} at byte {position}: {reason}")]
+pub struct FieldPathError {
+    path: Box<str>,
+    position: usize,
+    reason: &'static str,
+}
+
+impl FieldPath {
+    /// The path to the root value.
+    #[inline]
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// The segments of the path, outermost first.
+    #[inline]
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    /// Whether this is the path to the root value.
+    #[inline]
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Appends a segment to the path.
+    pub fn push(&mut self, segment: PathSegment) {
+        self.segments.push(segment);
+    }
+
+    /// Inserts a segment at the start of the path, e.g. while unwinding out of a nested value.
+    pub(crate) fn push_front(&mut self, segment: PathSegment) {
+        self.segments.insert(0, segment);
+    }
+
+    /// Returns the path extended with a segment.
+    pub fn join(mut self, segment: PathSegment) -> Self {
+        self.push(segment);
+        self
+    }
+
+    /// The path to the value containing this one, or `None` for the root.
+    pub fn parent(&self) -> Option<Self> {
+        let (_, parent) = self.segments.split_last()?;
+        Some(Self {
+            segments: parent.to_vec(),
+        })
+    }
+
+    /// Whether this path is `prefix`, or a path nested inside it.
+    pub fn starts_with(&self, prefix: &FieldPath) -> bool {
+        self.segments.starts_with(&prefix.segments)
+    }
+}
+
+impl From<Vec<PathSegment>> for FieldPath {
+    #[inline]
+    fn from(segments: Vec<PathSegment>) -> Self {
+        Self { segments }
+    }
+}
+
+impl FromIterator<PathSegment> for FieldPath {
+    fn from_iter<IterT>(segments: IterT) -> Self
+    where
+        IterT: IntoIterator<Item = PathSegment>,
+    {
+        Self {
+            segments: segments.into_iter().collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for FieldPath {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !matches!(self.segments.first(), Some(PathSegment::Field(_))) {
+            formatter.write_char('.')?;
+        }
+        for segment in &self.segments {
+            match segment {
+                PathSegment::Field(name) => write!(formatter, ".{name}")?,
+                PathSegment::Index(index) => write!(formatter, "[{index}]")?,
+                PathSegment::Key(key) => write!(formatter, "[{key}]")?,
+                PathSegment::Variant(variant) => write!(formatter, "::{variant}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for FieldPath {
+    type Err = FieldPathError;
+
+    fn from_str(path: &str) -> Result<Self, FieldPathError> {
+        let error = |rest: &str, reason| FieldPathError {
+            path: path.into(),
+            position: path.len() - rest.len(),
+            reason,
+        };
+        let mut segments = Vec::new();
+        let mut rest = path;
+        if let Some(after_dot) = rest.strip_prefix('.')
+            && (after_dot.is_empty() || after_dot.starts_with(['[', ':']))
+        {
+            rest = after_dot;
+        }
+        while !rest.is_empty() {
+            if let Some(after_dot) = rest.strip_prefix('.') {
+                let (name, after_name) = split_name(after_dot);
+                if name.is_empty() {
+                    return Err(error(after_dot, "expected a field name"));
+                }
+                segments.push(PathSegment::Field(name.into()));
+                rest = after_name;
+            } else if let Some(after_colons) = rest.strip_prefix("::") {
+                let (name, after_name) = split_name(after_colons);
+                if name.is_empty() {
+                    return Err(error(after_colons, "expected a variant name"));
+                }
+                segments.push(PathSegment::Variant(name.into()));
+                rest = after_name;
+            } else if let Some(after_bracket) = rest.strip_prefix('[') {
+                let end = find_closing_bracket(after_bracket)
+                    .ok_or_else(|| error(rest, "unclosed `[`"))?;
+                let key = &after_bracket[..end];
+                if key.is_empty() {
+                    return Err(error(after_bracket, "expected an index or key"));
+                }
+                segments.push(match key.parse() {
+                    Ok(index) => PathSegment::Index(index),
+                    Err(_) => PathSegment::Key(key.into()),
+                });
+                rest = &after_bracket[end + 1..];
+            } else {
+                return Err(error(rest, "expected `.`, `[` or `::`"));
+            }
+        }
+        Ok(Self { segments })
+    }
+}
+
+/// Splits a field or variant name from the rest of a path, at the start of the next segment.
+fn split_name(rest: &str) -> (&str, &str) {
+    rest.split_at(rest.find(['.', '[', ':']).unwrap_or(rest.len()))
+}
+
+/// Finds the `]` closing a key, skipping over any quoted strings inside it.
+fn find_closing_bracket(key: &str) -> Option<usize> {
+    let mut quoted = None;
+    let mut escaped = false;
+    for (position, character) in key.char_indices() {
+        match (quoted, character) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(quote), _) if character == quote => quoted = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quoted = Some(character),
+            (None, ']') => return Some(position),
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+impl Serialize for FieldPath {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldPath {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
@@ -0,0 +1,269 @@
+use serde::{
+    Deserialize, Deserializer,
+    de::{DeserializeSeed, Error as _},
+};
+use std::{marker::PhantomData, ops::Deref, sync::OnceLock};
+
+use crate::{
+    DecodeOptions, Schema,
+    de::SchemaDeserializer,
+    indices::{FieldNameListIndex, VariantNameIndex},
+    limits::AllocationBudget,
+    metrics::StaticMetrics,
+};
+
+/// A schema prepared to decode many values into the same type `T`, e.g. the rows of a large
+/// file, which resolves the names in the schema against the names `T` expects once, rather than
+/// for every value.
+///
+/// Decoding a value without a plan matches each of its field and variant names against the ones
+/// the target type expects. That's cheap for most schemas, but not for schemas with
+/// [hashed names][`Schema::hash_names`], where each name is matched by hashing the expected
+/// ones, nor with [`DecodeOptions::deny_unknown_fields`] or without
+/// [`DecodeOptions::default_missing_fields`], where each field is looked up among the expected
+/// ones. A plan remembers where each name of the schema is among the expected names of each
+/// struct and enum it's decoded into, the first time it is, and looks them up after that.
+///
+/// Only names are planned. Which member of a union a value is decoded as, and any coercions
+/// between primitive types, are still resolved for every value, as without a plan.
+///
+/// Plans can be shared between threads. The results are the same as decoding with
+/// [`Schema::describe_type`] and the plan's options.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_describe::{DecodeOptions, DecodePlan, SchemaBuilder};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct Row {
+///     id: u64,
+///     name: String,
+/// }
+///
+/// let rows = (0..100).map(|id| Row { id, name: format!("row {id}") }).collect::<Vec<_>>();
+/// let mut builder = SchemaBuilder::new();
+/// let traces = rows
+///     .iter()
+///     .map(|row| builder.trace(row))
+///     .collect::<Result<Vec<_>, _>>()?;
+/// let schema = builder.build()?.hash_names();
+///
+/// let plan = DecodePlan::<Row>::compile(&schema).options(DecodeOptions::strict());
+/// for (trace, row) in traces.into_iter().zip(&rows) {
+///     let bytes = postcard::to_stdvec(&schema.describe_trace(trace))?;
+///     let decoded = plan.deserialize(&mut postcard::Deserializer::from_bytes(&bytes))?;
+///     assert_eq!(&decoded, row);
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct DecodePlan<'schema, T> {
+    schema: &'schema Schema,
+    options: DecodeOptions,
+    resolved: ResolvedNames,
+    phantom: PhantomData<fn() -> T>,
+}
+
+impl<'schema, T> DecodePlan<'schema, T> {
+    /// Prepares to decode values described by `schema` into `T`s, with the default
+    /// [`DecodeOptions`].
+    pub fn compile(schema: &'schema Schema) -> Self {
+        Self {
+            schema,
+            options: DecodeOptions::default(),
+            resolved: ResolvedNames {
+                fields: std::iter::repeat_with(OnceLock::new)
+                    .take(schema.field_name_lists.len())
+                    .collect(),
+                variants: std::iter::repeat_with(OnceLock::new)
+                    .take(schema.variant_names.len())
+                    .collect(),
+            },
+            phantom: PhantomData,
+        }
+    }
+
+    /// Decodes values with the given options rather than the default ones.
+    #[inline]
+    pub fn options(mut self, options: DecodeOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The schema the plan decodes values of.
+    #[inline]
+    pub fn schema(&self) -> &'schema Schema {
+        self.schema
+    }
+
+    /// Decodes a value described by the plan's schema.
+    #[inline]
+    pub fn deserialize<'de, DeserializerT>(
+        &self,
+        deserializer: DeserializerT,
+    ) -> Result<T, DeserializerT::Error>
+    where
+        T: Deserialize<'de>,
+        DeserializerT: Deserializer<'de>,
+    {
+        DeserializeSeed::deserialize(self, deserializer)
+    }
+}
+
+impl<'de, T> DeserializeSeed<'de> for &DecodePlan<'_, T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = T;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let _budget = AllocationBudget::enter(self.options.max_allocation);
+        let schema = PlannedSchema {
+            schema: self.schema,
+            resolved: Some(&self.resolved),
+        };
+        let result = schema
+            .node(schema.root_index)
+            .map_err(D::Error::custom)
+            .and_then(|node| {
+                SchemaDeserializer::new(schema, node, self.options, PhantomData::<T>)
+                    .deserialize(deserializer)
+            });
+        if let Some(StaticMetrics(metrics)) = self.options.metrics {
+            match &result {
+                Ok(_) => metrics.value_decoded(),
+                Err(error) => metrics.decode_failed(error),
+            }
+        }
+        result
+    }
+}
+
+/// A schema, together with the names resolved by a [`DecodePlan`], if decoding with one.
+#[derive(Copy, Clone)]
+pub(crate) struct PlannedSchema<'schema> {
+    schema: &'schema Schema,
+    resolved: Option<&'schema ResolvedNames>,
+}
+
+impl<'schema> PlannedSchema<'schema> {
+    /// The schema itself, for the lifetime of the deserializer.
+    #[inline]
+    pub(crate) fn schema(self) -> &'schema Schema {
+        self.schema
+    }
+
+    /// The positions among `expected` of the fields in a list, each `None` if the field isn't
+    /// expected, or `None` altogether if there's no plan, or the list was resolved against
+    /// different names.
+    #[inline]
+    pub(crate) fn resolved_fields(
+        self,
+        list: FieldNameListIndex,
+        expected: &'static [&'static str],
+    ) -> Option<&'schema [Option<u32>]> {
+        let resolved = self
+            .resolved?
+            .fields
+            .get(usize::from(list))?
+            .get_or_init(|| Resolved {
+                expected,
+                positions: self.schema.field_name_list(list).map_or_else(
+                    |_| Box::default(),
+                    |names| {
+                        names
+                            .iter()
+                            .map(|&name| {
+                                let name = self.schema.field_name(name).ok()?;
+                                position(expected, self.schema.resolve_name(name, Some(expected)))
+                            })
+                            .collect()
+                    },
+                ),
+            });
+        same_names(resolved.expected, expected).then_some(&*resolved.positions)
+    }
+
+    /// The name among `expected` which a variant name resolves to, like
+    /// [`Schema::resolve_name`], or `None` if there's no plan, the variant isn't expected, or its
+    /// name was resolved against different names.
+    #[inline]
+    pub(crate) fn resolved_variant(
+        self,
+        variant: VariantNameIndex,
+        expected: &'static [&'static str],
+    ) -> Option<&'static str> {
+        let resolved = self
+            .resolved?
+            .variants
+            .get(usize::from(variant))?
+            .get_or_init(|| Resolved {
+                expected,
+                positions: self.schema.variant_name(variant).ok().and_then(|name| {
+                    position(expected, self.schema.resolve_name(name, Some(expected)))
+                }),
+            });
+        let position = resolved.positions?;
+        same_names(resolved.expected, expected)
+            .then(|| expected[usize::try_from(position).expect("usize must be at least 32 bits")])
+    }
+}
+
+impl<'schema> From<&'schema Schema> for PlannedSchema<'schema> {
+    #[inline]
+    fn from(schema: &'schema Schema) -> Self {
+        Self {
+            schema,
+            resolved: None,
+        }
+    }
+}
+
+impl Deref for PlannedSchema<'_> {
+    type Target = Schema;
+
+    #[inline]
+    fn deref(&self) -> &Schema {
+        self.schema
+    }
+}
+
+/// The names of a schema resolved against the names expected by the types decoded with a
+/// [`DecodePlan`], by the index of their field name list or variant name.
+struct ResolvedNames {
+    fields: Box<[OnceLock<Resolved<FieldPositions>>]>,
+    variants: Box<[OnceLock<Resolved<Option<u32>>>]>,
+}
+
+/// The positions of the fields of a list among the expected ones, see
+/// [`PlannedSchema::resolved_fields`].
+type FieldPositions = Box<[Option<u32>]>;
+
+/// Where names are among the names a type expects. Only the first type a name is decoded into
+/// is remembered, since it's rare for several types to share names with different expectations.
+struct Resolved<PositionsT> {
+    expected: &'static [&'static str],
+    positions: PositionsT,
+}
+
+/// Whether two lists of expected names are the same. The lists of a type are usually at the same
+/// address, but not always, since constants may be duplicated across codegen units, so the names
+/// are compared when the addresses differ.
+pub(crate) fn same_names(left: &[&str], right: &[&str]) -> bool {
+    left.len() == right.len()
+        && (std::ptr::eq(left, right)
+            || left
+                .iter()
+                .zip(right)
+                .all(|(&left, &right)| std::ptr::eq(left, right) || left == right))
+}
+
+fn position(expected: &[&str], name: &str) -> Option<u32> {
+    let position = expected.iter().position(|&expected| expected == name)?;
+    u32::try_from(position).ok()
+}
@@ -1,12 +1,19 @@
 use indexmap::IndexSet;
 use serde::{Deserialize, Serialize};
-use std::{borrow::Borrow, hash::Hash, marker::PhantomData};
+use std::{
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
 
-use crate::indices::{IndexIsEmpty, IsEmpty};
+use crate::{
+    fingerprint::{FNV1A_OFFSET_BASIS, fnv1a_extend},
+    indices::{IndexIsEmpty, IsEmpty},
+};
 
 #[derive(Clone)]
 pub(crate) struct Pool<ValueT, ValueIndexT> {
-    inner: IndexSet<ValueT>,
+    inner: IndexSet<Fingerprinted<ValueT>>,
     _dummy: PhantomData<ValueIndexT>,
 }
 
@@ -20,6 +27,14 @@ impl<ValueT, ValueIndexT> Default for Pool<ValueT, ValueIndexT> {
     }
 }
 
+impl<ValueT, ValueIndexT> Pool<ValueT, ValueIndexT> {
+    /// Reserves room for at least `additional` more values.
+    #[inline]
+    pub(crate) fn reserve(&mut self, additional: usize) {
+        self.inner.reserve(additional);
+    }
+}
+
 impl<ValueT, ValueIndexT> Pool<ValueT, ValueIndexT>
 where
     ValueT: Hash + Eq + IsEmpty,
@@ -29,6 +44,7 @@ where
         if value.is_empty() {
             Ok(ValueIndexT::EMPTY)
         } else {
+            let value = Fingerprinted::new(value);
             ValueIndexT::try_from(self.inner.insert_full(value).0 + 1)
         }
     }
@@ -44,11 +60,98 @@ where
         if value.is_empty() {
             Ok(ValueIndexT::EMPTY)
         } else {
-            ValueIndexT::try_from(self.inner.insert_full(value.into()).0 + 1)
+            let value = Fingerprinted::new(value.into());
+            ValueIndexT::try_from(self.inner.insert_full(value).0 + 1)
+        }
+    }
+
+    #[inline]
+    pub(crate) fn get(&self, index: ValueIndexT) -> Option<&ValueT::Borrowed>
+    where
+        ValueIndexT: IsEmpty + Into<usize>,
+    {
+        if index.is_empty() {
+            Some(ValueT::BORROWED_EMPTY)
+        } else {
+            self.inner
+                .get_index(index.into() - 1)
+                .map(|entry| entry.value.borrow())
+        }
+    }
+}
+
+/// A value in a [`Pool`], together with a fingerprint of its contents computed once, when it's
+/// interned.
+///
+/// The set of values then only hashes the fingerprint, and compares fingerprints before whole
+/// values, so the long node lists of large unions, which are interned again every time the union
+/// grows, are neither hashed with `std`'s slower hasher nor compared in full against every list
+/// landing in the same bucket.
+#[derive(Clone)]
+struct Fingerprinted<ValueT> {
+    fingerprint: u64,
+    value: ValueT,
+}
+
+impl<ValueT> Fingerprinted<ValueT>
+where
+    ValueT: Hash,
+{
+    #[inline]
+    fn new(value: ValueT) -> Self {
+        let mut hasher = FingerprintHasher::default();
+        value.hash(&mut hasher);
+        Self {
+            fingerprint: hasher.finish(),
+            value,
         }
     }
 }
 
+impl<ValueT> Hash for Fingerprinted<ValueT> {
+    #[inline]
+    fn hash<HasherT>(&self, state: &mut HasherT)
+    where
+        HasherT: Hasher,
+    {
+        state.write_u64(self.fingerprint);
+    }
+}
+
+impl<ValueT> PartialEq for Fingerprinted<ValueT>
+where
+    ValueT: PartialEq,
+{
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.fingerprint == other.fingerprint && self.value == other.value
+    }
+}
+
+impl<ValueT> Eq for Fingerprinted<ValueT> where ValueT: Eq {}
+
+/// Hashes everything written to it with FNV-1a.
+struct FingerprintHasher(u64);
+
+impl Default for FingerprintHasher {
+    #[inline]
+    fn default() -> Self {
+        Self(FNV1A_OFFSET_BASIS)
+    }
+}
+
+impl Hasher for FingerprintHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = fnv1a_extend(self.0, bytes);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct NonEmptyPool<ValueT, ValueIndexT> {
     inner: IndexSet<ValueT>,
@@ -83,6 +186,14 @@ where
     {
         ValueIndexT::try_from(self.inner.insert_full(value.into()).0)
     }
+
+    #[inline]
+    pub(crate) fn get(&self, index: ValueIndexT) -> Option<&ValueT>
+    where
+        ValueIndexT: Into<usize>,
+    {
+        self.inner.get_index(index.into())
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -116,7 +227,11 @@ where
     #[inline]
     fn from(value: Pool<FromT, ValueIndexT>) -> Self {
         Self {
-            values: value.inner.into_iter().map(Into::into).collect(),
+            values: value
+                .inner
+                .into_iter()
+                .map(|entry| entry.value.into())
+                .collect(),
             _dummy: PhantomData,
         }
     }
@@ -139,6 +254,24 @@ where
     pub(crate) fn get(&self, index: ValueIndexT) -> Option<&ValueT> {
         self.values.get(index.into())
     }
+
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.values.len()
+    }
+}
+
+impl<ValueT, ValueIndexT> ReadonlyNonEmptyPool<ValueT, ValueIndexT> {
+    /// Returns a pool of the values returned by `map`, at the same indices.
+    pub(crate) fn map<IntoT, MapT>(&self, map: MapT) -> ReadonlyNonEmptyPool<IntoT, ValueIndexT>
+    where
+        MapT: FnMut(&ValueT) -> IntoT,
+    {
+        ReadonlyNonEmptyPool {
+            values: self.values.iter().map(map).collect(),
+            _dummy: PhantomData,
+        }
+    }
 }
 
 impl<FromT, IntoT, ValueIndexT> From<NonEmptyPool<FromT, ValueIndexT>>
@@ -154,3 +287,72 @@ where
         }
     }
 }
+
+/// The entries appended to a pool, relative to an earlier version of it.
+///
+/// Pools only ever grow by appending, so a pool which shares a prefix with an earlier one can be
+/// sent as the length of the prefix and the entries after it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PoolDelta<ValueT> {
+    keep: usize,
+    append: Box<[ValueT]>,
+}
+
+impl<ValueT> PoolDelta<ValueT>
+where
+    ValueT: Clone + PartialEq,
+{
+    fn between(previous: &[ValueT], next: &[ValueT]) -> Self {
+        let keep = previous
+            .iter()
+            .zip(next)
+            .take_while(|(previous, next)| previous == next)
+            .count();
+        Self {
+            keep,
+            append: next[keep..].into(),
+        }
+    }
+
+    fn apply(self, previous: &[ValueT]) -> Option<Box<[ValueT]>> {
+        let kept = previous.get(..self.keep)?;
+        Some(kept.iter().cloned().chain(self.append).collect())
+    }
+}
+
+impl<ValueT> PoolDelta<ValueT> {
+    /// The number of entries sent in full.
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.append.len()
+    }
+}
+
+macro_rules! impl_pool_delta {
+    ($($pool:ident),+) => {
+        $(
+            impl<ValueT, ValueIndexT> $pool<ValueT, ValueIndexT>
+            where
+                ValueT: Clone + PartialEq,
+            {
+                /// Returns the entries of this pool which aren't in `previous`.
+                #[inline]
+                pub(crate) fn delta_from(&self, previous: &Self) -> PoolDelta<ValueT> {
+                    PoolDelta::between(&previous.values, &self.values)
+                }
+
+                /// Rebuilds the pool a delta was computed from, or `None` if the delta keeps
+                /// more entries than this pool has.
+                #[inline]
+                pub(crate) fn apply_delta(&self, delta: PoolDelta<ValueT>) -> Option<Self> {
+                    Some(Self {
+                        values: delta.apply(&self.values)?,
+                        _dummy: PhantomData,
+                    })
+                }
+            }
+        )+
+    };
+}
+
+impl_pool_delta!(ReadonlyPool, ReadonlyNonEmptyPool);
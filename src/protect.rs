@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use crate::{SchemaBuilder, SealingKey, transform::Sealer};
+
+/// How the values of a field or type are recorded under a [`ProtectionPolicy`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Protection {
+    /// Recorded as-is, e.g. to exempt a field of a protected type.
+    Plain,
+
+    /// Replaced by a keyed HMAC-SHA256 hash of the whole value, recorded as 32 bytes. Equal
+    /// values have equal hashes under the same key, so they can still be counted, grouped or
+    /// joined on.
+    Hash,
+
+    /// Encrypted whole with ChaCha20-Poly1305, recorded as bytes which can be decrypted back
+    /// into a [`Dynamic`][`crate::Dynamic`] with [`SealingKey::open_value`].
+    Encrypt,
+}
+
+/// Maps fields and types to the [`Protection`] of their values, applied while tracing with
+/// [`SchemaBuilder::protection_policy`], such that sensitive values never reach the trace while
+/// the rest of the value stays readable.
+///
+/// A protected field is replaced whole by a single byte string, so nothing else about its value
+/// is recorded: not the variant of an enum, whether an option is present, the length of a
+/// sequence or map, nor the fields of a struct. Protecting a type protects each of its fields
+/// this way (or the contents of a newtype, or the elements of a tuple struct), unless the field
+/// has a protection of its own, and replaces its unit variants whole. The names of its other
+/// variants and of its fields stay in the schema.
+///
+/// What does show is the length of the byte string: encrypted values are padded to a multiple
+/// of 32 bytes, such that it only tells apart values of very different sizes.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::Serialize;
+/// use serde_describe::{Dynamic, Protection, ProtectionPolicy, SchemaBuilder, SealingKey};
+///
+/// #[derive(Serialize)]
+/// struct Customer {
+///     email: String,
+///     ssn: String,
+///     country: String,
+/// }
+///
+/// let key = SealingKey::generate();
+/// let policy = ProtectionPolicy::new(key.clone())
+///     .field("Customer", "email", Protection::Hash)
+///     .field("Customer", "ssn", Protection::Encrypt);
+/// let mut builder = SchemaBuilder::new().protection_policy(&policy);
+/// let customer = Customer {
+///     email: "ana@example.com".to_owned(),
+///     ssn: "123-45-6789".to_owned(),
+///     country: "PT".to_owned(),
+/// };
+/// let first = builder.trace(&customer)?;
+/// let second = builder.trace(&customer)?;
+/// let schema = builder.build()?;
+/// assert_eq!(
+///     schema.to_string(),
+///     "Customer_3 { email: bytes_1, ssn: bytes_1, country: str_2 }"
+/// );
+///
+/// #[derive(serde::Deserialize)]
+/// struct StoredCustomer {
+///     email: serde_bytes::ByteBuf,
+///     ssn: serde_bytes::ByteBuf,
+///     country: String,
+/// }
+///
+/// let read = |trace| -> Result<StoredCustomer, Box<dyn std::error::Error>> {
+///     let bytes = postcard::to_stdvec(&schema.describe_trace(trace))?;
+///     Ok(schema.deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))?)
+/// };
+/// let (first, second) = (read(first)?, read(second)?);
+/// assert_eq!(first.country, "PT");
+/// assert_eq!(first.email, second.email);
+/// assert_eq!(
+///     key.open_value(&first.ssn)?,
+///     Dynamic::Str("123-45-6789".to_owned()),
+/// );
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ProtectionPolicy {
+    key: SealingKey,
+    fields: Vec<(Box<str>, Box<str>, Protection)>,
+    types: Vec<(Box<str>, Protection)>,
+}
+
+impl ProtectionPolicy {
+    /// Creates a policy without any protected fields, hashing and encrypting values with `key`.
+    pub fn new(key: SealingKey) -> Self {
+        Self {
+            key,
+            fields: Vec::new(),
+            types: Vec::new(),
+        }
+    }
+
+    /// Sets the protection of a field, named by its type and its serialized name like in
+    /// [`SchemaBuilder::transform_field`].
+    pub fn field(mut self, type_name: &str, field: &str, protection: Protection) -> Self {
+        self.fields
+            .retain(|(name, key, _)| (&**name, &**key) != (type_name, field));
+        self.fields
+            .push((type_name.into(), field.into(), protection));
+        self
+    }
+
+    /// Sets the protection of the fields of every value of a type, e.g. the contents of a newtype
+    /// for a logical type like an email address, see [`ProtectionPolicy`].
+    pub fn type_name(mut self, type_name: &str, protection: Protection) -> Self {
+        self.types.retain(|(name, _)| **name != *type_name);
+        self.types.push((type_name.into(), protection));
+        self
+    }
+}
+
+impl SchemaBuilder {
+    /// Protects the values of the fields and types of `policy` before they're recorded by
+    /// subsequent calls to [`trace`][`Self::trace`], see [`ProtectionPolicy`].
+    ///
+    /// Protected values are captured as they were serialized, before, and instead of, any
+    /// transformers registered for them.
+    pub fn protection_policy(mut self, policy: &ProtectionPolicy) -> Self {
+        for (type_name, field, protection) in &policy.fields {
+            let seal = sealer(&policy.key, *protection);
+            self.transformers.seal_field(type_name, field, seal);
+        }
+        for (type_name, protection) in &policy.types {
+            let seal = sealer(&policy.key, *protection);
+            self.transformers.seal_type(type_name, seal);
+        }
+        self
+    }
+}
+
+fn sealer(key: &SealingKey, protection: Protection) -> Sealer {
+    let key = key.clone();
+    match protection {
+        Protection::Plain => None,
+        Protection::Hash => Some(Arc::new(move |value| key.hash_value(value).to_vec())),
+        Protection::Encrypt => Some(Arc::new(move |value| key.seal_value(value))),
+    }
+}
@@ -0,0 +1,483 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fmt::Display,
+};
+use thiserror::Error;
+
+use crate::{
+    FieldPath, PathSegment, Schema,
+    indices::{
+        FieldNameIndex, FieldNameListIndex, MemberIndex, MemberListIndex, SchemaNodeIndex,
+        SchemaNodeListIndex, TypeNameIndex, VariantNameIndex,
+    },
+    pool::{NonEmptyPool, Pool},
+    schema::SchemaNode,
+};
+
+/// Returned by [`Schema::pruned`] for schemas which refer to entries they don't have, e.g.
+/// corrupt schemas read from untrusted sources.
+#[derive(Debug, Error)]
+#[error("invalid schema: {0}")]
+pub struct InvalidSchemaError(Box<str>);
+
+impl InvalidSchemaError {
+    pub(crate) fn new(error: impl Display) -> Self {
+        Self(error.to_string().into())
+    }
+}
+
+impl Schema {
+    /// Returns a copy of the schema without the entries which can't be reached from its root.
+    ///
+    /// Schemas keep every type they were ever given, e.g. the types added to a
+    /// [`SchemaGraphBuilder`][`crate::SchemaGraphBuilder`] but never used by the root. Pruning
+    /// them makes the schema smaller to store and send, without changing the data it describes:
+    /// values serialized with the original schema can be deserialized with the pruned one.
+    ///
+    /// Entries are numbered in the order they're reached from the root, and identical entries
+    /// are merged, e.g. duplicates in a schema assembled by other tools. The pruned schema only
+    /// depends on the types the schema describes, so schemas of the same types assembled in a
+    /// different order prune to the same schema, with the same fingerprint. The members of
+    /// unions keep their order, since it determines their discriminants.
+    ///
+    /// The remaining entries are renumbered, so the pruned schema can't serialize traces made by
+    /// the builder of the original, and has a different [fingerprint][`Self::fingerprint`].
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_describe::{PrimitiveType, SchemaBuilder, SchemaGraphBuilder};
+    ///
+    /// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    /// struct Point {
+    ///     x: i32,
+    ///     y: i32,
+    /// }
+    ///
+    /// let mut graph = SchemaGraphBuilder::new();
+    /// let int = graph.add_primitive(PrimitiveType::I32)?;
+    /// let point = graph.add_struct("Point", &[("x", int), ("y", int)], &[])?;
+    /// let _unused = graph.add_struct("Unused", &[("name", int)], &[])?;
+    /// let schema = graph.build(point)?;
+    ///
+    /// let pruned = schema.pruned()?;
+    /// assert!(postcard::to_stdvec(&pruned)?.len() < postcard::to_stdvec(&schema)?.len());
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&Point { x: 1, y: 2 })?;
+    /// let bytes = postcard::to_stdvec(&builder.build()?.describe_trace(trace))?;
+    /// let point: Point = pruned.deserialize_described(
+    ///     &mut postcard::Deserializer::from_bytes(&bytes),
+    /// )?;
+    /// assert_eq!(point, Point { x: 1, y: 2 });
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn pruned(&self) -> Result<Schema, InvalidSchemaError> {
+        let mut pruner = Pruner::new(self);
+        let root_index = pruner.node(self.root_index)?;
+        Ok(pruner.finish(root_index))
+    }
+
+    /// Returns a standalone schema for the values at a path inside the values of this schema,
+    /// e.g. to publish or validate the type of one field independently of the messages which
+    /// contain it, or `None` if no value of this schema has anything at that path.
+    ///
+    /// Paths are followed through the fields, items, map values and variants they name, and
+    /// implicitly through optional values and newtype structs. Where the path leads to values of
+    /// several types, e.g. to a field of several members of a union, the schema describes a union
+    /// of all of them.
+    ///
+    /// The subtree is [pruned][`Self::pruned`], so it only depends on the types at the path: it
+    /// is the same schema, with the same fingerprint, as the pruned schema of those types traced
+    /// on their own, and it can deserialize their serialized traces.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    /// struct Payment {
+    ///     amount: u64,
+    ///     currency: String,
+    /// }
+    ///
+    /// #[derive(Serialize)]
+    /// struct Envelope {
+    ///     id: u32,
+    ///     payload: Payment,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let payment = Payment { amount: 10, currency: "EUR".to_owned() };
+    /// builder.trace(&Envelope { id: 1, payload: payment })?;
+    /// let schema = builder.build()?;
+    /// let payload = schema.subtree(&".payload".parse()?)?.unwrap();
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let payment = Payment { amount: 25, currency: "USD".to_owned() };
+    /// let trace = builder.trace(&payment)?;
+    /// let payment_schema = builder.build()?;
+    /// assert_eq!(payload.fingerprint(), payment_schema.pruned()?.fingerprint());
+    ///
+    /// let bytes = postcard::to_stdvec(&payment_schema.describe_trace(trace))?;
+    /// let decoded: Payment =
+    ///     payload.deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))?;
+    /// assert_eq!(decoded, payment);
+    /// assert!(schema.subtree(&".payload.missing".parse()?)?.is_none());
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn subtree(&self, path: &FieldPath) -> Result<Option<Schema>, InvalidSchemaError> {
+        let mut nodes = vec![self.root_index];
+        for segment in path.segments() {
+            let mut next = Vec::new();
+            for index in self.expand_nodes(&nodes, true)? {
+                next.extend(self.node_at_segment(index, segment)?);
+            }
+            nodes = next;
+        }
+
+        // Union members keep the order in which they're reached, like traced members keep the
+        // order in which they were first seen. Different nodes may prune to the same one, so
+        // deduplicate them by copying them once, then copy them again in order.
+        let mut sorter = Pruner::new(self);
+        let mut seen = HashSet::new();
+        let mut members = Vec::new();
+        for index in self.expand_nodes(&nodes, false)? {
+            if seen.insert(sorter.node(index)?) {
+                members.push(index);
+            }
+        }
+
+        let mut pruner = Pruner::new(self);
+        let root_index = match *members {
+            [] => return Ok(None),
+            [member] => pruner.node(member)?,
+            _ => {
+                let members = members
+                    .into_iter()
+                    .map(|member| pruner.node(member))
+                    .collect::<Result<Box<[_]>, _>>()?;
+                let members = pruner
+                    .node_lists
+                    .intern(members)
+                    .map_err(InvalidSchemaError::new)?;
+                pruner
+                    .nodes
+                    .intern(SchemaNode::Union(members))
+                    .map_err(InvalidSchemaError::new)?
+            }
+        };
+        Ok(Some(pruner.finish(root_index)))
+    }
+
+    /// Replaces unions by their members, and if `transparent`, optional values and newtype
+    /// structs by their contents, see [`Schema::subtree`].
+    fn expand_nodes(
+        &self,
+        nodes: &[SchemaNodeIndex],
+        transparent: bool,
+    ) -> Result<Vec<SchemaNodeIndex>, InvalidSchemaError> {
+        let mut pending = nodes.iter().rev().copied().collect::<Vec<_>>();
+        let mut seen = HashSet::new();
+        let mut expanded = Vec::new();
+        while let Some(index) = pending.pop() {
+            if !seen.insert(index) {
+                continue;
+            }
+            match self.node(index).map_err(InvalidSchemaError::new)? {
+                SchemaNode::Union(members) => pending.extend(
+                    self.node_list(members)
+                        .map_err(InvalidSchemaError::new)?
+                        .iter()
+                        .rev(),
+                ),
+                SchemaNode::OptionNone if transparent => {}
+                SchemaNode::OptionSome(inner) | SchemaNode::NewtypeStruct(_, inner)
+                    if transparent =>
+                {
+                    pending.push(inner)
+                }
+                _ => expanded.push(index),
+            }
+        }
+        Ok(expanded)
+    }
+
+    /// Returns the node of the values at `segment` inside those of a node, if they have any.
+    fn node_at_segment(
+        &self,
+        index: SchemaNodeIndex,
+        segment: &PathSegment,
+    ) -> Result<Option<SchemaNodeIndex>, InvalidSchemaError> {
+        let item = |items, position: Option<usize>| -> Result<_, InvalidSchemaError> {
+            let items = self.node_list(items).map_err(InvalidSchemaError::new)?;
+            Ok(position.and_then(|position| items.get(position).copied()))
+        };
+        let is_variant = |variant| -> Result<_, InvalidSchemaError> {
+            let PathSegment::Variant(name) = segment else {
+                return Ok(false);
+            };
+            Ok(self
+                .variant_name(variant)
+                .map_err(InvalidSchemaError::new)?
+                == &**name)
+        };
+        Ok(
+            match (self.node(index).map_err(InvalidSchemaError::new)?, segment) {
+                (SchemaNode::NewtypeVariant(_, variant, inner), _) if is_variant(variant)? => {
+                    Some(inner)
+                }
+                (
+                    SchemaNode::UnitVariant(_, variant)
+                    | SchemaNode::TupleVariant(_, variant, _)
+                    | SchemaNode::StructVariant(_, variant, _, _, _),
+                    _,
+                ) if is_variant(variant)? => Some(index),
+                (
+                    SchemaNode::Struct(_, fields, _, types)
+                    | SchemaNode::StructVariant(_, _, fields, _, types),
+                    PathSegment::Field(name),
+                ) => {
+                    let fields = self
+                        .field_name_list(fields)
+                        .map_err(InvalidSchemaError::new)?;
+                    let mut position = None;
+                    for (i_field, &field) in fields.iter().enumerate() {
+                        if self.field_name(field).map_err(InvalidSchemaError::new)? == &**name {
+                            position = Some(i_field);
+                            break;
+                        }
+                    }
+                    item(types, position)?
+                }
+                (
+                    SchemaNode::Tuple(items)
+                    | SchemaNode::TupleStruct(_, items)
+                    | SchemaNode::TupleVariant(_, _, items),
+                    PathSegment::Field(position),
+                ) => item(items, position.parse().ok())?,
+                (
+                    SchemaNode::Tuple(items)
+                    | SchemaNode::TupleStruct(_, items)
+                    | SchemaNode::TupleVariant(_, _, items),
+                    &PathSegment::Index(position),
+                ) => item(items, Some(position))?,
+                (SchemaNode::Sequence(item), PathSegment::Field(position)) => {
+                    position.parse::<usize>().ok().map(|_| item)
+                }
+                (SchemaNode::Sequence(item), PathSegment::Index(_))
+                | (SchemaNode::Map(_, item), PathSegment::Index(_) | PathSegment::Key(_)) => {
+                    Some(item)
+                }
+                _ => None,
+            },
+        )
+    }
+}
+
+/// Copies the entries reachable from a node into fresh pools, see [`Schema::pruned`].
+struct Pruner<'schema> {
+    schema: &'schema Schema,
+
+    /// The new index of every node copied so far, or `None` while its children are being
+    /// copied, which catches cycles in corrupt schemas.
+    remapped: HashMap<SchemaNodeIndex, Option<SchemaNodeIndex>>,
+
+    nodes: Pool<SchemaNode, SchemaNodeIndex>,
+    node_lists: Pool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
+    member_lists: Pool<Box<[MemberIndex]>, MemberListIndex>,
+    field_name_lists: NonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
+    field_names: NonEmptyPool<Cow<'static, str>, FieldNameIndex>,
+    variant_names: NonEmptyPool<Cow<'static, str>, VariantNameIndex>,
+    type_names: NonEmptyPool<Cow<'static, str>, TypeNameIndex>,
+}
+
+impl<'schema> Pruner<'schema> {
+    fn new(schema: &'schema Schema) -> Self {
+        Self {
+            schema,
+            remapped: HashMap::new(),
+            nodes: Pool::default(),
+            node_lists: Pool::default(),
+            member_lists: Pool::default(),
+            field_name_lists: NonEmptyPool::default(),
+            field_names: NonEmptyPool::default(),
+            variant_names: NonEmptyPool::default(),
+            type_names: NonEmptyPool::default(),
+        }
+    }
+
+    /// Returns the schema of the copied entries, with the given root.
+    fn finish(self, root_index: SchemaNodeIndex) -> Schema {
+        Schema {
+            root_index,
+            nodes: self.nodes.into(),
+            node_lists: self.node_lists.into(),
+            member_lists: self.member_lists.into(),
+            field_name_lists: self.field_name_lists.into(),
+            field_names: self.field_names.into(),
+            variant_names: self.variant_names.into(),
+            type_names: self.type_names.into(),
+            human_readable: self.schema.human_readable,
+            version: self.schema.version,
+            min_reader_version: self.schema.min_reader_version,
+            hashed_names: self.schema.hashed_names,
+        }
+    }
+
+    fn node(&mut self, index: SchemaNodeIndex) -> Result<SchemaNodeIndex, InvalidSchemaError> {
+        match self.remapped.get(&index) {
+            Some(&Some(remapped)) => return Ok(remapped),
+            Some(None) => {
+                return Err(InvalidSchemaError::new(format_args!(
+                    "node with index {index:?} contains itself"
+                )));
+            }
+            None => self.remapped.insert(index, None),
+        };
+
+        let node = match self.schema.node(index).map_err(InvalidSchemaError::new)? {
+            node @ (SchemaNode::Bool
+            | SchemaNode::I8
+            | SchemaNode::I16
+            | SchemaNode::I32
+            | SchemaNode::I64
+            | SchemaNode::I128
+            | SchemaNode::U8
+            | SchemaNode::U16
+            | SchemaNode::U32
+            | SchemaNode::U64
+            | SchemaNode::U128
+            | SchemaNode::F32
+            | SchemaNode::F64
+            | SchemaNode::Char
+            | SchemaNode::String
+            | SchemaNode::Bytes
+            | SchemaNode::OptionNone
+            | SchemaNode::Unit) => node,
+            SchemaNode::OptionSome(inner) => SchemaNode::OptionSome(self.node(inner)?),
+            SchemaNode::UnitStruct(name) => SchemaNode::UnitStruct(self.type_name(name)?),
+            SchemaNode::UnitVariant(name, variant) => {
+                SchemaNode::UnitVariant(self.type_name(name)?, self.variant_name(variant)?)
+            }
+            SchemaNode::NewtypeStruct(name, inner) => {
+                SchemaNode::NewtypeStruct(self.type_name(name)?, self.node(inner)?)
+            }
+            SchemaNode::NewtypeVariant(name, variant, inner) => SchemaNode::NewtypeVariant(
+                self.type_name(name)?,
+                self.variant_name(variant)?,
+                self.node(inner)?,
+            ),
+            SchemaNode::Sequence(item) => SchemaNode::Sequence(self.node(item)?),
+            SchemaNode::Map(key, value) => SchemaNode::Map(self.node(key)?, self.node(value)?),
+            SchemaNode::Tuple(items) => SchemaNode::Tuple(self.node_list(items)?),
+            SchemaNode::TupleStruct(name, items) => {
+                SchemaNode::TupleStruct(self.type_name(name)?, self.node_list(items)?)
+            }
+            SchemaNode::TupleVariant(name, variant, items) => SchemaNode::TupleVariant(
+                self.type_name(name)?,
+                self.variant_name(variant)?,
+                self.node_list(items)?,
+            ),
+            SchemaNode::Struct(name, fields, members, types) => SchemaNode::Struct(
+                self.type_name(name)?,
+                self.field_name_list(fields)?,
+                self.member_list(members)?,
+                self.node_list(types)?,
+            ),
+            SchemaNode::StructVariant(name, variant, fields, members, types) => {
+                SchemaNode::StructVariant(
+                    self.type_name(name)?,
+                    self.variant_name(variant)?,
+                    self.field_name_list(fields)?,
+                    self.member_list(members)?,
+                    self.node_list(types)?,
+                )
+            }
+            SchemaNode::Union(members) => SchemaNode::Union(self.node_list(members)?),
+        };
+
+        let remapped = self.nodes.intern(node).map_err(InvalidSchemaError::new)?;
+        self.remapped.insert(index, Some(remapped));
+        Ok(remapped)
+    }
+
+    fn node_list(
+        &mut self,
+        index: SchemaNodeListIndex,
+    ) -> Result<SchemaNodeListIndex, InvalidSchemaError> {
+        let schema = self.schema;
+        let list = schema
+            .node_list(index)
+            .map_err(InvalidSchemaError::new)?
+            .iter()
+            .map(|&node| self.node(node))
+            .collect::<Result<Box<[_]>, _>>()?;
+        self.node_lists
+            .intern(list)
+            .map_err(InvalidSchemaError::new)
+    }
+
+    fn member_list(
+        &mut self,
+        index: MemberListIndex,
+    ) -> Result<MemberListIndex, InvalidSchemaError> {
+        let list = self
+            .schema
+            .member_list(index)
+            .map_err(InvalidSchemaError::new)?;
+        self.member_lists
+            .intern(list.into())
+            .map_err(InvalidSchemaError::new)
+    }
+
+    fn field_name_list(
+        &mut self,
+        index: FieldNameListIndex,
+    ) -> Result<FieldNameListIndex, InvalidSchemaError> {
+        let schema = self.schema;
+        let list = schema
+            .field_name_list(index)
+            .map_err(InvalidSchemaError::new)?
+            .iter()
+            .map(|&name| {
+                let name = schema.field_names.get(name).ok_or_else(|| {
+                    InvalidSchemaError::new(format_args!("no such field name with index {name:?}"))
+                })?;
+                self.field_names
+                    .intern(name.clone())
+                    .map_err(InvalidSchemaError::new)
+            })
+            .collect::<Result<Box<[_]>, _>>()?;
+        self.field_name_lists
+            .intern(list)
+            .map_err(InvalidSchemaError::new)
+    }
+
+    fn type_name(&mut self, index: TypeNameIndex) -> Result<TypeNameIndex, InvalidSchemaError> {
+        let name = self.schema.type_names.get(index).ok_or_else(|| {
+            InvalidSchemaError::new(format_args!("no such type name with index {index:?}"))
+        })?;
+        self.type_names
+            .intern(name.clone())
+            .map_err(InvalidSchemaError::new)
+    }
+
+    fn variant_name(
+        &mut self,
+        index: VariantNameIndex,
+    ) -> Result<VariantNameIndex, InvalidSchemaError> {
+        let name = self.schema.variant_names.get(index).ok_or_else(|| {
+            InvalidSchemaError::new(format_args!("no such variant name with index {index:?}"))
+        })?;
+        self.variant_names
+            .intern(name.clone())
+            .map_err(InvalidSchemaError::new)
+    }
+}
@@ -0,0 +1,228 @@
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{DeserializeSeed, Error as _, Visitor},
+};
+use std::{cell::RefCell, marker::PhantomData};
+
+use crate::{DescribedBy, Dynamic, Embedded, Schema, SchemaBuilder, Trace, TraceError};
+
+/// The name of the newtype a [`RawValue`] asks to deserialize, which described values capture
+/// instead of deserializing.
+pub(crate) const RAW_VALUE_TYPE_NAME: &str = "serde_describe::RawValue";
+
+thread_local! {
+    /// The value just captured for a [`RawValue`], handed from the deserializer to the visitor,
+    /// since `serde` gives them no other way to exchange values of their own.
+    static CAPTURED: RefCell<Option<Dynamic>> = const { RefCell::new(None) };
+}
+
+/// A value of any type, kept as is when deserialized and serialized again, like
+/// `serde_json::value::RawValue`, e.g. for brokers and proxies forwarding payloads they must not
+/// interpret.
+///
+/// Used as a field of a deserialized described value, it captures the field's value, whatever
+/// its type, together with the part of the schema describing it. Serializing it replays the
+/// captured value exactly like the original, so tracing it adds the original types to the new
+/// schema, and the final receiver can deserialize it with the original Rust type, or as another
+/// [`RawValue`]. The value is never deserialized into Rust types along the way, though it's
+/// inspectable with [`RawValue::described`].
+///
+/// A [`RawValue`] can only be deserialized from described values, e.g. with [`SelfDescribed`]
+/// or [`Schema::describe_type`], and not through types which buffer their contents, like
+/// untagged enums.
+///
+/// [`SelfDescribed`]: crate::SelfDescribed
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_describe::{RawValue, SelfDescribed};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// enum Payment {
+///     Card { number: String, cvc: Option<u16> },
+///     Cash(u64),
+/// }
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Message<PayloadT> {
+///     hops: u32,
+///     payload: PayloadT,
+/// }
+///
+/// let payment = Payment::Card { number: "4242".to_owned(), cvc: None };
+/// let bytes = postcard::to_stdvec(&SelfDescribed(Message { hops: 0, payload: &payment }))?;
+///
+/// // The proxy forwards the payload without knowing its type.
+/// let SelfDescribed(mut message) =
+///     postcard::from_bytes::<SelfDescribed<Message<RawValue>>>(&bytes)?;
+/// message.hops += 1;
+/// assert_eq!(message.payload.described().to_string(), r#"Card(number: "4242", cvc: None)"#);
+/// let bytes = postcard::to_stdvec(&SelfDescribed(&message))?;
+///
+/// let SelfDescribed(message) = postcard::from_bytes::<SelfDescribed<Message<Payment>>>(&bytes)?;
+/// assert_eq!((message.hops, message.payload), (1, payment));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct RawValue {
+    schema: Schema,
+    trace: Trace,
+}
+
+impl RawValue {
+    /// Captures a value, like deserializing a [`RawValue`] from it would.
+    pub fn new<T>(value: &T) -> Result<Self, TraceError>
+    where
+        T: Serialize,
+    {
+        let mut builder = SchemaBuilder::new();
+        let trace = builder.trace(value)?;
+        Ok(Self {
+            schema: builder.build()?,
+            trace,
+        })
+    }
+
+    /// The captured value's trace, together with the schema describing it.
+    #[inline]
+    pub fn described(&self) -> DescribedBy<'_, &Trace> {
+        self.schema.describe_trace_ref(&self.trace)
+    }
+}
+
+impl std::fmt::Debug for RawValue {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_tuple("RawValue")
+            .field(&format_args!("{}", self.described()))
+            .finish()
+    }
+}
+
+impl Serialize for RawValue {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        Embedded(self.described()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = CaptureSeed.deserialize(deserializer)?;
+        RawValue::new(&value).map_err(D::Error::custom)
+    }
+}
+
+/// Captures the next value of a described value as a [`Dynamic`], see [`RawValue`].
+#[derive(Default)]
+pub(crate) struct CaptureSeed;
+
+impl<'de> DeserializeSeed<'de> for CaptureSeed {
+    type Value = Dynamic;
+
+    #[inline]
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(RAW_VALUE_TYPE_NAME, CaptureVisitor)
+    }
+}
+
+/// Visits a captured value with `visitor`, which takes it if it's a [`CaptureVisitor`].
+///
+/// The slot is restored to what it held before once visited, so values captured while visiting,
+/// e.g. by a visitor decoding another described value before taking its own, can't take or
+/// overwrite this one, and one the visitor doesn't take isn't left behind.
+pub(crate) fn visit_captured<'de, V, E>(value: Dynamic, visitor: V) -> Result<V::Value, E>
+where
+    V: Visitor<'de>,
+    E: serde::de::Error,
+{
+    struct Restore(Option<Dynamic>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            CAPTURED.with(|captured| *captured.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(CAPTURED.with(|captured| captured.borrow_mut().replace(value)));
+    visitor.visit_unit()
+}
+
+pub(crate) struct CaptureVisitor;
+
+impl<'de> Visitor<'de> for CaptureVisitor {
+    type Value = Dynamic;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a described value")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        CAPTURED
+            .with(|captured| captured.borrow_mut().take())
+            .ok_or_else(|| E::custom("raw values can only be deserialized from described values"))
+    }
+}
+
+/// Captures the items of a sequence or tuple, see [`CaptureSeed`].
+pub(crate) struct CaptureItems;
+
+impl<'de> Visitor<'de> for CaptureItems {
+    type Value = Vec<Dynamic>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a described sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element_seed(CaptureSeed)? {
+            items.push(item);
+        }
+        Ok(items)
+    }
+}
+
+/// Captures the entries of a map, or the present fields of a struct by name, see
+/// [`CaptureSeed`].
+pub(crate) struct CaptureEntries<KeySeedT>(pub(crate) PhantomData<KeySeedT>);
+
+impl<'de, KeySeedT> Visitor<'de> for CaptureEntries<KeySeedT>
+where
+    KeySeedT: DeserializeSeed<'de> + Default,
+{
+    type Value = Vec<(KeySeedT::Value, Dynamic)>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a described map or struct")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+        while let Some(entry) = map.next_entry_seed(KeySeedT::default(), CaptureSeed)? {
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
@@ -0,0 +1,466 @@
+use serde::Deserialize;
+use std::cell::Cell;
+use thiserror::Error;
+
+use crate::{
+    DescribedBy, ElementValue, Schema, Trace,
+    dump::DumpError,
+    elements::read_value,
+    trace::{InvalidTraceError, ReadTraceExt, TraceNode},
+};
+
+/// Walks a [`Trace`] one item at a time without going through `serde`, returned by
+/// [`DescribedBy::reader`].
+///
+/// The trace is flattened into a sequence of [`TraceEvent`]-s in depth-first order: each
+/// container event (e.g. [`TraceEvent::Seq`]) gives its number of children, whose events follow
+/// it directly. Map entries are read as a key followed by a value.
+///
+/// Useful for integrations which need the raw values without deserializing them into Rust types,
+/// e.g. columnar engines or bindings to other languages.
+///
+/// Readers only borrow the trace, so any number of them may read the same trace, from any
+/// number of threads. Cloning a reader is cheap and makes a checkpoint: the clone carries on
+/// from the same item, independently of the original.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::Serialize;
+/// use serde_describe::{SchemaBuilder, TraceEvent};
+///
+/// #[derive(Serialize)]
+/// struct Point {
+///     x: i32,
+///     #[serde(skip_serializing_if = "Option::is_none")]
+///     label: Option<&'static str>,
+/// }
+///
+/// let mut builder = SchemaBuilder::new();
+/// let trace = builder.trace(&vec![Point { x: 1, label: Some("a") }, Point { x: 2, label: None }])?;
+/// let schema = builder.build()?;
+///
+/// let events = schema
+///     .describe_trace_ref(&trace)
+///     .reader()
+///     .collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(
+///     events,
+///     [
+///         TraceEvent::Seq { len: 2 },
+///         TraceEvent::Struct { name: "Point", fields: vec!["x", "label"] },
+///         TraceEvent::I32(1),
+///         TraceEvent::Some,
+///         TraceEvent::Str("a"),
+///         TraceEvent::Struct { name: "Point", fields: vec!["x"] },
+///         TraceEvent::I32(2),
+///     ]
+/// );
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct TraceReader<'data> {
+    schema: &'data Schema,
+    tail: Cell<&'data [u8]>,
+    len: usize,
+}
+
+/// Returned by [`TraceReader::finish`] when a trace holds more data than was read from it, which
+/// usually means the trace was read as a different shape than the one it was traced from, or
+/// that it's corrupted.
+#[derive(Clone, Debug, Error)]
+#[error("{remaining} unread trailing bytes at byte {offset} of the trace, starting with {next}")]
+pub struct TrailingDataError {
+    offset: usize,
+    remaining: usize,
+    next: Box<str>,
+}
+
+impl TrailingDataError {
+    /// The offset of the first unread byte in the trace.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The number of unread bytes.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Describes the first unread item, resolved using the schema, e.g.
+    /// `Struct { name: "Point", fields: ["x"] }`.
+    #[inline]
+    pub fn next(&self) -> &str {
+        &self.next
+    }
+}
+
+/// A single item of a traced value, read by a [`TraceReader`].
+///
+/// Names are resolved using the schema. Structs list the names of their present fields, in the
+/// order in which their values follow.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum TraceEvent<'data> {
+    /// A `bool`.
+    Bool(bool),
+    /// An `i8`.
+    I8(i8),
+    /// An `i16`.
+    I16(i16),
+    /// An `i32`.
+    I32(i32),
+    /// An `i64`.
+    I64(i64),
+    /// An `i128`.
+    I128(i128),
+    /// A `u8`.
+    U8(u8),
+    /// A `u16`.
+    U16(u16),
+    /// A `u32`.
+    U32(u32),
+    /// A `u64`.
+    U64(u64),
+    /// A `u128`.
+    U128(u128),
+    /// An `f32`.
+    F32(f32),
+    /// An `f64`.
+    F64(f64),
+    /// A `char`.
+    Char(char),
+    /// A string.
+    Str(&'data str),
+    /// A byte string.
+    Bytes(&'data [u8]),
+
+    /// An absent optional value.
+    None,
+    /// A present optional value, followed by the value.
+    Some,
+    /// A `()`.
+    Unit,
+    /// A unit struct.
+    UnitStruct {
+        /// The name of the struct.
+        name: &'data str,
+    },
+    /// A unit variant of an enum.
+    UnitVariant {
+        /// The name of the enum.
+        name: &'data str,
+        /// The name of the variant.
+        variant: &'data str,
+    },
+
+    /// A newtype struct, followed by its wrapped value.
+    NewtypeStruct {
+        /// The name of the struct.
+        name: &'data str,
+    },
+    /// A newtype variant of an enum, followed by its wrapped value.
+    NewtypeVariant {
+        /// The name of the enum.
+        name: &'data str,
+        /// The name of the variant.
+        variant: &'data str,
+    },
+
+    /// A sequence, followed by its items.
+    Seq {
+        /// The number of items.
+        len: usize,
+    },
+    /// A map, followed by its keys and values, alternating.
+    Map {
+        /// The number of entries.
+        len: usize,
+    },
+
+    /// A tuple, followed by its items.
+    Tuple {
+        /// The number of items.
+        len: usize,
+    },
+    /// A tuple struct, followed by its items.
+    TupleStruct {
+        /// The name of the struct.
+        name: &'data str,
+        /// The number of items.
+        len: usize,
+    },
+    /// A tuple variant of an enum, followed by its items.
+    TupleVariant {
+        /// The name of the enum.
+        name: &'data str,
+        /// The name of the variant.
+        variant: &'data str,
+        /// The number of items.
+        len: usize,
+    },
+
+    /// A struct, followed by the values of its present fields.
+    Struct {
+        /// The name of the struct.
+        name: &'data str,
+        /// The names of the present fields.
+        fields: Vec<&'data str>,
+    },
+    /// A struct variant of an enum, followed by the values of its present fields.
+    StructVariant {
+        /// The name of the enum.
+        name: &'data str,
+        /// The name of the variant.
+        variant: &'data str,
+        /// The names of the present fields.
+        fields: Vec<&'data str>,
+    },
+}
+
+impl<'data> DescribedBy<'data, &'data Trace> {
+    /// Returns a reader over the items of this trace, see [`TraceReader`].
+    pub fn reader(&self) -> TraceReader<'data> {
+        TraceReader {
+            schema: self.1,
+            tail: Cell::new(&(self.0).0),
+            len: (self.0).0.len(),
+        }
+    }
+
+    /// Returns an iterator over the items of this trace together with the offsets in the trace
+    /// at which they start, see [`TraceNodes`].
+    pub fn trace_nodes(&self) -> TraceNodes<'data> {
+        TraceNodes {
+            reader: self.reader(),
+        }
+    }
+}
+
+/// Iterates over the items of a [`Trace`] like a [`TraceReader`], also yielding the offset of the
+/// byte at which each item starts, returned by [`DescribedBy::trace_nodes`].
+///
+/// Meant for debugging, e.g. to see exactly what a value was traced as, or to find where a
+/// corrupted trace stops making sense. Offsets index the bytes of the trace, which are in this
+/// crate's internal format.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{SchemaBuilder, TraceEvent};
+///
+/// let mut builder = SchemaBuilder::new();
+/// let trace = builder.trace(&(7u8, Some("seven")))?;
+/// let schema = builder.build()?;
+///
+/// let nodes = schema
+///     .describe_trace_ref(&trace)
+///     .trace_nodes()
+///     .collect::<Result<Vec<_>, _>>()?;
+/// assert_eq!(
+///     nodes.iter().map(|(_, event)| event).collect::<Vec<_>>(),
+///     [
+///         &TraceEvent::Tuple { len: 2 },
+///         &TraceEvent::U8(7),
+///         &TraceEvent::Some,
+///         &TraceEvent::Str("seven"),
+///     ]
+/// );
+/// assert_eq!(nodes[0].0, 0);
+/// assert!(nodes.windows(2).all(|pair| pair[0].0 < pair[1].0));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct TraceNodes<'data> {
+    reader: TraceReader<'data>,
+}
+
+impl<'data> Iterator for TraceNodes<'data> {
+    type Item = Result<(usize, TraceEvent<'data>), InvalidTraceError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.reader.offset();
+        self.reader
+            .next_event()
+            .map(|event| event.map(|event| (offset, event)))
+            .transpose()
+    }
+}
+
+impl<'data> TraceReader<'data> {
+    /// Reads the next item, or returns `None` at the end of the trace.
+    ///
+    /// Returns an error if the trace doesn't match the schema, after which the reader is at the
+    /// end of the trace.
+    pub fn next_event(&mut self) -> Result<Option<TraceEvent<'data>>, InvalidTraceError> {
+        if self.tail.remaining() == 0 {
+            return Ok(None);
+        }
+        match self.read_event() {
+            Ok(event) => Ok(Some(event)),
+            Err(error) => {
+                // Nothing after an invalid item can be trusted, so stop reading.
+                self.tail.set(&[]);
+                Err(error.into())
+            }
+        }
+    }
+
+    /// Reads the next whole value, i.e. an item together with its children, and deserializes it
+    /// into a `T`. Values which don't match `T` are returned as an [`ElementValue`], like the
+    /// elements of [`SequenceElements`][`crate::SequenceElements`].
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::{SchemaBuilder, TraceEvent};
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&vec![(1u32, 2u32), (3, 4)])?;
+    /// let schema = builder.build()?;
+    ///
+    /// let mut reader = schema.describe_trace_ref(&trace).reader();
+    /// assert_eq!(reader.next_event()?, Some(TraceEvent::Seq { len: 2 }));
+    /// assert_eq!(reader.read_value::<(u32, u32)>().unwrap(), (1, 2));
+    ///
+    /// // Stopping early leaves the second element unread.
+    /// let offset = reader.offset();
+    /// assert!(reader.remaining_bytes() > 0);
+    /// let error = reader.finish().unwrap_err();
+    /// assert_eq!(error.offset(), offset);
+    /// assert_eq!(error.next(), "Tuple { len: 2 }");
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn read_value<T>(&mut self) -> Result<T, ElementValue<'data>>
+    where
+        T: Deserialize<'data>,
+    {
+        read_value(self).unwrap_or_else(Err)
+    }
+
+    /// The offset of the next unread byte in the trace.
+    #[inline]
+    pub fn offset(&self) -> usize {
+        self.len - self.tail.remaining()
+    }
+
+    /// The number of bytes left to read, zero once the whole trace has been read.
+    #[inline]
+    pub fn remaining_bytes(&self) -> usize {
+        self.tail.remaining()
+    }
+
+    /// Checks that the whole trace has been read, returning a [`TrailingDataError`] describing
+    /// the unread data otherwise.
+    ///
+    /// Deserializing a value with [`read_value`][`Self::read_value`] then calling `finish`
+    /// detects traces holding more than the value, instead of silently ignoring the rest.
+    pub fn finish(mut self) -> Result<(), TrailingDataError> {
+        let (offset, remaining) = (self.offset(), self.remaining_bytes());
+        if remaining == 0 {
+            return Ok(());
+        }
+        let next = match self.next_event() {
+            Ok(Some(event)) => format!("{event:?}"),
+            Ok(None) => "nothing".to_owned(),
+            Err(error) => format!("an invalid item ({error})"),
+        };
+        Err(TrailingDataError {
+            offset,
+            remaining,
+            next: next.into(),
+        })
+    }
+
+    fn read_event(&self) -> Result<TraceEvent<'data>, DumpError> {
+        let schema = self.schema;
+        let tail = &self.tail;
+        let len =
+            |length: u32| usize::try_from(length).expect("usize needs to be at least 32 bits");
+        Ok(match tail.pop_trace_node::<DumpError>()? {
+            TraceNode::Bool => TraceEvent::Bool(tail.pop_bool::<DumpError>()?),
+            TraceNode::I8 => TraceEvent::I8(tail.pop_i8::<DumpError>()?),
+            TraceNode::I16 => TraceEvent::I16(tail.pop_i16::<DumpError>()?),
+            TraceNode::I32 => TraceEvent::I32(tail.pop_i32::<DumpError>()?),
+            TraceNode::I64 => TraceEvent::I64(tail.pop_i64::<DumpError>()?),
+            TraceNode::I128 => TraceEvent::I128(tail.pop_i128::<DumpError>()?),
+            TraceNode::U8 => TraceEvent::U8(tail.pop_u8::<DumpError>()?),
+            TraceNode::U16 => TraceEvent::U16(tail.pop_u16::<DumpError>()?),
+            TraceNode::U32 => TraceEvent::U32(tail.pop_u32::<DumpError>()?),
+            TraceNode::U64 => TraceEvent::U64(tail.pop_u64::<DumpError>()?),
+            TraceNode::U128 => TraceEvent::U128(tail.pop_u128::<DumpError>()?),
+            TraceNode::F32 => TraceEvent::F32(tail.pop_f32::<DumpError>()?),
+            TraceNode::F64 => TraceEvent::F64(tail.pop_f64::<DumpError>()?),
+            TraceNode::Char => TraceEvent::Char(tail.pop_char::<DumpError>()?),
+            TraceNode::String => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                TraceEvent::Str(tail.pop_str::<DumpError>(length)?)
+            }
+            TraceNode::Bytes => {
+                let length = tail.pop_length_u32::<DumpError>()?;
+                TraceEvent::Bytes(tail.pop_slice::<DumpError>(length)?)
+            }
+
+            TraceNode::None => TraceEvent::None,
+            TraceNode::Some => TraceEvent::Some,
+            TraceNode::Unit => TraceEvent::Unit,
+            TraceNode::UnitStruct(name) => TraceEvent::UnitStruct {
+                name: schema.type_name(name)?,
+            },
+            TraceNode::UnitVariant(name, variant) => TraceEvent::UnitVariant {
+                name: schema.type_name(name)?,
+                variant: schema.variant_name(variant)?,
+            },
+
+            TraceNode::NewtypeStruct(name) => TraceEvent::NewtypeStruct {
+                name: schema.type_name(name)?,
+            },
+            TraceNode::NewtypeVariant(name, variant) => TraceEvent::NewtypeVariant {
+                name: schema.type_name(name)?,
+                variant: schema.variant_name(variant)?,
+            },
+
+            TraceNode::Sequence => TraceEvent::Seq {
+                len: tail.pop_length_u32::<DumpError>()?,
+            },
+            TraceNode::Map => TraceEvent::Map {
+                len: tail.pop_length_u32::<DumpError>()?,
+            },
+
+            TraceNode::Tuple(length) => TraceEvent::Tuple { len: len(length) },
+            TraceNode::TupleStruct(length, name) => TraceEvent::TupleStruct {
+                name: schema.type_name(name)?,
+                len: len(length),
+            },
+            TraceNode::TupleVariant(length, name, variant) => TraceEvent::TupleVariant {
+                name: schema.type_name(name)?,
+                variant: schema.variant_name(variant)?,
+                len: len(length),
+            },
+
+            TraceNode::Struct(name, field_names) => TraceEvent::Struct {
+                name: schema.type_name(name)?,
+                fields: schema.pop_trace_field_names(tail, field_names)?,
+            },
+            TraceNode::StructVariant(name, variant, field_names) => TraceEvent::StructVariant {
+                name: schema.type_name(name)?,
+                variant: schema.variant_name(variant)?,
+                fields: schema.pop_trace_field_names(tail, field_names)?,
+            },
+        })
+    }
+}
+
+impl<'data> Iterator for TraceReader<'data> {
+    type Item = Result<TraceEvent<'data>, InvalidTraceError>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
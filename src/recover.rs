@@ -0,0 +1,163 @@
+use serde::{
+    Deserialize,
+    de::{DeserializeSeed, Deserializer, SeqAccess, Visitor},
+};
+use std::marker::PhantomData;
+use thiserror::Error;
+
+use crate::{DecodeOptions, DescribedBy, Schema};
+
+/// A sequence deserialized by [`Schema::deserialize_recovering`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recovered<T> {
+    /// The elements which were deserialized, in order.
+    pub elements: Vec<T>,
+
+    /// The elements which failed to deserialize, in order.
+    pub errors: Vec<ElementError>,
+}
+
+/// An element of a sequence which failed to deserialize, see [`Recovered`].
+#[derive(Clone, Debug, Eq, PartialEq, Error)]
+#[error("element {index}: {message}")]
+pub struct ElementError {
+    /// The position of the element in the sequence.
+    pub index: usize,
+
+    /// The error message.
+    pub message: String,
+}
+
+impl Schema {
+    /// Deserializes a sequence, skipping and reporting elements which fail to deserialize instead
+    /// of failing altogether.
+    ///
+    /// The rest of a failed element is skipped using the schema, so decoding carries on with the
+    /// next one. This recovers from elements which don't match `T`, e.g. records written by a
+    /// different version of a program. Corrupt data is reported the same way, but the format may
+    /// not find the start of the next element, failing the following ones too. Errors reading the
+    /// sequence itself are still returned.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::{Deserialize, Serialize};
+    /// use serde_describe::SchemaBuilder;
+    ///
+    /// #[derive(Serialize)]
+    /// enum Written {
+    ///     Small(u32),
+    ///     Large(u64),
+    /// }
+    ///
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// enum Read {
+    ///     Small(u32),
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let trace = builder.trace(&[Written::Small(1), Written::Large(2), Written::Small(3)])?;
+    /// let schema = builder.build()?;
+    /// let bytes = postcard::to_stdvec(&schema.describe_trace(trace))?;
+    ///
+    /// let recovered = schema
+    ///     .deserialize_recovering::<Read, _>(&mut postcard::Deserializer::from_bytes(&bytes))?;
+    /// assert_eq!(recovered.elements, [Read::Small(1), Read::Small(3)]);
+    /// assert_eq!(recovered.errors[0].index, 1);
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn deserialize_recovering<'de, DeserializeT, DeserializerT>(
+        &self,
+        deserializer: DeserializerT,
+    ) -> Result<Recovered<DeserializeT>, DeserializerT::Error>
+    where
+        DeserializeT: Deserialize<'de>,
+        DeserializerT: Deserializer<'de>,
+    {
+        self.deserialize_recovering_with_options(DecodeOptions::default(), deserializer)
+    }
+
+    /// Like [`Self::deserialize_recovering`], with the given [`DecodeOptions`].
+    pub fn deserialize_recovering_with_options<'de, DeserializeT, DeserializerT>(
+        &self,
+        options: DecodeOptions,
+        deserializer: DeserializerT,
+    ) -> Result<Recovered<DeserializeT>, DeserializerT::Error>
+    where
+        DeserializeT: Deserialize<'de>,
+        DeserializerT: Deserializer<'de>,
+    {
+        let options = DecodeOptions {
+            skip_failed_values: true,
+            ..options
+        };
+        Ok(DescribedBy(RecoveringSeq(PhantomData), self)
+            .with_options(options)
+            .deserialize(deserializer)?
+            .0)
+    }
+}
+
+struct RecoveringSeq<T>(PhantomData<T>);
+
+impl<'de, T> DeserializeSeed<'de> for RecoveringSeq<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Recovered<T>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for RecoveringSeq<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Recovered<T>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut recovered = Recovered {
+            elements: Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096)),
+            errors: Vec::new(),
+        };
+        let mut index = 0;
+        while let Some(element) = seq.next_element_seed(Attempt(PhantomData))? {
+            match element {
+                Ok(element) => recovered.elements.push(element),
+                Err(message) => recovered.errors.push(ElementError { index, message }),
+            }
+            index += 1;
+        }
+        Ok(recovered)
+    }
+}
+
+/// Deserializes a `T`, turning errors into values such that the sequence carries on.
+struct Attempt<T>(PhantomData<T>);
+
+impl<'de, T> DeserializeSeed<'de> for Attempt<T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Result<T, String>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(T::deserialize(deserializer).map_err(|error: D::Error| error.to_string()))
+    }
+}
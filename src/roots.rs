@@ -0,0 +1,227 @@
+use serde::{
+    Deserializer, Serialize, Serializer,
+    de::{DeserializeSeed, EnumAccess, VariantAccess, Visitor},
+};
+use std::marker::PhantomData;
+
+use crate::{DescribedBy, Schema, SchemaBuilder, Trace, TraceError, schema::SchemaNode};
+
+/// The name of the enum named roots are traced as, see [`SchemaBuilder::trace_root`].
+const ROOT_TYPE_NAME: &str = "serde_describe::Root";
+
+impl SchemaBuilder {
+    /// Like [`trace`][`Self::trace`], but records the value under a named root.
+    ///
+    /// This allows a single schema to describe several unrelated message types (e.g. a service's
+    /// requests and responses), sharing the names and types they have in common.
+    ///
+    /// The name of the root is part of the value, and the value must be deserialized with
+    /// [`Schema::describe_root`], or as an enum with a newtype variant for each root.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::{Deserialize, Serialize, de::DeserializeSeed};
+    /// use serde_describe::{DescribedBy, SchemaBuilder};
+    ///
+    /// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    /// struct Request {
+    ///     id: u32,
+    /// }
+    ///
+    /// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    /// struct Response {
+    ///     id: u32,
+    ///     body: String,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new();
+    /// let request = builder.trace_root("Request", &Request { id: 1 })?;
+    /// let response = builder.trace_root(
+    ///     "Response",
+    ///     &Response { id: 1, body: "hello".to_owned() },
+    /// )?;
+    /// let schema = builder.build()?;
+    /// assert_eq!(schema.roots(), ["Request", "Response"]);
+    ///
+    /// let request = postcard::to_stdvec(&schema.describe_trace(request))?;
+    /// let response = postcard::to_stdvec(&schema.describe_trace(response))?;
+    ///
+    /// // Deserialize a known root.
+    /// let DescribedBy(roundtripped, _) = schema
+    ///     .describe_root::<Request>("Request")
+    ///     .deserialize(&mut postcard::Deserializer::from_bytes(&request))?;
+    /// assert_eq!(roundtripped, Request { id: 1 });
+    ///
+    /// // Or dispatch on the root's name.
+    /// #[derive(Debug, PartialEq, Deserialize)]
+    /// enum Message {
+    ///     Request(Request),
+    ///     Response(Response),
+    /// }
+    /// let DescribedBy(roundtripped, _) = schema
+    ///     .describe_type::<Message>()
+    ///     .deserialize(&mut postcard::Deserializer::from_bytes(&response))?;
+    /// assert_eq!(
+    ///     roundtripped,
+    ///     Message::Response(Response { id: 1, body: "hello".to_owned() }),
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn trace_root<ValueT>(
+        &mut self,
+        root: &'static str,
+        value: &ValueT,
+    ) -> Result<Trace, TraceError>
+    where
+        ValueT: Serialize,
+    {
+        self.trace(&NamedRoot { root, value })
+    }
+}
+
+impl Schema {
+    /// The names of the roots recorded with [`SchemaBuilder::trace_root`].
+    pub fn roots(&self) -> Vec<&str> {
+        let root_name = |node| match node {
+            SchemaNode::NewtypeVariant(name, variant, _)
+                if self.type_name(name).ok() == Some(ROOT_TYPE_NAME) =>
+            {
+                self.variant_name(variant).ok()
+            }
+            _ => None,
+        };
+        match self.node(self.root_index) {
+            Ok(SchemaNode::Union(variants)) => self
+                .node_list(variants)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|&variant| self.node(variant).ok().and_then(root_name))
+                .collect(),
+            Ok(node) => root_name(node).into_iter().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Like [`describe_type`][`Self::describe_type`], but for values traced with
+    /// [`SchemaBuilder::trace_root`] under the given root.
+    ///
+    /// Deserializing a value traced under a different root is an error.
+    pub fn describe_root<'schema, 'root, 'de, T>(
+        &'schema self,
+        root: &'root str,
+    ) -> DescribedBy<'schema, RootSeed<'root, PhantomData<T>>>
+    where
+        T: serde::Deserialize<'de>,
+    {
+        self.describe_root_seed(root, PhantomData)
+    }
+
+    /// Like [`describe_seed`][`Self::describe_seed`], but for values traced with
+    /// [`SchemaBuilder::trace_root`] under the given root.
+    pub fn describe_root_seed<'schema, 'root, 'de, SeedT>(
+        &'schema self,
+        root: &'root str,
+        seed: SeedT,
+    ) -> DescribedBy<'schema, RootSeed<'root, SeedT>>
+    where
+        SeedT: DeserializeSeed<'de>,
+    {
+        DescribedBy(RootSeed { root, seed }, self)
+    }
+}
+
+/// Deserializes a value traced with [`SchemaBuilder::trace_root`], returned by
+/// [`Schema::describe_root`] and [`Schema::describe_root_seed`].
+#[derive(Copy, Clone, Debug)]
+pub struct RootSeed<'root, SeedT> {
+    root: &'root str,
+    seed: SeedT,
+}
+
+impl<'de, SeedT> DeserializeSeed<'de> for RootSeed<'_, SeedT>
+where
+    SeedT: DeserializeSeed<'de>,
+{
+    type Value = SeedT::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_enum(ROOT_TYPE_NAME, &[], self)
+    }
+}
+
+impl<'de, SeedT> Visitor<'de> for RootSeed<'_, SeedT>
+where
+    SeedT: DeserializeSeed<'de>,
+{
+    type Value = SeedT::Value;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a value traced under the root `{}`", self.root)
+    }
+
+    fn visit_enum<A>(self, data: A) -> Result<Self::Value, A::Error>
+    where
+        A: EnumAccess<'de>,
+    {
+        let ((), variant) = data.variant_seed(RootName(self.root))?;
+        variant.newtype_variant_seed(self.seed)
+    }
+}
+
+/// Checks that the root of a value is the expected one.
+struct RootName<'root>(&'root str);
+
+impl<'de> DeserializeSeed<'de> for RootName<'_> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl<'de> Visitor<'de> for RootName<'_> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "the root `{}`", self.0)
+    }
+
+    fn visit_str<E>(self, root: &str) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        if root == self.0 {
+            Ok(())
+        } else {
+            Err(E::custom(format_args!(
+                "expected a value traced under the root `{}`, found `{root}`",
+                self.0
+            )))
+        }
+    }
+}
+
+struct NamedRoot<'a, ValueT> {
+    root: &'static str,
+    value: &'a ValueT,
+}
+
+impl<ValueT> Serialize for NamedRoot<'_, ValueT>
+where
+    ValueT: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_variant(ROOT_TYPE_NAME, 0, self.root, self.value)
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Deserializer, Serialize, de::DeserializeSeed};
-use std::{hash::Hash, marker::PhantomData};
+use std::{borrow::Cow, hash::Hash, marker::PhantomData};
 use thiserror::Error;
 
 use crate::{
@@ -8,6 +8,7 @@ use crate::{
         FieldNameIndex, FieldNameListIndex, IndexIsEmpty, IsEmpty, MemberIndex, MemberListIndex,
         SchemaNodeIndex, SchemaNodeListIndex, TypeNameIndex, VariantNameIndex,
     },
+    name_hash::{hex_name, unhex_name},
     pool::{ReadonlyNonEmptyPool, ReadonlyPool},
     trace::Trace,
 };
@@ -25,16 +26,122 @@ use crate::{
 #[derive(Clone, Debug)]
 pub struct Schema {
     pub(crate) root_index: SchemaNodeIndex,
+    // Serializers and deserializers interpret the pools directly: nodes are `Copy` and lists are
+    // borrowed as slices, so each step is an indexed load. They aren't flattened into a separate
+    // per-root representation, since interning shares subtrees between nodes, and inlining them
+    // could grow exponentially with nesting, while keeping them shared brings the indices back.
     pub(crate) nodes: ReadonlyPool<SchemaNode, SchemaNodeIndex>,
     pub(crate) node_lists: ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
     pub(crate) member_lists: ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
     pub(crate) field_name_lists: ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
-    pub(crate) field_names: ReadonlyNonEmptyPool<Box<str>, FieldNameIndex>,
-    pub(crate) variant_names: ReadonlyNonEmptyPool<Box<str>, VariantNameIndex>,
-    pub(crate) type_names: ReadonlyNonEmptyPool<Box<str>, TypeNameIndex>,
+    // Names are borrowed from the traced types' `&'static str`s, so building a schema doesn't
+    // copy them; deserialized schemas own their names instead.
+    pub(crate) field_names: ReadonlyNonEmptyPool<Cow<'static, str>, FieldNameIndex>,
+    pub(crate) variant_names: ReadonlyNonEmptyPool<Cow<'static, str>, VariantNameIndex>,
+    pub(crate) type_names: ReadonlyNonEmptyPool<Cow<'static, str>, TypeNameIndex>,
+    /// Whether the data was traced in human-readable mode, see
+    /// [`SchemaBuilder::human_readable`][`crate::SchemaBuilder::human_readable`].
+    pub(crate) human_readable: bool,
+    /// See [`SchemaBuilder::version`][`crate::SchemaBuilder::version`].
+    pub(crate) version: u32,
+    /// See [`SchemaBuilder::min_reader_version`][`crate::SchemaBuilder::min_reader_version`].
+    pub(crate) min_reader_version: u32,
+    /// Whether names are replaced by their hashes, see [`Schema::hash_names`].
+    pub(crate) hashed_names: bool,
+}
+
+/// Returned by [`Schema::check_reader_version`] when data requires a newer reader.
+#[derive(Clone, Debug, Error)]
+#[error(
+    "schema version {version} requires reader version {min_reader_version} or newer, but this \
+     reader is version {reader_version}"
+)]
+pub struct ReaderVersionError {
+    version: u32,
+    min_reader_version: u32,
+    reader_version: u32,
+}
+
+impl ReaderVersionError {
+    /// The version of the schema, see [`Schema::version`].
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The oldest reader version which may read the schema, see [`Schema::min_reader_version`].
+    #[inline]
+    pub fn min_reader_version(&self) -> u32 {
+        self.min_reader_version
+    }
+
+    /// The version of the reader which was refused.
+    #[inline]
+    pub fn reader_version(&self) -> u32 {
+        self.reader_version
+    }
 }
 
 impl Schema {
+    /// Whether the data described by this schema was traced in human-readable mode, see
+    /// [`SchemaBuilder::human_readable`][`crate::SchemaBuilder::human_readable`].
+    ///
+    /// Data is always deserialized in the mode it was traced in, whatever the underlying format.
+    #[inline]
+    pub fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+
+    /// The application-defined version of this schema, see
+    /// [`SchemaBuilder::version`][`crate::SchemaBuilder::version`]. Zero unless set.
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The oldest application-defined reader version which may read data described by this
+    /// schema, see [`SchemaBuilder::min_reader_version`][`crate::SchemaBuilder::min_reader_version`].
+    /// Zero unless set.
+    #[inline]
+    pub fn min_reader_version(&self) -> u32 {
+        self.min_reader_version
+    }
+
+    /// Checks whether a reader of the given version may read data described by this schema,
+    /// i.e. whether `reader_version` is at least [`min_reader_version`][`Self::min_reader_version`].
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde_describe::{Schema, SchemaBuilder};
+    ///
+    /// // Version 3 of the data added a field which version 1 readers would silently drop.
+    /// let mut builder = SchemaBuilder::new().version(3).min_reader_version(2);
+    /// let _ = builder.trace(&(1u32, "name"))?;
+    /// let schema = builder.build()?;
+    ///
+    /// let schema: Schema = postcard::from_bytes(&postcard::to_stdvec(&schema)?)?;
+    /// assert_eq!(schema.version(), 3);
+    /// assert!(schema.check_reader_version(2).is_ok());
+    /// assert_eq!(
+    ///     schema.check_reader_version(1).unwrap_err().to_string(),
+    ///     "schema version 3 requires reader version 2 or newer, but this reader is version 1",
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn check_reader_version(&self, reader_version: u32) -> Result<(), ReaderVersionError> {
+        if reader_version >= self.min_reader_version {
+            Ok(())
+        } else {
+            Err(ReaderVersionError {
+                version: self.version,
+                min_reader_version: self.min_reader_version,
+                reader_version,
+            })
+        }
+    }
+
     /// Deserializes a value that was previously serialized with [`Self::describe_trace`].
     ///
     /// If you don't need a shared schema, use the much simpler [`crate::SelfDescribed`] wrapper
@@ -290,6 +397,13 @@ impl IsEmpty for SchemaNode {
     }
 }
 
+/// Resolves the names of nodes which are still being built.
+pub(crate) trait SchemaNames {
+    fn type_name(&self, index: TypeNameIndex) -> &str;
+    fn variant_name(&self, index: VariantNameIndex) -> &str;
+    fn field_names(&self, index: FieldNameListIndex) -> Vec<&str>;
+}
+
 #[derive(Serialize)]
 enum VersionedSchemaSerializeProxy<'a> {
     V0 {
@@ -298,9 +412,52 @@ enum VersionedSchemaSerializeProxy<'a> {
         node_lists: &'a ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
         member_lists: &'a ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
         field_name_lists: &'a ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
-        field_names: &'a ReadonlyNonEmptyPool<Box<str>, FieldNameIndex>,
-        variant_names: &'a ReadonlyNonEmptyPool<Box<str>, VariantNameIndex>,
-        type_names: &'a ReadonlyNonEmptyPool<Box<str>, TypeNameIndex>,
+        field_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, FieldNameIndex>,
+        variant_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, VariantNameIndex>,
+        type_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, TypeNameIndex>,
+    },
+    /// Added for schemas traced in human-readable mode. Other schemas are still serialized as
+    /// `V0`, keeping their serialized form, and fingerprint, unchanged.
+    V1 {
+        root_index: SchemaNodeIndex,
+        nodes: &'a ReadonlyPool<SchemaNode, SchemaNodeIndex>,
+        node_lists: &'a ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
+        member_lists: &'a ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
+        field_name_lists: &'a ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
+        field_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, FieldNameIndex>,
+        variant_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, VariantNameIndex>,
+        type_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, TypeNameIndex>,
+        human_readable: bool,
+    },
+    /// Added for schemas with a version, see [`Schema::version`]. Unversioned schemas are still
+    /// serialized as `V0` or `V1`.
+    V2 {
+        root_index: SchemaNodeIndex,
+        nodes: &'a ReadonlyPool<SchemaNode, SchemaNodeIndex>,
+        node_lists: &'a ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
+        member_lists: &'a ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
+        field_name_lists: &'a ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
+        field_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, FieldNameIndex>,
+        variant_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, VariantNameIndex>,
+        type_names: &'a ReadonlyNonEmptyPool<Cow<'static, str>, TypeNameIndex>,
+        human_readable: bool,
+        version: u32,
+        min_reader_version: u32,
+    },
+    /// Added for schemas with hashed names, see [`Schema::hash_names`], which store each name as
+    /// the 8 bytes of its hash. Other schemas are still serialized as `V0`, `V1` or `V2`.
+    V3 {
+        root_index: SchemaNodeIndex,
+        nodes: &'a ReadonlyPool<SchemaNode, SchemaNodeIndex>,
+        node_lists: &'a ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
+        member_lists: &'a ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
+        field_name_lists: &'a ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
+        field_names: ReadonlyNonEmptyPool<[u8; 8], FieldNameIndex>,
+        variant_names: ReadonlyNonEmptyPool<[u8; 8], VariantNameIndex>,
+        type_names: ReadonlyNonEmptyPool<[u8; 8], TypeNameIndex>,
+        human_readable: bool,
+        version: u32,
+        min_reader_version: u32,
     },
 }
 
@@ -312,9 +469,46 @@ enum VersionedSchemaDeserializeProxy {
         node_lists: ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
         member_lists: ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
         field_name_lists: ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
-        field_names: ReadonlyNonEmptyPool<Box<str>, FieldNameIndex>,
-        variant_names: ReadonlyNonEmptyPool<Box<str>, VariantNameIndex>,
-        type_names: ReadonlyNonEmptyPool<Box<str>, TypeNameIndex>,
+        field_names: ReadonlyNonEmptyPool<Cow<'static, str>, FieldNameIndex>,
+        variant_names: ReadonlyNonEmptyPool<Cow<'static, str>, VariantNameIndex>,
+        type_names: ReadonlyNonEmptyPool<Cow<'static, str>, TypeNameIndex>,
+    },
+    V1 {
+        root_index: SchemaNodeIndex,
+        nodes: ReadonlyPool<SchemaNode, SchemaNodeIndex>,
+        node_lists: ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
+        member_lists: ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
+        field_name_lists: ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
+        field_names: ReadonlyNonEmptyPool<Cow<'static, str>, FieldNameIndex>,
+        variant_names: ReadonlyNonEmptyPool<Cow<'static, str>, VariantNameIndex>,
+        type_names: ReadonlyNonEmptyPool<Cow<'static, str>, TypeNameIndex>,
+        human_readable: bool,
+    },
+    V2 {
+        root_index: SchemaNodeIndex,
+        nodes: ReadonlyPool<SchemaNode, SchemaNodeIndex>,
+        node_lists: ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
+        member_lists: ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
+        field_name_lists: ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
+        field_names: ReadonlyNonEmptyPool<Cow<'static, str>, FieldNameIndex>,
+        variant_names: ReadonlyNonEmptyPool<Cow<'static, str>, VariantNameIndex>,
+        type_names: ReadonlyNonEmptyPool<Cow<'static, str>, TypeNameIndex>,
+        human_readable: bool,
+        version: u32,
+        min_reader_version: u32,
+    },
+    V3 {
+        root_index: SchemaNodeIndex,
+        nodes: ReadonlyPool<SchemaNode, SchemaNodeIndex>,
+        node_lists: ReadonlyPool<Box<[SchemaNodeIndex]>, SchemaNodeListIndex>,
+        member_lists: ReadonlyPool<Box<[MemberIndex]>, MemberListIndex>,
+        field_name_lists: ReadonlyNonEmptyPool<Box<[FieldNameIndex]>, FieldNameListIndex>,
+        field_names: ReadonlyNonEmptyPool<[u8; 8], FieldNameIndex>,
+        variant_names: ReadonlyNonEmptyPool<[u8; 8], VariantNameIndex>,
+        type_names: ReadonlyNonEmptyPool<[u8; 8], TypeNameIndex>,
+        human_readable: bool,
+        version: u32,
+        min_reader_version: u32,
     },
 }
 
@@ -323,17 +517,64 @@ impl Serialize for Schema {
     where
         S: serde::Serializer,
     {
-        VersionedSchemaSerializeProxy::V0 {
-            root_index: self.root_index,
-            nodes: &self.nodes,
-            node_lists: &self.node_lists,
-            member_lists: &self.member_lists,
-            field_name_lists: &self.field_name_lists,
-            field_names: &self.field_names,
-            variant_names: &self.variant_names,
-            type_names: &self.type_names,
+        if self.hashed_names {
+            VersionedSchemaSerializeProxy::V3 {
+                root_index: self.root_index,
+                nodes: &self.nodes,
+                node_lists: &self.node_lists,
+                member_lists: &self.member_lists,
+                field_name_lists: &self.field_name_lists,
+                field_names: self.field_names.map(|name| unhex_name(name).to_le_bytes()),
+                variant_names: self
+                    .variant_names
+                    .map(|name| unhex_name(name).to_le_bytes()),
+                type_names: self.type_names.map(|name| unhex_name(name).to_le_bytes()),
+                human_readable: self.human_readable,
+                version: self.version,
+                min_reader_version: self.min_reader_version,
+            }
+            .serialize(serializer)
+        } else if self.version != 0 || self.min_reader_version != 0 {
+            VersionedSchemaSerializeProxy::V2 {
+                root_index: self.root_index,
+                nodes: &self.nodes,
+                node_lists: &self.node_lists,
+                member_lists: &self.member_lists,
+                field_name_lists: &self.field_name_lists,
+                field_names: &self.field_names,
+                variant_names: &self.variant_names,
+                type_names: &self.type_names,
+                human_readable: self.human_readable,
+                version: self.version,
+                min_reader_version: self.min_reader_version,
+            }
+            .serialize(serializer)
+        } else if self.human_readable {
+            VersionedSchemaSerializeProxy::V1 {
+                root_index: self.root_index,
+                nodes: &self.nodes,
+                node_lists: &self.node_lists,
+                member_lists: &self.member_lists,
+                field_name_lists: &self.field_name_lists,
+                field_names: &self.field_names,
+                variant_names: &self.variant_names,
+                type_names: &self.type_names,
+                human_readable: true,
+            }
+            .serialize(serializer)
+        } else {
+            VersionedSchemaSerializeProxy::V0 {
+                root_index: self.root_index,
+                nodes: &self.nodes,
+                node_lists: &self.node_lists,
+                member_lists: &self.member_lists,
+                field_name_lists: &self.field_name_lists,
+                field_names: &self.field_names,
+                variant_names: &self.variant_names,
+                type_names: &self.type_names,
+            }
+            .serialize(serializer)
         }
-        .serialize(serializer)
     }
 }
 
@@ -361,6 +602,86 @@ impl<'de> Deserialize<'de> for Schema {
                 field_names,
                 variant_names,
                 type_names,
+                human_readable: false,
+                version: 0,
+                min_reader_version: 0,
+                hashed_names: false,
+            }),
+            VersionedSchemaDeserializeProxy::V1 {
+                root_index,
+                nodes,
+                node_lists,
+                member_lists,
+                field_name_lists,
+                field_names,
+                variant_names,
+                type_names,
+                human_readable,
+            } => Ok(Self {
+                root_index,
+                nodes,
+                node_lists,
+                member_lists,
+                field_name_lists,
+                field_names,
+                variant_names,
+                type_names,
+                human_readable,
+                version: 0,
+                min_reader_version: 0,
+                hashed_names: false,
+            }),
+            VersionedSchemaDeserializeProxy::V2 {
+                root_index,
+                nodes,
+                node_lists,
+                member_lists,
+                field_name_lists,
+                field_names,
+                variant_names,
+                type_names,
+                human_readable,
+                version,
+                min_reader_version,
+            } => Ok(Self {
+                root_index,
+                nodes,
+                node_lists,
+                member_lists,
+                field_name_lists,
+                field_names,
+                variant_names,
+                type_names,
+                human_readable,
+                version,
+                min_reader_version,
+                hashed_names: false,
+            }),
+            VersionedSchemaDeserializeProxy::V3 {
+                root_index,
+                nodes,
+                node_lists,
+                member_lists,
+                field_name_lists,
+                field_names,
+                variant_names,
+                type_names,
+                human_readable,
+                version,
+                min_reader_version,
+            } => Ok(Self {
+                root_index,
+                nodes,
+                node_lists,
+                member_lists,
+                field_name_lists,
+                field_names: field_names.map(|&hash| hex_name(u64::from_le_bytes(hash))),
+                variant_names: variant_names.map(|&hash| hex_name(u64::from_le_bytes(hash))),
+                type_names: type_names.map(|&hash| hex_name(u64::from_le_bytes(hash))),
+                human_readable,
+                version,
+                min_reader_version,
+                hashed_names: true,
             }),
         }
     }
@@ -0,0 +1,435 @@
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, KeyInit, Nonce,
+    aead::{Aead, AeadCore, OsRng, Payload},
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+
+use crate::{
+    DetachedTrace, Dynamic, DynamicFields, Schema, SchemaFingerprint, SchemaMismatchError,
+};
+
+const NONCE_LENGTH: usize = 12;
+
+/// Sealed values are padded with zeros to a multiple of this many bytes, such that the length of
+/// the ciphertext doesn't tell small values apart, e.g. `None` from `Some(true)`.
+const SEALED_PADDING: usize = 32;
+
+/// The version of the encoding of sealed and hashed values, written before them, such that a
+/// later encoding can tell them apart. Changing the encoding changes every value's hash.
+const VALUE_ENCODING_VERSION: u8 = 1;
+
+/// A 256-bit key for sealing traces into [`SealedTrace`]-s.
+#[derive(Clone)]
+pub struct SealingKey([u8; 32]);
+
+impl SealingKey {
+    /// Wraps the raw bytes of a key, e.g. one loaded from a key management system.
+    #[inline]
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Generates a random key using the operating system's random number generator.
+    pub fn generate() -> Self {
+        Self(ChaCha20Poly1305::generate_key(&mut OsRng).into())
+    }
+
+    /// Decrypts a value encrypted by a [`Protection::Encrypt`][`crate::Protection::Encrypt`]
+    /// policy, which is recorded as a byte string.
+    pub fn open_value(&self, sealed: &[u8]) -> Result<Dynamic, OpenSealedError> {
+        if sealed.len() < NONCE_LENGTH {
+            return Err(OpenSealedError);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        let encoded = self
+            .cipher()
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| OpenSealedError)?;
+        let Some((&VALUE_ENCODING_VERSION, mut tail)) = encoded.split_first() else {
+            return Err(OpenSealedError);
+        };
+        let value = decode_dynamic(&mut tail).ok_or(OpenSealedError)?;
+        if tail.iter().any(|&padding| padding != 0) {
+            return Err(OpenSealedError);
+        }
+        Ok(value)
+    }
+
+    /// Encrypts a value, padded to a multiple of [`SEALED_PADDING`] bytes, using a random
+    /// nonce, prepended to the result.
+    pub(crate) fn seal_value(&self, value: &Dynamic) -> Vec<u8> {
+        let mut encoded = vec![VALUE_ENCODING_VERSION];
+        encode_dynamic(value, &mut encoded);
+        encoded.resize(encoded.len().next_multiple_of(SEALED_PADDING), 0);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, &*encoded)
+            .expect("values are shorter than the cipher's limit of 256GiB");
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        sealed
+    }
+
+    /// Hashes a value with HMAC-SHA256, keyed by a key derived from this one, such that hashes
+    /// don't reveal anything about the encryption key.
+    pub(crate) fn hash_value(&self, value: &Dynamic) -> [u8; 32] {
+        let mut derive =
+            <Hmac<Sha256> as Mac>::new_from_slice(&self.0).expect("HMAC takes any key");
+        derive.update(b"serde_describe value hash");
+        let hash_key = derive.finalize().into_bytes();
+        let mut hash =
+            <Hmac<Sha256> as Mac>::new_from_slice(&hash_key).expect("HMAC takes any key");
+        let mut encoded = vec![VALUE_ENCODING_VERSION];
+        encode_dynamic(value, &mut encoded);
+        hash.update(&encoded);
+        hash.finalize().into_bytes().into()
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.0))
+    }
+}
+
+impl std::fmt::Debug for SealingKey {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter.write_str("SealingKey(..)")
+    }
+}
+
+/// A [`DetachedTrace`] encrypted with ChaCha20-Poly1305, returned by [`DetachedTrace::seal`].
+///
+/// The fingerprint of the schema stays in plaintext (and is authenticated), and so can the schema
+/// itself (see [`with_plaintext_schema`][`Self::with_plaintext_schema`]), such that sealed traces
+/// can be routed, or their shape inspected, without the key. Only the data is encrypted.
+///
+/// Like [`DetachedTrace`], it implements [`Serialize`] and [`Deserialize`].
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{SchemaBuilder, SealedTrace, SealingKey};
+///
+/// let mut builder = SchemaBuilder::new();
+/// let trace = builder.trace(&("ana@example.com", 30u32))?;
+/// let schema = builder.build()?;
+///
+/// let key = SealingKey::generate();
+/// let sealed = schema
+///     .describe_trace(trace)
+///     .detach()
+///     .seal(&key)
+///     .with_plaintext_schema(schema.clone())?;
+/// let bytes = postcard::to_stdvec(&sealed)?;
+///
+/// // The schema can be read without the key, but the data can't.
+/// let sealed: SealedTrace = postcard::from_bytes(&bytes)?;
+/// let schema = sealed.schema().expect("sealed with its schema");
+/// assert_eq!(schema.to_string(), "tuple_3(str_1, u32_2)");
+/// assert!(sealed.open(&SealingKey::generate()).is_err());
+///
+/// let described = schema.bind(sealed.open(&key)?)?;
+/// assert_eq!(described.to_string(), r#"("ana@example.com", 30)"#);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SealedTrace {
+    fingerprint: SchemaFingerprint,
+    schema: Option<Schema>,
+    nonce: [u8; 12],
+    ciphertext: Vec<u8>,
+}
+
+/// Returned by [`SealedTrace::open`] and [`SealingKey::open_value`] when the key is wrong or the
+/// sealed data was tampered with.
+#[derive(Clone, Debug, Error)]
+#[error("failed to open sealed data: wrong key, or corrupted data")]
+pub struct OpenSealedError;
+
+impl DetachedTrace {
+    /// Encrypts the trace with `key`, using a random nonce, see [`SealedTrace`].
+    pub fn seal(self, key: &SealingKey) -> SealedTrace {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = key
+            .cipher()
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: &self.trace,
+                    aad: &self.fingerprint.0.to_le_bytes(),
+                },
+            )
+            .expect("traces are shorter than the cipher's limit of 256GiB");
+        SealedTrace {
+            fingerprint: self.fingerprint,
+            schema: None,
+            nonce: nonce.into(),
+            ciphertext,
+        }
+    }
+}
+
+impl SealedTrace {
+    /// The fingerprint of the schema the sealed trace was produced with.
+    #[inline]
+    pub fn fingerprint(&self) -> SchemaFingerprint {
+        self.fingerprint
+    }
+
+    /// The schema stored in plaintext with the sealed trace, if any.
+    #[inline]
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    /// Stores `schema` in plaintext alongside the encrypted data, e.g. for routing.
+    ///
+    /// Returns an error if the trace was produced with a different schema.
+    pub fn with_plaintext_schema(mut self, schema: Schema) -> Result<Self, SchemaMismatchError> {
+        let fingerprint = schema.fingerprint();
+        if fingerprint != self.fingerprint {
+            return Err(SchemaMismatchError {
+                trace: self.fingerprint,
+                schema: fingerprint,
+            });
+        }
+        self.schema = Some(schema);
+        Ok(self)
+    }
+
+    /// Decrypts the trace with `key`, to be bound to its schema with [`Schema::bind`].
+    pub fn open(&self, key: &SealingKey) -> Result<DetachedTrace, OpenSealedError> {
+        let trace = key
+            .cipher()
+            .decrypt(
+                Nonce::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: &self.fingerprint.0.to_le_bytes(),
+                },
+            )
+            .map_err(|_| OpenSealedError)?;
+        Ok(DetachedTrace {
+            fingerprint: self.fingerprint,
+            trace,
+        })
+    }
+}
+
+/// Encodes a value as a tag byte, followed by its little-endian or UTF-8 bytes, and by the
+/// length-prefixed names and contents of compound values.
+fn encode_dynamic(value: &Dynamic, encoded: &mut Vec<u8>) {
+    let mut push = |tag: u8, bytes: &[u8]| {
+        encoded.push(tag);
+        encoded.extend_from_slice(bytes);
+    };
+    match value {
+        Dynamic::Bool(value) => push(0, &[u8::from(*value)]),
+        Dynamic::I8(value) => push(1, &value.to_le_bytes()),
+        Dynamic::I16(value) => push(2, &value.to_le_bytes()),
+        Dynamic::I32(value) => push(3, &value.to_le_bytes()),
+        Dynamic::I64(value) => push(4, &value.to_le_bytes()),
+        Dynamic::I128(value) => push(5, &value.to_le_bytes()),
+        Dynamic::U8(value) => push(6, &value.to_le_bytes()),
+        Dynamic::U16(value) => push(7, &value.to_le_bytes()),
+        Dynamic::U32(value) => push(8, &value.to_le_bytes()),
+        Dynamic::U64(value) => push(9, &value.to_le_bytes()),
+        Dynamic::U128(value) => push(10, &value.to_le_bytes()),
+        Dynamic::F32(value) => push(11, &value.to_le_bytes()),
+        Dynamic::F64(value) => push(12, &value.to_le_bytes()),
+        Dynamic::Char(value) => push(13, &u32::from(*value).to_le_bytes()),
+        Dynamic::Str(value) => {
+            push(14, &[]);
+            encode_bytes(value.as_bytes(), encoded);
+        }
+        Dynamic::Bytes(value) => {
+            push(15, &[]);
+            encode_bytes(value, encoded);
+        }
+        Dynamic::Unit => push(16, &[]),
+        Dynamic::None => push(17, &[]),
+        Dynamic::Some(value) => {
+            push(18, &[]);
+            encode_dynamic(value, encoded);
+        }
+        Dynamic::Seq(items) => {
+            push(19, &[]);
+            encode_items(items, encoded);
+        }
+        Dynamic::Tuple(items) => {
+            push(20, &[]);
+            encode_items(items, encoded);
+        }
+        Dynamic::Map(entries) => {
+            push(21, &[]);
+            encode_length(entries.len(), encoded);
+            for (key, value) in entries {
+                encode_dynamic(key, encoded);
+                encode_dynamic(value, encoded);
+            }
+        }
+        Dynamic::Struct { name, fields } => {
+            push(22, &[]);
+            encode_bytes(name.as_bytes(), encoded);
+            encode_fields(fields, encoded);
+        }
+        Dynamic::Variant {
+            name,
+            variant,
+            fields,
+        } => {
+            push(23, &[]);
+            encode_bytes(name.as_bytes(), encoded);
+            encode_bytes(variant.as_bytes(), encoded);
+            encode_fields(fields, encoded);
+        }
+    }
+}
+
+fn encode_fields(fields: &DynamicFields, encoded: &mut Vec<u8>) {
+    match fields {
+        DynamicFields::Unit => encoded.push(0),
+        DynamicFields::Newtype(value) => {
+            encoded.push(1);
+            encode_dynamic(value, encoded);
+        }
+        DynamicFields::Tuple(items) => {
+            encoded.push(2);
+            encode_items(items, encoded);
+        }
+        DynamicFields::Named(fields) => {
+            encoded.push(3);
+            encode_length(fields.len(), encoded);
+            for (name, value) in fields {
+                encode_bytes(name.as_bytes(), encoded);
+                match value {
+                    Some(value) => {
+                        encoded.push(1);
+                        encode_dynamic(value, encoded);
+                    }
+                    None => encoded.push(0),
+                }
+            }
+        }
+    }
+}
+
+fn encode_items(items: &[Dynamic], encoded: &mut Vec<u8>) {
+    encode_length(items.len(), encoded);
+    for item in items {
+        encode_dynamic(item, encoded);
+    }
+}
+
+fn encode_bytes(bytes: &[u8], encoded: &mut Vec<u8>) {
+    encode_length(bytes.len(), encoded);
+    encoded.extend_from_slice(bytes);
+}
+
+fn encode_length(length: usize, encoded: &mut Vec<u8>) {
+    let length = u32::try_from(length).expect("sealed values are shorter than 4GiB");
+    encoded.extend_from_slice(&length.to_le_bytes());
+}
+
+/// The inverse of [`encode_dynamic`], consuming the value from the front of `tail`.
+fn decode_dynamic(tail: &mut &[u8]) -> Option<Dynamic> {
+    Some(match take_byte(tail)? {
+        0 => Dynamic::Bool(match take_byte(tail)? {
+            0 => false,
+            1 => true,
+            _ => return None,
+        }),
+        1 => Dynamic::I8(i8::from_le_bytes(take_array(tail)?)),
+        2 => Dynamic::I16(i16::from_le_bytes(take_array(tail)?)),
+        3 => Dynamic::I32(i32::from_le_bytes(take_array(tail)?)),
+        4 => Dynamic::I64(i64::from_le_bytes(take_array(tail)?)),
+        5 => Dynamic::I128(i128::from_le_bytes(take_array(tail)?)),
+        6 => Dynamic::U8(u8::from_le_bytes(take_array(tail)?)),
+        7 => Dynamic::U16(u16::from_le_bytes(take_array(tail)?)),
+        8 => Dynamic::U32(u32::from_le_bytes(take_array(tail)?)),
+        9 => Dynamic::U64(u64::from_le_bytes(take_array(tail)?)),
+        10 => Dynamic::U128(u128::from_le_bytes(take_array(tail)?)),
+        11 => Dynamic::F32(f32::from_le_bytes(take_array(tail)?)),
+        12 => Dynamic::F64(f64::from_le_bytes(take_array(tail)?)),
+        13 => Dynamic::Char(char::from_u32(u32::from_le_bytes(take_array(tail)?))?),
+        14 => Dynamic::Str(take_str(tail)?.into()),
+        15 => Dynamic::Bytes(take_bytes(tail)?.to_vec()),
+        16 => Dynamic::Unit,
+        17 => Dynamic::None,
+        18 => Dynamic::Some(Box::new(decode_dynamic(tail)?)),
+        19 => Dynamic::Seq(decode_items(tail)?),
+        20 => Dynamic::Tuple(decode_items(tail)?),
+        21 => Dynamic::Map(
+            (0..take_length(tail)?)
+                .map(|_| Some((decode_dynamic(tail)?, decode_dynamic(tail)?)))
+                .collect::<Option<_>>()?,
+        ),
+        22 => Dynamic::Struct {
+            name: take_str(tail)?.into(),
+            fields: decode_fields(tail)?,
+        },
+        23 => Dynamic::Variant {
+            name: take_str(tail)?.into(),
+            variant: take_str(tail)?.into(),
+            fields: decode_fields(tail)?,
+        },
+        _ => return None,
+    })
+}
+
+fn decode_fields(tail: &mut &[u8]) -> Option<DynamicFields> {
+    Some(match take_byte(tail)? {
+        0 => DynamicFields::Unit,
+        1 => DynamicFields::Newtype(Box::new(decode_dynamic(tail)?)),
+        2 => DynamicFields::Tuple(decode_items(tail)?),
+        3 => DynamicFields::Named(
+            (0..take_length(tail)?)
+                .map(|_| {
+                    let name = take_str(tail)?.into();
+                    let value = match take_byte(tail)? {
+                        0 => None,
+                        1 => Some(decode_dynamic(tail)?),
+                        _ => return None,
+                    };
+                    Some((name, value))
+                })
+                .collect::<Option<_>>()?,
+        ),
+        _ => return None,
+    })
+}
+
+fn decode_items(tail: &mut &[u8]) -> Option<Vec<Dynamic>> {
+    (0..take_length(tail)?)
+        .map(|_| decode_dynamic(tail))
+        .collect()
+}
+
+fn take_str<'a>(tail: &mut &'a [u8]) -> Option<&'a str> {
+    std::str::from_utf8(take_bytes(tail)?).ok()
+}
+
+fn take_bytes<'a>(tail: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let length = take_length(tail)?;
+    let bytes = tail.get(..length)?;
+    *tail = &tail[length..];
+    Some(bytes)
+}
+
+fn take_length(tail: &mut &[u8]) -> Option<usize> {
+    usize::try_from(u32::from_le_bytes(take_array(tail)?)).ok()
+}
+
+fn take_byte(tail: &mut &[u8]) -> Option<u8> {
+    take_array::<1>(tail).map(|[byte]| byte)
+}
+
+fn take_array<const N: usize>(tail: &mut &[u8]) -> Option<[u8; N]> {
+    let (bytes, rest) = tail.split_first_chunk::<N>()?;
+    *tail = rest;
+    Some(*bytes)
+}
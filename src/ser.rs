@@ -1,19 +1,44 @@
 use crate::{
-    DescribedBy, Schema, Trace,
-    anonymous_union::ChunkedEnum,
+    DescribedBy, FieldPath, PathSegment, Schema, Trace,
+    anonymous_union::{ChunkedEnum, Discriminant},
     builder::SchemaBuilder,
     described::SelfDescribed,
     indices::{
-        FieldNameListIndex, MemberIndex, MemberListIndex, SchemaNodeIndex, SchemaNodeListIndex,
+        FieldNameIndex, FieldNameListIndex, MemberIndex, MemberListIndex, SchemaNodeIndex,
+        SchemaNodeListIndex, TypeNameIndex, VariantNameIndex,
     },
     schema::SchemaNode,
-    trace::{ReadTraceExt, TraceNode},
+    trace::{ReadTraceExt, TraceNode, is_present, present_members},
 };
 use serde::{
     Serialize,
     ser::{Error as _, SerializeMap, SerializeSeq, SerializeTuple, Serializer},
 };
-use std::{cell::Cell, fmt::Debug};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::Debug,
+};
+use thiserror::Error;
+
+/// Reported when serializing a [`Trace`] together with a [`Schema`] that it doesn't match, e.g.
+/// the schema of a different [`SchemaBuilder`], or a corrupted trace.
+///
+/// Serializers only take error messages, so this is surfaced through `S::Error::custom`.
+/// `expected` and `found` describe the schema node and the trace node, e.g. `tuple of 2`, with
+/// the names of the trace resolved using the schema, and left out if they can't be. `offset` is
+/// the offset of the mismatched value's content in the trace, just after its header.
+#[derive(Clone, Debug, Error)]
+#[error(
+    "schema-trace mismatch at `{path}` (byte {offset} of the trace): expected {expected}, found \
+     {found}"
+)]
+pub(crate) struct TraceMismatchError {
+    path: FieldPath,
+    expected: Box<str>,
+    found: Box<str>,
+    offset: usize,
+}
 
 impl<T> Serialize for SelfDescribed<T>
 where
@@ -38,7 +63,9 @@ impl<'schema, 'trace> Serialize for DescribedBy<'schema, &'trace Trace> {
         S: Serializer,
     {
         let tail = Cell::new(&*(self.0).0);
-        let cursor = TraceCursor::start(self.1, &tail)?;
+        let path = RefCell::new(Vec::new());
+        let unions = RefCell::new(HashMap::new());
+        let cursor = TraceCursor::start(self.1, &tail, &path, &unions)?;
         cursor.serialize(serializer)
     }
 }
@@ -60,6 +87,27 @@ struct TraceCursor<'a> {
     trace: TraceNode,
     data: &'a [u8],
     tail: &'a Cell<&'a [u8]>,
+    /// The length of the whole trace, for reporting offsets.
+    len: usize,
+    /// The steps from the root to the value being serialized, only resolved into a
+    /// [`FieldPath`] when reporting a [`TraceMismatchError`].
+    path: &'a RefCell<Vec<PathStep>>,
+    /// The member of each union matched by each kind of trace node seen so far, see
+    /// [`check`][`Self::check`].
+    unions: &'a UnionMatches,
+}
+
+/// Whether (and which member) of a union matches a trace node only depends on the two, so
+/// matches are computed once per union instead of once per value, e.g. for long sequences of
+/// unions.
+type UnionMatches = RefCell<HashMap<(SchemaNodeListIndex, TraceNode), Option<usize>>>;
+
+#[derive(Copy, Clone)]
+enum PathStep {
+    Field(FieldNameIndex),
+    Position(usize),
+    Index(usize),
+    Variant(VariantNameIndex),
 }
 
 #[derive(Copy, Clone)]
@@ -84,16 +132,25 @@ impl Debug for CheckResult<'_> {
 
 impl<'a> TraceCursor<'a> {
     #[inline]
-    fn start<ErrorT>(schema: &'a Schema, tail: &'a Cell<&'a [u8]>) -> Result<Self, ErrorT>
+    fn start<ErrorT>(
+        schema: &'a Schema,
+        tail: &'a Cell<&'a [u8]>,
+        path: &'a RefCell<Vec<PathStep>>,
+        unions: &'a UnionMatches,
+    ) -> Result<Self, ErrorT>
     where
         ErrorT: serde::ser::Error,
     {
+        let len = tail.get().len();
         Ok(Self {
             schema,
             node: schema.node(schema.root_index).map_err(ErrorT::custom)?,
             trace: tail.pop_trace_node()?,
             tail,
             data: tail.get(),
+            len,
+            path,
+            unions,
         })
     }
 
@@ -108,6 +165,9 @@ impl<'a> TraceCursor<'a> {
             trace: self.tail.pop_trace_node()?,
             data: self.tail.get(),
             tail: self.tail,
+            len: self.len,
+            path: self.path,
+            unions: self.unions,
         })
     }
 
@@ -122,9 +182,46 @@ impl<'a> TraceCursor<'a> {
             trace,
             data: self.tail.get(),
             tail: self.tail,
+            len: self.len,
+            path: self.path,
+            unions: self.unions,
         })
     }
 
+    /// Runs `serialize` for the child reached by `step`.
+    #[inline]
+    fn with_step<ValueT>(&self, step: PathStep, serialize: impl FnOnce() -> ValueT) -> ValueT {
+        self.path.borrow_mut().push(step);
+        let result = serialize();
+        self.path.borrow_mut().pop();
+        result
+    }
+
+    fn mismatch(&self) -> TraceMismatchError {
+        let schema = self.schema;
+        let path = self
+            .path
+            .borrow()
+            .iter()
+            .map(|&step| match step {
+                PathStep::Field(name) => {
+                    PathSegment::Field(schema.field_name(name).unwrap_or("?").into())
+                }
+                PathStep::Position(position) => PathSegment::Field(position.to_string().into()),
+                PathStep::Index(index) => PathSegment::Index(index),
+                PathStep::Variant(variant) => {
+                    PathSegment::Variant(schema.variant_name(variant).unwrap_or("?").into())
+                }
+            })
+            .collect();
+        TraceMismatchError {
+            path,
+            expected: describe_schema_node(schema, self.node),
+            found: describe_trace_node(schema, self.trace),
+            offset: self.len - self.data.len(),
+        }
+    }
+
     #[inline]
     fn serialize_inner<S>(&self, serializer: S, inner: SchemaNodeIndex) -> Result<S::Ok, S::Error>
     where
@@ -137,7 +234,6 @@ impl<'a> TraceCursor<'a> {
     fn serialize_tuple<S>(
         &self,
         serializer: S,
-        length: usize,
         node_list: SchemaNodeListIndex,
     ) -> Result<S::Ok, S::Error>
     where
@@ -145,9 +241,11 @@ impl<'a> TraceCursor<'a> {
     {
         let node_list = self.schema.node_list(node_list).map_err(S::Error::custom)?;
 
-        let mut serializer = serializer.serialize_tuple(length)?;
-        for &node in node_list {
-            serializer.serialize_element(&self.pop_child(node)?)?
+        let mut serializer = serializer.serialize_tuple(node_list.len())?;
+        for (position, &node) in node_list.iter().enumerate() {
+            self.with_step(PathStep::Position(position), || {
+                serializer.serialize_element(&self.pop_child(node)?)
+            })?;
         }
         serializer.end()
     }
@@ -164,9 +262,11 @@ impl<'a> TraceCursor<'a> {
         S: Serializer,
     {
         let mut serializer = serializer.serialize_map(Some(length))?;
-        for _ in 0..length {
-            serializer.serialize_key(&self.pop_child(key)?)?;
-            serializer.serialize_value(&self.pop_child(value)?)?;
+        for index in 0..length {
+            self.with_step(PathStep::Index(index), || {
+                serializer.serialize_key(&self.pop_child(key)?)?;
+                serializer.serialize_value(&self.pop_child(value)?)
+            })?;
         }
         serializer.end()
     }
@@ -182,8 +282,10 @@ impl<'a> TraceCursor<'a> {
         S: Serializer,
     {
         let mut serializer = serializer.serialize_seq(Some(length))?;
-        for _ in 0..length {
-            serializer.serialize_element(&self.pop_child(item)?)?;
+        for index in 0..length {
+            self.with_step(PathStep::Index(index), || {
+                serializer.serialize_element(&self.pop_child(item)?)
+            })?;
         }
         serializer.end()
     }
@@ -208,8 +310,7 @@ impl<'a> TraceCursor<'a> {
             .schema
             .field_name_list(name_list)
             .map_err(S::Error::custom)?;
-        let length = self.tail.pop_length_u32()?;
-        let presence = self.tail.pop_slice(length * std::mem::size_of::<u32>())?;
+        let presence = self.tail.pop_presence(name_list.len())?;
         if name_list.len() != node_list.len() {
             return Err(S::Error::custom(
                 "field name - field type length mismatch in schema",
@@ -222,9 +323,10 @@ impl<'a> TraceCursor<'a> {
             &SkippableStructSerializer {
                 cursor: self,
                 presence,
+                name_list,
                 node_list,
             },
-        )?
+        )
         .serialize(serializer)
     }
 
@@ -307,17 +409,31 @@ impl<'a> TraceCursor<'a> {
 
             (trace, SchemaNode::Union(schema_list)) => {
                 let variants = self.schema.node_list(schema_list).map_err(ErrorT::custom)?;
-                for (discriminant, &node) in variants.iter().enumerate() {
-                    let child = self.traced_child(node, trace)?;
-                    if child.check()?.is_some() {
-                        return Ok(Some(CheckResult::Discriminated(
-                            discriminant,
-                            variants.len(),
-                            child,
-                        )));
+                let cached = self.unions.borrow().get(&(schema_list, trace)).copied();
+                let matched = match cached {
+                    Some(matched) => matched,
+                    None => {
+                        let mut matched = None;
+                        for (discriminant, &node) in variants.iter().enumerate() {
+                            if self.traced_child(node, trace)?.check()?.is_some() {
+                                matched = Some(discriminant);
+                                break;
+                            }
+                        }
+                        self.unions
+                            .borrow_mut()
+                            .insert((schema_list, trace), matched);
+                        matched
                     }
-                }
-                return Ok(None);
+                };
+                return match matched {
+                    Some(discriminant) => Ok(Some(CheckResult::Discriminated(
+                        discriminant,
+                        variants.len(),
+                        self.traced_child(variants[discriminant], trace)?,
+                    ))),
+                    None => Ok(None),
+                };
             }
 
             _ => false,
@@ -363,10 +479,12 @@ impl<'a> TraceCursor<'a> {
             return ChunkedEnum::serializable(
                 usize::try_from(usize::BITS - (num_variants - 1).leading_zeros())
                     .expect("usize must be at least 32 bits"),
-                u64::try_from(discriminant)
-                    .map_err(|_| S::Error::custom("too many discriminants"))?,
+                Discriminant::from(
+                    u64::try_from(discriminant)
+                        .map_err(|_| S::Error::custom("too many discriminants"))?,
+                ),
                 &child,
-            )?
+            )
             .serialize(serializer);
         }
         match self.node {
@@ -394,9 +512,13 @@ impl<'a> TraceCursor<'a> {
             | SchemaNode::UnitVariant(_, _)
             | SchemaNode::OptionNone => serializer.serialize_unit(),
 
-            SchemaNode::OptionSome(inner)
-            | SchemaNode::NewtypeStruct(_, inner)
-            | SchemaNode::NewtypeVariant(_, _, inner) => self.serialize_inner(serializer, inner),
+            SchemaNode::OptionSome(inner) | SchemaNode::NewtypeStruct(_, inner) => {
+                self.serialize_inner(serializer, inner)
+            }
+            SchemaNode::NewtypeVariant(_, variant, inner) => self
+                .with_step(PathStep::Variant(variant), || {
+                    self.serialize_inner(serializer, inner)
+                }),
 
             SchemaNode::Map(key, value) => {
                 self.serialize_map(serializer, data.pop_length_u32()?, key, value)
@@ -405,21 +527,21 @@ impl<'a> TraceCursor<'a> {
                 self.serialize_sequence(serializer, data.pop_length_u32()?, item)
             }
 
-            SchemaNode::Tuple(type_list)
-            | SchemaNode::TupleStruct(_, type_list)
-            | SchemaNode::TupleVariant(_, _, type_list) => self.serialize_tuple(
-                serializer,
-                self.schema
-                    .node_list(type_list)
-                    .map_err(S::Error::custom)?
-                    .len(),
-                type_list,
-            ),
+            SchemaNode::Tuple(type_list) | SchemaNode::TupleStruct(_, type_list) => {
+                self.serialize_tuple(serializer, type_list)
+            }
+            SchemaNode::TupleVariant(_, variant, type_list) => self
+                .with_step(PathStep::Variant(variant), || {
+                    self.serialize_tuple(serializer, type_list)
+                }),
 
-            SchemaNode::Struct(_, name_list, skip_list, type_list)
-            | SchemaNode::StructVariant(_, _, name_list, skip_list, type_list) => {
+            SchemaNode::Struct(_, name_list, skip_list, type_list) => {
                 self.serialize_struct(serializer, name_list, skip_list, type_list)
             }
+            SchemaNode::StructVariant(_, variant, name_list, skip_list, type_list) => self
+                .with_step(PathStep::Variant(variant), || {
+                    self.serialize_struct(serializer, name_list, skip_list, type_list)
+                }),
 
             SchemaNode::Union(_) => unreachable!("union finish called with simple check result"),
         }
@@ -429,6 +551,7 @@ impl<'a> TraceCursor<'a> {
 struct SkippableStructSerializer<'a, 'v> {
     cursor: &'v TraceCursor<'a>,
     presence: &'a [u8],
+    name_list: &'a [FieldNameIndex],
     node_list: &'a [SchemaNodeIndex],
 }
 
@@ -437,46 +560,34 @@ impl<'a, 'v> Serialize for SkippableStructSerializer<'a, 'v> {
     where
         S: Serializer,
     {
-        let mut serializer =
-            serializer.serialize_tuple(self.presence.len() / std::mem::size_of::<u32>())?;
-        for field in iter_field_indices(self.presence) {
-            serializer.serialize_element(&self.cursor.pop_child(
-                *self.node_list.get(usize::from(field)).ok_or_else(|| {
+        let mut serializer = serializer.serialize_tuple(present_members(self.presence).count())?;
+        for field in present_members(self.presence) {
+            let field = usize::from(field);
+            let (&name, &node) = self
+                .name_list
+                .get(field)
+                .zip(self.node_list.get(field))
+                .ok_or_else(|| {
                     S::Error::custom("member index out of bounds for struct in schema")
-                })?,
-            )?)?;
+                })?;
+            self.cursor.with_step(PathStep::Field(name), || {
+                serializer.serialize_element(&self.cursor.pop_child(node)?)
+            })?;
         }
         serializer.end()
     }
 }
 
-fn discriminant_from_presence(skip_list: &[MemberIndex], presence: &[u8]) -> u64 {
-    let mut discriminant = 0u64;
-    let mut presence = iter_field_indices(presence).rev().peekable();
-    for &skip in skip_list.iter().rev() {
-        discriminant <<= 1;
-        while let Some(&present) = presence.peek() {
-            if present > skip {
-                presence.next();
-                continue;
-            }
-            if present == skip {
-                discriminant |= 1;
-                presence.next();
-            }
-            break;
+fn discriminant_from_presence(skip_list: &[MemberIndex], presence: &[u8]) -> Discriminant {
+    let mut discriminant = Discriminant::with_bits(skip_list.len());
+    for (i_skip, &skip) in skip_list.iter().enumerate() {
+        if is_present(presence, skip) {
+            discriminant.set_bit(i_skip);
         }
     }
     discriminant
 }
 
-fn iter_field_indices(presence: &[u8]) -> impl DoubleEndedIterator<Item = MemberIndex> {
-    presence
-        .chunks_exact(std::mem::size_of::<MemberIndex>())
-        .map(|chunk| u32::from_le_bytes(chunk.try_into().expect("impossible")))
-        .map(MemberIndex::from)
-}
-
 // Any issues caused by a mismatch between the schema and the trace are technically bugs but
 // instead of panicking, we still propagate errors as `S::Error::custom`. Traces are not
 // serializable as such, so they don't come from an untrusted source.
@@ -495,7 +606,174 @@ impl Serialize for TraceCursor<'_> {
         self.finish_serialize(
             serializer,
             self.check()?
-                .ok_or_else(|| S::Error::custom("schema-trace mismatch"))?,
+                .ok_or_else(|| S::Error::custom(self.mismatch()))?,
         )
     }
 }
+
+/// Describes a schema node for a [`TraceMismatchError`].
+fn describe_schema_node(schema: &Schema, node: SchemaNode) -> Box<str> {
+    let length = |list| schema.node_list(list).map_or(0, |list| list.len());
+    match node {
+        SchemaNode::Bool => "bool".into(),
+        SchemaNode::I8 => "i8".into(),
+        SchemaNode::I16 => "i16".into(),
+        SchemaNode::I32 => "i32".into(),
+        SchemaNode::I64 => "i64".into(),
+        SchemaNode::I128 => "i128".into(),
+        SchemaNode::U8 => "u8".into(),
+        SchemaNode::U16 => "u16".into(),
+        SchemaNode::U32 => "u32".into(),
+        SchemaNode::U64 => "u64".into(),
+        SchemaNode::U128 => "u128".into(),
+        SchemaNode::F32 => "f32".into(),
+        SchemaNode::F64 => "f64".into(),
+        SchemaNode::Char => "char".into(),
+        SchemaNode::String => "string".into(),
+        SchemaNode::Bytes => "bytes".into(),
+        SchemaNode::OptionNone => "none".into(),
+        SchemaNode::OptionSome(_) => "some".into(),
+        SchemaNode::Unit => "unit".into(),
+        SchemaNode::Sequence(_) => "sequence".into(),
+        SchemaNode::Map(_, _) => "map".into(),
+        SchemaNode::Union(_) => "union".into(),
+        SchemaNode::UnitStruct(name) => describe_named(schema, "unit struct", name, None, ""),
+        SchemaNode::UnitVariant(name, variant) => {
+            describe_named(schema, "unit variant", name, Some(variant), "")
+        }
+        SchemaNode::NewtypeStruct(name, _) => {
+            describe_named(schema, "newtype struct", name, None, "")
+        }
+        SchemaNode::NewtypeVariant(name, variant, _) => {
+            describe_named(schema, "newtype variant", name, Some(variant), "")
+        }
+        SchemaNode::Tuple(list) => format!("tuple of {}", length(list)).into(),
+        SchemaNode::TupleStruct(name, list) => describe_named(
+            schema,
+            "tuple struct",
+            name,
+            None,
+            &describe_length(length(list)),
+        ),
+        SchemaNode::TupleVariant(name, variant, list) => describe_named(
+            schema,
+            "tuple variant",
+            name,
+            Some(variant),
+            &describe_length(length(list)),
+        ),
+        SchemaNode::Struct(name, fields, _, _) => describe_named(
+            schema,
+            "struct",
+            name,
+            None,
+            &describe_fields(schema, fields),
+        ),
+        SchemaNode::StructVariant(name, variant, fields, _, _) => describe_named(
+            schema,
+            "struct variant",
+            name,
+            Some(variant),
+            &describe_fields(schema, fields),
+        ),
+    }
+}
+
+/// Describes a trace node for a [`TraceMismatchError`].
+fn describe_trace_node(schema: &Schema, trace: TraceNode) -> Box<str> {
+    let length = |length: u32| usize::try_from(length).expect("usize must be at least 32 bits");
+    match trace {
+        TraceNode::Bool => "bool".into(),
+        TraceNode::I8 => "i8".into(),
+        TraceNode::I16 => "i16".into(),
+        TraceNode::I32 => "i32".into(),
+        TraceNode::I64 => "i64".into(),
+        TraceNode::I128 => "i128".into(),
+        TraceNode::U8 => "u8".into(),
+        TraceNode::U16 => "u16".into(),
+        TraceNode::U32 => "u32".into(),
+        TraceNode::U64 => "u64".into(),
+        TraceNode::U128 => "u128".into(),
+        TraceNode::F32 => "f32".into(),
+        TraceNode::F64 => "f64".into(),
+        TraceNode::Char => "char".into(),
+        TraceNode::String => "string".into(),
+        TraceNode::Bytes => "bytes".into(),
+        TraceNode::None => "none".into(),
+        TraceNode::Some => "some".into(),
+        TraceNode::Unit => "unit".into(),
+        TraceNode::Sequence => "sequence".into(),
+        TraceNode::Map => "map".into(),
+        TraceNode::UnitStruct(name) => describe_named(schema, "unit struct", name, None, ""),
+        TraceNode::UnitVariant(name, variant) => {
+            describe_named(schema, "unit variant", name, Some(variant), "")
+        }
+        TraceNode::NewtypeStruct(name) => describe_named(schema, "newtype struct", name, None, ""),
+        TraceNode::NewtypeVariant(name, variant) => {
+            describe_named(schema, "newtype variant", name, Some(variant), "")
+        }
+        TraceNode::Tuple(len) => format!("tuple of {}", length(len)).into(),
+        TraceNode::TupleStruct(len, name) => describe_named(
+            schema,
+            "tuple struct",
+            name,
+            None,
+            &describe_length(length(len)),
+        ),
+        TraceNode::TupleVariant(len, name, variant) => describe_named(
+            schema,
+            "tuple variant",
+            name,
+            Some(variant),
+            &describe_length(length(len)),
+        ),
+        TraceNode::Struct(name, fields) => describe_named(
+            schema,
+            "struct",
+            name,
+            None,
+            &describe_fields(schema, fields),
+        ),
+        TraceNode::StructVariant(name, variant, fields) => describe_named(
+            schema,
+            "struct variant",
+            name,
+            Some(variant),
+            &describe_fields(schema, fields),
+        ),
+    }
+}
+
+/// Names which can't be resolved, e.g. as the trace is from a different schema, are left out.
+fn describe_named(
+    schema: &Schema,
+    kind: &str,
+    name: TypeNameIndex,
+    variant: Option<VariantNameIndex>,
+    suffix: &str,
+) -> Box<str> {
+    let Ok(name) = schema.type_name(name) else {
+        return format!("{kind}{suffix}").into();
+    };
+    match variant.map(|variant| schema.variant_name(variant)) {
+        Some(Ok(variant)) => format!("{kind} {name}::{variant}{suffix}").into(),
+        Some(Err(_)) | None => format!("{kind} {name}{suffix}").into(),
+    }
+}
+
+fn describe_length(length: usize) -> String {
+    format!(" of {length}")
+}
+
+fn describe_fields(schema: &Schema, fields: FieldNameListIndex) -> String {
+    let names = schema.field_name_list(fields).ok().and_then(|fields| {
+        fields
+            .iter()
+            .map(|&field| schema.field_name(field).ok())
+            .collect::<Option<Vec<_>>>()
+    });
+    match names {
+        Some(names) => format!(" {{ {} }}", names.join(", ")),
+        None => String::new(),
+    }
+}
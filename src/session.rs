@@ -0,0 +1,361 @@
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{DeserializeSeed, Error as _, SeqAccess, Visitor},
+};
+use std::marker::PhantomData;
+use thiserror::Error;
+
+use crate::{
+    DescribedBy, Schema, SchemaBuilder, SchemaDelta, SchemaDeltaError, SchemaFingerprint, Trace,
+    TraceError,
+};
+
+/// The sending half of a stream of values which share a schema, see [`DecoderSession`] for the
+/// receiving half.
+///
+/// Each value is traced into a schema which widens as new shapes of values are seen. When the
+/// schema changes, [`encode`][`Self::encode`] returns a [`SchemaUpdate`] to send ahead of the
+/// value; otherwise only the value needs to be sent. Updates are deltas against the last schema
+/// the receiver [acknowledged][`Self::acknowledge`], or the full schema if there isn't one yet.
+///
+/// The session doesn't own a transport: messages, updates and acknowledgements are
+/// [`Serialize`]-able and can be sent with any format, over any ordered channel.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_describe::{DecoderSession, EncoderSession};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// #[serde(untagged)]
+/// enum Reply {
+///     Number(u32),
+///     Text(String),
+/// }
+///
+/// let mut encoder = EncoderSession::new();
+/// let mut decoder = DecoderSession::new();
+///
+/// for reply in [Reply::Number(1), Reply::Number(2), Reply::Text("three".to_owned())] {
+///     // The sender serializes the schema update (if any), then the value.
+///     let message = encoder.encode(&reply)?;
+///     let update = message
+///         .update
+///         .map(|update| postcard::to_stdvec(&update))
+///         .transpose()?;
+///     let data = postcard::to_stdvec(&message.data)?;
+///
+///     // The receiver applies the update and acknowledges it, then reads the value.
+///     if let Some(update) = update {
+///         let acknowledgement = decoder.receive(postcard::from_bytes(&update)?)?;
+///         encoder.acknowledge(acknowledgement);
+///     }
+///     let received: Reply = decoder.deserialize(&mut postcard::Deserializer::from_bytes(&data))?;
+///     assert_eq!(received, reply);
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Default)]
+pub struct EncoderSession {
+    builder: SchemaBuilder,
+    sent: Vec<(SchemaFingerprint, Schema)>,
+    acknowledged: bool,
+}
+
+/// A value encoded by [`EncoderSession::encode`].
+pub struct EncodedMessage<'session> {
+    /// The schema update to send ahead of the value, if the schema changed.
+    pub update: Option<SchemaUpdate>,
+
+    /// The value, to be serialized with the current schema.
+    pub data: DescribedBy<'session, Trace>,
+}
+
+/// A change to the schema of an [`EncoderSession`], to be passed to [`DecoderSession::receive`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SchemaUpdate {
+    /// The full schema, sent when the receiver hasn't acknowledged any schema yet.
+    Full(Schema),
+
+    /// The changes from the last schema the receiver acknowledged.
+    Delta(SchemaDelta),
+}
+
+impl EncoderSession {
+    /// Creates a new session, which hasn't sent any schema yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Traces a value, returning it along with the schema update to send ahead of it, if any.
+    ///
+    /// This rebuilds the schema for every value, so it's best suited to streams of small to
+    /// medium-sized schemas.
+    pub fn encode<ValueT>(&mut self, value: &ValueT) -> Result<EncodedMessage<'_>, TraceError>
+    where
+        ValueT: Serialize,
+    {
+        let trace = self.builder.trace(value)?;
+        let schema = self.builder.clone().build()?;
+        let fingerprint = schema.fingerprint();
+
+        let update = if self.sent.last().map(|(sent, _)| *sent) == Some(fingerprint) {
+            None
+        } else {
+            Some(match self.sent.first() {
+                Some((_, acknowledged)) if self.acknowledged => {
+                    SchemaUpdate::Delta(schema.delta_from(acknowledged))
+                }
+                _ => SchemaUpdate::Full(schema.clone()),
+            })
+        };
+        if update.is_some() {
+            self.sent.push((fingerprint, schema));
+        }
+
+        let (_, schema) = self.sent.last().expect("a schema was sent");
+        Ok(EncodedMessage {
+            update,
+            data: schema.describe_trace(trace),
+        })
+    }
+
+    /// Returns a writer which encodes a sequence too large to trace at once in chunks of at
+    /// most `chunk_length` items, see [`ChunkedWriter`].
+    ///
+    /// # Panics
+    /// If `chunk_length` is zero.
+    pub fn chunked<ItemT>(&mut self, chunk_length: usize) -> ChunkedWriter<'_, ItemT>
+    where
+        ItemT: Serialize,
+    {
+        assert!(chunk_length > 0, "chunks must hold at least one item");
+        ChunkedWriter {
+            session: self,
+            chunk_length,
+            chunk: Vec::with_capacity(chunk_length),
+        }
+    }
+
+    /// Records that the receiver applied the schema update with the given fingerprint, as
+    /// returned by [`DecoderSession::receive`]. Subsequent updates are deltas against it.
+    ///
+    /// Acknowledgements for schemas which weren't sent, or which are older than the last
+    /// acknowledged one, are ignored.
+    pub fn acknowledge(&mut self, fingerprint: SchemaFingerprint) {
+        // The acknowledged schema is kept first, followed by the ones sent after it.
+        if let Some(position) = self.sent.iter().position(|(sent, _)| *sent == fingerprint) {
+            self.sent.drain(..position);
+            self.acknowledged = true;
+        }
+    }
+}
+
+/// Encodes the items of a huge sequence in bounded chunks, returned by
+/// [`EncoderSession::chunked`].
+///
+/// Tracing a multi-gigabyte sequence at once produces a trace (and serialized data) just as
+/// large. Instead, items are [pushed][`Self::push`] one at a time and, whenever a chunk fills up,
+/// it's encoded as a message holding a `Vec` of the items, to be sent before moving on. Only one
+/// chunk is in memory at a time.
+///
+/// The receiver reassembles the sequence by passing each message to
+/// [`DecoderSession::deserialize_extend`]. Marking the end of the sequence is left to the
+/// transport.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{DecoderSession, EncodedMessage, EncoderSession};
+///
+/// let mut frames = Vec::new();
+/// let mut send = |message: EncodedMessage| -> Result<(), postcard::Error> {
+///     let update = message.update.map(|update| postcard::to_stdvec(&update)).transpose()?;
+///     frames.push((update, postcard::to_stdvec(&message.data)?));
+///     Ok(())
+/// };
+///
+/// let mut encoder = EncoderSession::new();
+/// let mut writer = encoder.chunked(1000);
+/// for i in 0..2500u32 {
+///     if let Some(message) = writer.push(i)? {
+///         send(message)?;
+///     }
+/// }
+/// if let Some(message) = writer.finish()? {
+///     send(message)?;
+/// }
+/// assert_eq!(frames.len(), 3);
+///
+/// let mut decoder = DecoderSession::new();
+/// let mut items = Vec::<u32>::new();
+/// for (update, data) in &frames {
+///     if let Some(update) = update {
+///         decoder.receive(postcard::from_bytes(update)?)?;
+///     }
+///     decoder.deserialize_extend(&mut postcard::Deserializer::from_bytes(data), &mut items)?;
+/// }
+/// assert_eq!(items, (0..2500).collect::<Vec<u32>>());
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ChunkedWriter<'session, ItemT> {
+    session: &'session mut EncoderSession,
+    chunk_length: usize,
+    chunk: Vec<ItemT>,
+}
+
+impl<'session, ItemT> ChunkedWriter<'session, ItemT>
+where
+    ItemT: Serialize,
+{
+    /// Adds an item to the current chunk, returning the encoded chunk if it's now full.
+    pub fn push(&mut self, item: ItemT) -> Result<Option<EncodedMessage<'_>>, TraceError> {
+        self.chunk.push(item);
+        if self.chunk.len() < self.chunk_length {
+            return Ok(None);
+        }
+        let message = self.session.encode(&self.chunk)?;
+        self.chunk.clear();
+        Ok(Some(message))
+    }
+
+    /// Returns the last, partially filled chunk, if it has any items.
+    pub fn finish(self) -> Result<Option<EncodedMessage<'session>>, TraceError> {
+        if self.chunk.is_empty() {
+            return Ok(None);
+        }
+        self.session.encode(&self.chunk).map(Some)
+    }
+}
+
+/// The receiving half of a stream of values sent by an [`EncoderSession`].
+#[derive(Clone, Default)]
+pub struct DecoderSession {
+    received: Vec<(SchemaFingerprint, Schema)>,
+}
+
+/// Returned by [`DecoderSession::receive`] when a schema update can't be applied.
+#[derive(Clone, Debug, Error)]
+#[non_exhaustive]
+pub enum SessionError {
+    /// The update is a delta against a schema this session never received.
+    #[error("schema update applies to unknown schema {0}")]
+    UnknownBase(SchemaFingerprint),
+
+    /// The update is a delta which doesn't apply to its base schema.
+    #[error(transparent)]
+    Delta(#[from] SchemaDeltaError),
+}
+
+impl DecoderSession {
+    /// Creates a new session, which hasn't received any schema yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Applies a schema update, returning the fingerprint of the new schema to acknowledge back
+    /// to the [`EncoderSession`] with [`EncoderSession::acknowledge`].
+    pub fn receive(&mut self, update: SchemaUpdate) -> Result<SchemaFingerprint, SessionError> {
+        let schema = match update {
+            SchemaUpdate::Full(schema) => schema,
+            SchemaUpdate::Delta(delta) => {
+                let base = delta.base();
+                let position = self
+                    .received
+                    .iter()
+                    .position(|(received, _)| *received == base)
+                    .ok_or(SessionError::UnknownBase(base))?;
+                // The sender only bases deltas on acknowledged schemas, so older ones won't be
+                // needed again.
+                self.received.drain(..position);
+                self.received[0].1.apply_delta(delta)?
+            }
+        };
+        let fingerprint = schema.fingerprint();
+        self.received.push((fingerprint, schema));
+        Ok(fingerprint)
+    }
+
+    /// The most recently received schema, if any.
+    #[inline]
+    pub fn schema(&self) -> Option<&Schema> {
+        self.received.last().map(|(_, schema)| schema)
+    }
+
+    /// Deserializes a value sent after the most recently received schema update.
+    ///
+    /// Returns an error if no schema was received yet.
+    pub fn deserialize<'de, DeserializeT, DeserializerT>(
+        &self,
+        deserializer: DeserializerT,
+    ) -> Result<DeserializeT, DeserializerT::Error>
+    where
+        DeserializeT: Deserialize<'de>,
+        DeserializerT: Deserializer<'de>,
+    {
+        self.schema()
+            .ok_or_else(|| DeserializerT::Error::custom("no schema was received yet"))?
+            .deserialize_described(deserializer)
+    }
+
+    /// Deserializes a chunk encoded by a [`ChunkedWriter`], appending its items to `items`.
+    ///
+    /// Returns an error if no schema was received yet.
+    pub fn deserialize_extend<'de, ItemT, DeserializerT>(
+        &self,
+        deserializer: DeserializerT,
+        items: &mut Vec<ItemT>,
+    ) -> Result<(), DeserializerT::Error>
+    where
+        ItemT: Deserialize<'de>,
+        DeserializerT: Deserializer<'de>,
+    {
+        self.schema()
+            .ok_or_else(|| DeserializerT::Error::custom("no schema was received yet"))?
+            .describe_seed(ExtendSeed(items, PhantomData))
+            .deserialize(deserializer)
+            .map(|_| ())
+    }
+}
+
+/// Appends the items of a deserialized sequence to a `Vec`, rather than allocating a new one.
+struct ExtendSeed<'items, 'de, ItemT>(&'items mut Vec<ItemT>, PhantomData<&'de ()>);
+
+impl<'de, ItemT> DeserializeSeed<'de> for ExtendSeed<'_, 'de, ItemT>
+where
+    ItemT: Deserialize<'de>,
+{
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, ItemT> Visitor<'de> for ExtendSeed<'_, 'de, ItemT>
+where
+    ItemT: Deserialize<'de>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        self.0.reserve(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            self.0.push(item);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{Mutex, PoisonError},
+};
+use tracing_core::{
+    Event, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::{EncodedMessage, EncoderSession};
+
+/// A [`tracing_subscriber`] layer which encodes structured log events, see [`LogEvent`].
+///
+/// Events are traced into a single, shared [`EncoderSession`], whose schema widens as new fields
+/// and field types show up. Each encoded event is handed to a sink, which serializes it with a
+/// format of its choosing and writes it out, e.g. to a file. The log can then be read back with
+/// a [`DecoderSession`][`crate::DecoderSession`].
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{DecoderSession, LogEvent, LogValue, TracingLayer};
+/// use std::sync::{Arc, Mutex};
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let log = Arc::new(Mutex::new(Vec::new()));
+/// let layer = TracingLayer::new({
+///     let log = log.clone();
+///     move |message| {
+///         let update = message.update.map(|update| postcard::to_stdvec(&update).unwrap());
+///         let data = postcard::to_stdvec(&message.data).unwrap();
+///         log.lock().unwrap().push((update, data));
+///     }
+/// });
+///
+/// let subscriber = tracing_subscriber::registry().with(layer);
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(user = "ana", attempts = 3u64, "logged in");
+/// });
+///
+/// let mut decoder = DecoderSession::new();
+/// let mut events = Vec::new();
+/// for (update, data) in log.lock().unwrap().iter() {
+///     if let Some(update) = update {
+///         decoder.receive(postcard::from_bytes(update)?)?;
+///     }
+///     events.push(decoder.deserialize::<LogEvent, _>(
+///         &mut postcard::Deserializer::from_bytes(data),
+///     )?);
+/// }
+/// assert_eq!(events[0].level, "INFO");
+/// assert_eq!(events[0].fields["message"], LogValue::Str("logged in".to_owned()));
+/// assert_eq!(events[0].fields["attempts"], LogValue::U64(3));
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct TracingLayer<SinkT> {
+    state: Mutex<(EncoderSession, SinkT)>,
+}
+
+/// A structured log event, as encoded by a [`TracingLayer`].
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LogEvent {
+    /// The verbosity level, e.g. `INFO`.
+    pub level: String,
+
+    /// The target of the event, usually the module it was emitted from.
+    pub target: String,
+
+    /// The fields of the event, including its `message`, if any.
+    pub fields: BTreeMap<String, LogValue>,
+}
+
+/// The value of a field of a [`LogEvent`].
+///
+/// Values recorded with their `Debug` implementation (including messages) are stored as strings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum LogValue {
+    /// A `bool`.
+    Bool(bool),
+    /// An `i64`.
+    I64(i64),
+    /// A `u64`.
+    U64(u64),
+    /// An `i128`.
+    I128(i128),
+    /// A `u128`.
+    U128(u128),
+    /// An `f64`.
+    F64(f64),
+    /// A string, or the `Debug` rendering of any other value.
+    Str(String),
+}
+
+impl<SinkT> TracingLayer<SinkT>
+where
+    SinkT: FnMut(EncodedMessage<'_>) + Send + 'static,
+{
+    /// Creates a layer which passes each encoded event to `sink`.
+    pub fn new(sink: SinkT) -> Self {
+        Self {
+            state: Mutex::new((EncoderSession::new(), sink)),
+        }
+    }
+}
+
+impl<SubscriberT, SinkT> Layer<SubscriberT> for TracingLayer<SinkT>
+where
+    SubscriberT: Subscriber,
+    SinkT: FnMut(EncodedMessage<'_>) + Send + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, _context: Context<'_, SubscriberT>) {
+        let metadata = event.metadata();
+        let mut record = LogEvent {
+            level: metadata.level().to_string(),
+            target: metadata.target().to_owned(),
+            fields: BTreeMap::new(),
+        };
+        event.record(&mut FieldVisitor(&mut record.fields));
+
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let (session, sink) = &mut *state;
+        // Log events hold only strings and numbers, so tracing them can't fail.
+        if let Ok(message) = session.encode(&record) {
+            sink(message);
+        }
+    }
+}
+
+struct FieldVisitor<'fields>(&'fields mut BTreeMap<String, LogValue>);
+
+impl FieldVisitor<'_> {
+    fn insert(&mut self, field: &Field, value: LogValue) {
+        self.0.insert(field.name().to_owned(), value);
+    }
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, LogValue::Bool(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, LogValue::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, LogValue::U64(value));
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.insert(field, LogValue::I128(value));
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.insert(field, LogValue::U128(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field, LogValue::F64(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, LogValue::Str(value.to_owned()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.insert(field, LogValue::Str(format!("{value:?}")));
+    }
+}
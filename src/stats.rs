@@ -0,0 +1,155 @@
+use std::collections::BTreeMap;
+
+use crate::indices::SchemaNodeIndex;
+
+/// How many times each member of each union in a schema was traced, returned by
+/// [`SchemaBuilder::build_with_union_stats`][`crate::SchemaBuilder::build_with_union_stats`].
+///
+/// Useful for choosing encodings, e.g. ordering members by how frequently they occur. Unions
+/// are identified by their index in the schema, as [displayed][`crate::Schema`] after the
+/// union's name, e.g. `union_4`. Identical unions in different places in a schema are the same
+/// node, so their counts are added up.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnionStats {
+    member_counts: BTreeMap<SchemaNodeIndex, Box<[u64]>>,
+}
+
+impl UnionStats {
+    /// The number of unions in the schema.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.member_counts.len()
+    }
+
+    /// Whether the schema has no unions.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.member_counts.is_empty()
+    }
+
+    /// How many times each member of the union with the given index was traced, in the order of
+    /// their discriminants, or `None` if the node isn't a union.
+    pub fn member_counts(&self, union: u32) -> Option<&[u64]> {
+        self.member_counts
+            .get(&SchemaNodeIndex::from(union))
+            .map(|counts| &**counts)
+    }
+
+    /// Iterates over the indices of the unions, and how many times each of their members was
+    /// traced, ordered by index.
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &[u64])> {
+        self.member_counts
+            .iter()
+            .map(|(&union, counts)| (u32::from(union), &**counts))
+    }
+
+    pub(crate) fn add(&mut self, union: SchemaNodeIndex, member_counts: &[u64]) {
+        let counts = self
+            .member_counts
+            .entry(union)
+            .or_insert_with(|| vec![0; member_counts.len()].into());
+        for (count, added) in counts.iter_mut().zip(member_counts) {
+            *count += added;
+        }
+    }
+}
+
+impl std::fmt::Display for UnionStats {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (position, (union, counts)) in self.iter().enumerate() {
+            if position > 0 {
+                formatter.write_str("\n")?;
+            }
+            write!(formatter, "union_{union}: {counts:?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// How many times each field of each struct was present and skipped, returned by
+/// [`SchemaBuilder::skip_stats`][`crate::SchemaBuilder::skip_stats`].
+///
+/// Useful for evolving schemas, e.g. an optional field which was never skipped across a large
+/// batch may as well be required in the next version. Structs are identified by their name, or
+/// `Enum::Variant` for struct variants, and the counts of every occurrence of a struct are added
+/// up, wherever it is in the values.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SkipStats {
+    fields: BTreeMap<(String, String), FieldPresence>,
+}
+
+/// How many times a field was present and skipped, see [`SkipStats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FieldPresence {
+    present: u64,
+    skipped: u64,
+}
+
+impl SkipStats {
+    /// The number of fields, across all structs.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// Whether no structs were traced.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// How many times a field of a struct was present and skipped, or `None` if no such
+    /// struct field was traced.
+    pub fn field(&self, struct_name: &str, field: &str) -> Option<FieldPresence> {
+        self.fields
+            .get(&(struct_name.to_owned(), field.to_owned()))
+            .copied()
+    }
+
+    /// Iterates over the structs' names, their fields' names and how many times each field was
+    /// present and skipped, ordered by struct and field name.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, FieldPresence)> {
+        self.fields
+            .iter()
+            .map(|((struct_name, field), &presence)| (&**struct_name, &**field, presence))
+    }
+
+    pub(crate) fn add(&mut self, struct_name: String, field: &str, present: u64, skipped: u64) {
+        let presence = self
+            .fields
+            .entry((struct_name, field.to_owned()))
+            .or_default();
+        presence.present += present;
+        presence.skipped += skipped;
+    }
+}
+
+impl FieldPresence {
+    /// The number of times the field was present.
+    #[inline]
+    pub fn present(&self) -> u64 {
+        self.present
+    }
+
+    /// The number of times the field was skipped.
+    #[inline]
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+}
+
+impl std::fmt::Display for SkipStats {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (position, (struct_name, field, presence)) in self.iter().enumerate() {
+            if position > 0 {
+                formatter.write_str("\n")?;
+            }
+            write!(
+                formatter,
+                "{struct_name}.{field}: {} present, {} skipped",
+                presence.present, presence.skipped
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,260 @@
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{Error, SeqAccess, Visitor},
+    ser::SerializeStruct,
+};
+use thiserror::Error;
+
+/// A multi-dimensional array of numbers, stored contiguously in row-major order.
+///
+/// Serialized as a struct with the array's `shape` and its elements packed into a single byte
+/// string, instead of as nested sequences. This keeps traces compact, since the elements share a
+/// single trace tag rather than carrying one each, and makes the shape explicit. The element
+/// type is recorded in the name of the struct, e.g. `serde_describe::Tensor<f32>`.
+///
+/// With the `ndarray` feature, tensors can be converted from and to `ndarray` arrays.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{SelfDescribed, Tensor};
+///
+/// let matrix = Tensor::new(vec![2, 3], vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0])?;
+/// let bytes = postcard::to_stdvec(&SelfDescribed(&matrix))?;
+/// let SelfDescribed(roundtripped) = postcard::from_bytes::<SelfDescribed<Tensor<f32>>>(&bytes)?;
+/// assert_eq!(roundtripped, matrix);
+/// assert_eq!(roundtripped.shape(), [2, 3]);
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tensor<ElementT> {
+    shape: Vec<usize>,
+    data: Vec<ElementT>,
+}
+
+impl<ElementT> Tensor<ElementT>
+where
+    ElementT: TensorElement,
+{
+    /// Creates a tensor with the given shape from its elements in row-major order.
+    ///
+    /// The number of elements must be the product of the dimensions in the shape.
+    pub fn new(shape: Vec<usize>, data: Vec<ElementT>) -> Result<Self, TensorShapeError> {
+        if shape
+            .iter()
+            .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+            != Some(data.len())
+        {
+            return Err(TensorShapeError {
+                shape,
+                len: data.len(),
+            });
+        }
+        Ok(Self { shape, data })
+    }
+
+    /// The size of each dimension of the tensor.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// The elements of the tensor, in row-major order.
+    pub fn data(&self) -> &[ElementT] {
+        &self.data
+    }
+
+    /// Returns the shape and elements of the tensor.
+    pub fn into_parts(self) -> (Vec<usize>, Vec<ElementT>) {
+        (self.shape, self.data)
+    }
+}
+
+/// The number of elements of a [`Tensor`] doesn't match its shape.
+#[derive(Clone, Debug, Error)]
+#[error("a tensor of shape {shape:?} cannot have {len} elements")]
+pub struct TensorShapeError {
+    shape: Vec<usize>,
+    len: usize,
+}
+
+/// Numeric types which can be the elements of a [`Tensor`].
+///
+/// This trait is sealed and cannot be implemented outside this crate.
+pub trait TensorElement: Copy + sealed::Sealed {
+    /// The name of the struct a tensor of this element type is serialized as.
+    const TENSOR_TYPE_NAME: &'static str;
+
+    /// The size in bytes of an element.
+    const SIZE: usize;
+
+    #[doc(hidden)]
+    fn write_le_bytes(self, bytes: &mut Vec<u8>);
+
+    #[doc(hidden)]
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! impl_tensor_element {
+    ($($element:ty),+) => {
+        $(
+            impl sealed::Sealed for $element {}
+
+            impl TensorElement for $element {
+                const TENSOR_TYPE_NAME: &'static str =
+                    concat!("serde_describe::Tensor<", stringify!($element), ">");
+                const SIZE: usize = size_of::<$element>();
+
+                #[inline]
+                fn write_le_bytes(self, bytes: &mut Vec<u8>) {
+                    bytes.extend_from_slice(&self.to_le_bytes());
+                }
+
+                #[inline]
+                fn from_le_bytes(bytes: &[u8]) -> Self {
+                    <$element>::from_le_bytes(bytes.try_into().expect("element size mismatch"))
+                }
+            }
+        )+
+    };
+}
+
+impl_tensor_element!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+impl<ElementT> Serialize for Tensor<ElementT>
+where
+    ElementT: TensorElement,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut data = Vec::with_capacity(self.data.len() * ElementT::SIZE);
+        for &element in &self.data {
+            element.write_le_bytes(&mut data);
+        }
+        let mut tensor = serializer.serialize_struct(ElementT::TENSOR_TYPE_NAME, 2)?;
+        tensor.serialize_field("shape", &self.shape)?;
+        tensor.serialize_field("data", &Bytes(data))?;
+        tensor.end()
+    }
+}
+
+impl<'de, ElementT> Deserialize<'de> for Tensor<ElementT>
+where
+    ElementT: TensorElement,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct SerializedTensor {
+            shape: Vec<usize>,
+            data: Bytes,
+        }
+
+        let SerializedTensor {
+            shape,
+            data: Bytes(data),
+        } = SerializedTensor::deserialize(deserializer)?;
+        if data.len() % ElementT::SIZE != 0 {
+            return Err(D::Error::custom(format_args!(
+                "tensor data of {} bytes is not a whole number of {}-byte elements",
+                data.len(),
+                ElementT::SIZE
+            )));
+        }
+        let data = data
+            .chunks_exact(ElementT::SIZE)
+            .map(ElementT::from_le_bytes)
+            .collect();
+        Tensor::new(shape, data).map_err(D::Error::custom)
+    }
+}
+
+/// A byte string, serialized with `serialize_bytes`.
+pub(crate) struct Bytes(pub(crate) Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(formatter, "a byte string")
+    }
+
+    fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Bytes(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, bytes: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: Error,
+    {
+        Ok(Bytes(bytes))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(Bytes(bytes))
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<ElementT, DataT, DimensionT> From<&ndarray::ArrayBase<DataT, DimensionT>> for Tensor<ElementT>
+where
+    ElementT: TensorElement,
+    DataT: ndarray::Data<Elem = ElementT>,
+    DimensionT: ndarray::Dimension,
+{
+    fn from(array: &ndarray::ArrayBase<DataT, DimensionT>) -> Self {
+        Self {
+            shape: array.shape().to_vec(),
+            data: array.iter().copied().collect(),
+        }
+    }
+}
+
+#[cfg(feature = "ndarray")]
+impl<ElementT> From<Tensor<ElementT>> for ndarray::ArrayD<ElementT>
+where
+    ElementT: TensorElement,
+{
+    fn from(tensor: Tensor<ElementT>) -> Self {
+        ndarray::ArrayD::from_shape_vec(tensor.shape, tensor.data)
+            .expect("tensor shapes are checked on construction")
+    }
+}
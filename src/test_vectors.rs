@@ -0,0 +1,299 @@
+use serde::{Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::{DescribedBy, RawValue, Schema, SchemaBuilder, Trace};
+
+/// A canonical value traced by this crate, to check other implementations of the format
+/// against, e.g. a decoder written in another language.
+///
+/// The vectors returned by [`TestVector::all`] cover every kind of schema node, as well as
+/// unions, sometimes skipped fields and nesting. Serializing a vector's [schema][`Self::schema`]
+/// and [described value][`Self::described`] with the serde format in use produces the byte
+/// fixtures for the other implementation to decode, and [`Self::expected`] is what it should
+/// decode them to. In the other direction, [`Self::verify`] checks bytes encoded by the other
+/// implementation from the same value.
+///
+/// Vectors are built from fixed Rust values, so the same version of this crate always produces
+/// the same schemas and traces. Vectors are only ever added, never changed, so the fixtures of
+/// older versions stay valid.
+///
+/// Requires the `test-vectors` feature.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde_describe::{Schema, TestVector};
+///
+/// for vector in TestVector::all() {
+///     // The fixtures to write out for the other implementation.
+///     let schema_bytes = postcard::to_stdvec(vector.schema())?;
+///     let data_bytes = postcard::to_stdvec(&vector.described())?;
+///     println!("{}: {}", vector.name(), vector.expected());
+///
+///     // What the other implementation encoded from the same value, here this one.
+///     let schema: Schema = postcard::from_bytes(&schema_bytes)?;
+///     vector.verify(&schema, &mut postcard::Deserializer::from_bytes(&data_bytes))?;
+/// }
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct TestVector {
+    name: &'static str,
+    schema: Schema,
+    trace: Trace,
+}
+
+/// Returned by [`TestVector::verify`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TestVectorError {
+    /// The schema doesn't describe the same data model as the vector's.
+    #[error("test vector `{name}` has a different schema: {explanation}")]
+    Schema {
+        /// The name of the vector.
+        name: &'static str,
+        /// Why the schemas differ, see [`Schema::explain_difference`].
+        explanation: String,
+    },
+
+    /// The data couldn't be deserialized with the schema.
+    #[error("test vector `{name}` failed to decode: {message}")]
+    Decode {
+        /// The name of the vector.
+        name: &'static str,
+        /// The error of the deserializer.
+        message: String,
+    },
+
+    /// The data was decoded to a different value.
+    #[error("test vector `{name}` decoded to `{actual}`, instead of `{expected}`")]
+    Mismatch {
+        /// The name of the vector.
+        name: &'static str,
+        /// The value of the vector, see [`TestVector::expected`].
+        expected: String,
+        /// The decoded value.
+        actual: String,
+    },
+}
+
+impl TestVector {
+    /// Returns every test vector, in a fixed order.
+    pub fn all() -> Vec<TestVector> {
+        vec![
+            Self::new("bool", &vec![true, false]),
+            Self::new(
+                "signed",
+                &(i8::MIN, i16::MIN, i32::MIN, i64::MIN, i128::MIN, -1i8),
+            ),
+            Self::new(
+                "unsigned",
+                &(u8::MAX, u16::MAX, u32::MAX, u64::MAX, u128::MAX, 0u8),
+            ),
+            Self::new(
+                "float",
+                &(1.5f32, -0.0f64, f64::INFINITY, f64::MIN_POSITIVE),
+            ),
+            Self::new("char", &vec!['a', 'é', '\u{1f980}']),
+            Self::new("string", &vec!["", "hello", "ünïcödé"]),
+            Self::new("bytes", &vec![Bytes(&[]), Bytes(&[0, 1, 0xfe, 0xff])]),
+            Self::new("option", &(None::<u8>, Some(1u8), Some(Some(2u8)))),
+            Self::new("unit", &((), UnitStruct)),
+            Self::new("newtype", &Newtype(7)),
+            Self::new("tuple_struct", &TupleStruct(-1, "two".to_owned())),
+            Self::new("empty_sequence", &Vec::<u32>::new()),
+            Self::new("nested_sequence", &vec![vec![1u16], vec![], vec![2, 3]]),
+            Self::new(
+                "map",
+                &BTreeMap::from([("one".to_owned(), 1u32), ("two".to_owned(), 2)]),
+            ),
+            Self::new(
+                "struct",
+                &Struct {
+                    id: 42,
+                    label: "answer".to_owned(),
+                    tags: vec!["a".to_owned()],
+                    note: None,
+                },
+            ),
+            Self::new(
+                "skipped_fields",
+                &vec![
+                    Struct {
+                        id: 1,
+                        label: "first".to_owned(),
+                        tags: Vec::new(),
+                        note: Some("kept".to_owned()),
+                    },
+                    Struct {
+                        id: 2,
+                        label: "second".to_owned(),
+                        tags: vec!["b".to_owned()],
+                        note: None,
+                    },
+                ],
+            ),
+            Self::new(
+                "enum",
+                &vec![
+                    Enum::Unit,
+                    Enum::Newtype(3),
+                    Enum::Tuple(true, 'x'),
+                    Enum::Struct {
+                        x: -0.5,
+                        y: Some(()),
+                    },
+                ],
+            ),
+            Self::new(
+                "union",
+                &vec![Union::Int(1), Union::Text("two".to_owned()), Union::Missing],
+            ),
+            Self::new(
+                "nested",
+                &BTreeMap::from([(
+                    (1u8, 'k'),
+                    vec![Some(Enum::Newtype(9)), None, Some(Enum::Unit)],
+                )]),
+            ),
+        ]
+    }
+
+    fn new<ValueT>(name: &'static str, value: &ValueT) -> Self
+    where
+        ValueT: Serialize,
+    {
+        let mut builder = SchemaBuilder::new();
+        let trace = builder.trace(value).expect("test vectors are traceable");
+        let schema = builder.build().expect("test vectors are traceable");
+        Self {
+            name,
+            schema,
+            trace,
+        }
+    }
+
+    /// The name of the vector, unique among [`TestVector::all`].
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The schema of the vector's value.
+    #[inline]
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// The trace of the vector's value, together with its schema. Serializing it produces the
+    /// data bytes of the vector.
+    #[inline]
+    pub fn described(&self) -> DescribedBy<'_, &Trace> {
+        self.schema.describe_trace_ref(&self.trace)
+    }
+
+    /// The vector's value as displayed by this crate, e.g. to compare against what another
+    /// implementation decoded.
+    pub fn expected(&self) -> String {
+        self.described().to_string()
+    }
+
+    /// Checks data encoded by another implementation from this vector's value, together with
+    /// the schema it encoded.
+    ///
+    /// The schema must be [equivalent][`Schema::equivalent`] to the vector's, and the data
+    /// must decode with it to the same content, whatever the order of union members or fields
+    /// the other implementation chose.
+    pub fn verify<'de, DeserializerT>(
+        &self,
+        schema: &Schema,
+        data: DeserializerT,
+    ) -> Result<(), TestVectorError>
+    where
+        DeserializerT: Deserializer<'de>,
+    {
+        if !self.schema.equivalent(schema) {
+            return Err(TestVectorError::Schema {
+                name: self.name,
+                explanation: match self.schema.explain_difference(schema) {
+                    Ok(explanation) => explanation.to_string(),
+                    Err(error) => error.to_string(),
+                },
+            });
+        }
+        let decoded: RawValue =
+            schema
+                .deserialize_described(data)
+                .map_err(|error| TestVectorError::Decode {
+                    name: self.name,
+                    message: error.to_string(),
+                })?;
+        if decoded.described().content_eq(&self.described()) {
+            Ok(())
+        } else {
+            Err(TestVectorError::Mismatch {
+                name: self.name,
+                expected: self.expected(),
+                actual: decoded.described().to_string(),
+            })
+        }
+    }
+}
+
+impl std::fmt::Debug for TestVector {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("TestVector")
+            .field("name", &self.name)
+            .field("value", &format_args!("{}", self.described()))
+            .finish()
+    }
+}
+
+/// Serializes as bytes rather than a sequence of numbers.
+struct Bytes(&'static [u8]);
+
+impl Serialize for Bytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+#[derive(Serialize)]
+struct UnitStruct;
+
+#[derive(Serialize)]
+struct Newtype(u32);
+
+#[derive(Serialize)]
+struct TupleStruct(i16, String);
+
+#[derive(Serialize)]
+struct Struct {
+    id: u64,
+    label: String,
+    tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+#[derive(Serialize)]
+enum Enum {
+    Unit,
+    Newtype(u8),
+    Tuple(bool, char),
+    Struct { x: f32, y: Option<()> },
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Union {
+    Int(i32),
+    Text(String),
+    Missing,
+}
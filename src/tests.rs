@@ -1,10 +1,27 @@
-use crate::{Schema, described::SelfDescribed};
+use crate::{
+    CanonicalEncodingError, DecodeOptions, DecoderSession, Dynamic, DynamicFields, Embedded,
+    EncoderSession, EnumRepresentation, FieldPath, FloatNormalization, KvCodec, LeafValue,
+    LogicalTypes, MessageEncoder, MessageHeader, MessageHeaderError, NumericCoercion, PathSegment,
+    PrimitiveType, Qualified, RawValue, Schema, SchemaBuilder, SchemaGraphBuilder,
+    SchemaGraphError, SchemaStore, SchemaUpdate, Tensor, Trace, TraceError, TraceEvent, Typed,
+    TypedConversionError,
+    described::SelfDescribed,
+    indices::FieldNameIndex,
+    pool::Pool,
+    raw_value::{CaptureVisitor, RAW_VALUE_TYPE_NAME},
+    schema::SchemaNode,
+};
 use maplit::{btreemap, btreeset};
-use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde::{
+    Deserialize, Serialize, Serializer,
+    de::{DeserializeOwned, DeserializeSeed},
+};
 use serde_bytes::ByteBuf;
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fmt::Debug,
+    marker::PhantomData,
 };
 
 fn if_zero(value: &u32) -> bool {
@@ -48,6 +65,17 @@ fn postcard_roundtrip<T: Serialize + DeserializeOwned>(value: &T) -> T {
     from_self_described_postcard(&to_self_described_postcard(value))
 }
 
+fn postcard_convert_with<FromT: Serialize, IntoT: DeserializeOwned>(
+    value: &FromT,
+    options: DecodeOptions,
+) -> postcard::Result<IntoT> {
+    let bytes = to_self_described_postcard(value);
+    SelfDescribed(PhantomData::<IntoT>)
+        .with_options(options)
+        .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+        .map(|SelfDescribed(value)| value)
+}
+
 fn check_roundtrip<T: Serialize + DeserializeOwned + PartialEq + Debug>(original: &T) {
     let schema = Schema::display_for_value(original)
         .map(|display| display.to_string())
@@ -248,6 +276,81 @@ fn test_always_skipped() {
     check_roundtrip(&original);
 }
 
+#[test]
+fn test_struct_presence_bitset() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq, Default)]
+    struct Sparse {
+        #[serde(default, skip_serializing_if = "if_zero")]
+        a: u32,
+        #[serde(default, skip_serializing_if = "if_zero")]
+        b: u32,
+        #[serde(default, skip_serializing_if = "if_zero")]
+        c: u32,
+        #[serde(default, skip_serializing_if = "if_zero")]
+        d: u32,
+        #[serde(default, skip_serializing_if = "if_zero")]
+        e: u32,
+        #[serde(default, skip_serializing_if = "if_zero")]
+        f: u32,
+        #[serde(default, skip_serializing_if = "if_zero")]
+        g: u32,
+        #[serde(default, skip_serializing_if = "if_zero")]
+        h: u32,
+        #[serde(default, skip_serializing_if = "if_zero")]
+        i: u32,
+        last: u32,
+    }
+
+    let original = vec![
+        (
+            Sparse {
+                last: 1,
+                ..Sparse::default()
+            },
+            "after".to_owned(),
+        ),
+        (
+            Sparse {
+                a: 2,
+                h: 3,
+                i: 4,
+                last: 5,
+                ..Sparse::default()
+            },
+            "other".to_owned(),
+        ),
+    ];
+    check_roundtrip(&original);
+
+    // Ten fields take up two bytes of presence, however many of them are present.
+    let mut builder = SchemaBuilder::new();
+    let trace = builder
+        .trace(&Sparse {
+            i: 1,
+            last: 2,
+            ..Sparse::default()
+        })
+        .unwrap();
+    assert_eq!(trace.0.len(), 1 + 4 + 4 + 2 + 2 * (1 + 4));
+    let schema = builder.build().unwrap();
+    let events = schema
+        .describe_trace_ref(&trace)
+        .reader()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        events,
+        [
+            TraceEvent::Struct {
+                name: "Sparse",
+                fields: vec!["i", "last"],
+            },
+            TraceEvent::U32(1),
+            TraceEvent::U32(2),
+        ]
+    );
+}
+
 #[test]
 fn test_stress_skipped_fields() {
     macro_rules! definitions {
@@ -477,3 +580,4059 @@ fn test_complex_full() {
         ],
     });
 }
+
+#[test]
+fn test_options_numeric_coercions() {
+    let lenient = DecodeOptions::lenient();
+    let strict = DecodeOptions::strict();
+
+    assert_eq!(postcard_convert_with::<u8, u64>(&10, lenient), Ok(10));
+    assert_eq!(postcard_convert_with::<u64, u8>(&10, lenient), Ok(10));
+    assert_eq!(postcard_convert_with::<f32, f64>(&2.5, lenient), Ok(2.5));
+    assert!(postcard_convert_with::<u8, u64>(&10, strict).is_err());
+    assert!(postcard_convert_with::<f32, f64>(&2.5, strict).is_err());
+    assert_eq!(postcard_convert_with::<u64, u64>(&10, strict), Ok(10));
+}
+
+#[test]
+fn test_options_numeric_widening() {
+    let widening = DecodeOptions::strict().numeric_coercion(NumericCoercion::Widening);
+
+    assert_eq!(postcard_convert_with::<u8, u64>(&10, widening), Ok(10));
+    assert_eq!(postcard_convert_with::<u8, i16>(&10, widening), Ok(10));
+    assert_eq!(postcard_convert_with::<i8, i64>(&-10, widening), Ok(-10));
+    assert_eq!(postcard_convert_with::<f32, f64>(&2.5, widening), Ok(2.5));
+    assert!(postcard_convert_with::<u8, i8>(&10, widening).is_err());
+    assert!(postcard_convert_with::<i8, u64>(&10, widening).is_err());
+    assert!(postcard_convert_with::<u64, u32>(&10, widening).is_err());
+    assert!(postcard_convert_with::<f64, f32>(&2.5, widening).is_err());
+}
+
+#[test]
+fn test_options_numeric_checked() {
+    let checked = DecodeOptions::strict().numeric_coercion(NumericCoercion::Checked);
+
+    assert_eq!(postcard_convert_with::<u64, u8>(&255, checked), Ok(255));
+    assert_eq!(postcard_convert_with::<i64, u32>(&10, checked), Ok(10));
+    assert_eq!(postcard_convert_with::<f64, f32>(&2.5, checked), Ok(2.5));
+    assert!(postcard_convert_with::<u64, u8>(&256, checked).is_err());
+    assert!(postcard_convert_with::<i64, u32>(&-1, checked).is_err());
+    assert!(postcard_convert_with::<f64, f32>(&1e300, checked).is_err());
+    assert_eq!(
+        postcard_convert_with::<f64, f32>(&f64::INFINITY, checked),
+        Ok(f32::INFINITY)
+    );
+}
+
+#[test]
+fn test_options_default_matches_baseline() {
+    #[derive(Serialize)]
+    struct Before {
+        id: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        extra: bool,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct After {
+        id: u64,
+        note: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Tag {
+        Alpha,
+    }
+
+    let default = DecodeOptions::default();
+    assert_eq!(default, DecodeOptions::new());
+
+    assert_eq!(postcard_convert_with::<u64, u8>(&255, default), Ok(255));
+    assert!(postcard_convert_with::<u64, u8>(&256, default).is_err());
+    assert_eq!(postcard_convert_with::<f32, f64>(&2.5, default), Ok(2.5));
+    assert_eq!(
+        postcard_convert_with::<f64, f32>(&1e300, default),
+        Ok(f32::INFINITY)
+    );
+    assert_eq!(
+        postcard_convert_with::<_, After>(
+            &Before {
+                id: 7,
+                note: None,
+                extra: true,
+            },
+            default,
+        ),
+        Ok(After { id: 7, note: None })
+    );
+    assert!(postcard_convert_with::<char, String>(&'x', default).is_err());
+    assert!(postcard_convert_with::<String, char>(&"y".to_owned(), default).is_err());
+    assert!(postcard_convert_with::<_, Tag>(&"Alpha".to_owned(), default).is_err());
+    assert!(postcard_convert_with::<_, String>(&Tag::Alpha, default).is_err());
+}
+
+#[test]
+fn test_options_char_string_coercions() {
+    let lenient = DecodeOptions::lenient();
+    let strict = DecodeOptions::strict();
+
+    assert_eq!(
+        postcard_convert_with::<char, String>(&'x', lenient),
+        Ok("x".to_owned())
+    );
+    assert_eq!(
+        postcard_convert_with::<String, char>(&"y".to_owned(), lenient),
+        Ok('y')
+    );
+    assert!(postcard_convert_with::<String, char>(&"yz".to_owned(), lenient).is_err());
+    assert!(postcard_convert_with::<char, String>(&'x', strict).is_err());
+    assert!(postcard_convert_with::<String, char>(&"y".to_owned(), strict).is_err());
+}
+
+#[test]
+fn test_options_enum_string_coercions() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Tag {
+        Alpha,
+        Beta,
+    }
+
+    let coerced = DecodeOptions::new().enum_string_coercions(true);
+    let lenient = DecodeOptions::lenient();
+
+    assert_eq!(
+        postcard_convert_with::<_, Tag>(&"Beta".to_owned(), coerced),
+        Ok(Tag::Beta)
+    );
+    assert_eq!(
+        postcard_convert_with::<_, Vec<Tag>>(&vec!["Alpha", "Beta"], coerced),
+        Ok(vec![Tag::Alpha, Tag::Beta])
+    );
+    assert_eq!(
+        postcard_convert_with::<_, String>(&Tag::Alpha, coerced),
+        Ok("Alpha".to_owned())
+    );
+    assert_eq!(
+        postcard_convert_with::<_, Vec<String>>(&vec![Tag::Alpha, Tag::Beta], coerced),
+        Ok(vec!["Alpha".to_owned(), "Beta".to_owned()])
+    );
+    assert!(postcard_convert_with::<_, Tag>(&"Gamma".to_owned(), coerced).is_err());
+    assert!(postcard_convert_with::<_, Tag>(&"Beta".to_owned(), lenient).is_err());
+    assert!(postcard_convert_with::<_, String>(&Tag::Alpha, lenient).is_err());
+}
+
+#[test]
+fn test_options_enum_representation() {
+    #[derive(Serialize)]
+    enum Shape {
+        Circle { radius: u32 },
+        Square(Side),
+        Line(u32, u32),
+        Empty,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Side {
+        side: u32,
+    }
+
+    /// Deserializes via `deserialize_any`, which is how enum representations are presented.
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Any<T> {
+        Value(T),
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum External {
+        Circle { radius: u32 },
+        Square(Side),
+        Line(u32, u32),
+        Empty,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Circle { radius: u32 },
+        Square(Side),
+        Empty,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Circle { radius: u32 },
+        Square(Side),
+        Line(u32, u32),
+        Empty,
+    }
+
+    let shapes = vec![
+        Shape::Circle { radius: 1 },
+        Shape::Square(Side { side: 2 }),
+        Shape::Line(3, 4),
+        Shape::Empty,
+    ];
+    let options = DecodeOptions::lenient();
+
+    assert_eq!(
+        postcard_convert_with::<_, Vec<Any<External>>>(
+            &shapes,
+            options.enum_representation(EnumRepresentation::External),
+        ),
+        Ok(vec![
+            Any::Value(External::Circle { radius: 1 }),
+            Any::Value(External::Square(Side { side: 2 })),
+            Any::Value(External::Line(3, 4)),
+            Any::Value(External::Empty),
+        ])
+    );
+    assert_eq!(
+        postcard_convert_with::<_, Vec<Any<Adjacent>>>(
+            &shapes,
+            options.enum_representation(EnumRepresentation::Adjacent {
+                tag: "t",
+                content: "c",
+            }),
+        ),
+        Ok(vec![
+            Any::Value(Adjacent::Circle { radius: 1 }),
+            Any::Value(Adjacent::Square(Side { side: 2 })),
+            Any::Value(Adjacent::Line(3, 4)),
+            Any::Value(Adjacent::Empty),
+        ])
+    );
+
+    let internal = options.enum_representation(EnumRepresentation::Internal { tag: "type" });
+    assert_eq!(
+        postcard_convert_with::<_, Vec<Internal>>(&&shapes[..2], internal),
+        Ok(vec![
+            Internal::Circle { radius: 1 },
+            Internal::Square(Side { side: 2 }),
+        ])
+    );
+    assert_eq!(
+        postcard_convert_with::<_, Internal>(&Shape::Empty, internal),
+        Ok(Internal::Empty)
+    );
+    // Tuple variants can't be internally tagged.
+    assert!(postcard_convert_with::<_, Internal>(&Shape::Line(3, 4), internal).is_err());
+
+    // By default, variant names are dropped.
+    assert_eq!(
+        postcard_convert_with::<_, Any<(u32, u32)>>(&Shape::Line(3, 4), options),
+        Ok(Any::Value((3, 4)))
+    );
+}
+
+#[test]
+fn test_options_fields() {
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Writer {
+        kept: u32,
+
+        #[serde(default, skip_serializing_if = "if_zero")]
+        skippable: u32,
+
+        extra: u32,
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Reader {
+        kept: u32,
+
+        #[serde(default)]
+        skippable: u32,
+    }
+
+    let skipped = Writer {
+        kept: 1,
+        skippable: 0,
+        extra: 2,
+    };
+    let present = Writer {
+        kept: 1,
+        skippable: 3,
+        extra: 2,
+    };
+    let lenient = DecodeOptions::lenient();
+    let strict = DecodeOptions::strict();
+    let no_unknown = lenient.deny_unknown_fields(true);
+
+    assert_eq!(
+        postcard_convert_with::<_, Reader>(&skipped, lenient),
+        Ok(Reader {
+            kept: 1,
+            skippable: 0,
+        })
+    );
+    assert!(postcard_convert_with::<_, Reader>(&skipped, no_unknown).is_err());
+    assert!(postcard_convert_with::<_, Reader>(&present, no_unknown).is_err());
+    assert!(postcard_convert_with::<_, Writer>(&skipped, strict).is_err());
+    assert_eq!(
+        postcard_convert_with::<_, Writer>(&present, strict),
+        Ok(present)
+    );
+}
+
+#[test]
+fn test_display_trace() {
+    let mut builder = SchemaBuilder::new();
+    let trace = builder
+        .trace(&(
+            AllVariantKinds::Unit,
+            AllVariantKinds::Newtype(1),
+            AllVariantKinds::Tuple(2, 3),
+            AllVariantKinds::StructTwo { x: 4, y: 5 },
+            btreemap! { "a" => vec![Some('b')], "c" => vec![] },
+            (UnitStruct, NewtypeStruct(6), EmptyFieldStruct {}),
+            ByteBuf::from(b"\x00a".to_vec()),
+            -7.5f32,
+        ))
+        .unwrap();
+    let schema = builder.build().unwrap();
+
+    assert_eq!(
+        schema.describe_trace_ref(&trace).to_string(),
+        "(Unit, Newtype(1), Tuple(2, 3), StructTwo(x: 4, y: 5), {\"a\": [Some('b')], \"c\": []}, \
+         (UnitStruct, NewtypeStruct(6), EmptyFieldStruct()), b\"\\x00a\", -7.5)"
+    );
+    assert_eq!(
+        format!("{:#}", schema.describe_trace(trace)),
+        r#"(
+  Unit,
+  Newtype(1),
+  Tuple(2, 3),
+  StructTwo(x: 4, y: 5),
+  {"a": [Some('b')], "c": []},
+  (UnitStruct, NewtypeStruct(6), EmptyFieldStruct()),
+  b"\x00a",
+  -7.5,
+)"#
+    );
+}
+
+#[test]
+fn test_diff_traces() {
+    #[derive(Serialize)]
+    #[serde(rename = "Config")]
+    struct Before {
+        name: &'static str,
+        tags: BTreeMap<&'static str, u32>,
+        items: Vec<AllVariantKinds>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        removed: Option<u32>,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "Config")]
+    struct After {
+        name: &'static str,
+        tags: BTreeMap<&'static str, u32>,
+        items: Vec<AllVariantKinds>,
+        added: (u32, u64),
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let before = builder
+        .trace(&Before {
+            name: "same",
+            tags: btreemap! { "a" => 1, "b" => 2 },
+            items: vec![
+                AllVariantKinds::StructTwo { x: 1, y: 2 },
+                AllVariantKinds::Newtype(3),
+            ],
+            removed: Some(4),
+        })
+        .unwrap();
+    let before_schema = builder.build().unwrap();
+
+    let mut builder = SchemaBuilder::new();
+    let after = builder
+        .trace(&After {
+            name: "same",
+            tags: btreemap! { "b" => 3, "c" => 4 },
+            items: vec![
+                AllVariantKinds::StructTwo { x: 1, y: 5 },
+                AllVariantKinds::Tuple(3, 4),
+                AllVariantKinds::Unit,
+            ],
+            added: (5, 6),
+        })
+        .unwrap();
+    let after_schema = builder.build().unwrap();
+
+    let diff = before_schema
+        .describe_trace_ref(&before)
+        .diff(&after_schema.describe_trace_ref(&after))
+        .unwrap();
+    assert_eq!(
+        diff.to_string(),
+        "- .tags[\"a\"]: 1\n\
+         ~ .tags[\"b\"]: 2 -> 3\n\
+         + .tags[\"c\"]: 4\n\
+         ~ .items[0].y: 2 -> 5\n\
+         ~ .items[1]: Newtype(3) -> Tuple(3, 4)\n\
+         + .items[2]: Unit\n\
+         - .removed: Some(4)\n\
+         + .added: (5, 6)\n"
+    );
+
+    let same = before_schema
+        .describe_trace_ref(&before)
+        .diff(&before_schema.describe_trace_ref(&before))
+        .unwrap();
+    assert!(same.is_empty());
+}
+
+#[test]
+fn test_trace_content_eq() {
+    let mut builder = SchemaBuilder::new();
+    let first = builder
+        .trace(&AllVariantKinds::StructTwo { x: 1, y: 2 })
+        .unwrap();
+    let second = builder.trace(&AllVariantKinds::Tuple(1, 2)).unwrap();
+    let third = builder
+        .trace(&AllVariantKinds::StructTwo { x: 1, y: 3 })
+        .unwrap();
+    let shared_schema = builder.build().unwrap();
+
+    let mut builder = SchemaBuilder::new();
+    let alone = builder
+        .trace(&AllVariantKinds::StructTwo { x: 1, y: 2 })
+        .unwrap();
+    let alone_schema = builder.build().unwrap();
+
+    let first = shared_schema.describe_trace(first);
+    let second = shared_schema.describe_trace(second);
+    let third = shared_schema.describe_trace(third);
+    let alone = alone_schema.describe_trace(alone);
+    assert!(first.content_eq(&alone));
+    assert!(!first.content_eq(&second));
+    assert!(!first.content_eq(&third));
+
+    let invalid = Trace(vec![u8::MAX]);
+    let cloned_schema = shared_schema.clone();
+    let invalid_shared = shared_schema.describe_trace_ref(&invalid);
+    assert!(invalid_shared.content_eq(&cloned_schema.describe_trace_ref(&invalid)));
+    assert!(!invalid_shared.content_eq(&alone_schema.describe_trace_ref(&invalid)));
+    assert!(!invalid_shared.content_eq(&first.1.describe_trace_ref(&first.0)));
+
+    assert_eq!(HashSet::from([first, second, third, alone]).len(), 3);
+}
+
+#[test]
+fn test_schema_fingerprint() {
+    let schema = |value: &dyn Fn(&mut SchemaBuilder)| {
+        let mut builder = SchemaBuilder::new();
+        value(&mut builder);
+        builder.build().unwrap()
+    };
+    let first = schema(&|builder| {
+        let _ = builder.trace(&AllVariantKinds::Unit).unwrap();
+    });
+    let second = schema(&|builder| {
+        let _ = builder.trace(&AllVariantKinds::Unit).unwrap();
+    });
+    let third = schema(&|builder| {
+        let _ = builder.trace(&AllVariantKinds::Newtype(1)).unwrap();
+    });
+    let roundtripped =
+        postcard::from_bytes::<Schema>(&postcard::to_stdvec(&first).unwrap()).unwrap();
+
+    assert_eq!(first.fingerprint(), second.fingerprint());
+    assert_eq!(first.fingerprint(), roundtripped.fingerprint());
+    assert_ne!(first.fingerprint(), third.fingerprint());
+
+    // Schemas which only differ in the order of their nodes, and so of their union members.
+    let integer_first = schema(&|builder| {
+        let _ = builder.trace(&vec![1u32]).unwrap();
+        let _ = builder.trace(&vec!["one"]).unwrap();
+    });
+    let string_first = schema(&|builder| {
+        let _ = builder.trace(&vec!["one"]).unwrap();
+        let _ = builder.trace(&vec![1u32]).unwrap();
+    });
+    assert_ne!(integer_first.fingerprint(), string_first.fingerprint());
+}
+
+#[test]
+fn test_named_roots() {
+    let mut builder = SchemaBuilder::new();
+    let first = builder
+        .trace_root("First", &FieldStructOne { x: 1 })
+        .unwrap();
+    let second = builder.trace_root("Second", &TupleStruct(2, 3)).unwrap();
+    let unnamed = builder.trace(&4u32).unwrap();
+    let schema = builder.build().unwrap();
+    assert_eq!(schema.roots(), ["First", "Second"]);
+
+    let deserialize_root = |root, trace| {
+        let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+        schema
+            .describe_root::<FieldStructOne>(root)
+            .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+            .map(|described| described.0)
+    };
+    assert_eq!(
+        deserialize_root("First", first.clone()),
+        Ok(FieldStructOne { x: 1 })
+    );
+    assert!(deserialize_root("Second", first).is_err());
+    assert!(deserialize_root("Second", second).is_err());
+    assert!(deserialize_root("First", unnamed).is_err());
+}
+
+#[test]
+fn test_qualified_type_names() {
+    mod first {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        pub struct Config {
+            pub value: u32,
+        }
+    }
+
+    mod second {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        pub struct Config {
+            pub value: String,
+        }
+    }
+
+    let trace_both = |qualify: bool| {
+        let mut builder = SchemaBuilder::new();
+        if qualify {
+            let _ = builder
+                .trace(&Qualified(first::Config { value: 1 }))
+                .unwrap();
+            let _ = builder
+                .trace(&Qualified(second::Config {
+                    value: "2".to_owned(),
+                }))
+                .unwrap();
+        } else {
+            let _ = builder.trace(&first::Config { value: 1 }).unwrap();
+            let _ = builder
+                .trace(&second::Config {
+                    value: "2".to_owned(),
+                })
+                .unwrap();
+        }
+        builder.build().unwrap().to_string()
+    };
+    assert!(trace_both(false).starts_with("Config"));
+    let qualified = trace_both(true);
+    assert!(qualified.starts_with("union"), "{qualified}");
+    assert!(qualified.contains("first::Config"), "{qualified}");
+    assert!(qualified.contains("second::Config"), "{qualified}");
+
+    assert_eq!(
+        postcard_roundtrip(&Qualified(first::Config { value: 3 })),
+        Qualified(first::Config { value: 3 })
+    );
+}
+
+#[test]
+fn test_schema_graph_builder() {
+    let mut graph = SchemaGraphBuilder::new();
+    let unit = graph.add_unit_variant("Enum", "Unit").unwrap();
+    let integer = graph.add_primitive(PrimitiveType::U32).unwrap();
+    let newtype = graph
+        .add_newtype_variant("Enum", "Newtype", integer)
+        .unwrap();
+    let inner = graph.add_union(&[unit, newtype]).unwrap();
+    let outer = graph.add_union(&[newtype, inner, unit]).unwrap();
+    assert_eq!(inner, outer);
+    assert_eq!(graph.add_union(&[integer, integer]).unwrap(), integer);
+    assert!(matches!(
+        graph.add_struct("Struct", &[("x", integer)], &["y"]),
+        Err(SchemaGraphError::UnknownSkippableField(_))
+    ));
+
+    let other = SchemaGraphBuilder::new()
+        .add_primitive(PrimitiveType::Bool)
+        .unwrap();
+    assert!(matches!(
+        SchemaGraphBuilder::new().add_sequence(other),
+        Err(SchemaGraphError::UnknownNode(_))
+    ));
+
+    let schema = graph.build(outer).unwrap();
+    for value in [AllVariantKinds::Unit, AllVariantKinds::Newtype(10)] {
+        let mut builder = SchemaBuilder::new();
+        let _ = builder.trace(&AllVariantKinds::Unit).unwrap();
+        let _ = builder.trace(&AllVariantKinds::Newtype(0)).unwrap();
+        let trace = builder.trace(&value).unwrap();
+        let bytes = postcard::to_stdvec(&builder.build().unwrap().describe_trace(trace)).unwrap();
+        let crate::DescribedBy(roundtripped, _) = schema
+            .describe_type::<AllVariantKinds>()
+            .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+            .unwrap();
+        assert_eq!(roundtripped, value);
+    }
+}
+
+#[test]
+fn test_schema_pruned() {
+    let mut graph = SchemaGraphBuilder::new();
+    let _unused = graph.add_unit_variant("Unused", "Unit").unwrap();
+    let unit = graph.add_unit_variant("Enum", "Unit").unwrap();
+    let integer = graph.add_primitive(PrimitiveType::U32).unwrap();
+    let _unused = graph.add_sequence(integer).unwrap();
+    let newtype = graph
+        .add_newtype_variant("Enum", "Newtype", integer)
+        .unwrap();
+    let root = graph.add_union(&[unit, newtype]).unwrap();
+    let schema = graph.build(root).unwrap();
+
+    let mut graph = SchemaGraphBuilder::new();
+    let unit = graph.add_unit_variant("Enum", "Unit").unwrap();
+    let integer = graph.add_primitive(PrimitiveType::U32).unwrap();
+    let newtype = graph
+        .add_newtype_variant("Enum", "Newtype", integer)
+        .unwrap();
+    let root = graph.add_union(&[unit, newtype]).unwrap();
+    let minimal = graph.build(root).unwrap();
+
+    let pruned = schema.pruned().unwrap();
+    assert_eq!(pruned.to_string(), minimal.to_string());
+    assert_eq!(pruned.fingerprint(), minimal.fingerprint());
+    assert_eq!(
+        pruned.pruned().unwrap().fingerprint(),
+        minimal.fingerprint()
+    );
+
+    let mut builder = SchemaBuilder::new();
+    let _ = builder.trace(&AllVariantKinds::Unit).unwrap();
+    let trace = builder.trace(&AllVariantKinds::Newtype(10)).unwrap();
+    let bytes = postcard::to_stdvec(&builder.build().unwrap().describe_trace(trace)).unwrap();
+    let value: AllVariantKinds = pruned
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(value, AllVariantKinds::Newtype(10));
+
+    // A corrupt schema whose root contains itself.
+    let mut nodes = Pool::default();
+    let root = nodes.intern(SchemaNode::Sequence(1.into())).unwrap();
+    let corrupt = Schema {
+        root_index: root,
+        nodes: nodes.into(),
+        ..minimal
+    };
+    assert!(corrupt.pruned().is_err());
+}
+
+#[test]
+fn test_schema_pruned_canonical() {
+    let tuple = |unit_first: bool| {
+        let mut graph = SchemaGraphBuilder::new();
+        let (unit, integer) = if unit_first {
+            let unit = graph.add_unit_variant("Enum", "Unit").unwrap();
+            (unit, graph.add_primitive(PrimitiveType::U32).unwrap())
+        } else {
+            let integer = graph.add_primitive(PrimitiveType::U32).unwrap();
+            (graph.add_unit_variant("Enum", "Unit").unwrap(), integer)
+        };
+        let root = graph.add_tuple(&[unit, integer]).unwrap();
+        graph.build(root).unwrap()
+    };
+    assert_ne!(tuple(true).fingerprint(), tuple(false).fingerprint());
+    assert_eq!(
+        tuple(true).pruned().unwrap().fingerprint(),
+        tuple(false).pruned().unwrap().fingerprint()
+    );
+
+    // Union members keep their order, since it determines their discriminants.
+    let values = [
+        AllVariantKinds::Tuple(1, 2),
+        AllVariantKinds::StructTwo { x: 1, y: 2 },
+    ];
+    let forward = SchemaBuilder::trace_all(&values).unwrap().0;
+    let mut backward = values;
+    backward.reverse();
+    let backward = SchemaBuilder::trace_all(&backward).unwrap().0;
+    assert_ne!(
+        forward.pruned().unwrap().fingerprint(),
+        backward.pruned().unwrap().fingerprint()
+    );
+
+    // Duplicate entries are merged.
+    let duplicated: Schema = ron::from_str(
+        "V0(root_index:(3),nodes:[U32,U32,Tuple((1))],node_lists:[[(1),(2)]],member_lists:[],\
+         field_name_lists:[],field_names:[],variant_names:[],type_names:[])",
+    )
+    .unwrap();
+    let mut graph = SchemaGraphBuilder::new();
+    let integer = graph.add_primitive(PrimitiveType::U32).unwrap();
+    let tuple = graph.add_tuple(&[integer, integer]).unwrap();
+    let minimal = graph.build(tuple).unwrap();
+    assert_ne!(duplicated.fingerprint(), minimal.fingerprint());
+    assert_eq!(
+        duplicated.pruned().unwrap().fingerprint(),
+        minimal.fingerprint()
+    );
+}
+
+#[cfg(feature = "import")]
+#[test]
+fn test_import_schemas() {
+    #[derive(Serialize)]
+    struct User {
+        id: i64,
+        email: Option<String>,
+    }
+
+    let avro = Schema::from_avro(
+        r#"{
+            "type": "record",
+            "name": "User",
+            "namespace": "com.example",
+            "fields": [
+                {"name": "id", "type": "long"},
+                {"name": "email", "type": ["null", "string"]}
+            ]
+        }"#,
+    )
+    .unwrap();
+    let json_schema = Schema::from_json_schema(
+        r##"{
+            "$ref": "#/$defs/User",
+            "$defs": {
+                "User": {
+                    "type": "object",
+                    "properties": {
+                        "id": {"type": "integer"},
+                        "email": {"type": ["string", "null"]}
+                    },
+                    "required": ["id", "email"]
+                }
+            }
+        }"##,
+    )
+    .unwrap();
+    let mut builder = SchemaBuilder::new();
+    let _ = builder
+        .trace(&User {
+            id: 1,
+            email: Some("a@b.c".to_owned()),
+        })
+        .unwrap();
+    let _ = builder.trace(&User { id: 2, email: None }).unwrap();
+    assert_eq!(avro.to_string(), builder.build().unwrap().to_string());
+    assert_eq!(
+        json_schema.to_string(),
+        "User_6 { email: union_4(some_2(str_1), none_3), id: i64_5 }",
+    );
+
+    assert!(matches!(
+        Schema::from_avro(
+            r#"{"type": "record", "name": "List", "fields": [{"name": "next", "type": ["null", "List"]}]}"#
+        ),
+        Err(crate::ImportError::Recursive(_))
+    ));
+    assert!(matches!(
+        Schema::from_json_schema(r#"{"type": "object", "properties": {"x": {"type": "date"}}}"#),
+        Err(crate::ImportError::UnknownType(_))
+    ));
+}
+
+#[test]
+fn test_trace_all() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Circle(f32),
+        Square(f32),
+    }
+
+    let values = [Shape::Square(1.0), Shape::Circle(2.0), Shape::Square(3.0)];
+    let (schema, traces) = SchemaBuilder::trace_all(&values).unwrap();
+    let serialized = traces
+        .into_iter()
+        .map(|trace| postcard::to_stdvec(&schema.describe_trace(trace)).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(serialized[0][0], serialized[2][0]);
+    assert_ne!(serialized[0][0], serialized[1][0]);
+    for (bytes, value) in serialized.iter().zip(&values) {
+        let crate::DescribedBy(roundtripped, _) = schema
+            .describe_type::<Shape>()
+            .deserialize(&mut postcard::Deserializer::from_bytes(bytes))
+            .unwrap();
+        assert_eq!(&roundtripped, value);
+    }
+}
+
+#[test]
+fn test_schema_only() {
+    let values = (
+        "x".repeat(1000),
+        ByteBuf::from(vec![7; 1000]),
+        vec![
+            AllVariantKinds::NewTypeStructFieldTwo(FieldStructTwo { x: 1, y: 2 }),
+            AllVariantKinds::Tuple(3, 4),
+        ],
+    );
+    let (traced, _) = SchemaBuilder::trace_all([&values]).unwrap();
+    let schema_only = SchemaBuilder::schema_only(&values).unwrap();
+    assert_eq!(schema_only.to_string(), traced.to_string());
+    assert_eq!(schema_only.fingerprint(), traced.fingerprint());
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&Some(AllVariantKinds::Unit)).unwrap();
+    builder.trace_schema_only(&None::<AllVariantKinds>).unwrap();
+    builder
+        .trace_schema_only(&Some(AllVariantKinds::StructOne { x: 5 }))
+        .unwrap();
+    let schema = builder.build().unwrap();
+    let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    let roundtripped: Option<AllVariantKinds> = schema
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(roundtripped, Some(AllVariantKinds::Unit));
+}
+
+#[test]
+fn test_infer_schema() {
+    let values = [
+        AllVariantKinds::Newtype(1),
+        AllVariantKinds::Newtype(2),
+        AllVariantKinds::Unit,
+        AllVariantKinds::NewTypeOption(None),
+        AllVariantKinds::Newtype(3),
+        AllVariantKinds::NewTypeOption(Some(Box::new(AllVariantKinds::Unit))),
+        AllVariantKinds::Unit,
+    ];
+    let inference = SchemaBuilder::infer_schema(&values, usize::MAX).unwrap();
+    assert_eq!(inference.widened_by(), [0, 2, 3, 5]);
+    assert_eq!(inference.sampled(), values.len());
+    let (traced, _) = SchemaBuilder::trace_all(&values).unwrap();
+    assert_eq!(inference.schema().fingerprint(), traced.fingerprint());
+
+    let inference = SchemaBuilder::infer_schema(&values, 1).unwrap();
+    assert_eq!(inference.widened_by(), [0]);
+    assert_eq!(inference.sampled(), 2);
+    let inference = SchemaBuilder::infer_schema(&values[..0], 1).unwrap();
+    assert!(inference.widened_by().is_empty());
+    assert_eq!(inference.sampled(), 0);
+
+    // Newly skipped fields and new members of nested unions widen the schema, repeated ones
+    // don't, whatever order they come in.
+    #[derive(Serialize)]
+    struct Row {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<u8>,
+        values: Vec<Option<u8>>,
+    }
+
+    let rows = [
+        Row {
+            note: Some(1),
+            values: vec![],
+        },
+        Row {
+            note: Some(2),
+            values: vec![Some(1)],
+        },
+        Row {
+            note: None,
+            values: vec![Some(1), None],
+        },
+        Row {
+            note: None,
+            values: vec![None, Some(2)],
+        },
+        Row {
+            note: Some(3),
+            values: vec![None],
+        },
+    ];
+    let inference = SchemaBuilder::infer_schema(&rows, usize::MAX).unwrap();
+    assert_eq!(inference.widened_by(), [0, 1, 2]);
+}
+
+#[test]
+fn test_union_stats() {
+    #[derive(Serialize)]
+    enum Value {
+        Int(u32),
+        Text(&'static str),
+    }
+
+    #[derive(Serialize)]
+    struct Row {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<Value>,
+        previous: Option<u8>,
+    }
+
+    let rows = [
+        Row {
+            value: None,
+            previous: None,
+        },
+        Row {
+            value: Some(Value::Int(1)),
+            previous: Some(1),
+        },
+        Row {
+            value: None,
+            previous: Some(2),
+        },
+        Row {
+            value: Some(Value::Text("a")),
+            previous: Some(3),
+        },
+        Row {
+            value: Some(Value::Int(2)),
+            previous: None,
+        },
+    ];
+    let mut builder = SchemaBuilder::new();
+    builder.trace_schema_only(&&rows[..3]).unwrap();
+    builder.trace_schema_only(&&rows[3..]).unwrap();
+    builder
+        .trace_schema_only(&btreemap! { 1u8 => None, 2 => Some(5u8) })
+        .unwrap();
+    let (schema, stats) = builder.build_with_union_stats().unwrap();
+    assert_eq!(
+        schema.to_string(),
+        "union_14(\
+         seq_12(Row_11 { \
+         value?: some_6(union_5(Value::Text_2(str_1), Value::Int_4(u32_3))), \
+         previous: union_10(none_7, some_9(u8_8)) }), \
+         map_13(u8_8, union_10(..)))",
+    );
+    assert_eq!(stats.len(), 3);
+    assert_eq!(stats.member_counts(14), Some(&[2, 1][..]));
+    assert_eq!(stats.member_counts(5), Some(&[1, 2][..]));
+    assert_eq!(stats.member_counts(10), Some(&[3, 4][..]));
+    assert_eq!(stats.member_counts(12), None);
+}
+
+#[test]
+fn test_human_readable() {
+    /// Serializes as a string in human-readable formats and as a number otherwise.
+    #[derive(Debug, PartialEq)]
+    struct Id(u32);
+
+    impl Serialize for Id {
+        fn serialize<SerializerT: Serializer>(
+            &self,
+            serializer: SerializerT,
+        ) -> Result<SerializerT::Ok, SerializerT::Error> {
+            if serializer.is_human_readable() {
+                serializer.collect_str(&format_args!("id-{}", self.0))
+            } else {
+                serializer.serialize_u32(self.0)
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Id {
+        fn deserialize<DeserializerT: serde::Deserializer<'de>>(
+            deserializer: DeserializerT,
+        ) -> Result<Self, DeserializerT::Error> {
+            if deserializer.is_human_readable() {
+                let string = String::deserialize(deserializer)?;
+                string
+                    .strip_prefix("id-")
+                    .and_then(|id| id.parse().ok())
+                    .map(Id)
+                    .ok_or_else(|| serde::de::Error::custom("bad id"))
+            } else {
+                u32::deserialize(deserializer).map(Id)
+            }
+        }
+    }
+
+    let mut builder = SchemaBuilder::new().human_readable(true);
+    let trace = builder.trace(&vec![Id(1), Id(2)]).unwrap();
+    let schema = builder.build().unwrap();
+    assert!(schema.is_human_readable());
+    assert_eq!(schema.to_string(), "seq_2(str_1)");
+
+    // The mode survives serializing the schema, and is read back by the data.
+    let schema: Schema = postcard::from_bytes(&postcard::to_stdvec(&schema).unwrap()).unwrap();
+    assert!(schema.is_human_readable());
+    let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    let ids: Vec<Id> = schema
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(ids, [Id(1), Id(2)]);
+
+    // Otherwise, schemas serialize as before, as the first version of the format.
+    let mut builder = SchemaBuilder::new();
+    let _ = builder.trace(&Id(1)).unwrap();
+    let schema = builder.build().unwrap();
+    assert!(!schema.is_human_readable());
+    assert_eq!(schema.to_string(), "u32_1");
+    assert_eq!(postcard::to_stdvec(&schema).unwrap()[0], 0);
+}
+
+#[test]
+fn test_logical_types() {
+    #[derive(Serialize)]
+    struct Celsius(f64);
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Kelvin(f64);
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Stored(u64);
+
+    static READER_TYPES: std::sync::LazyLock<LogicalTypes> = std::sync::LazyLock::new(|| {
+        LogicalTypes::new().register(
+            "Kelvin",
+            "temperature-mk",
+            |value| value,
+            |value| match value {
+                LeafValue::U64(millikelvin) => LeafValue::F64(millikelvin as f64 / 1000.0),
+                other => other,
+            },
+        )
+    });
+
+    let writer_types = LogicalTypes::new().register(
+        "Celsius",
+        "temperature-mk",
+        |value| match value {
+            LeafValue::F64(celsius) => LeafValue::U64(((celsius + 273.15) * 1000.0).round() as u64),
+            other => other,
+        },
+        |value| value,
+    );
+    let mut builder = SchemaBuilder::new().logical_types(writer_types);
+    let trace = builder.trace(&vec![Celsius(0.0), Celsius(26.85)]).unwrap();
+    let schema = builder.build().unwrap();
+    assert_eq!(schema.to_string(), "seq_3(temperature-mk_2(u64_1))");
+    let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+
+    // Readers registering the same id decode the stored value, whatever their types are called.
+    let kelvins = schema
+        .describe_type::<Vec<Kelvin>>()
+        .with_options(DecodeOptions::lenient().logical_types(&READER_TYPES))
+        .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap()
+        .0;
+    assert_eq!(kelvins, [Kelvin(273.15), Kelvin(300.0)]);
+
+    // Readers without the registry see the stored value.
+    let stored: Vec<Stored> = schema
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(stored, [Stored(273150), Stored(300000)]);
+
+    // Logical types only wrap single primitives, rather than converting each of several.
+    #[derive(Serialize)]
+    struct Range {
+        low: f64,
+        high: f64,
+    }
+
+    #[derive(Serialize)]
+    struct CelsiusRange(Range);
+
+    let writer_types = LogicalTypes::new().register(
+        "CelsiusRange",
+        "temperature-range-mk",
+        |value| value,
+        |value| value,
+    );
+    let mut builder = SchemaBuilder::new().logical_types(writer_types);
+    assert!(matches!(
+        builder.trace(&CelsiusRange(Range {
+            low: 0.0,
+            high: 26.85
+        })),
+        Err(TraceError::LogicalType("temperature-range-mk"))
+    ));
+}
+
+#[test]
+fn test_trace_reader_trailing_data() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Point {
+        x: i32,
+        y: i32,
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&Point { x: 1, y: 2 }).unwrap();
+    let schema = builder.build().unwrap();
+
+    let mut reader = schema.describe_trace_ref(&trace).reader();
+    assert_eq!(reader.read_value::<Point>().unwrap(), Point { x: 1, y: 2 });
+    assert_eq!(reader.remaining_bytes(), 0);
+    reader.finish().unwrap();
+
+    // A trace holding a second value, e.g. two concatenated payloads.
+    let mut doubled = trace.0.clone();
+    doubled.extend_from_slice(&trace.0);
+    let doubled = Trace(doubled);
+    let mut reader = schema.describe_trace_ref(&doubled).reader();
+    assert_eq!(reader.read_value::<Point>().unwrap(), Point { x: 1, y: 2 });
+    assert_eq!(reader.offset(), trace.0.len());
+    assert_eq!(reader.remaining_bytes(), trace.0.len());
+    let error = reader.finish().unwrap_err();
+    assert_eq!(error.offset(), trace.0.len());
+    assert_eq!(error.remaining(), trace.0.len());
+    assert_eq!(
+        error.next(),
+        r#"Struct { name: "Point", fields: ["x", "y"] }"#
+    );
+}
+
+#[test]
+fn test_trace_mismatch_error() {
+    #[derive(Serialize)]
+    enum Shape {
+        Polygon { points: Vec<(i32, i32)> },
+    }
+
+    mod v2 {
+        #[derive(serde::Serialize)]
+        pub(super) enum Shape {
+            Polygon { points: Vec<(i32, i32, i32)> },
+        }
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder
+        .trace(&vec![Shape::Polygon {
+            points: vec![(1, 2), (3, 4)],
+        }])
+        .unwrap();
+    let _ = builder.build().unwrap();
+
+    let mut other = SchemaBuilder::new();
+    let _ = other
+        .trace(&vec![v2::Shape::Polygon {
+            points: vec![(1, 2, 3)],
+        }])
+        .unwrap();
+    let other = other.build().unwrap();
+
+    assert_eq!(
+        ron::to_string(&other.describe_trace(trace))
+            .unwrap_err()
+            .to_string(),
+        "schema-trace mismatch at `.[0]::Polygon.points[0]` (byte 29 of the trace): expected \
+         tuple of 3, found tuple of 2",
+    );
+}
+
+#[test]
+fn test_schema_versions() {
+    let mut builder = SchemaBuilder::new()
+        .human_readable(true)
+        .version(7)
+        .min_reader_version(5);
+    let trace = builder.trace(&vec![(1u8, 'a')]).unwrap();
+    let schema = builder.build().unwrap();
+    assert_eq!((schema.version(), schema.min_reader_version()), (7, 5));
+
+    // Versioned schemas serialize as a new version of the format, keeping every setting.
+    let bytes = postcard::to_stdvec(&schema).unwrap();
+    assert_eq!(bytes[0], 2);
+    let schema: Schema = postcard::from_bytes(&bytes).unwrap();
+    assert_eq!((schema.version(), schema.min_reader_version()), (7, 5));
+    assert!(schema.is_human_readable());
+    let data = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    let values: Vec<(u8, char)> = schema
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&data))
+        .unwrap();
+    assert_eq!(values, [(1, 'a')]);
+
+    assert!(schema.check_reader_version(5).is_ok());
+    let error = schema.check_reader_version(4).unwrap_err();
+    assert_eq!(
+        (
+            error.version(),
+            error.min_reader_version(),
+            error.reader_version()
+        ),
+        (7, 5, 4)
+    );
+
+    let pruned = schema.pruned().unwrap();
+    assert_eq!((pruned.version(), pruned.min_reader_version()), (7, 5));
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn test_sealed_trace() {
+    use crate::{SealedTrace, SealingKey};
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&vec!["secret".to_owned()]).unwrap();
+    let schema = builder.build().unwrap();
+    let key = SealingKey::new([7; 32]);
+    let sealed = schema.describe_trace(trace).detach().seal(&key);
+    assert_eq!(sealed.fingerprint(), schema.fingerprint());
+    assert!(sealed.schema().is_none());
+
+    let mut other = SchemaBuilder::new();
+    let _ = other.trace(&1u8).unwrap();
+    assert!(
+        sealed
+            .clone()
+            .with_plaintext_schema(other.build().unwrap())
+            .is_err()
+    );
+
+    // Tampering with the encrypted data is detected.
+    let mut bytes = postcard::to_stdvec(&sealed).unwrap();
+    let reopened: SealedTrace = postcard::from_bytes(&bytes).unwrap();
+    assert_eq!(
+        schema
+            .bind(reopened.open(&key).unwrap())
+            .unwrap()
+            .to_string(),
+        r#"["secret"]"#
+    );
+    *bytes.last_mut().unwrap() ^= 1;
+    let tampered: SealedTrace = postcard::from_bytes(&bytes).unwrap();
+    assert!(tampered.open(&key).is_err());
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn test_protection_policy() {
+    use crate::{Protection, ProtectionPolicy, SealingKey};
+
+    #[derive(Serialize)]
+    struct Account {
+        id: u64,
+        balance: i64,
+    }
+
+    #[derive(Deserialize)]
+    struct StoredAccount {
+        id: u64,
+        balance: ByteBuf,
+    }
+
+    let key = SealingKey::new([3; 32]);
+    let policy = ProtectionPolicy::new(key.clone())
+        .type_name("Account", Protection::Encrypt)
+        .field("Account", "id", Protection::Plain);
+    let mut builder = SchemaBuilder::new().protection_policy(&policy);
+    let trace = builder
+        .trace(&Account {
+            id: 7,
+            balance: -20,
+        })
+        .unwrap();
+    let schema = builder.build().unwrap();
+    assert_eq!(
+        schema.to_string(),
+        "Account_3 { id: u64_1, balance: bytes_2 }"
+    );
+    let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    let stored: StoredAccount = schema
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(stored.id, 7);
+    assert_eq!(key.open_value(&stored.balance).unwrap(), Dynamic::I64(-20));
+    assert!(
+        SealingKey::new([4; 32])
+            .open_value(&stored.balance)
+            .is_err()
+    );
+
+    // Hashes only depend on the value and the key.
+    let hash = |key: &SealingKey, id| {
+        let policy = ProtectionPolicy::new(key.clone()).field("Account", "id", Protection::Hash);
+        let mut builder = SchemaBuilder::new().protection_policy(&policy);
+        let trace = builder.trace(&Account { id, balance: 0 }).unwrap();
+        builder.build().unwrap().describe_trace(trace).to_string()
+    };
+    assert_eq!(hash(&key, 1), hash(&key, 1));
+    assert_ne!(hash(&key, 1), hash(&key, 2));
+    assert_ne!(hash(&key, 1), hash(&SealingKey::new([4; 32]), 1));
+
+    // Stored hashes are compared with new ones, so they mustn't change between versions.
+    assert_eq!(
+        key.hash_value(&Dynamic::U64(1)),
+        [
+            193, 172, 34, 22, 39, 4, 150, 254, 76, 221, 254, 44, 126, 219, 178, 46, 12, 84, 204,
+            53, 208, 253, 232, 5, 148, 102, 118, 22, 253, 109, 88, 81
+        ]
+    );
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn test_protection_policy_seals_whole_values() {
+    use crate::{Protection, ProtectionPolicy, SealingKey};
+
+    #[derive(Serialize)]
+    enum Diagnosis {
+        Healthy,
+        Flu,
+    }
+
+    #[derive(Serialize)]
+    struct Patient {
+        diagnosis: Diagnosis,
+        allergic: Option<bool>,
+    }
+
+    let key = SealingKey::new([5; 32]);
+    let policy = ProtectionPolicy::new(key.clone())
+        .field("Patient", "diagnosis", Protection::Encrypt)
+        .field("Patient", "allergic", Protection::Encrypt);
+    let mut builder = SchemaBuilder::new().protection_policy(&policy);
+    let healthy = builder
+        .trace(&Patient {
+            diagnosis: Diagnosis::Healthy,
+            allergic: None,
+        })
+        .unwrap();
+    let flu = builder
+        .trace(&Patient {
+            diagnosis: Diagnosis::Flu,
+            allergic: Some(true),
+        })
+        .unwrap();
+    let schema = builder.build().unwrap();
+
+    // Neither the variants nor the presence of the option are recorded, and the sealed values
+    // are padded to the same length.
+    assert_eq!(
+        schema.to_string(),
+        "Patient_2 { diagnosis: bytes_1, allergic: bytes_1 }"
+    );
+    assert_eq!(healthy.0.len(), flu.0.len());
+
+    let open = |trace: &Trace, field| {
+        let value = schema.describe_trace_ref(trace).to_dynamic().unwrap();
+        match value.field(field) {
+            Some(Dynamic::Bytes(sealed)) => key.open_value(sealed).unwrap(),
+            other => panic!("`{field}` isn't sealed: {other:?}"),
+        }
+    };
+    assert_eq!(
+        open(&flu, "diagnosis"),
+        Dynamic::Variant {
+            name: "Diagnosis".into(),
+            variant: "Flu".into(),
+            fields: DynamicFields::Unit,
+        }
+    );
+    assert_eq!(open(&healthy, "allergic"), Dynamic::None);
+    assert_eq!(
+        open(&flu, "allergic"),
+        Dynamic::Some(Box::new(Dynamic::Bool(true)))
+    );
+
+    // Protecting the enum itself seals its unit variants wherever they are.
+    let policy = ProtectionPolicy::new(key.clone()).type_name("Diagnosis", Protection::Hash);
+    let mut builder = SchemaBuilder::new().protection_policy(&policy);
+    let _ = builder
+        .trace(&vec![Diagnosis::Healthy, Diagnosis::Flu])
+        .unwrap();
+    assert_eq!(builder.build().unwrap().to_string(), "seq_2(bytes_1)");
+}
+
+#[test]
+fn test_coerce_byte_sequences() {
+    let values = (vec![1u8, 2, 3], vec![vec![4u8], vec![]], vec![5u16]);
+    let mut builder = SchemaBuilder::new().coerce_byte_sequences(true);
+    let trace = builder.trace(&values).unwrap();
+    let schema = builder.build().unwrap();
+    assert_eq!(
+        schema.to_string(),
+        "tuple_7(bytes_1, seq_4(union_3(bytes_1, seq_2(!))), seq_6(u16_5))",
+    );
+
+    let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    let roundtripped = schema
+        .describe_type::<(Vec<u8>, Vec<Vec<u8>>, Vec<u16>)>()
+        .with_options(DecodeOptions::new().byte_sequence_coercions(true))
+        .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap()
+        .0;
+    assert_eq!(roundtripped, values);
+
+    let uncoerced = schema.deserialize_described::<(Vec<u8>, Vec<Vec<u8>>, Vec<u16>), _>(
+        &mut postcard::Deserializer::from_bytes(&bytes),
+    );
+    assert!(uncoerced.is_err());
+
+    let unbytes = to_self_described_postcard(&values);
+    assert!(bytes.len() < unbytes.len());
+}
+
+#[test]
+fn test_union_discriminants_stable_when_widened() {
+    let serialize_second = |values: &[AllVariantKinds]| {
+        let (schema, traces) = SchemaBuilder::trace_all(values).unwrap();
+        let second = traces.into_iter().nth(1).unwrap();
+        postcard::to_stdvec(&schema.describe_trace(second)).unwrap()
+    };
+
+    // New members are appended, rather than inserted before existing ones.
+    let before = serialize_second(&[AllVariantKinds::Unit, AllVariantKinds::Tuple(1, 2)]);
+    let appended = serialize_second(&[
+        AllVariantKinds::Unit,
+        AllVariantKinds::Tuple(1, 2),
+        AllVariantKinds::Newtype(3),
+        AllVariantKinds::EmptyStruct {},
+    ]);
+    assert_eq!(before, appended);
+
+    // Widening the type nested in one member doesn't renumber the others.
+    let narrow = serialize_second(&[
+        AllVariantKinds::NewTypeOption(None),
+        AllVariantKinds::Newtype(2),
+        AllVariantKinds::Unit,
+    ]);
+    let widened = serialize_second(&[
+        AllVariantKinds::NewTypeOption(None),
+        AllVariantKinds::Newtype(2),
+        AllVariantKinds::Unit,
+        AllVariantKinds::NewTypeOption(Some(Box::new(AllVariantKinds::EmptyTuple()))),
+    ]);
+    assert_eq!(narrow, widened);
+}
+
+#[test]
+fn test_float_normalization() {
+    let negative_nan = f64::from_bits(f64::NAN.to_bits() | (1 << 63) | 1);
+    let trace_bytes = |normalization, values: &[f64]| {
+        let mut builder = SchemaBuilder::new().float_normalization(normalization);
+        let traces = values
+            .iter()
+            .map(|value| builder.trace(value))
+            .collect::<Result<Vec<_>, _>>()?;
+        let schema = builder.build()?;
+        Ok::<_, crate::TraceError>(
+            traces
+                .into_iter()
+                .map(|trace| postcard::to_stdvec(&schema.describe_trace(trace)).unwrap())
+                .collect::<Vec<_>>(),
+        )
+    };
+
+    let preserved = trace_bytes(FloatNormalization::Preserve, &[f64::NAN, negative_nan]).unwrap();
+    assert_ne!(preserved[0], preserved[1]);
+    let canonical = trace_bytes(
+        FloatNormalization::Canonical,
+        &[f64::NAN, negative_nan, 0.0, -0.0],
+    )
+    .unwrap();
+    assert_eq!(canonical[0], canonical[1]);
+    assert_eq!(canonical[2], canonical[3]);
+    assert_eq!(canonical[0], preserved[0]);
+    assert!(matches!(
+        trace_bytes(FloatNormalization::RejectNan, &[1.0, negative_nan]),
+        Err(crate::TraceError::Nan)
+    ));
+}
+
+#[test]
+fn test_trace_transformers() {
+    #[derive(Serialize)]
+    struct Secret(&'static str);
+
+    #[derive(Serialize)]
+    struct Written {
+        id: &'static str,
+        tags: Vec<&'static str>,
+        secrets: (Secret, &'static str),
+        count: u32,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Secret2(String);
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Read {
+        id: u64,
+        tags: Vec<String>,
+        secrets: (Secret2, String),
+        count: u32,
+    }
+
+    let mut builder = SchemaBuilder::new()
+        .transform_field("Written", "id", |value| match value {
+            LeafValue::Str(id) => LeafValue::U64(id.len() as u64),
+            other => other,
+        })
+        .transform_field("Written", "tags", |_| LeafValue::I8(0))
+        .transform_field("Written", "tags", |value| match value {
+            LeafValue::Str(tag) => LeafValue::Str(tag[..1].to_owned().into()),
+            other => other,
+        })
+        .transform_field("Written", "secrets", |value| match value {
+            LeafValue::Str(_) => LeafValue::Str("***".into()),
+            other => other,
+        })
+        .transform_type("Secret", |_| LeafValue::Str("<secret>".into()));
+    let trace = builder
+        .trace(&Written {
+            id: "abcd",
+            tags: vec!["red", "green"],
+            secrets: (Secret("password"), "token"),
+            count: 3,
+        })
+        .unwrap();
+    let schema = builder.build().unwrap();
+    let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    let read: Read = schema
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(
+        read,
+        Read {
+            id: 4,
+            tags: vec!["r".to_owned(), "g".to_owned()],
+            secrets: (Secret2("<secret>".to_owned()), "***".to_owned()),
+            count: 3,
+        }
+    );
+}
+
+#[test]
+fn test_data_budget() {
+    #[derive(Serialize)]
+    enum Shape {
+        Polygon { points: Vec<(f64, f64)> },
+        Label(String, String),
+    }
+
+    fn budget_error(max: usize, value: &impl Serialize) -> String {
+        match SchemaBuilder::new().max_data_bytes(max).trace(value) {
+            Err(TraceError::DataBudget(error)) => error.path().to_string(),
+            Err(error) => panic!("unexpected error {error}"),
+            Ok(_) => panic!("budget of {max} bytes not exceeded"),
+        }
+    }
+
+    let polygon = Shape::Polygon {
+        points: vec![(0.0, 0.0); 4],
+    };
+    let label = Shape::Label("a".to_owned(), "x".repeat(100));
+    let map = btreemap! { "a" => vec![label] };
+    assert_eq!(budget_error(58, &polygon), ".::Polygon.points[1].1");
+    assert_eq!(budget_error(100, &map), r#".[0][0]::Label.1"#);
+    assert_eq!(budget_error(4, &1u64), ".");
+
+    let mut builder = SchemaBuilder::new().max_data_bytes(1000);
+    let traces = [
+        builder.trace(&polygon).unwrap(),
+        builder.trace(&map).unwrap(),
+    ];
+    assert!(builder.trace(&"x".repeat(1000)).is_err());
+    let schema = builder.build().unwrap();
+    for trace in traces {
+        postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    }
+}
+
+#[cfg(feature = "half")]
+#[test]
+fn test_half_floats() {
+    use crate::{Bf16, F16};
+    use half::{bf16, f16};
+
+    let (schema, _) = SchemaBuilder::trace_all([F16(f16::ONE)]).unwrap();
+    assert_eq!(schema.to_string(), "serde_describe::f16_2(u16_1)");
+    assert_eq!(
+        from_self_described_bitcode::<(F16, Bf16)>(&to_self_described_bitcode((
+            F16(f16::from_f32(1.5)),
+            Bf16(bf16::from_f32(-2.0)),
+        ))),
+        (F16(f16::from_f32(1.5)), Bf16(bf16::from_f32(-2.0)))
+    );
+}
+
+#[test]
+fn test_tensor() {
+    let tensor = Tensor::new(vec![2, 2], vec![1i32, -2, 3, -4]).unwrap();
+    assert_eq!(
+        from_self_described_bitcode::<Tensor<i32>>(&to_self_described_bitcode(&tensor)),
+        tensor
+    );
+    assert!(Tensor::new(vec![2, 3], vec![1.0f64; 5]).is_err());
+
+    let (schema, _) = SchemaBuilder::trace_all([&tensor]).unwrap();
+    assert!(
+        schema
+            .to_string()
+            .starts_with("serde_describe::Tensor<i32>")
+    );
+
+    // Data whose length doesn't match the shape is rejected.
+    #[derive(Serialize)]
+    #[serde(rename = "serde_describe::Tensor<i32>")]
+    struct Corrupt {
+        shape: Vec<usize>,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    }
+    let corrupt = to_self_described_bitcode(Corrupt {
+        shape: vec![2],
+        data: vec![0; 4],
+    });
+    assert!(bitcode::deserialize::<SelfDescribed<Tensor<i32>>>(&corrupt).is_err());
+
+    #[cfg(feature = "ndarray")]
+    {
+        let array = ndarray::array![[1.0f32, 2.0], [3.0, 4.0]];
+        let tensor = Tensor::from(&array.t());
+        assert_eq!(tensor.data(), [1.0, 3.0, 2.0, 4.0]);
+        assert_eq!(ndarray::ArrayD::from(tensor), array.t().into_dyn());
+    }
+}
+
+#[test]
+fn test_trace_audit() {
+    #[derive(Serialize)]
+    enum Value {
+        Int(u32),
+        Pair(u8, u8),
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let first = builder.trace(&btreemap! { "a" => Value::Int(1) }).unwrap();
+    let second = builder
+        .trace(&btreemap! { "a" => Value::Pair(1, 2) })
+        .unwrap();
+    let schema = builder.build().unwrap();
+
+    let first = schema.describe_trace_ref(&first).audit().unwrap();
+    assert_eq!(
+        first.to_string(),
+        ".: serialize_map(1)\n\
+         .{\"a\"}: serialize_str(\"a\")\n\
+         .[\"a\"]: serialize_newtype_variant(\"Value\", \"Int\")\n\
+         .[\"a\"]: serialize_u32(1)\n"
+    );
+
+    // The calls at the path whose type widened tell the two shapes apart.
+    let second = schema.describe_trace_ref(&second).audit().unwrap();
+    let methods = second
+        .calls_at(".[\"a\"]")
+        .map(|call| (call.method(), call.variant(), call.length()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        methods,
+        [("serialize_tuple_variant", Some("Pair"), Some(2))]
+    );
+    assert_eq!(
+        second.calls_at(".[\"a\"].1").next().unwrap().value(),
+        Some("2")
+    );
+}
+
+#[test]
+fn test_decode_limits() {
+    let limited = DecodeOptions::lenient()
+        .max_sequence_length(3)
+        .max_string_length(5);
+
+    assert_eq!(
+        postcard_convert_with::<_, Vec<u32>>(&vec![1u32, 2, 3], limited).unwrap(),
+        [1, 2, 3]
+    );
+    assert!(postcard_convert_with::<_, Vec<u32>>(&vec![1u32, 2, 3, 4], limited).is_err());
+    assert!(
+        postcard_convert_with::<_, BTreeMap<u8, u8>>(
+            &btreemap! { 1u8 => 1u8, 2 => 2, 3 => 3, 4 => 4 },
+            limited
+        )
+        .is_err()
+    );
+
+    assert_eq!(
+        postcard_convert_with::<_, String>(&"hello", limited).unwrap(),
+        "hello"
+    );
+    assert!(postcard_convert_with::<_, String>(&"hello!", limited).is_err());
+    assert!(
+        postcard_convert_with::<_, ByteBuf>(&ByteBuf::from(b"hello!".to_vec()), limited).is_err()
+    );
+    assert!(postcard_convert_with::<_, Vec<String>>(&vec!["hello!"], limited).is_err());
+
+    // The allocation budget is shared by the whole value, but not between values.
+    let budgeted = DecodeOptions::new().max_allocation(10);
+    let words = vec!["hello".to_owned(), "world".to_owned()];
+    assert_eq!(
+        postcard_convert_with::<_, Vec<String>>(&words, budgeted).unwrap(),
+        words
+    );
+    assert_eq!(
+        postcard_convert_with::<_, Vec<String>>(&words, budgeted).unwrap(),
+        words
+    );
+    assert!(postcard_convert_with::<_, (Vec<String>, String)>(&(&words, "!"), budgeted).is_err());
+    assert!(
+        postcard_convert_with::<_, Vec<Vec<u8>>>(
+            &vec![ByteBuf::from(vec![0; 6]), ByteBuf::from(vec![0; 6])],
+            budgeted.byte_sequence_coercions(true)
+        )
+        .is_err()
+    );
+
+    // Values decoded while decoding another, on the same thread, are charged to its budget
+    // too, and what they spent is taken off the rest of it.
+    #[derive(Debug, PartialEq)]
+    struct Nested(String);
+
+    impl<'de> Deserialize<'de> for Nested {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            let payload = ByteBuf::deserialize(deserializer)?;
+            let options = DecodeOptions::new().max_allocation(1000);
+            SelfDescribed(PhantomData::<String>)
+                .with_options(options)
+                .deserialize(&mut postcard::Deserializer::from_bytes(&payload))
+                .map(|SelfDescribed(value)| Nested(value))
+                .map_err(serde::de::Error::custom)
+        }
+    }
+
+    let payload = ByteBuf::from(to_self_described_postcard("hello"));
+    let budgeted = DecodeOptions::new().max_allocation(payload.len() + 5 + 3);
+    assert_eq!(
+        postcard_convert_with::<_, (Nested, String)>(&(&payload, "abc"), budgeted).unwrap(),
+        (Nested("hello".to_owned()), "abc".to_owned())
+    );
+    assert!(postcard_convert_with::<_, (Nested, String)>(&(&payload, "abcd"), budgeted).is_err());
+
+    // A corrupted length prefix in a trace is rejected, rather than trusted.
+    let mut builder = SchemaBuilder::new();
+    let Trace(mut bytes) = builder.trace(&vec![1u8, 2, 3]).unwrap();
+    let schema = builder.build().unwrap();
+    bytes[1..5].copy_from_slice(&u32::MAX.to_le_bytes());
+    let corrupted = Trace(bytes);
+    assert!(schema.describe_trace_ref(&corrupted).audit().is_err());
+    assert!(postcard::to_stdvec(&schema.describe_trace(corrupted)).is_err());
+}
+
+#[test]
+fn test_schema_delta() {
+    #[derive(Serialize)]
+    struct Reading {
+        sensor: &'static str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        value: Option<f64>,
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let _ = builder
+        .trace(&Reading {
+            sensor: "a",
+            value: None,
+        })
+        .unwrap();
+    let before = builder.clone().build().unwrap();
+    assert!(before.delta_from(&before).is_empty());
+
+    let _ = builder
+        .trace(&Reading {
+            sensor: "b",
+            value: Some(1.5),
+        })
+        .unwrap();
+    let after = builder.build().unwrap();
+    let delta = after.delta_from(&before);
+    assert!(!delta.is_empty());
+    assert_eq!(delta.base(), before.fingerprint());
+
+    let applied = before.apply_delta(delta.clone()).unwrap();
+    assert_eq!(applied.to_string(), after.to_string());
+    assert_eq!(applied.fingerprint(), after.fingerprint());
+
+    // Deltas only apply to the schema they were computed from.
+    assert!(after.apply_delta(delta).is_err());
+}
+
+#[test]
+fn test_sessions() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Event {
+        Start(u8),
+        Stop,
+        Log(String),
+    }
+
+    let mut encoder = EncoderSession::new();
+    let mut decoder = DecoderSession::new();
+    let send = |encoder: &mut EncoderSession, value: &Event| {
+        let message = encoder.encode(value).unwrap();
+        let update = message
+            .update
+            .map(|update| postcard::to_stdvec(&update).unwrap());
+        (update, postcard::to_stdvec(&message.data).unwrap())
+    };
+    let receive = |decoder: &DecoderSession, data: &[u8]| {
+        decoder.deserialize::<Event, _>(&mut postcard::Deserializer::from_bytes(data))
+    };
+
+    // Nothing can be read before the first schema.
+    let (first_update, first_data) = send(&mut encoder, &Event::Start(1));
+    let first_update = postcard::from_bytes::<SchemaUpdate>(&first_update.unwrap()).unwrap();
+    assert!(matches!(first_update, SchemaUpdate::Full(_)));
+    assert!(receive(&decoder, &first_data).is_err());
+
+    // Updates sent before an acknowledgement are full schemas.
+    let (second_update, second_data) = send(&mut encoder, &Event::Stop);
+    let first = decoder.receive(first_update).unwrap();
+    assert_eq!(receive(&decoder, &first_data).unwrap(), Event::Start(1));
+    decoder
+        .receive(postcard::from_bytes(&second_update.unwrap()).unwrap())
+        .unwrap();
+    assert_eq!(receive(&decoder, &second_data).unwrap(), Event::Stop);
+
+    // Once the first schema is acknowledged, updates are deltas against it, even if later
+    // schemas were received in the meantime.
+    encoder.acknowledge(first);
+    let (unchanged_update, unchanged_data) = send(&mut encoder, &Event::Start(2));
+    assert!(unchanged_update.is_none());
+    assert_eq!(receive(&decoder, &unchanged_data).unwrap(), Event::Start(2));
+
+    let (third_update, third_data) = send(&mut encoder, &Event::Log("hi".to_owned()));
+    let third_update = postcard::from_bytes::<SchemaUpdate>(&third_update.unwrap()).unwrap();
+    assert!(matches!(&third_update, SchemaUpdate::Delta(delta) if delta.base() == first));
+    decoder.receive(third_update).unwrap();
+    assert_eq!(
+        receive(&decoder, &third_data).unwrap(),
+        Event::Log("hi".to_owned())
+    );
+}
+
+#[test]
+fn test_chunked_writer() {
+    let mut encoder = EncoderSession::new();
+    let mut writer = encoder.chunked(2);
+    let mut frames = Vec::new();
+    for name in ["a", "b", "c", "d", "e"] {
+        if let Some(message) = writer.push(name).unwrap() {
+            let update = message
+                .update
+                .map(|update| postcard::to_stdvec(&update).unwrap());
+            frames.push((update, postcard::to_stdvec(&message.data).unwrap()));
+        }
+    }
+    let last = writer.finish().unwrap().unwrap();
+    assert!(last.update.is_none());
+    frames.push((None, postcard::to_stdvec(&last.data).unwrap()));
+
+    // The schema is only sent once, ahead of the first chunk.
+    assert_eq!(
+        frames
+            .iter()
+            .map(|(update, _)| update.is_some())
+            .collect::<Vec<_>>(),
+        [true, false, false]
+    );
+
+    let mut decoder = DecoderSession::new();
+    let mut names = Vec::<String>::new();
+    for (update, data) in &frames {
+        if let Some(update) = update {
+            decoder
+                .receive(postcard::from_bytes(update).unwrap())
+                .unwrap();
+        }
+        decoder
+            .deserialize_extend(&mut postcard::Deserializer::from_bytes(data), &mut names)
+            .unwrap();
+    }
+    assert_eq!(names, ["a", "b", "c", "d", "e"]);
+
+    let mut encoder = EncoderSession::new();
+    let mut writer = encoder.chunked::<u8>(2);
+    assert!(writer.push(1).unwrap().is_none());
+    assert!(writer.push(2).unwrap().is_some());
+    assert!(writer.finish().unwrap().is_none());
+}
+
+#[test]
+fn test_schema_names_are_borrowed_until_deserialized() {
+    #[derive(Serialize)]
+    struct Named {
+        field: u8,
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let _ = builder.trace(&Named { field: 1 }).unwrap();
+    let schema = builder.build().unwrap();
+    let is_borrowed = |schema: &Schema| {
+        matches!(
+            schema
+                .field_names
+                .get(FieldNameIndex::try_from(0usize).unwrap()),
+            Some(Cow::Borrowed("field"))
+        )
+    };
+    assert!(is_borrowed(&schema));
+
+    let bytes = postcard::to_stdvec(&schema).unwrap();
+    let deserialized = postcard::from_bytes::<Schema>(&bytes).unwrap();
+    assert!(!is_borrowed(&deserialized));
+    assert_eq!(deserialized.to_string(), schema.to_string());
+}
+
+#[test]
+fn test_trace_reader() {
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rect { w: u8, h: u8 },
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder
+        .trace(&btreemap! {
+            'a' => vec![Shape::Empty, Shape::Circle(0.5)],
+            'b' => vec![Shape::Rect { w: 1, h: 2 }],
+        })
+        .unwrap();
+    let schema = builder.build().unwrap();
+    let events = schema
+        .describe_trace_ref(&trace)
+        .reader()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        events,
+        [
+            TraceEvent::Map { len: 2 },
+            TraceEvent::Char('a'),
+            TraceEvent::Seq { len: 2 },
+            TraceEvent::UnitVariant {
+                name: "Shape",
+                variant: "Empty"
+            },
+            TraceEvent::NewtypeVariant {
+                name: "Shape",
+                variant: "Circle"
+            },
+            TraceEvent::F32(0.5),
+            TraceEvent::Char('b'),
+            TraceEvent::Seq { len: 1 },
+            TraceEvent::StructVariant {
+                name: "Shape",
+                variant: "Rect",
+                fields: vec!["w", "h"]
+            },
+            TraceEvent::U8(1),
+            TraceEvent::U8(2),
+        ]
+    );
+
+    // Reading stops at the first invalid item.
+    let Trace(mut bytes) = trace;
+    bytes.truncate(bytes.len() - 1);
+    let truncated = Trace(bytes);
+    let mut reader = schema.describe_trace_ref(&truncated).reader();
+    assert!(reader.by_ref().any(|event| event.is_err()));
+    assert!(reader.next().is_none());
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_layer() {
+    use crate::{LogEvent, LogValue, TracingLayer};
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let messages = Arc::new(Mutex::new(Vec::new()));
+    let layer = TracingLayer::new({
+        let messages = messages.clone();
+        move |message: crate::EncodedMessage| {
+            let update = message
+                .update
+                .map(|update| postcard::to_stdvec(&update).unwrap());
+            let data = postcard::to_stdvec(&message.data).unwrap();
+            messages.lock().unwrap().push((update, data));
+        }
+    });
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+        tracing::warn!(target: "app", id = 1i64, "first");
+        tracing::warn!(target: "app", id = 2i64, "second");
+        tracing::error!(target: "app", id = "three", ok = false);
+    });
+
+    // The schema is only resent when a new field or field type shows up.
+    let messages = messages.lock().unwrap();
+    assert_eq!(
+        messages
+            .iter()
+            .map(|(update, _)| update.is_some())
+            .collect::<Vec<_>>(),
+        [true, false, true]
+    );
+
+    let mut decoder = DecoderSession::new();
+    let events = messages
+        .iter()
+        .map(|(update, data)| {
+            if let Some(update) = update {
+                decoder
+                    .receive(postcard::from_bytes(update).unwrap())
+                    .unwrap();
+            }
+            decoder
+                .deserialize::<LogEvent, _>(&mut postcard::Deserializer::from_bytes(data))
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events[2],
+        LogEvent {
+            level: "ERROR".to_owned(),
+            target: "app".to_owned(),
+            fields: btreemap! {
+                "id".to_owned() => LogValue::Str("three".to_owned()),
+                "ok".to_owned() => LogValue::Bool(false),
+            },
+        }
+    );
+    assert_eq!(events[1].fields["id"], LogValue::I64(2));
+    assert_eq!(
+        events[0].fields["message"],
+        LogValue::Str("first".to_owned())
+    );
+}
+
+#[test]
+fn test_kv_codec() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Setting {
+        Flag(bool),
+        Name(String),
+    }
+
+    let mut store = BTreeMap::<Vec<u8>, Vec<u8>>::new();
+    let mut codec = KvCodec::new();
+    let mut put = |codec: &mut KvCodec, key: &str, value: &Setting| {
+        let encoded = codec.encode(value).unwrap();
+        let widened = encoded.new_schema.is_some();
+        if let Some(schema) = encoded.new_schema {
+            store.insert(
+                KvCodec::schema_key(encoded.fingerprint),
+                postcard::to_stdvec(schema).unwrap(),
+            );
+        }
+        let mut bytes = KvCodec::value_prefix(encoded.fingerprint).to_vec();
+        bytes.extend(postcard::to_stdvec(&encoded.data).unwrap());
+        store.insert(key.as_bytes().to_vec(), bytes);
+        widened
+    };
+    assert!(put(&mut codec, "a", &Setting::Flag(true)));
+    assert!(!put(&mut codec, "b", &Setting::Flag(false)));
+    assert!(put(&mut codec, "c", &Setting::Name("x".to_owned())));
+
+    // Values written before the schema widened keep their original schema.
+    let mut codec = KvCodec::new();
+    let schemas = store
+        .iter()
+        .filter(|(key, _)| key.starts_with(KvCodec::SCHEMA_KEY_PREFIX))
+        .map(|(_, bytes)| codec.insert_schema(postcard::from_bytes(bytes).unwrap()))
+        .collect::<HashSet<_>>();
+    assert_eq!(schemas.len(), 2);
+    let get = |key: &str| -> Setting {
+        let (fingerprint, payload) = KvCodec::split_value(&store[key.as_bytes()]).unwrap();
+        codec
+            .decode(
+                fingerprint,
+                &mut postcard::Deserializer::from_bytes(payload),
+            )
+            .unwrap()
+    };
+    assert_eq!(get("a"), Setting::Flag(true));
+    assert_eq!(get("c"), Setting::Name("x".to_owned()));
+    assert_ne!(
+        KvCodec::split_value(&store[&b"a"[..]]).unwrap().0,
+        KvCodec::split_value(&store[&b"c"[..]]).unwrap().0
+    );
+    assert!(KvCodec::split_value(&[1, 2, 3]).is_none());
+}
+
+#[test]
+fn test_messaging() {
+    let mut encoder = MessageEncoder::new(HashMap::new());
+    let mut messages = Vec::new();
+    for value in [Some(1u16), Some(2), None] {
+        let (header, data) = encoder.encode(&value).unwrap();
+        let mut message = header.to_bytes().to_vec();
+        message.extend(postcard::to_stdvec(&data).unwrap());
+        messages.push(message);
+    }
+    assert_eq!(messages[0][0], MessageHeader::MAGIC);
+    // Widening the schema registered a second one.
+    assert_eq!(encoder.store().len(), 2);
+
+    let mut store = encoder.into_store();
+    let values = messages
+        .iter()
+        .map(|message| {
+            let (header, payload) = MessageHeader::split(message).unwrap();
+            store
+                .decode(header, &mut postcard::Deserializer::from_bytes(payload))
+                .unwrap()
+        })
+        .collect::<Vec<Option<u16>>>();
+    assert_eq!(values, [Some(1), Some(2), None]);
+
+    assert!(matches!(
+        MessageHeader::split(&[0; 4]),
+        Err(MessageHeaderError::TooShort(4))
+    ));
+    assert!(matches!(
+        MessageHeader::split(&[1; 9]),
+        Err(MessageHeaderError::BadMagic(1))
+    ));
+    let (header, _) = MessageHeader::split(&[0; 9]).unwrap();
+    assert!(
+        HashMap::new()
+            .decode::<Option<u16>, _>(header, &mut postcard::Deserializer::from_bytes(&[]))
+            .is_err()
+    );
+}
+
+#[test]
+fn test_encode_canonical() {
+    #[derive(Serialize)]
+    enum Reading {
+        Missing,
+        Celsius(f32),
+    }
+
+    /// Serializes as a map, in the given order.
+    struct Entries<const N: usize>([(u8, Reading); N]);
+
+    impl<const N: usize> Serialize for Entries<N> {
+        fn serialize<SerializerT: Serializer>(
+            &self,
+            serializer: SerializerT,
+        ) -> Result<SerializerT::Ok, SerializerT::Error> {
+            serializer.collect_map(self.0.iter().map(|(key, value)| (key, value)))
+        }
+    }
+
+    // Union variants discovered in different orders, and entries serialized in different orders.
+    let mut first_builder = SchemaBuilder::new();
+    let first = first_builder
+        .trace(&Entries([
+            (1, Reading::Missing),
+            (0, Reading::Celsius(-0.0)),
+        ]))
+        .unwrap();
+    let first_schema = first_builder.build().unwrap();
+    let mut second_builder = SchemaBuilder::new();
+    let _ = second_builder
+        .trace(&Entries([(0, Reading::Celsius(1.0))]))
+        .unwrap();
+    let second = second_builder
+        .trace(&Entries([
+            (0, Reading::Celsius(0.0)),
+            (1, Reading::Missing),
+        ]))
+        .unwrap();
+    let second_schema = second_builder.build().unwrap();
+    let first = first_schema.describe_trace(first);
+    let second = second_schema.describe_trace(second);
+    assert!(!first.content_eq(&second));
+
+    let first = first.encode_canonical().unwrap();
+    assert_eq!(first, second.encode_canonical().unwrap());
+
+    // The encoding is pinned, since changing it would break existing signatures.
+    #[rustfmt::skip]
+    assert_eq!(
+        first,
+        [
+            23, 2, 0, 0, 0, // Map of 2 entries.
+            6, 0, // Key 0u8.
+            22, 7, 0, 0, 0, b'R', b'e', b'a', b'd', b'i', b'n', b'g', // Newtype variant `Reading`,
+            7, 0, 0, 0, b'C', b'e', b'l', b's', b'i', b'u', b's', // `Celsius`.
+            11, 0, 0, 0, 0, // 0.0f32.
+            6, 1, // Key 1u8.
+            20, 7, 0, 0, 0, b'R', b'e', b'a', b'd', b'i', b'n', b'g', // Unit variant `Reading`,
+            7, 0, 0, 0, b'M', b'i', b's', b's', b'i', b'n', b'g', // `Missing`.
+        ]
+    );
+
+    // Keys without a canonical order are rejected, rather than sorted by their encoding.
+    let mut builder = SchemaBuilder::new();
+    let tuple_keys = builder.trace(&btreemap! { (1u8, 2u8) => () }).unwrap();
+    let unit_keys = builder.trace(&btreemap! { () => 1u8 }).unwrap();
+    let schema = builder.build().unwrap();
+    assert!(matches!(
+        schema.describe_trace(tuple_keys).encode_canonical(),
+        Err(CanonicalEncodingError::UnorderedMapKey { kind }) if &*kind == "Tuple",
+    ));
+    assert!(schema.describe_trace(unit_keys).encode_canonical().is_ok());
+}
+
+#[test]
+fn test_trace_byte_order() {
+    // Traces are little-endian on every platform. The values are chosen such that reading them
+    // with the opposite byte order would produce different values.
+    #[rustfmt::skip]
+    const FIXTURE: [u8; 33] = [
+        25, 5, 0, 0, 0, // Tuple of 5.
+        7, 0x02, 0x01, // 0x0102u16.
+        3, 0xfe, 0xff, 0xff, 0xff, // -2i32.
+        11, 0x00, 0x00, 0xc0, 0x3f, // 1.5f32.
+        13, 0xe9, 0x00, 0x00, 0x00, // 'é'.
+        24, 1, 0, 0, 0, // Sequence of 1.
+        8, 0x04, 0x03, 0x02, 0x01, // 0x01020304u32.
+    ];
+    type Value = (u16, i32, f32, char, Vec<u32>);
+    let value: Value = (0x0102, -2, 1.5, 'é', vec![0x0102_0304]);
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&value).unwrap();
+    assert_eq!(trace.0, FIXTURE);
+
+    // Traces with the same bytes read back as the same values, wherever they were produced.
+    let schema = builder.build().unwrap();
+    let bytes = postcard::to_stdvec(&schema.describe_trace(Trace(FIXTURE.to_vec()))).unwrap();
+    let decoded: Value = schema
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(decoded, value);
+}
+
+#[test]
+fn test_deserialize_recovering() {
+    #[derive(Serialize)]
+    enum Written {
+        Point { x: i64, y: i64, tags: Vec<String> },
+        Labels(BTreeMap<String, u32>),
+        Removed(Vec<(u8, String)>),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    enum Read {
+        Point { x: i32, y: i32, tags: Vec<String> },
+        Labels(BTreeMap<String, u8>),
+    }
+
+    let written = vec![
+        Written::Point {
+            x: 1,
+            y: 2,
+            tags: vec!["a".to_owned()],
+        },
+        // Fails on the first field, the rest of the struct needs skipping.
+        Written::Point {
+            x: i64::MAX,
+            y: 3,
+            tags: vec!["b".to_owned(), "c".to_owned()],
+        },
+        // Fails on the second entry of the map.
+        Written::Labels(
+            btreemap! { "a".to_owned() => 1, "b".to_owned() => 1000, "c".to_owned() => 3 },
+        ),
+        // An unknown variant, whose content is never read.
+        Written::Removed(vec![(1, "d".to_owned())]),
+        Written::Labels(btreemap! { "e".to_owned() => 5 }),
+    ];
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&written).unwrap();
+    let schema = builder.build().unwrap();
+    let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+
+    let recovered = schema
+        .deserialize_recovering::<Read, _>(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(
+        recovered.elements,
+        [
+            Read::Point {
+                x: 1,
+                y: 2,
+                tags: vec!["a".to_owned()]
+            },
+            Read::Labels(btreemap! { "e".to_owned() => 5 }),
+        ]
+    );
+    assert_eq!(
+        recovered
+            .errors
+            .iter()
+            .map(|error| error.index)
+            .collect::<Vec<_>>(),
+        [1, 2, 3]
+    );
+
+    // Without recovery, the first error is fatal.
+    assert!(
+        schema
+            .deserialize_described::<Vec<Read>, _>(&mut postcard::Deserializer::from_bytes(&bytes))
+            .is_err()
+    );
+
+    // Errors reading the sequence itself are still returned.
+    assert!(
+        schema
+            .deserialize_recovering::<Read, _>(&mut postcard::Deserializer::from_bytes(&[]))
+            .is_err()
+    );
+}
+
+#[test]
+fn test_iter_sequence() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Meters(f64);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(Meters),
+        Rect(u16, u16),
+        Named {
+            name: String,
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            alias: Option<char>,
+        },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        shapes: Vec<Shape>,
+        extra: BTreeMap<u8, Option<ByteBuf>>,
+        unit: (),
+    }
+
+    let records = vec![
+        Record {
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(Meters(1.5)),
+                Shape::Rect(2, 3),
+                Shape::Named {
+                    name: "a".to_owned(),
+                    alias: Some('b'),
+                },
+                Shape::Named {
+                    name: "c".to_owned(),
+                    alias: None,
+                },
+            ],
+            extra: btreemap! { 1 => None, 2 => Some(ByteBuf::from(vec![1, 2])) },
+            unit: (),
+        },
+        Record {
+            shapes: vec![],
+            extra: BTreeMap::new(),
+            unit: (),
+        },
+    ];
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&records).unwrap();
+    let schema = builder.build().unwrap();
+    let described = schema.describe_trace_ref(&trace);
+
+    let decoded = described
+        .iter_sequence::<Record>()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(decoded, records);
+
+    // Elements which don't match are returned as events, and iteration carries on.
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Shapes {
+        shapes: Vec<Shape>,
+    }
+    let mut elements = described.iter_sequence::<(u8, u8)>().unwrap();
+    let first = elements.next().unwrap().unwrap_err();
+    assert_eq!(
+        first.events[0],
+        TraceEvent::Struct {
+            name: "Record",
+            fields: vec!["shapes", "extra", "unit"]
+        }
+    );
+    assert_eq!(first.events[1], TraceEvent::Seq { len: 5 });
+    assert!(elements.next().unwrap().is_err());
+    assert!(elements.next().is_none());
+
+    // Unknown fields are skipped.
+    let shapes = described
+        .iter_sequence::<Shapes>()
+        .unwrap()
+        .map(|shapes| shapes.unwrap().shapes.len())
+        .collect::<Vec<_>>();
+    assert_eq!(shapes, [5, 0]);
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&5u32).unwrap();
+    let schema = builder.build().unwrap();
+    assert!(
+        schema
+            .describe_trace_ref(&trace)
+            .iter_sequence::<u32>()
+            .is_none()
+    );
+}
+
+#[test]
+fn test_field_path() {
+    let before = btreemap! { "a]\"" => vec![(1u32, 2u32)], "b" => vec![] };
+    let after = btreemap! { "a]\"" => vec![(1u32, 3u32)], "b" => vec![(4, 5)] };
+    let mut builder = SchemaBuilder::new();
+    let before = builder.trace(&before).unwrap();
+    let after = builder.trace(&after).unwrap();
+    let schema = builder.build().unwrap();
+    let diff = schema
+        .describe_trace_ref(&before)
+        .diff(&schema.describe_trace_ref(&after))
+        .unwrap();
+    let paths = diff
+        .changes()
+        .iter()
+        .map(|change| change.path().parse::<FieldPath>().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paths,
+        [
+            FieldPath::root()
+                .join(PathSegment::Key("\"a]\\\"\"".into()))
+                .join(PathSegment::Index(0))
+                .join(PathSegment::Field("1".into())),
+            FieldPath::root()
+                .join(PathSegment::Key("\"b\"".into()))
+                .join(PathSegment::Index(0)),
+        ]
+    );
+    for (path, change) in paths.iter().zip(diff.changes()) {
+        assert_eq!(path.to_string(), change.path());
+    }
+
+    for path in [".", ".[0]", ".::Some.x", ".x::Unit[\"[\"]"] {
+        let parsed = path.parse::<FieldPath>().unwrap();
+        assert_eq!(parsed.to_string(), path);
+        assert_eq!(
+            ron::from_str::<FieldPath>(&ron::to_string(&parsed).unwrap()).unwrap(),
+            parsed
+        );
+    }
+    assert!("".parse::<FieldPath>().unwrap().is_root());
+    for invalid in ["x", ".x.", "..x", ".x[", ".x[]", ".x::", ".x:y"] {
+        assert!(invalid.parse::<FieldPath>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn test_sample() {
+    fn xorshift(mut state: u64) -> impl FnMut() -> u64 {
+        move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        }
+    }
+
+    #[derive(Serialize)]
+    #[serde(untagged)]
+    enum Written {
+        Number(u32),
+        Text(&'static str),
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let values = (0..1000)
+        .map(|index| match index {
+            500 => Written::Text("odd one out"),
+            index => Written::Number(index),
+        })
+        .collect::<Vec<_>>();
+    let trace = builder.trace(&values).unwrap();
+    let schema = builder.build().unwrap();
+    let elements = || {
+        schema
+            .describe_trace_ref(&trace)
+            .iter_sequence::<u32>()
+            .unwrap()
+    };
+
+    let sample = elements().sample(20, xorshift(7));
+    assert_eq!(sample.len(), 20);
+    let numbers = sample
+        .iter()
+        .filter_map(|element| element.as_ref().ok().copied())
+        .collect::<Vec<_>>();
+    assert!(numbers.is_sorted_by(|a, b| a < b));
+    assert!(numbers.iter().any(|&number| number >= 500));
+    let all = elements().sample(2000, xorshift(7));
+    assert_eq!(all.len(), 1000);
+    assert_eq!(
+        all[500].as_ref().unwrap_err().events,
+        [TraceEvent::Str("odd one out")]
+    );
+
+    // Sampling stops at the first element which doesn't match the schema.
+    let truncated = Trace(trace.0[..trace.0.len() - 1].to_vec());
+    let sample = schema
+        .describe_trace_ref(&truncated)
+        .iter_sequence::<u32>()
+        .unwrap()
+        .sample(5, xorshift(7));
+    assert_eq!(sample.len(), 6);
+    assert!(sample.last().unwrap().is_err());
+}
+
+#[test]
+fn test_to_rust_literal() {
+    #[derive(Debug, Default, PartialEq, Serialize)]
+    struct Unit;
+
+    #[derive(Debug, PartialEq, Serialize)]
+    enum Event {
+        Started,
+        Moved(i32, i32),
+        Renamed { from: String, to: String },
+    }
+
+    #[derive(Debug, Default, PartialEq, Serialize)]
+    struct Record {
+        r#type: char,
+        tag: (u8,),
+        ratios: Vec<f32>,
+        limits: (f64, f64),
+        payload: ByteBuf,
+        labels: BTreeMap<u16, Option<String>>,
+        empty: BTreeMap<u16, ()>,
+        events: Vec<Event>,
+        unit: Unit,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    }
+
+    let record = Record {
+        r#type: '\'',
+        tag: (7,),
+        ratios: vec![0.1, -2.0],
+        limits: (f64::NEG_INFINITY, 1e100),
+        payload: ByteBuf::from(b"\x00a\"".to_vec()),
+        labels: btreemap! { 1 => None, 2 => Some("two\n".to_owned()) },
+        empty: BTreeMap::new(),
+        events: vec![
+            Event::Started,
+            Event::Moved(-1, 2),
+            Event::Renamed {
+                from: "a".to_owned(),
+                to: "b".to_owned(),
+            },
+        ],
+        unit: Unit,
+        note: None,
+    };
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&record).unwrap();
+    let schema = builder.build().unwrap();
+    assert_eq!(
+        schema.describe_trace_ref(&trace).to_rust_literal().unwrap(),
+        concat!(
+            r#"Record { r#type: '\'', tag: (7,), ratios: vec![0.1, -2.0], "#,
+            r#"limits: (f64::NEG_INFINITY, 1e100), payload: b"\x00a\"".to_vec().into(), "#,
+            r#"labels: [(1, None), (2, Some("two\n".into()))].into_iter().collect(), "#,
+            r#"empty: Default::default(), events: vec![Event::Started, Event::Moved(-1, 2), "#,
+            r#"Event::Renamed { from: "a".into(), to: "b".into() }], unit: Unit, "#,
+            r#"..Default::default() }"#,
+        ),
+    );
+
+    // The literal above, pasted back in.
+    #[rustfmt::skip]
+    let literal = Record { r#type: '\'', tag: (7,), ratios: vec![0.1, -2.0], limits: (f64::NEG_INFINITY, 1e100), payload: b"\x00a\"".to_vec().into(), labels: [(1, None), (2, Some("two\n".into()))].into_iter().collect(), empty: Default::default(), events: vec![Event::Started, Event::Moved(-1, 2), Event::Renamed { from: "a".into(), to: "b".into() }], unit: Unit, ..Default::default() };
+    assert_eq!(literal, record);
+}
+
+#[test]
+fn test_dynamic() {
+    #[derive(Serialize)]
+    struct Marker;
+
+    #[derive(Serialize)]
+    struct Meters(f32);
+
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(Meters),
+        Rect(u16, u16),
+        Named {
+            name: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            alias: Option<char>,
+        },
+    }
+
+    #[derive(Serialize)]
+    struct Record {
+        marker: Marker,
+        shapes: Vec<Shape>,
+        extra: BTreeMap<u8, Option<ByteBuf>>,
+        pair: (i128, u128),
+        unit: (),
+    }
+
+    let record = Record {
+        marker: Marker,
+        shapes: vec![
+            Shape::Empty,
+            Shape::Circle(Meters(1.5)),
+            Shape::Rect(2, 3),
+            Shape::Named {
+                name: "a".to_owned(),
+                alias: Some('b'),
+            },
+            Shape::Named {
+                name: "c".to_owned(),
+                alias: None,
+            },
+        ],
+        extra: btreemap! { 1 => None, 2 => Some(ByteBuf::from(vec![1, 2])) },
+        pair: (-1, u128::MAX),
+        unit: (),
+    };
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&record).unwrap();
+    let schema = builder.build().unwrap();
+    let mut dynamic = schema.describe_trace_ref(&trace).to_dynamic().unwrap();
+
+    // Unchanged values round-trip to the same trace and schema.
+    let mut builder = SchemaBuilder::new();
+    let retraced = builder.trace(&dynamic).unwrap();
+    let reschema = builder.build().unwrap();
+    assert_eq!(reschema.to_string(), schema.to_string());
+    assert_eq!(retraced.0, trace.0);
+
+    let Some(Dynamic::Tuple(pair)) = dynamic.field("pair") else {
+        panic!("expected a tuple, got {dynamic:?}");
+    };
+    assert_eq!(pair[0].as_i128(), Some(-1));
+    assert_eq!(pair[1].as_i128(), None);
+    let Some(Dynamic::Seq(shapes)) = dynamic.field_mut("shapes") else {
+        panic!("expected a sequence");
+    };
+    assert_eq!(
+        shapes[1],
+        Dynamic::Variant {
+            name: "Shape".into(),
+            variant: "Circle".into(),
+            fields: DynamicFields::Newtype(Box::new(Dynamic::Struct {
+                name: "Meters".into(),
+                fields: DynamicFields::Newtype(Box::new(Dynamic::F32(1.5))),
+            })),
+        },
+    );
+    assert_eq!(shapes[4].field("name").and_then(Dynamic::as_str), Some("c"));
+    assert_eq!(shapes[4].field("alias"), None);
+    shapes.retain(|shape| shape.field("name").is_some());
+    *shapes[1].field_mut("name").unwrap() = Dynamic::Str("renamed".to_owned());
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&dynamic).unwrap();
+    let schema = builder.build().unwrap();
+    assert_eq!(
+        schema.describe_trace(trace).to_string(),
+        concat!(
+            r#"Record(marker: Marker, shapes: [Named(name: "a", alias: Some('b')), "#,
+            r#"Named(name: "renamed")], extra: {1: None, 2: Some(b"\x01\x02")}, "#,
+            r#"pair: (-1, 340282366920938463463374607431768211455), unit: ())"#,
+        ),
+    );
+}
+
+#[cfg(feature = "export")]
+#[test]
+fn test_json_export() {
+    use crate::{ByteEncoding, EnumRepresentation, JsonExportError, JsonExportOptions};
+
+    #[derive(Serialize)]
+    struct Meters(f32);
+
+    #[derive(Serialize)]
+    enum Shape {
+        Empty,
+        Circle(Meters),
+        Rect(u16, u16),
+        Named {
+            name: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            alias: Option<char>,
+        },
+    }
+
+    #[derive(Serialize)]
+    struct Frame {
+        shapes: Vec<Shape>,
+        data: ByteBuf,
+        extra: BTreeMap<u8, (i128, u128)>,
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let first = builder
+        .trace(&Frame {
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(Meters(0.1)),
+                Shape::Rect(2, 3),
+                Shape::Named {
+                    name: "a".to_owned(),
+                    alias: None,
+                },
+            ],
+            data: ByteBuf::from(b"hello".to_vec()),
+            extra: btreemap! { 1 => (-1, u128::MAX) },
+        })
+        .unwrap();
+    let second = builder
+        .trace(&Frame {
+            shapes: vec![],
+            data: ByteBuf::new(),
+            extra: BTreeMap::new(),
+        })
+        .unwrap();
+    let schema = builder.build().unwrap();
+
+    let export = |options: JsonExportOptions| {
+        let mut output = Vec::new();
+        let values = [&first, &second].map(|trace| schema.describe_trace_ref(trace));
+        options
+            .write_json_lines(&mut output, values)
+            .map(|()| String::from_utf8(output).unwrap())
+    };
+    assert_eq!(
+        export(JsonExportOptions::new()).unwrap(),
+        concat!(
+            r#"{"shapes":["Empty",{"Circle":0.1},{"Rect":[2,3]},{"Named":{"name":"a"}}],"#,
+            r#""data":[104,101,108,108,111],"#,
+            r#""extra":{"1":[-1,"340282366920938463463374607431768211455"]}}"#,
+            "\n",
+            r#"{"shapes":[],"data":[],"extra":{}}"#,
+            "\n",
+        ),
+    );
+    assert_eq!(
+        export(
+            JsonExportOptions::new()
+                .enum_representation(EnumRepresentation::Adjacent {
+                    tag: "t",
+                    content: "c"
+                })
+                .byte_encoding(ByteEncoding::Base64),
+        )
+        .unwrap()
+        .lines()
+        .next()
+        .unwrap(),
+        concat!(
+            r#"{"shapes":[{"t":"Empty"},{"t":"Circle","c":0.1},{"t":"Rect","c":[2,3]},"#,
+            r#"{"t":"Named","c":{"name":"a"}}],"data":"aGVsbG8=","#,
+            r#""extra":{"1":[-1,"340282366920938463463374607431768211455"]}}"#,
+        ),
+    );
+    assert_eq!(
+        export(
+            JsonExportOptions::new()
+                .enum_representation(EnumRepresentation::Untagged)
+                .byte_encoding(ByteEncoding::Hex),
+        )
+        .unwrap()
+        .lines()
+        .next()
+        .unwrap(),
+        concat!(
+            r#"{"shapes":[null,0.1,[2,3],{"name":"a"}],"data":"68656c6c6f","#,
+            r#""extra":{"1":[-1,"340282366920938463463374607431768211455"]}}"#,
+        ),
+    );
+    assert!(matches!(
+        export(
+            JsonExportOptions::new().enum_representation(EnumRepresentation::Internal {
+                tag: "type"
+            })
+        ),
+        Err(JsonExportError::Untaggable(variant)) if &*variant == "Circle",
+    ));
+
+    let mut output = Vec::new();
+    let second = schema.describe_trace_ref(&second);
+    second
+        .to_json_writer(&mut output, JsonExportOptions::new())
+        .unwrap();
+    assert_eq!(
+        output,
+        second.to_json(JsonExportOptions::new()).unwrap().as_bytes()
+    );
+    assert_eq!(output, br#"{"shapes":[],"data":[],"extra":{}}"#);
+}
+
+#[test]
+fn test_metrics() {
+    use crate::{FloatNormalization, Metrics};
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+    struct Counters {
+        traced: AtomicU64,
+        trace_bytes: AtomicUsize,
+        widened: AtomicU64,
+        trace_errors: AtomicU64,
+        decoded: AtomicU64,
+        decode_errors: AtomicU64,
+    }
+
+    impl Metrics for Counters {
+        fn value_traced(&self, trace_bytes: usize) {
+            self.traced.fetch_add(1, Ordering::Relaxed);
+            self.trace_bytes.fetch_add(trace_bytes, Ordering::Relaxed);
+        }
+
+        fn schema_widened(&self) {
+            self.widened.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn trace_failed(&self, error: &TraceError) {
+            assert!(matches!(error, TraceError::Nan));
+            self.trace_errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn value_decoded(&self) {
+            self.decoded.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn decode_failed(&self, _error: &dyn std::fmt::Display) {
+            self.decode_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    static COUNTERS: Counters = Counters {
+        traced: AtomicU64::new(0),
+        trace_bytes: AtomicUsize::new(0),
+        widened: AtomicU64::new(0),
+        trace_errors: AtomicU64::new(0),
+        decoded: AtomicU64::new(0),
+        decode_errors: AtomicU64::new(0),
+    };
+    let count = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+
+    let mut builder = SchemaBuilder::new()
+        .float_normalization(FloatNormalization::RejectNan)
+        .metrics(&COUNTERS);
+    let mut traces = Vec::new();
+    for value in [vec![1.0], vec![2.0, 3.0], vec![f64::NAN], vec![]] {
+        traces.extend(builder.trace(&value).ok().map(|trace| (value, trace)));
+    }
+    builder.trace_schema_only(&Some(vec![4.0])).unwrap();
+    assert_eq!(count(&COUNTERS.traced), 4);
+    assert_eq!(count(&COUNTERS.trace_errors), 1);
+    // The first value, then the option around the sequence.
+    assert_eq!(count(&COUNTERS.widened), 2);
+    assert_eq!(
+        COUNTERS.trace_bytes.load(Ordering::Relaxed),
+        traces.iter().map(|(_, trace)| trace.0.len()).sum::<usize>()
+    );
+
+    let schema = builder.build().unwrap();
+    let options = DecodeOptions::default().metrics(&COUNTERS);
+    assert_eq!(options, DecodeOptions::default().metrics(&COUNTERS));
+    assert_ne!(options, DecodeOptions::default());
+    for (value, trace) in &traces {
+        let bytes = postcard::to_stdvec(&schema.describe_trace_ref(trace)).unwrap();
+        let decode = |options| {
+            schema
+                .describe_type::<Vec<f64>>()
+                .with_options(options)
+                .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+                .map(|described| described.0)
+        };
+        assert_eq!(decode(options).unwrap(), *value);
+        assert_eq!(decode(DecodeOptions::default()).unwrap(), *value);
+        // Only empty sequences decode with a limit of zero.
+        assert_eq!(
+            decode(options.max_sequence_length(0)).is_ok(),
+            value.is_empty()
+        );
+    }
+    assert_eq!(count(&COUNTERS.decoded), 4);
+    assert_eq!(count(&COUNTERS.decode_errors), 2);
+}
+
+#[test]
+fn test_max_depth() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Node {
+        next: Option<Box<Node>>,
+    }
+
+    let mut node = Node { next: None };
+    for _ in 0..100 {
+        node = Node {
+            next: Some(Box::new(node)),
+        };
+    }
+    let bytes = to_self_described_postcard(&node);
+    let decode = |options| {
+        SelfDescribed(PhantomData::<Node>)
+            .with_options(options)
+            .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+            .map(|described| described.0)
+    };
+    assert_eq!(decode(DecodeOptions::default()).unwrap(), node);
+    // Each of the 101 structs and 100 options is a level, and so is the described root.
+    assert_eq!(
+        decode(DecodeOptions::default().max_depth(202)).unwrap(),
+        node
+    );
+    assert!(decode(DecodeOptions::default().max_depth(201)).is_err());
+    assert!(decode(DecodeOptions::strict().max_depth(0)).is_err());
+}
+
+#[test]
+fn test_typed_conversions() {
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("unserializable"))
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Circle { radius: f32 },
+        Polygon(Vec<(i32, i32)>),
+    }
+
+    for shape in [
+        Shape::Circle { radius: 1.5 },
+        Shape::Polygon(vec![(0, 0), (1, 0), (0, 1)]),
+    ] {
+        let dynamic = Dynamic::try_from(Typed(&shape)).unwrap();
+        let Typed(roundtripped) = Typed::<Shape>::try_from(&dynamic).unwrap();
+        assert_eq!(roundtripped, shape);
+        let Typed(roundtripped): Typed<Shape> = dynamic.try_into().unwrap();
+        assert_eq!(roundtripped, shape);
+    }
+
+    let dynamic: Dynamic = Typed(Some("text")).try_into().unwrap();
+    assert!(matches!(
+        Typed::<Option<u32>>::try_from(&dynamic),
+        Err(TypedConversionError::Deserialize(_))
+    ));
+    assert!(matches!(
+        Dynamic::try_from(Typed(Unserializable)),
+        Err(TypedConversionError::Trace(TraceError::Custom(_)))
+    ));
+}
+
+#[test]
+fn test_schema_equivalence() {
+    mod first {
+        #[derive(serde::Serialize)]
+        pub struct Point {
+            pub x: i32,
+            pub y: Option<Box<Point>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub label: Option<&'static str>,
+        }
+    }
+    mod second {
+        #[derive(serde::Serialize)]
+        pub struct Point {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub label: Option<&'static str>,
+            pub y: Option<Box<Point>>,
+            pub x: i32,
+        }
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let _ = builder.trace(&Some(vec![1u8])).unwrap();
+    let _ = builder
+        .trace(&first::Point {
+            x: 1,
+            y: Some(Box::new(first::Point {
+                x: 2,
+                y: None,
+                label: Some("two"),
+            })),
+            label: None,
+        })
+        .unwrap();
+    let first = builder.build().unwrap();
+
+    let mut builder = SchemaBuilder::new();
+    let _ = builder.trace(&Some(vec![2u8])).unwrap();
+    let _ = builder
+        .trace(&second::Point {
+            label: None,
+            y: Some(Box::new(second::Point {
+                label: Some("three"),
+                y: None,
+                x: 3,
+            })),
+            x: 1,
+        })
+        .unwrap();
+    let second = builder.build().unwrap();
+
+    assert_ne!(first.fingerprint(), second.fingerprint());
+    assert!(first.equivalent(&second));
+    assert!(second.equivalent(&first));
+    assert!(first.equivalent(&first));
+
+    // Different names, kinds or contents aren't equivalent.
+    fn schema_of<T: Serialize>(value: &T) -> Schema {
+        let mut builder = SchemaBuilder::new();
+        let _ = builder.trace(value).unwrap();
+        builder.build().unwrap()
+    }
+    assert!(!first.equivalent(&schema_of(&Some(vec![1u8]))));
+    assert!(!schema_of(&(1u8, 2u16)).equivalent(&schema_of(&(2u16, 1u8))));
+    assert!(!schema_of(&vec![1u8]).equivalent(&schema_of(&vec![1i8])));
+    let point = second::Point {
+        label: None,
+        y: None,
+        x: 0,
+    };
+    assert!(!schema_of(&Qualified(&point)).equivalent(&schema_of(&point)));
+    assert!(
+        schema_of(&btreemap! {"a" => 1u8, "b" => 2u8})
+            .equivalent(&schema_of(&btreemap! {"b" => 3u8}))
+    );
+}
+
+#[test]
+fn test_hashed_names() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Event {
+        Opened(u32),
+        Renamed { from: String, to: String },
+        Closed,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Log {
+        events: Vec<Event>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+    }
+
+    let log = Log {
+        events: vec![
+            Event::Opened(1),
+            Event::Renamed {
+                from: "a".to_owned(),
+                to: "b".to_owned(),
+            },
+            Event::Closed,
+        ],
+        note: None,
+    };
+    let mut builder = SchemaBuilder::new().version(3);
+    let trace = builder.trace(&log).unwrap();
+    let schema = builder.build().unwrap();
+    let hashed = schema.hash_names();
+    assert!(hashed.has_hashed_names() && !schema.has_hashed_names());
+    assert_eq!(hashed.hash_names().fingerprint(), hashed.fingerprint());
+    assert_eq!(hashed.version(), 3);
+
+    let schema_bytes = postcard::to_stdvec(&hashed).unwrap();
+    let hashed = postcard::from_bytes::<Schema>(&schema_bytes).unwrap();
+    assert!(hashed.has_hashed_names());
+    assert_eq!(hashed.fingerprint(), schema.hash_names().fingerprint());
+
+    let bytes = postcard::to_stdvec(&hashed.describe_trace_ref(&trace)).unwrap();
+    let decoded: Log = hashed
+        .deserialize_described(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(decoded, log);
+
+    // Without a type to match against, the hashes are all there is.
+    let dynamic = hashed.describe_trace_ref(&trace).to_dynamic().unwrap();
+    assert!(dynamic.field("events").is_none());
+    let Dynamic::Struct { name, .. } = dynamic else {
+        panic!("expected a struct, got {dynamic:?}");
+    };
+    assert!(name.starts_with('#') && name.len() == 17, "{name}");
+}
+
+#[test]
+fn test_collect_str() {
+    struct Displayed<T>(T);
+
+    impl<T: std::fmt::Display> Serialize for Displayed<T> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_str(&self.0)
+        }
+    }
+
+    struct Failing;
+
+    impl std::fmt::Display for Failing {
+        fn fmt(&self, _formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            Err(std::fmt::Error)
+        }
+    }
+
+    let address = std::net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+    let value = (Displayed(address), Displayed(u128::MAX), Displayed(""));
+    let expected = (address.to_string(), u128::MAX.to_string(), String::new());
+
+    let mut builder = SchemaBuilder::new();
+    let collected = builder.trace(&value).unwrap();
+    let strings = builder.trace(&expected).unwrap();
+    assert_eq!(collected.0, strings.0);
+    let schema = builder.build().unwrap();
+    assert_eq!(
+        schema.describe_trace(collected).to_string(),
+        r#"("2001:db8::1", "340282366920938463463374607431768211455", "")"#
+    );
+
+    let mut builder = SchemaBuilder::new();
+    builder.trace_schema_only(&value).unwrap();
+    let _ = builder.trace(&expected).unwrap();
+    assert_eq!(builder.build().unwrap().to_string(), schema.to_string());
+
+    // Budgets count the same bytes as for plain strings.
+    for budget in 0..=address.to_string().len() + 8 {
+        let builder = SchemaBuilder::new().max_data_bytes(budget);
+        assert_eq!(
+            builder.clone().trace(&Displayed(address)).is_ok(),
+            builder.clone().trace(&address.to_string()).is_ok(),
+        );
+    }
+    assert!(matches!(
+        SchemaBuilder::new().trace(&Displayed(Failing)),
+        Err(TraceError::Custom(_))
+    ));
+}
+
+#[test]
+fn test_heterogeneous_map() {
+    #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+    #[serde(untagged)]
+    enum Key {
+        Index(u32),
+        Name(String),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(untagged)]
+    enum Value {
+        Flag(bool),
+        Text(String),
+        List(Vec<u8>),
+    }
+
+    struct Entries {
+        separate_calls: bool,
+    }
+
+    impl Serialize for Entries {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeMap;
+
+            fn entry<M: SerializeMap>(
+                map: &mut M,
+                separate_calls: bool,
+                key: &(impl Serialize + ?Sized),
+                value: &(impl Serialize + ?Sized),
+            ) -> Result<(), M::Error> {
+                if separate_calls {
+                    map.serialize_key(key)?;
+                    map.serialize_value(value)
+                } else {
+                    map.serialize_entry(key, value)
+                }
+            }
+
+            let mut map = serializer.serialize_map(Some(4))?;
+            entry(&mut map, self.separate_calls, "name", "widget")?;
+            entry(&mut map, self.separate_calls, &1u32, &true)?;
+            entry(&mut map, self.separate_calls, &2u32, &vec![1u8, 2])?;
+            entry(&mut map, self.separate_calls, "enabled", &false)?;
+            map.end()
+        }
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let entries = builder
+        .trace(&Entries {
+            separate_calls: false,
+        })
+        .unwrap();
+    let calls = builder
+        .trace(&Entries {
+            separate_calls: true,
+        })
+        .unwrap();
+    assert_eq!(entries.0, calls.0);
+    let schema = builder.build().unwrap();
+    assert_eq!(
+        schema.to_string(),
+        "map_8(union_3(str_1, u32_2), union_7(str_1, bool_4, seq_6(u8_5)))"
+    );
+
+    let bytes = postcard::to_stdvec(&schema.describe_trace(entries)).unwrap();
+    let crate::DescribedBy(map, _) = schema
+        .describe_type::<BTreeMap<Key, Value>>()
+        .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+        .unwrap();
+    assert_eq!(
+        map,
+        btreemap! {
+            Key::Index(1) => Value::Flag(true),
+            Key::Index(2) => Value::List(vec![1, 2]),
+            Key::Name("enabled".to_owned()) => Value::Flag(false),
+            Key::Name("name".to_owned()) => Value::Text("widget".to_owned()),
+        }
+    );
+
+    // Keys and values which don't alternate are rejected.
+    struct Unpaired(&'static [bool]);
+
+    impl Serialize for Unpaired {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::SerializeMap;
+
+            let mut map = serializer.serialize_map(None)?;
+            for &is_key in self.0 {
+                if is_key {
+                    map.serialize_key(&1u8)?;
+                } else {
+                    map.serialize_value(&2u8)?;
+                }
+            }
+            map.end()
+        }
+    }
+    for calls in [
+        &[false][..],
+        &[true, true, false],
+        &[true, false, false],
+        &[true],
+    ] {
+        assert!(matches!(
+            SchemaBuilder::new().trace(&Unpaired(calls)),
+            Err(TraceError::Custom(_))
+        ));
+    }
+    assert!(
+        SchemaBuilder::new()
+            .trace(&Unpaired(&[true, false]))
+            .is_ok()
+    );
+}
+
+#[test]
+fn test_trace_nodes() {
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&(1u32, vec!["ab"], ())).unwrap();
+    let schema = builder.build().unwrap();
+    let nodes = schema
+        .describe_trace_ref(&trace)
+        .trace_nodes()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        nodes,
+        [
+            (0, TraceEvent::Tuple { len: 3 }),
+            (5, TraceEvent::U32(1)),
+            (10, TraceEvent::Seq { len: 1 }),
+            (15, TraceEvent::Str("ab")),
+            (22, TraceEvent::Unit),
+        ]
+    );
+    assert_eq!(trace.0.len(), 23);
+
+    // Items are yielded up to where a truncated trace stops making sense.
+    let truncated = Trace(trace.0[..19].to_vec());
+    let mut nodes = schema.describe_trace_ref(&truncated).trace_nodes();
+    assert_eq!(nodes.next().unwrap().unwrap().0, 0);
+    assert_eq!(nodes.next().unwrap().unwrap().0, 5);
+    assert_eq!(nodes.next().unwrap().unwrap().0, 10);
+    assert!(nodes.next().unwrap().is_err());
+    assert!(nodes.next().is_none());
+}
+
+#[test]
+fn test_trace_in() {
+    let values = [vec!["a".repeat(100)], vec![], vec!["b".to_owned(); 3]];
+    let mut builder = SchemaBuilder::new().max_data_bytes(200);
+    let mut reused = Trace::default();
+    for value in &values {
+        builder.trace_in(value, &mut reused).unwrap();
+        let trace = builder.clone().trace(value).unwrap();
+        assert_eq!(reused.0, trace.0);
+    }
+    let capacity = reused.0.capacity();
+    builder.trace_in(&values[2], &mut reused).unwrap();
+    assert_eq!(reused.0.capacity(), capacity);
+
+    assert!(matches!(
+        builder.trace_in(&vec!["c".repeat(300)], &mut reused),
+        Err(TraceError::DataBudget(_))
+    ));
+    builder.trace_in(&values[0], &mut reused).unwrap();
+    let schema = builder.build().unwrap();
+    assert_eq!(
+        schema.describe_trace_ref(&reused).to_string(),
+        format!("[{:?}]", values[0][0])
+    );
+}
+
+#[test]
+fn test_trace_reader_checkpoints() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        sensor: String,
+        value: f64,
+    }
+
+    let readings = (0..4)
+        .map(|i| Reading {
+            sensor: format!("s{i}"),
+            value: f64::from(i) / 2.0,
+        })
+        .collect::<Vec<_>>();
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&readings).unwrap();
+    let schema = builder.build().unwrap();
+    let described = schema.describe_trace_ref(&trace);
+
+    let mut reader = described.reader();
+    assert_eq!(
+        reader.next_event().unwrap(),
+        Some(TraceEvent::Seq { len: 4 })
+    );
+    assert_eq!(reader.read_value::<Reading>().unwrap(), readings[0]);
+    let checkpoint = reader.clone();
+    assert_eq!(reader.read_value::<Reading>().unwrap(), readings[1]);
+    for _ in 0..2 {
+        let mut resumed = checkpoint.clone();
+        assert_eq!(resumed.read_value::<Reading>().unwrap(), readings[1]);
+        assert_eq!(resumed.offset(), reader.offset());
+    }
+
+    // The same trace can be read and serialized from several threads at once.
+    let expected = postcard::to_stdvec(&described).unwrap();
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let mut reader = described.reader();
+                reader.next_event().unwrap();
+                let decoded = (0..4)
+                    .map(|_| reader.read_value::<Reading>().unwrap())
+                    .collect::<Vec<_>>();
+                assert_eq!(decoded, readings);
+                reader.finish().unwrap();
+                assert_eq!(postcard::to_stdvec(&described).unwrap(), expected);
+            });
+        }
+    });
+}
+
+#[test]
+fn test_schema_subtree() {
+    #[derive(Serialize)]
+    struct Point(i32, i32);
+
+    #[derive(Serialize)]
+    enum Event {
+        Click { at: u32, point: Point },
+        Note { at: String },
+        Wrapped(Option<Box<Event>>),
+    }
+
+    let mut builder = SchemaBuilder::new();
+    let _ = builder.trace(&vec![
+        Event::Click {
+            at: 1,
+            point: Point(2, 3),
+        },
+        Event::Note {
+            at: "now".to_owned(),
+        },
+        Event::Wrapped(Some(Box::new(Event::Click {
+            at: 4,
+            point: Point(5, 6),
+        }))),
+    ]);
+    let schema = builder.build().unwrap();
+    let subtree = |path: &str| schema.subtree(&path.parse().unwrap()).unwrap();
+
+    let mut builder = SchemaBuilder::new();
+    let _ = builder.trace(&1u32);
+    let _ = builder.trace(&"now");
+    let at = builder.build().unwrap().pruned().unwrap();
+    assert_eq!(subtree("[0].at").unwrap().fingerprint(), at.fingerprint());
+    assert_eq!(subtree("[0].at").unwrap().to_string(), at.to_string());
+
+    let mut builder = SchemaBuilder::new();
+    let _ = builder.trace(&2i32);
+    let int = builder.build().unwrap().pruned().unwrap();
+    for path in [
+        "[0]::Click.point.1",
+        "[1].point[0]",
+        "[0]::Wrapped::Click.point.0",
+    ] {
+        assert_eq!(subtree(path).unwrap().fingerprint(), int.fingerprint());
+    }
+
+    assert_eq!(
+        subtree(".").unwrap().fingerprint(),
+        schema.pruned().unwrap().fingerprint()
+    );
+    assert!(subtree("[0]::Note.point").is_none());
+    assert!(subtree("[0].point.2").is_none());
+    assert!(subtree(r#"["key"]"#).is_none());
+}
+
+#[test]
+fn test_nested_described() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Payload {
+        Text(String),
+        Point { x: i32, y: Option<i32> },
+    }
+
+    #[derive(Serialize)]
+    struct Envelope<'schema, 'trace> {
+        route: &'static str,
+        payloads: Vec<Embedded<'schema, 'trace>>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct ReceivedEnvelope<PayloadT> {
+        route: String,
+        payloads: Vec<PayloadT>,
+    }
+
+    let payloads = [
+        Payload::Text("hi".to_owned()),
+        Payload::Point { x: 1, y: None },
+    ];
+    let mut builder = SchemaBuilder::new();
+    let traces = payloads
+        .iter()
+        .map(|payload| builder.trace(payload).unwrap())
+        .collect::<Vec<_>>();
+    let schema = builder.build().unwrap();
+
+    let envelope = Envelope {
+        route: "a.b",
+        payloads: traces
+            .iter()
+            .map(|trace| Embedded(schema.describe_trace_ref(trace)))
+            .collect(),
+    };
+    let bytes = to_self_described_postcard(&envelope);
+    let received: ReceivedEnvelope<Payload> = from_self_described_postcard(&bytes);
+    assert_eq!(
+        received,
+        ReceivedEnvelope {
+            route: "a.b".to_owned(),
+            payloads: payloads.into(),
+        }
+    );
+
+    // Embedding the trace of the envelope again nests envelopes without losing the payloads.
+    let mut builder = SchemaBuilder::new();
+    let envelope_trace = builder.trace(&envelope).unwrap();
+    let envelope_schema = builder.build().unwrap();
+    let rewrapped = to_self_described_postcard(Embedded(
+        envelope_schema.describe_trace_ref(&envelope_trace),
+    ));
+    assert_eq!(rewrapped, bytes);
+}
+
+#[test]
+fn test_raw_value() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Body {
+        Text(String),
+        Fields {
+            #[serde(default, skip_serializing_if = "Option::is_none")]
+            id: Option<u8>,
+            tags: BTreeMap<String, (i16, Vec<u8>)>,
+        },
+        Empty,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message<BodyT> {
+        hops: u32,
+        body: BodyT,
+        reply: Option<BodyT>,
+    }
+
+    let messages = vec![
+        Message {
+            hops: 0,
+            body: Body::Text("hi".to_owned()),
+            reply: None,
+        },
+        Message {
+            hops: 0,
+            body: Body::Fields {
+                id: None,
+                tags: btreemap! { "a".to_owned() => (-1, vec![1, 2]) },
+            },
+            reply: Some(Body::Empty),
+        },
+        Message {
+            hops: 0,
+            body: Body::Fields {
+                id: Some(3),
+                tags: BTreeMap::new(),
+            },
+            reply: Some(Body::Text("ok".to_owned())),
+        },
+    ];
+    let bytes = to_self_described_postcard(&messages);
+
+    let mut forwarded: Vec<Message<RawValue>> = from_self_described_postcard(&bytes);
+    for message in &mut forwarded {
+        message.hops += 1;
+    }
+    assert_eq!(format!("{:?}", forwarded[1].reply), "Some(RawValue(Empty))");
+    let bytes = to_self_described_postcard(&forwarded);
+
+    let received: Vec<Message<Body>> = from_self_described_postcard(&bytes);
+    for (received, message) in received.into_iter().zip(messages) {
+        assert_eq!(received, Message { hops: 1, ..message });
+    }
+
+    // Raw values need a schema to capture their contents.
+    assert!(postcard::from_bytes::<RawValue>(&[1]).is_err());
+
+    // A raw value containing raw values captures them along with the rest.
+    let nested = Message {
+        hops: 0,
+        body: forwarded.remove(1),
+        reply: Some(forwarded.remove(0)),
+    };
+    let bytes = to_self_described_postcard(&nested);
+    let captured: Message<RawValue> = from_self_described_postcard(&bytes);
+    let received: Message<Message<Body>> =
+        from_self_described_postcard(&to_self_described_postcard(&captured));
+    assert_eq!(received.body.reply, Some(Body::Empty));
+    assert_eq!(received.reply.unwrap().body, Body::Text("hi".to_owned()));
+
+    // Values captured while visiting another don't replace it.
+    #[derive(Debug)]
+    struct Reentrant(RawValue, RawValue);
+
+    impl<'de> Deserialize<'de> for Reentrant {
+        fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            struct ReentrantVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for ReentrantVisitor {
+                type Value = Reentrant;
+
+                fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    formatter.write_str("a described value")
+                }
+
+                fn visit_unit<E>(self) -> Result<Self::Value, E>
+                where
+                    E: serde::de::Error,
+                {
+                    let inner: RawValue =
+                        from_self_described_postcard(&to_self_described_postcard("inner"));
+                    let outer =
+                        RawValue::new(&CaptureVisitor.visit_unit::<E>()?).map_err(E::custom)?;
+                    Ok(Reentrant(outer, inner))
+                }
+            }
+
+            deserializer.deserialize_newtype_struct(RAW_VALUE_TYPE_NAME, ReentrantVisitor)
+        }
+    }
+
+    let Reentrant(outer, inner) = from_self_described_postcard(&to_self_described_postcard(7u8));
+    assert_eq!(
+        format!("{outer:?} {inner:?}"),
+        r#"RawValue(7) RawValue("inner")"#
+    );
+}
+
+#[test]
+fn test_large_union_interning() {
+    // Tuples of every length up to 300, each widening the union of the sequence's items.
+    let items = (1..=300)
+        .map(|length| Dynamic::Tuple(vec![Dynamic::U8(0); length]))
+        .collect::<Vec<_>>();
+    let build = |mut builder: SchemaBuilder| {
+        let trace = builder.trace(&items).unwrap();
+        let again = builder.trace(&&items[..10]).unwrap();
+        let schema = builder.build().unwrap();
+        (
+            schema.fingerprint(),
+            schema.describe_trace_ref(&trace).to_dynamic().unwrap(),
+            schema.describe_trace_ref(&again).to_dynamic().unwrap(),
+        )
+    };
+    let (fingerprint, dynamic, again) = build(SchemaBuilder::new());
+    assert_eq!(dynamic, Dynamic::Seq(items.clone()));
+    assert_eq!(again, Dynamic::Seq(items[..10].to_vec()));
+    assert_eq!(
+        build(SchemaBuilder::new().node_capacity(1024)).0,
+        fingerprint
+    );
+}
+
+#[test]
+fn test_forbid_unions() {
+    #[derive(Serialize)]
+    enum Event {
+        Click { x: u32, y: u32 },
+        Key(char),
+    }
+
+    #[derive(Serialize)]
+    struct Batch {
+        events: Vec<Event>,
+        tags: BTreeMap<&'static str, Dynamic>,
+    }
+
+    fn union_error(value: &impl Serialize) -> (String, String, String) {
+        match SchemaBuilder::new().forbid_unions(true).trace(value) {
+            Err(TraceError::Union(error)) => (
+                error.path().to_string(),
+                error.existing().to_owned(),
+                error.conflicting().to_owned(),
+            ),
+            Err(error) => panic!("unexpected error {error}"),
+            Ok(_) => panic!("union not rejected"),
+        }
+    }
+
+    // Options and variants of the same enum are a single type.
+    let batch = Batch {
+        events: vec![Event::Click { x: 1, y: 2 }, Event::Key('k')],
+        tags: btreemap! { "a" => Dynamic::U8(1), "b" => Dynamic::U8(2) },
+    };
+    let mut builder = SchemaBuilder::new().forbid_unions(true);
+    let _ = builder.trace(&batch).unwrap();
+    let _ = builder.trace(&vec![Some(1u32), None, Some(2)]).unwrap();
+    // Separate values may still differ.
+    let _ = builder.trace(&"a string").unwrap();
+
+    let batch = Batch {
+        events: Vec::new(),
+        tags: btreemap! { "a" => Dynamic::U8(1), "b" => Dynamic::Str("two".to_owned()) },
+    };
+    assert_eq!(
+        union_error(&batch),
+        (".tags[1]".to_owned(), "u8".to_owned(), "str".to_owned())
+    );
+    assert_eq!(
+        union_error(&vec![
+            vec![(1u32, Some(Dynamic::U8(1)))],
+            vec![(2, None), (3, Some(Dynamic::U16(2)))]
+        ]),
+        (".[1]".to_owned(), "u8".to_owned(), "u16".to_owned())
+    );
+    assert_eq!(
+        SchemaBuilder::new()
+            .forbid_unions(true)
+            .trace(&vec![Dynamic::Unit, Dynamic::Bool(true)])
+            .err()
+            .unwrap()
+            .to_string(),
+        "unions are forbidden, but `.[1]` is `bool`, unlike the earlier `unit`"
+    );
+}
+
+#[test]
+fn test_schema_explain_difference() {
+    mod first {
+        #[derive(serde::Serialize)]
+        pub(super) struct Record {
+            pub(super) id: u32,
+            pub(super) scores: std::collections::BTreeMap<u8, (f32, bool)>,
+            pub(super) legacy: (),
+        }
+    }
+    mod second {
+        #[derive(serde::Serialize)]
+        pub(super) struct Record {
+            pub(super) id: i64,
+            pub(super) scores: std::collections::BTreeMap<String, (f64, bool, bool)>,
+            pub(super) tags: Vec<Option<&'static str>>,
+        }
+    }
+
+    let first = SchemaBuilder::schema_only(&first::Record {
+        id: 1,
+        scores: btreemap! { 1 => (1.0, true) },
+        legacy: (),
+    })
+    .unwrap();
+    let second = SchemaBuilder::schema_only(&second::Record {
+        id: 1,
+        scores: btreemap! { "a".to_owned() => (1.0, true, false) },
+        tags: vec![Some("x"), None],
+    })
+    .unwrap();
+    let explanation = first.explain_difference(&second).unwrap();
+    assert_eq!(
+        explanation.to_string(),
+        "\
+            .id: type differs: `u32` in the first schema, `i64` in the second\n\
+            .scores{}: type differs: `u8` in the first schema, `str` in the second\n\
+            .scores[]: has 2 items in the first schema and 3 in the second\n\
+            .: field `legacy` appears only in the first schema\n\
+            .: field `tags` appears only in the second schema\n"
+    );
+    assert_eq!(explanation.differences()[0].path(), ".id");
+
+    let narrow = SchemaBuilder::schema_only(&vec![(1u8, 1.0f32)]).unwrap();
+    let wide = SchemaBuilder::schema_only(&vec![(1u16, 1.0f64)]).unwrap();
+    assert_eq!(
+        narrow.explain_difference(&wide).unwrap().to_string(),
+        "\
+            .[].0: integer width differs: `u8` in the first schema, `u16` in the second\n\
+            .[].1: float width differs: `f32` in the first schema, `f64` in the second\n"
+    );
+
+    // Schemas which only differ by the order values were traced in are equivalent.
+    let (forward, _) = SchemaBuilder::trace_all([Some(1u8), None]).unwrap();
+    let (backward, _) = SchemaBuilder::trace_all([None, Some(1u8)]).unwrap();
+    assert!(forward.explain_difference(&backward).unwrap().is_empty());
+    let (required, _) = SchemaBuilder::trace_all([Some(1u8)]).unwrap();
+    assert_eq!(
+        forward.explain_difference(&required).unwrap().to_string(),
+        ".: `none` appears only in the first schema\n"
+    );
+}
+
+#[test]
+fn test_skip_stats() {
+    #[derive(Serialize)]
+    struct Inner {
+        #[serde(skip_serializing_if = "if_zero")]
+        value: u32,
+    }
+
+    #[derive(Serialize)]
+    enum Event {
+        Move {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            inner: Option<Inner>,
+            #[serde(skip_serializing_if = "if_zero")]
+            hidden: u32,
+        },
+    }
+
+    let mut builder = SchemaBuilder::new();
+    assert!(builder.skip_stats().is_empty());
+    let events = [
+        Event::Move {
+            inner: Some(Inner { value: 0 }),
+            hidden: 0,
+        },
+        Event::Move {
+            inner: None,
+            hidden: 0,
+        },
+        Event::Move {
+            inner: Some(Inner { value: 1 }),
+            hidden: 0,
+        },
+    ];
+    let _ = builder
+        .trace(&btreemap! { "a" => &events[..2], "b" => &events[2..] })
+        .unwrap();
+    let _ = builder
+        .trace(
+            &btreemap! { "c" => &[Event::Move { inner: Some(Inner { value: 2 }), hidden: 0 }][..] },
+        )
+        .unwrap();
+    let stats = builder.skip_stats();
+    assert_eq!(stats.len(), 3);
+    assert_eq!(
+        stats.to_string(),
+        "\
+            Event::Move.hidden: 0 present, 4 skipped\n\
+            Event::Move.inner: 3 present, 1 skipped\n\
+            Inner.value: 2 present, 1 skipped"
+    );
+    assert_eq!(stats.field("Inner", "value").unwrap().present(), 2);
+    assert_eq!(stats.field("Inner", "missing"), None);
+}
+
+#[cfg(feature = "test-vectors")]
+#[test]
+fn test_test_vectors() {
+    use crate::{TestVector, TestVectorError};
+    use std::collections::HashSet;
+
+    let vectors = TestVector::all();
+    let names = vectors.iter().map(TestVector::name).collect::<HashSet<_>>();
+    assert_eq!(names.len(), vectors.len());
+
+    // Changing a vector would invalidate fixtures generated by earlier versions.
+    let union = vectors
+        .iter()
+        .find(|vector| vector.name() == "union")
+        .unwrap();
+    assert_eq!(
+        union.schema().to_string(),
+        "seq_5(union_4(i32_1, str_2, unit_3))"
+    );
+    assert_eq!(union.expected(), r#"[1, "two", ()]"#);
+
+    for vector in &vectors {
+        let schema_bytes = postcard::to_stdvec(vector.schema()).unwrap();
+        let data_bytes = postcard::to_stdvec(&vector.described()).unwrap();
+        let schema: Schema = postcard::from_bytes(&schema_bytes).unwrap();
+        vector
+            .verify(
+                &schema,
+                &mut postcard::Deserializer::from_bytes(&data_bytes),
+            )
+            .unwrap();
+    }
+
+    // Same schema, different data.
+    let mut builder = SchemaBuilder::new();
+    let trace = builder
+        .trace(&vec![
+            Dynamic::I32(2),
+            Dynamic::Str("two".to_owned()),
+            Dynamic::Unit,
+        ])
+        .unwrap();
+    let schema = builder.build().unwrap();
+    let data_bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    assert!(matches!(
+        union.verify(
+            &schema,
+            &mut postcard::Deserializer::from_bytes(&data_bytes)
+        ),
+        Err(TestVectorError::Mismatch { .. })
+    ));
+
+    let other = &vectors[0];
+    assert!(matches!(
+        other.verify(
+            &schema,
+            &mut postcard::Deserializer::from_bytes(&data_bytes)
+        ),
+        Err(TestVectorError::Schema { name: "bool", .. })
+    ));
+}
+
+#[test]
+fn test_limits() {
+    let limits = crate::limits();
+    assert_eq!(limits.max_length(), usize::try_from(u32::MAX).unwrap());
+    assert_eq!(limits.discriminant_variants(), 256);
+    assert!(limits.max_union_members() >= limits.max_names());
+
+    // One more member than an enum has variants nests the discriminant one enum deeper.
+    let members = (0..=limits.discriminant_variants())
+        .map(|i_member| Dynamic::Struct {
+            name: format!("Member{i_member}").into(),
+            fields: DynamicFields::Unit,
+        })
+        .collect::<Vec<_>>();
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&Dynamic::Seq(members)).unwrap();
+    let schema = builder.build().unwrap();
+    let serialized = ron::to_string(&schema.describe_trace(trace)).unwrap();
+    assert!(serialized.contains("_00(_ff("), "{serialized}");
+    assert!(serialized.contains("_01(_00("), "{serialized}");
+}
+
+#[test]
+fn test_wide_skippable_struct() {
+    // More skippable fields than fit in a single 64-bit discriminant, present in various
+    // patterns, including all and none.
+    const FIELDS: usize = 100;
+    let wide = |present: &dyn Fn(usize) -> bool| Dynamic::Struct {
+        name: "Wide".into(),
+        fields: DynamicFields::Named(
+            (0..FIELDS)
+                .map(|i_field| {
+                    let value = Dynamic::U32(u32::try_from(i_field).unwrap());
+                    (
+                        format!("field{i_field}").into(),
+                        present(i_field).then_some(value),
+                    )
+                })
+                .collect(),
+        ),
+    };
+    let value = Dynamic::Seq(vec![
+        wide(&|_| true),
+        wide(&|_| false),
+        wide(&|i_field| i_field % 3 == 0),
+        wide(&|i_field| i_field >= 64),
+        wide(&|i_field| i_field == FIELDS - 1),
+    ]);
+
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&value).unwrap();
+    // Every struct after the first reserves its presence bitset for all of its fields.
+    assert_eq!(
+        builder.struct_fields.values().copied().collect::<Vec<_>>(),
+        [FIELDS]
+    );
+    let schema = builder.build().unwrap();
+    let described = schema.describe_trace_ref(&trace);
+    let expected = described.to_string();
+
+    let bytes = postcard::to_stdvec(&SelfDescribed(&value)).unwrap();
+    let SelfDescribed(decoded) = postcard::from_bytes::<SelfDescribed<RawValue>>(&bytes).unwrap();
+    assert_eq!(decoded.described().to_string(), expected);
+
+    let bytes = bitcode::serialize(&SelfDescribed(&value)).unwrap();
+    let SelfDescribed(decoded) = bitcode::deserialize::<SelfDescribed<RawValue>>(&bytes).unwrap();
+    assert!(decoded.described().content_eq(&described));
+}
+
+#[test]
+fn test_decode_plan() {
+    use crate::DecodePlan;
+
+    #[derive(Serialize)]
+    enum Kind {
+        Small,
+        Large(u32),
+    }
+
+    #[derive(Serialize)]
+    struct Written {
+        id: u32,
+        kind: Kind,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        extra: bool,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum ReadKind {
+        Small,
+        Large(u32),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Read {
+        id: u32,
+        kind: ReadKind,
+        note: Option<String>,
+    }
+
+    // Shares the field list of `Read`, but expects other fields.
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Other {
+        id: u32,
+    }
+
+    let written = [
+        Written {
+            id: 1,
+            kind: Kind::Small,
+            note: None,
+            extra: true,
+        },
+        Written {
+            id: 2,
+            kind: Kind::Large(7),
+            note: Some("seven".to_owned()),
+            extra: false,
+        },
+    ];
+    let (schema, traces) = SchemaBuilder::trace_all(&written).unwrap();
+    let schema = schema.hash_names();
+    let encoded = traces
+        .into_iter()
+        .map(|trace| postcard::to_stdvec(&schema.describe_trace(trace)).unwrap())
+        .collect::<Vec<_>>();
+    let expected = [
+        Read {
+            id: 1,
+            kind: ReadKind::Small,
+            note: None,
+        },
+        Read {
+            id: 2,
+            kind: ReadKind::Large(7),
+            note: Some("seven".to_owned()),
+        },
+    ];
+
+    let plan = DecodePlan::<Read>::compile(&schema);
+    fn assert_sync<T: Sync>(_: &T) {}
+    assert_sync(&plan);
+    for _ in 0..2 {
+        for (bytes, expected) in encoded.iter().zip(&expected) {
+            let decoded = plan
+                .deserialize(&mut postcard::Deserializer::from_bytes(bytes))
+                .unwrap();
+            assert_eq!(&decoded, expected);
+        }
+    }
+
+    // Options apply like with `describe_type`, for the `extra` field here.
+    let strict = DecodePlan::<Read>::compile(&schema).options(DecodeOptions::strict());
+    let error = strict
+        .deserialize(&mut postcard::Deserializer::from_bytes(&encoded[0]))
+        .unwrap_err();
+    let unplanned = schema
+        .describe_type::<Read>()
+        .with_options(DecodeOptions::strict())
+        .deserialize(&mut postcard::Deserializer::from_bytes(&encoded[0]))
+        .err()
+        .unwrap();
+    assert_eq!(error.to_string(), unplanned.to_string());
+
+    // Different types decoded from the same struct expect different fields.
+    let mut builder = SchemaBuilder::new();
+    let trace = builder.trace(&(&written[1], &written[0])).unwrap();
+    let schema = builder.build().unwrap().hash_names();
+    let bytes = postcard::to_stdvec(&schema.describe_trace(trace)).unwrap();
+    let plan = DecodePlan::<(Other, Read)>::compile(&schema);
+    for _ in 0..2 {
+        let (other, read) = plan
+            .deserialize(&mut postcard::Deserializer::from_bytes(&bytes))
+            .unwrap();
+        assert_eq!((other, &read), (Other { id: 2 }, &expected[0]));
+    }
+}
+
+#[test]
+fn test_decode_plan_same_names() {
+    use crate::plan::same_names;
+
+    const FIELDS: &[&str] = &["id", "kind", "note"];
+    // A copy at another address, like a constant duplicated in another codegen unit.
+    let copied = FIELDS
+        .iter()
+        .map(|name| name.to_string())
+        .collect::<Vec<_>>();
+    let copied = copied.iter().map(String::as_str).collect::<Vec<_>>();
+
+    assert!(same_names(FIELDS, FIELDS));
+    assert!(same_names(FIELDS, &copied));
+    assert!(!same_names(FIELDS, &copied[..2]));
+    assert!(!same_names(FIELDS, &["id", "kind", "notes"]));
+}
@@ -1,9 +1,10 @@
 use serde::Serialize;
 use std::{cell::Cell, hash::Hash};
+use thiserror::Error;
 
-use crate::indices::{FieldNameListIndex, TypeNameIndex, VariantNameIndex};
+use crate::indices::{FieldNameListIndex, MemberIndex, TypeNameIndex, VariantNameIndex};
 
-#[derive(Copy, Debug, Clone)]
+#[derive(Copy, Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) enum TraceNode {
     Bool,
     I8,
@@ -57,6 +58,12 @@ pub(crate) enum TraceNode {
 #[must_use = "a trace is only useful if it's later serialized with the resulting schema"]
 pub struct Trace(pub(crate) Vec<u8>);
 
+/// Returned when a [`Trace`] is inspected together with a [`Schema`][`crate::Schema`] that it
+/// was not produced with.
+#[derive(Debug, Error)]
+#[error("trace does not match schema: {0}")]
+pub struct InvalidTraceError(pub(crate) Box<str>);
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize)]
 #[repr(u8)]
 pub enum TraceNodeKind {
@@ -153,8 +160,50 @@ impl From<TraceNodeKind> for u8 {
     }
 }
 
+/// Fixed-width values as stored in traces.
+///
+/// Traces are always little-endian, whatever the byte order of the platform they're built or
+/// read on, such that they can be moved between platforms (e.g. as a
+/// [`DetachedTrace`][`crate::DetachedTrace`]). Every conversion between values and trace bytes
+/// goes through this trait.
+pub(crate) trait TraceBytes: Sized {
+    type Bytes: AsRef<[u8]>;
+
+    const SIZE: usize;
+
+    fn to_trace_bytes(self) -> Self::Bytes;
+
+    /// Panics unless `bytes` is exactly [`Self::SIZE`] long.
+    fn from_trace_bytes(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_trace_bytes {
+    ($($value:ty),+) => {
+        $(
+            impl TraceBytes for $value {
+                type Bytes = [u8; std::mem::size_of::<$value>()];
+
+                const SIZE: usize = std::mem::size_of::<$value>();
+
+                #[inline]
+                fn to_trace_bytes(self) -> Self::Bytes {
+                    self.to_le_bytes()
+                }
+
+                #[inline]
+                fn from_trace_bytes(bytes: &[u8]) -> Self {
+                    <$value>::from_le_bytes(bytes.try_into().expect("impossible"))
+                }
+            }
+        )+
+    };
+}
+
+impl_trace_bytes!(u16, u32, u64, u128, i16, i32, i64, i128, f32, f64);
+
 pub(crate) trait ReadTraceExt<'data> {
     fn pop_u8<ErrorT: serde::ser::Error>(&self) -> Result<u8, ErrorT>;
+    fn remaining(&self) -> usize;
     fn pop_slice<ErrorT: serde::ser::Error>(&self, len: usize) -> Result<&'data [u8], ErrorT>;
 
     fn pop_str<ErrorT: serde::ser::Error>(&self, len: usize) -> Result<&'data str, ErrorT> {
@@ -162,36 +211,24 @@ pub(crate) trait ReadTraceExt<'data> {
             .map_err(|_| ErrorT::custom("invalid utf-8 in traced string"))
     }
 
+    fn pop_fixed<ValueT: TraceBytes, ErrorT: serde::ser::Error>(&self) -> Result<ValueT, ErrorT> {
+        Ok(ValueT::from_trace_bytes(self.pop_slice(ValueT::SIZE)?))
+    }
+
     fn pop_u16<ErrorT: serde::ser::Error>(&self) -> Result<u16, ErrorT> {
-        Ok(u16::from_le_bytes(
-            self.pop_slice(std::mem::size_of::<u16>())?
-                .try_into()
-                .expect("impossible"),
-        ))
+        self.pop_fixed()
     }
 
     fn pop_u32<ErrorT: serde::ser::Error>(&self) -> Result<u32, ErrorT> {
-        Ok(u32::from_le_bytes(
-            self.pop_slice(std::mem::size_of::<u32>())?
-                .try_into()
-                .expect("impossible"),
-        ))
+        self.pop_fixed()
     }
 
     fn pop_u64<ErrorT: serde::ser::Error>(&self) -> Result<u64, ErrorT> {
-        Ok(u64::from_le_bytes(
-            self.pop_slice(std::mem::size_of::<u64>())?
-                .try_into()
-                .expect("impossible"),
-        ))
+        self.pop_fixed()
     }
 
     fn pop_u128<ErrorT: serde::ser::Error>(&self) -> Result<u128, ErrorT> {
-        Ok(u128::from_le_bytes(
-            self.pop_slice(std::mem::size_of::<u128>())?
-                .try_into()
-                .expect("impossible"),
-        ))
+        self.pop_fixed()
     }
 
     fn pop_trace_node<ErrorT: serde::ser::Error>(&self) -> Result<TraceNode, ErrorT> {
@@ -276,19 +313,19 @@ pub(crate) trait ReadTraceExt<'data> {
     }
 
     fn pop_i16<ErrorT: serde::ser::Error>(&self) -> Result<i16, ErrorT> {
-        Ok(self.pop_u16()? as i16)
+        self.pop_fixed()
     }
 
     fn pop_i32<ErrorT: serde::ser::Error>(&self) -> Result<i32, ErrorT> {
-        Ok(self.pop_u32()? as i32)
+        self.pop_fixed()
     }
 
     fn pop_i64<ErrorT: serde::ser::Error>(&self) -> Result<i64, ErrorT> {
-        Ok(self.pop_u64()? as i64)
+        self.pop_fixed()
     }
 
     fn pop_i128<ErrorT: serde::ser::Error>(&self) -> Result<i128, ErrorT> {
-        Ok(self.pop_u128()? as i128)
+        self.pop_fixed()
     }
 
     fn pop_char<ErrorT: serde::ser::Error>(&self) -> Result<char, ErrorT> {
@@ -296,19 +333,67 @@ pub(crate) trait ReadTraceExt<'data> {
     }
 
     fn pop_f32<ErrorT: serde::ser::Error>(&self) -> Result<f32, ErrorT> {
-        Ok(f32::from_bits(self.pop_u32()?))
+        self.pop_fixed()
     }
 
     fn pop_f64<ErrorT: serde::ser::Error>(&self) -> Result<f64, ErrorT> {
-        Ok(f64::from_bits(self.pop_u64()?))
+        self.pop_fixed()
     }
 
+    /// Pops a length prefix, rejecting lengths longer than the rest of the trace.
+    ///
+    /// Every element of a string, sequence or map takes up at least one byte of the trace, so
+    /// this bounds the allocations callers make based on the length, even for corrupted traces.
     fn pop_length_u32<ErrorT: serde::ser::Error>(&self) -> Result<usize, ErrorT> {
-        Ok(usize::try_from(self.pop_u32()?).expect("usize needs to be at least 32 bits"))
+        let length = usize::try_from(self.pop_u32()?).expect("usize needs to be at least 32 bits");
+        if length > self.remaining() {
+            return Err(ErrorT::custom("length prefix exceeds the end of trace"));
+        }
+        Ok(length)
+    }
+
+    /// Pops the presence bitset of a struct with `fields` fields (including skipped ones), see
+    /// [`present_members`].
+    fn pop_presence<ErrorT: serde::ser::Error>(
+        &self,
+        fields: usize,
+    ) -> Result<&'data [u8], ErrorT> {
+        self.pop_slice(presence_len(fields))
     }
 }
 
+/// The size of the presence bitset of a traced struct with `fields` fields.
+///
+/// Structs are traced as their field name list, followed by a bitset with one bit per field
+/// (including skipped ones) which is set for the fields that are present, followed by the values
+/// of the present fields.
+#[inline]
+pub(crate) fn presence_len(fields: usize) -> usize {
+    fields.div_ceil(8)
+}
+
+/// Whether a member is set in the presence bitset of a traced struct.
+#[inline]
+pub(crate) fn is_present(presence: &[u8], member: MemberIndex) -> bool {
+    let member = usize::from(member);
+    presence
+        .get(member / 8)
+        .is_some_and(|byte| byte & (1 << (member % 8)) != 0)
+}
+
+/// The members set in the presence bitset of a traced struct, in order.
+pub(crate) fn present_members(presence: &[u8]) -> impl DoubleEndedIterator<Item = MemberIndex> {
+    (0..u32::try_from(presence.len() * 8).unwrap_or(u32::MAX))
+        .map(MemberIndex::from)
+        .filter(|&member| is_present(presence, member))
+}
+
 impl<'data> ReadTraceExt<'data> for Cell<&'data [u8]> {
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.get().len()
+    }
+
     fn pop_u8<ErrorT: serde::ser::Error>(&self) -> Result<u8, ErrorT> {
         let mut data = self.get();
         let byte = *data
@@ -0,0 +1,298 @@
+use serde::{Deserializer, Serialize, Serializer, de::Visitor, forward_to_deserialize_any};
+use std::{borrow::Cow, marker::PhantomData, sync::Arc};
+
+use crate::{Dynamic, SchemaBuilder};
+
+/// A primitive value passed through a transformer registered with
+/// [`SchemaBuilder::transform_field`] or [`SchemaBuilder::transform_type`].
+///
+/// The transformer may return a value of a different kind (e.g. replace a string with its hash),
+/// in which case the schema records the returned kind.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum LeafValue<'value> {
+    /// `bool`.
+    Bool(bool),
+    /// `i8`.
+    I8(i8),
+    /// `i16`.
+    I16(i16),
+    /// `i32`.
+    I32(i32),
+    /// `i64`.
+    I64(i64),
+    /// `i128`.
+    I128(i128),
+    /// `u8`.
+    U8(u8),
+    /// `u16`.
+    U16(u16),
+    /// `u32`.
+    U32(u32),
+    /// `u64`.
+    U64(u64),
+    /// `u128`.
+    U128(u128),
+    /// `f32`.
+    F32(f32),
+    /// `f64`.
+    F64(f64),
+    /// `char`.
+    Char(char),
+    /// A string.
+    Str(Cow<'value, str>),
+    /// A byte array.
+    Bytes(Cow<'value, [u8]>),
+}
+
+impl Serialize for LeafValue<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Self::Bool(value) => serializer.serialize_bool(*value),
+            Self::I8(value) => serializer.serialize_i8(*value),
+            Self::I16(value) => serializer.serialize_i16(*value),
+            Self::I32(value) => serializer.serialize_i32(*value),
+            Self::I64(value) => serializer.serialize_i64(*value),
+            Self::I128(value) => serializer.serialize_i128(*value),
+            Self::U8(value) => serializer.serialize_u8(*value),
+            Self::U16(value) => serializer.serialize_u16(*value),
+            Self::U32(value) => serializer.serialize_u32(*value),
+            Self::U64(value) => serializer.serialize_u64(*value),
+            Self::U128(value) => serializer.serialize_u128(*value),
+            Self::F32(value) => serializer.serialize_f32(*value),
+            Self::F64(value) => serializer.serialize_f64(*value),
+            Self::Char(value) => serializer.serialize_char(*value),
+            Self::Str(value) => serializer.serialize_str(value),
+            Self::Bytes(value) => serializer.serialize_bytes(value),
+        }
+    }
+}
+
+/// Presents a [`LeafValue`] to a visitor, e.g. once decoded by a
+/// [logical type][`crate::LogicalTypes`].
+pub(crate) struct LeafValueDeserializer<'value, ErrorT> {
+    value: LeafValue<'value>,
+    phantom: PhantomData<ErrorT>,
+}
+
+impl<'value, ErrorT> LeafValueDeserializer<'value, ErrorT> {
+    #[inline]
+    pub(crate) fn new(value: LeafValue<'value>) -> Self {
+        Self {
+            value,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<'de, ErrorT> Deserializer<'de> for LeafValueDeserializer<'_, ErrorT>
+where
+    ErrorT: serde::de::Error,
+{
+    type Error = ErrorT;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.value {
+            LeafValue::Bool(value) => visitor.visit_bool(value),
+            LeafValue::I8(value) => visitor.visit_i8(value),
+            LeafValue::I16(value) => visitor.visit_i16(value),
+            LeafValue::I32(value) => visitor.visit_i32(value),
+            LeafValue::I64(value) => visitor.visit_i64(value),
+            LeafValue::I128(value) => visitor.visit_i128(value),
+            LeafValue::U8(value) => visitor.visit_u8(value),
+            LeafValue::U16(value) => visitor.visit_u16(value),
+            LeafValue::U32(value) => visitor.visit_u32(value),
+            LeafValue::U64(value) => visitor.visit_u64(value),
+            LeafValue::U128(value) => visitor.visit_u128(value),
+            LeafValue::F32(value) => visitor.visit_f32(value),
+            LeafValue::F64(value) => visitor.visit_f64(value),
+            LeafValue::Char(value) => visitor.visit_char(value),
+            LeafValue::Str(value) => visitor.visit_str(&value),
+            LeafValue::Bytes(value) => visitor.visit_bytes(&value),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+
+    #[inline]
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+pub(crate) type TransformFn =
+    dyn for<'value> Fn(LeafValue<'value>) -> LeafValue<'value> + Send + Sync;
+
+/// Replaces a whole value with a single byte string, see [`SchemaBuilder::protection_policy`].
+pub(crate) type SealFn = dyn Fn(&Dynamic) -> Vec<u8> + Send + Sync;
+
+/// The sealer of a field or type, or `None` if it's explicitly recorded as-is.
+pub(crate) type Sealer = Option<Arc<SealFn>>;
+
+/// The transformers registered with a [`SchemaBuilder`].
+#[derive(Clone, Default)]
+pub(crate) struct Transformers {
+    fields: Vec<(Box<str>, Box<str>, Arc<TransformFn>)>,
+    types: Vec<(Box<str>, Arc<TransformFn>)>,
+    /// Fields sealed by a protection policy, `None` for fields exempted from their type's.
+    sealed_fields: Vec<(Box<str>, Box<str>, Sealer)>,
+    /// Types whose fields are sealed by a protection policy.
+    sealed_types: Vec<(Box<str>, Sealer)>,
+}
+
+impl Transformers {
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+            && self.types.is_empty()
+            && self.sealed_fields.is_empty()
+            && self.sealed_types.is_empty()
+    }
+
+    /// Seals a field of a type, or exempts it from its type's sealer if `seal` is `None`.
+    #[cfg(feature = "crypto")]
+    pub(crate) fn seal_field(&mut self, type_name: &str, field: &str, seal: Sealer) {
+        let fields = &mut self.sealed_fields;
+        fields.retain(|(name, key, _)| (&**name, &**key) != (type_name, field));
+        fields.push((type_name.into(), field.into(), seal));
+    }
+
+    /// Seals every field of a type, or stops sealing them if `seal` is `None`.
+    #[cfg(feature = "crypto")]
+    pub(crate) fn seal_type(&mut self, type_name: &str, seal: Sealer) {
+        let types = &mut self.sealed_types;
+        types.retain(|(name, _)| **name != *type_name);
+        types.push((type_name.into(), seal));
+    }
+
+    /// The sealer of a field of a type, or of its unnamed contents (e.g. the value of a newtype
+    /// or unit variant) if `field` is `None`.
+    pub(crate) fn sealer(&self, type_name: &str, field: Option<&str>) -> Option<&SealFn> {
+        let sealed_field = field.and_then(|field| {
+            self.sealed_fields
+                .iter()
+                .find(|(name, key, _)| **name == *type_name && **key == *field)
+        });
+        match sealed_field {
+            Some((_, _, seal)) => seal.as_deref(),
+            None => self
+                .sealed_types
+                .iter()
+                .find(|(name, _)| **name == *type_name)
+                .and_then(|(_, seal)| seal.as_deref()),
+        }
+    }
+
+    pub(crate) fn for_field(&self, type_name: &str, field: &str) -> Option<&TransformFn> {
+        self.fields
+            .iter()
+            .find(|(name, key, _)| **name == *type_name && **key == *field)
+            .map(|(_, _, transform)| &**transform)
+    }
+
+    pub(crate) fn for_type(&self, type_name: &str) -> Option<&TransformFn> {
+        self.types
+            .iter()
+            .find(|(name, _)| **name == *type_name)
+            .map(|(_, transform)| &**transform)
+    }
+}
+
+impl SchemaBuilder {
+    /// Rewrites the primitive values inside a field before they're recorded by subsequent calls
+    /// to [`trace`][`Self::trace`], e.g. to truncate long strings or hash identifiers.
+    ///
+    /// The field is named by its type (a struct, or an enum for the fields of struct variants)
+    /// and its serialized name. The transformer is called for every primitive value nested inside
+    /// the field, e.g. every string of a `Vec<String>`, unless a more deeply nested field or type
+    /// has a transformer of its own. Registering a transformer for the same field again replaces
+    /// it.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::{LeafValue, SchemaBuilder};
+    ///
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     email: String,
+    ///     bio: String,
+    /// }
+    ///
+    /// let mut builder = SchemaBuilder::new()
+    ///     .transform_field("User", "email", |_| LeafValue::Str("<redacted>".into()))
+    ///     .transform_field("User", "bio", |value| match value {
+    ///         LeafValue::Str(bio) => LeafValue::Str(bio.chars().take(5).collect()),
+    ///         other => other,
+    ///     });
+    /// let trace = builder.trace(&User {
+    ///     email: "ana@example.com".to_owned(),
+    ///     bio: "Likes long walks".to_owned(),
+    /// })?;
+    /// let schema = builder.build()?;
+    /// assert_eq!(
+    ///     schema.describe_trace(trace).to_string(),
+    ///     r#"User(email: "<redacted>", bio: "Likes")"#,
+    /// );
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn transform_field<TransformT>(
+        mut self,
+        type_name: &str,
+        field: &str,
+        transform: TransformT,
+    ) -> Self
+    where
+        TransformT: for<'value> Fn(LeafValue<'value>) -> LeafValue<'value> + Send + Sync + 'static,
+    {
+        let fields = &mut self.transformers.fields;
+        fields.retain(|(name, key, _)| (&**name, &**key) != (type_name, field));
+        fields.push((type_name.into(), field.into(), Arc::new(transform)));
+        self
+    }
+
+    /// Like [`transform_field`][`Self::transform_field`], but rewrites the primitive values
+    /// inside every value of the named type, e.g. a newtype wrapping a float which should be
+    /// recorded as an `f32`.
+    ///
+    /// Example
+    /// -------
+    /// ```rust
+    /// use serde::Serialize;
+    /// use serde_describe::{LeafValue, SchemaBuilder};
+    ///
+    /// #[derive(Serialize)]
+    /// struct Celsius(f64);
+    ///
+    /// let mut builder = SchemaBuilder::new().transform_type("Celsius", |value| match value {
+    ///     LeafValue::F64(value) => LeafValue::F32(value as f32),
+    ///     other => other,
+    /// });
+    /// let trace = builder.trace(&[Celsius(21.5), Celsius(-3.0)])?;
+    /// let schema = builder.build()?;
+    /// assert_eq!(schema.to_string(), "tuple_3(Celsius_2(f32_1), Celsius_2(..))");
+    ///
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn transform_type<TransformT>(mut self, type_name: &str, transform: TransformT) -> Self
+    where
+        TransformT: for<'value> Fn(LeafValue<'value>) -> LeafValue<'value> + Send + Sync + 'static,
+    {
+        let types = &mut self.transformers.types;
+        types.retain(|(name, _)| **name != *type_name);
+        types.push((type_name.into(), Arc::new(transform)));
+        self
+    }
+}
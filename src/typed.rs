@@ -0,0 +1,100 @@
+use serde::{Serialize, de::DeserializeOwned};
+use thiserror::Error;
+
+use crate::{Dynamic, SchemaBuilder, TraceError};
+
+/// Wraps a statically typed value to convert it to and from a [`Dynamic`] with `TryFrom`.
+///
+/// `TryFrom` can't be implemented between [`Dynamic`] and every serializable type directly, since
+/// that would overlap with the standard library's blanket implementations, hence the wrapper.
+/// Converting to a [`Dynamic`] traces the value on its own, like
+/// [`DescribedBy::to_dynamic`][`crate::DescribedBy::to_dynamic`] would; converting back traces
+/// the [`Dynamic`] and deserializes it, so any value that deserializes from the original type's
+/// traces works, e.g. after adding an optional field.
+///
+/// Example
+/// -------
+/// ```rust
+/// use serde::{Deserialize, Serialize};
+/// use serde_describe::{Dynamic, Typed};
+///
+/// #[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// struct User {
+///     name: String,
+///     visits: u32,
+/// }
+///
+/// let user = User { name: "ana".to_owned(), visits: 3 };
+/// let mut dynamic: Dynamic = Typed(&user).try_into()?;
+/// if let Some(Dynamic::U32(visits)) = dynamic.field_mut("visits") {
+///     *visits += 1;
+/// }
+///
+/// let Typed(updated): Typed<User> = dynamic.try_into()?;
+/// assert_eq!(updated, User { name: "ana".to_owned(), visits: 4 });
+///
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Typed<T>(pub T);
+
+/// Returned by converting between [`Typed`] values and [`Dynamic`]-s.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TypedConversionError {
+    /// The value couldn't be traced.
+    #[error(transparent)]
+    Trace(#[from] TraceError),
+
+    /// The [`Dynamic`] couldn't be deserialized into the requested type.
+    #[error("cannot deserialize dynamic value: {0}")]
+    Deserialize(String),
+}
+
+impl<T> TryFrom<Typed<T>> for Dynamic
+where
+    T: Serialize,
+{
+    type Error = TypedConversionError;
+
+    fn try_from(Typed(value): Typed<T>) -> Result<Self, Self::Error> {
+        let mut builder = SchemaBuilder::new();
+        let trace = builder.trace(&value)?;
+        let schema = builder.build()?;
+        Ok(schema
+            .describe_trace_ref(&trace)
+            .to_dynamic()
+            .expect("traces match the schema they were traced with"))
+    }
+}
+
+impl<T> TryFrom<Dynamic> for Typed<T>
+where
+    T: DeserializeOwned,
+{
+    type Error = TypedConversionError;
+
+    #[inline]
+    fn try_from(value: Dynamic) -> Result<Self, Self::Error> {
+        Self::try_from(&value)
+    }
+}
+
+impl<T> TryFrom<&'_ Dynamic> for Typed<T>
+where
+    T: DeserializeOwned,
+{
+    type Error = TypedConversionError;
+
+    fn try_from(value: &Dynamic) -> Result<Self, Self::Error> {
+        let mut builder = SchemaBuilder::new();
+        let trace = builder.trace(value)?;
+        let schema = builder.build()?;
+        schema
+            .describe_trace_ref(&trace)
+            .reader()
+            .read_value()
+            .map(Typed)
+            .map_err(|value| TypedConversionError::Deserialize(value.error))
+    }
+}